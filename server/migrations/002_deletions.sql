@@ -0,0 +1,15 @@
+-- Tombstones for deleted notes
+-- Kept after the note row itself is hard-deleted, so pulls can still report the deletion
+
+CREATE TABLE deletions (
+    id TEXT PRIMARY KEY,                    -- Note UUID
+    client_id TEXT NOT NULL,                -- Owner client
+    deleted_at TEXT NOT NULL,               -- ISO 8601 (server timestamp of the delete)
+    FOREIGN KEY (client_id) REFERENCES clients(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_deletions_client_deleted ON deletions(client_id, deleted_at);
+
+-- Soft-deleted notes already on the server become tombstones
+INSERT INTO deletions (id, client_id, deleted_at)
+SELECT id, client_id, server_modified_at FROM notes WHERE deleted = 1;
@@ -0,0 +1,6 @@
+-- Idempotency-Key of the registration that created each client (SHA-256 hashed),
+-- so a retried registration is refused instead of creating a duplicate
+
+ALTER TABLE clients ADD COLUMN registration_key TEXT;
+
+CREATE UNIQUE INDEX idx_clients_registration_key ON clients(registration_key);
@@ -0,0 +1,3 @@
+-- When the note's reminder is due (RFC 3339, NULL = no reminder); stored as sent, like the other note metadata
+
+ALTER TABLE notes ADD COLUMN reminder_at TEXT;
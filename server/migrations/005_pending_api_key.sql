@@ -0,0 +1,7 @@
+-- Key issued by a rotation but not used yet (SHA-256 hashed). Both it and api_key are
+-- accepted until the client first authenticates with it, which promotes it to api_key,
+-- so a rotation whose response is lost doesn't lock the client out
+
+ALTER TABLE clients ADD COLUMN pending_api_key TEXT;
+
+CREATE INDEX idx_clients_pending_api_key ON clients(pending_api_key);
@@ -1,50 +1,77 @@
-use axum::{extract::State, http::StatusCode, Json};
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    Json,
+};
 use rand::Rng;
 use sha2::{Digest, Sha256};
 use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::{
-    error::AppResult,
+    error::{AppError, AppResult},
     models::{RegisterRequest, RegisterResponse},
     AppState,
 };
 
+/// Header a client sets to make retried registrations safe
+const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
 pub async fn register(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
     Json(req): Json<RegisterRequest>,
 ) -> AppResult<(StatusCode, Json<RegisterResponse>)> {
     tracing::info!("Registration request received: device_name={}, device_type={}", req.device_name, req.device_type);
 
-    // Generate client ID
-    let client_id = Uuid::new_v4().to_string();
+    let registration_key = headers
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty())
+        .map(hash_key);
 
     // Generate random API key (64 hex characters)
     let api_key = generate_api_key();
 
     // Hash API key for storage
-    let mut hasher = Sha256::new();
-    hasher.update(api_key.as_bytes());
-    let hashed_key = format!("{:x}", hasher.finalize());
+    let hashed_key = hash_key(&api_key);
+
+    // Generate client ID
+    let client_id = Uuid::new_v4().to_string();
 
     // Current timestamp
     let now = chrono::Utc::now().to_rfc3339();
 
-    // Insert into database
-    sqlx::query!(
+    // Insert into database. registration_key is unique, so a repeated Idempotency-Key
+    // (a retry, or two racing requests) can't create a second client. The first client's
+    // key can't be returned again (only its hash is stored) and issuing a new one would
+    // hand that client to anyone holding the Idempotency-Key, so the repeat is refused.
+    let inserted = sqlx::query!(
         r#"
-        INSERT INTO clients (id, api_key, device_name, device_type, created_at, last_seen_at, is_active)
-        VALUES (?, ?, ?, ?, ?, ?, 1)
+        INSERT INTO clients (id, api_key, device_name, device_type, created_at, last_seen_at, is_active, registration_key)
+        VALUES (?, ?, ?, ?, ?, ?, 1, ?)
         "#,
         client_id,
         hashed_key,
         req.device_name,
         req.device_type,
         now,
-        now
+        now,
+        registration_key
     )
     .execute(&state.pool)
-    .await?;
+    .await;
+
+    match inserted {
+        Ok(_) => {}
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() && registration_key.is_some() => {
+            tracing::info!("Refused a repeated registration for device {}", req.device_name);
+            return Err(AppError::Conflict(
+                "A client was already registered with this Idempotency-Key; register again with a new key".to_string(),
+            ));
+        }
+        Err(e) => return Err(e.into()),
+    }
 
     tracing::info!("Registered new client: {} ({})", client_id, req.device_name);
 
@@ -59,7 +86,15 @@ pub async fn register(
     Ok((StatusCode::CREATED, Json(response)))
 }
 
-fn generate_api_key() -> String {
+/// SHA-256 hex digest, the form in which API keys are stored and looked up
+pub fn hash_key(key: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(key.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// Random API key (64 hex characters)
+pub fn generate_api_key() -> String {
     let mut rng = rand::thread_rng();
     let bytes: Vec<u8> = (0..32).map(|_| rng.gen()).collect();
     hex::encode(bytes)
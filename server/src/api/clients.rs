@@ -0,0 +1,117 @@
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use std::sync::Arc;
+
+use crate::{
+    api::{
+        auth::{generate_api_key, hash_key},
+        sync::ClientId,
+    },
+    error::{AppError, AppResult},
+    models::{ClientInfo, RotateKeyResponse},
+    AppState,
+};
+
+/// Header carrying the server's `ADMIN_TOKEN`
+const ADMIN_TOKEN_HEADER: &str = "X-Admin-Token";
+
+// Scoping: there is no account grouping, so a client on its own only sees and revokes
+// itself. Managing other devices needs the server's `ADMIN_TOKEN`, which only the
+// server's owner knows; without one configured nobody can touch another client.
+fn is_admin(state: &AppState, headers: &HeaderMap) -> bool {
+    let provided = headers.get(ADMIN_TOKEN_HEADER).and_then(|h| h.to_str().ok());
+    matches!((&state.admin_token, provided), (Some(token), Some(provided)) if token == provided)
+}
+
+pub async fn list_clients(
+    State(state): State<Arc<AppState>>,
+    ClientId(client_id): ClientId,
+    headers: HeaderMap,
+) -> AppResult<Json<Vec<ClientInfo>>> {
+    let admin = is_admin(&state, &headers);
+    let clients = sqlx::query!(
+        "SELECT id, device_name, device_type, created_at, last_seen_at, is_active FROM clients
+         WHERE ? OR id = ? ORDER BY created_at",
+        admin,
+        client_id
+    )
+    .fetch_all(&state.pool)
+    .await?
+    .into_iter()
+    .filter_map(|row| {
+        let id = row.id?;
+        Some(ClientInfo {
+            current: id == client_id,
+            id,
+            device_name: row.device_name,
+            device_type: row.device_type,
+            created_at: row.created_at,
+            last_seen_at: row.last_seen_at,
+            is_active: row.is_active != 0,
+        })
+    })
+    .collect();
+
+    Ok(Json(clients))
+}
+
+pub async fn revoke_client(
+    State(state): State<Arc<AppState>>,
+    ClientId(client_id): ClientId,
+    headers: HeaderMap,
+    Path(target_id): Path<String>,
+) -> AppResult<StatusCode> {
+    // Other clients are reported as missing rather than forbidden, so ids can't be probed
+    if target_id != client_id && !is_admin(&state, &headers) {
+        return Err(AppError::NotFound(format!("Client {} not found", target_id)));
+    }
+
+    // auth_middleware checks is_active on every request, so the key stops working immediately
+    let result = sqlx::query!(
+        "UPDATE clients SET is_active = 0 WHERE id = ?",
+        target_id
+    )
+    .execute(&state.pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!("Client {} not found", target_id)));
+    }
+
+    tracing::info!("Client {} revoked by {}", target_id, client_id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Issue a new API key for the calling client; the new key is only returned here
+///
+/// The key is stored as pending: the old key keeps working until the new one is first
+/// used, so a client that never receives or saves the response isn't locked out and can
+/// simply rotate again.
+pub async fn rotate_key(
+    State(state): State<Arc<AppState>>,
+    ClientId(client_id): ClientId,
+) -> AppResult<Json<RotateKeyResponse>> {
+    let api_key = generate_api_key();
+    let hashed_key = hash_key(&api_key);
+
+    // Replaces any earlier pending key, whose response was evidently lost
+    let result = sqlx::query!(
+        "UPDATE clients SET pending_api_key = ? WHERE id = ? AND is_active = 1",
+        hashed_key,
+        client_id
+    )
+    .execute(&state.pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::Unauthorized);
+    }
+
+    tracing::info!("Issued a rotated API key for client {}", client_id);
+
+    Ok(Json(RotateKeyResponse { api_key, client_id }))
+}
@@ -0,0 +1,92 @@
+use axum::{
+    extract::State,
+    http::{header, HeaderMap},
+    response::IntoResponse,
+};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::{
+    error::{AppError, AppResult},
+    AppState,
+};
+
+/// In-process request counters, reset on restart
+#[derive(Default)]
+pub struct Metrics {
+    pub push_requests: AtomicU64,
+    pub pull_requests: AtomicU64,
+    pub notes_accepted: AtomicU64,
+    pub notes_rejected: AtomicU64,
+}
+
+/// Prometheus text exposition of server metrics. When `METRICS_TOKEN` is set the
+/// request must carry `Authorization: Bearer <token>`.
+///
+/// Gauges (read from the database on each scrape):
+/// - `jottery_notes{state="live"|"deleted"}`: stored notes
+/// - `jottery_clients{state="active"|"revoked"}`: registered clients
+/// - `jottery_attachment_bytes`: total size of stored attachment blobs
+///
+/// Counters (since process start):
+/// - `jottery_push_requests_total`, `jottery_pull_requests_total`: sync requests handled
+/// - `jottery_notes_accepted_total`, `jottery_notes_rejected_total`: pushed notes by outcome
+pub async fn metrics(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> AppResult<impl IntoResponse> {
+    if let Some(token) = &state.metrics_token {
+        let provided = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.strip_prefix("Bearer "));
+        if provided != Some(token.as_str()) {
+            return Err(AppError::Unauthorized);
+        }
+    }
+
+    let notes = sqlx::query!(
+        r#"SELECT COALESCE(SUM(deleted = 0), 0) AS "live!: i64", COALESCE(SUM(deleted = 1), 0) AS "deleted!: i64" FROM notes"#
+    )
+    .fetch_one(&state.pool)
+    .await?;
+    let clients = sqlx::query!(
+        r#"SELECT COALESCE(SUM(is_active = 1), 0) AS "active!: i64", COALESCE(SUM(is_active = 0), 0) AS "revoked!: i64" FROM clients"#
+    )
+    .fetch_one(&state.pool)
+    .await?;
+    let attachment_bytes = sqlx::query_scalar!(
+        r#"SELECT COALESCE(SUM(LENGTH(data)), 0) AS "bytes!: i64" FROM attachments_data"#
+    )
+    .fetch_one(&state.pool)
+    .await?;
+
+    let counters = &state.metrics;
+    let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+
+    let mut body = String::new();
+    let _ = writeln!(body, "# HELP jottery_notes Stored notes by state.");
+    let _ = writeln!(body, "# TYPE jottery_notes gauge");
+    let _ = writeln!(body, "jottery_notes{{state=\"live\"}} {}", notes.live);
+    let _ = writeln!(body, "jottery_notes{{state=\"deleted\"}} {}", notes.deleted);
+    let _ = writeln!(body, "# HELP jottery_clients Registered clients by state.");
+    let _ = writeln!(body, "# TYPE jottery_clients gauge");
+    let _ = writeln!(body, "jottery_clients{{state=\"active\"}} {}", clients.active);
+    let _ = writeln!(body, "jottery_clients{{state=\"revoked\"}} {}", clients.revoked);
+    let _ = writeln!(body, "# HELP jottery_attachment_bytes Total size of stored attachment blobs.");
+    let _ = writeln!(body, "# TYPE jottery_attachment_bytes gauge");
+    let _ = writeln!(body, "jottery_attachment_bytes {}", attachment_bytes);
+    for (name, help, counter) in [
+        ("jottery_push_requests_total", "Sync push requests handled.", &counters.push_requests),
+        ("jottery_pull_requests_total", "Sync pull requests handled.", &counters.pull_requests),
+        ("jottery_notes_accepted_total", "Pushed notes accepted.", &counters.notes_accepted),
+        ("jottery_notes_rejected_total", "Pushed notes rejected as conflicts.", &counters.notes_rejected),
+    ] {
+        let _ = writeln!(body, "# HELP {} {}", name, help);
+        let _ = writeln!(body, "# TYPE {} counter", name);
+        let _ = writeln!(body, "{} {}", name, load(counter));
+    }
+
+    Ok(([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body))
+}
@@ -1,18 +1,66 @@
 pub mod auth;
+pub mod clients;
+pub mod metrics;
 pub mod sync;
 
 // Middleware for API key authentication
 pub mod middleware {
     use axum::{
-        extract::{Request, State},
-        http::{HeaderMap, StatusCode},
+        extract::{ConnectInfo, Request, State},
+        http::{HeaderMap, HeaderValue, StatusCode},
         middleware::Next,
         response::Response,
     };
-    use sha2::{Sha256, Digest};
+    use tracing::Instrument;
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
     use std::sync::Arc;
 
-    use crate::AppState;
+    use crate::{api::auth::hash_key, error::AppError, AppState};
+
+    /// Response header carrying the id of the request's log span
+    pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+    /// Run each request inside a tracing span tagged with a fresh id, echoed in `X-Request-Id`
+    ///
+    /// `auth_middleware` adds the client id to the span once the caller is known.
+    pub async fn request_id(request: Request, next: Next) -> Response {
+        let request_id = uuid::Uuid::new_v4().to_string();
+        let span = tracing::info_span!(
+            "request",
+            id = %request_id,
+            method = %request.method(),
+            path = %request.uri().path(),
+            client_id = tracing::field::Empty,
+        );
+
+        let mut response = next.run(request).instrument(span).await;
+        if let Ok(value) = HeaderValue::from_str(&request_id) {
+            response.headers_mut().insert(REQUEST_ID_HEADER, value);
+        }
+        response
+    }
+
+    /// Limit registrations per peer IP (all unknown peers share one bucket)
+    pub async fn register_rate_limit(
+        State(state): State<Arc<AppState>>,
+        request: Request,
+        next: Next,
+    ) -> Result<Response, AppError> {
+        let ip = request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip())
+            .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+
+        if !state.register_limiter.check(ip) {
+            tracing::warn!("Registration rate limit exceeded for {}", ip);
+            return Err(AppError::TooManyRequests(
+                "Too many registrations, try again later".to_string(),
+            ));
+        }
+
+        Ok(next.run(request).await)
+    }
 
     pub async fn auth_middleware(
         State(state): State<Arc<AppState>>,
@@ -34,13 +82,14 @@ pub mod middleware {
         let api_key = &auth_header[7..]; // Remove "Bearer " prefix
 
         // Hash the API key
-        let mut hasher = Sha256::new();
-        hasher.update(api_key.as_bytes());
-        let hashed_key = format!("{:x}", hasher.finalize());
+        let hashed_key = hash_key(api_key);
 
-        // Look up client in database
+        // Look up client in database; a key pending from a rotation is accepted too
         let result = sqlx::query!(
-            "SELECT id, is_active FROM clients WHERE api_key = ?",
+            r#"SELECT id, is_active, COALESCE(pending_api_key = ?, 0) AS "pending!: bool" FROM clients
+               WHERE api_key = ? OR pending_api_key = ?"#,
+            hashed_key,
+            hashed_key,
             hashed_key
         )
         .fetch_optional(&state.pool)
@@ -50,6 +99,20 @@ pub mod middleware {
         match result {
             Some(client) if client.is_active == 1 => {
                 let client_id = client.id.clone().unwrap_or_default();
+                tracing::Span::current().record("client_id", client_id.as_str());
+
+                // First use of a rotated key: the client has it, so the old key can go
+                if client.pending {
+                    sqlx::query!(
+                        "UPDATE clients SET api_key = pending_api_key, pending_api_key = NULL WHERE id = ? AND pending_api_key = ?",
+                        client_id,
+                        hashed_key
+                    )
+                    .execute(&state.pool)
+                    .await
+                    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+                    tracing::info!("Client {} switched to its rotated API key", client_id);
+                }
 
                 // Add client_id to request extensions
                 request.extensions_mut().insert(client_id.clone());
@@ -4,17 +4,32 @@ use axum::{
     Json,
 };
 use base64::Engine;
+use std::collections::HashSet;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 use crate::{
     error::{AppError, AppResult},
     models::{
-        SyncAccepted, SyncAttachmentData, SyncNote, SyncPullRequest,
-        SyncPullResponse, SyncPushRequest, SyncPushResponse, SyncRejected, SyncStatusResponse,
+        SyncAccepted, SyncAttachmentData, SyncAttachmentsRequest, SyncAttachmentsResponse,
+        SyncDeletion, SyncNote, SyncPullRequest, SyncPullResponse, SyncPushRequest,
+        SyncPushResponse, SyncRejected, SyncStatusResponse,
     },
     AppState,
 };
 
+/// Notes per pull page when the client does not ask for a limit
+const DEFAULT_PULL_LIMIT: i64 = 200;
+
+/// Largest pull page a client may ask for
+const MAX_PULL_LIMIT: i64 = 500;
+
+/// Largest number of attachment blobs fetched in one request
+const MAX_ATTACHMENTS_PER_REQUEST: usize = 50;
+
+/// How far ahead of server time a pushed `modifiedAt` may be before it is rejected
+const MAX_CLOCK_SKEW_HOURS: i64 = 24;
+
 // Custom extractor for authenticated client ID
 pub struct ClientId(pub String);
 
@@ -78,9 +93,11 @@ pub async fn push(
     ClientId(client_id): ClientId,
     Json(push_req): Json<SyncPushRequest>,
 ) -> AppResult<Json<SyncPushResponse>> {
-
+    state.metrics.push_requests.fetch_add(1, Ordering::Relaxed);
     let mut accepted = Vec::new();
     let mut rejected = Vec::new();
+    // Blobs are only stored for attachments of accepted notes (their metadata row must exist)
+    let mut accepted_attachments = HashSet::new();
     let errors = Vec::new();
 
     tracing::info!(
@@ -90,9 +107,47 @@ pub async fn push(
         push_req.attachments.len()
     );
 
+    // Decode and size-check blobs up front so an oversized push changes nothing
+    let mut blobs = Vec::with_capacity(push_req.attachments.len());
+    for attachment in push_req.attachments {
+        let data = base64::engine::general_purpose::STANDARD
+            .decode(&attachment.data)
+            .map_err(|e| AppError::BadRequest(format!("Invalid base64: {}", e)))?;
+        if data.len() > state.max_attachment_size {
+            return Err(AppError::PayloadTooLarge(format!(
+                "Attachment {} is {} bytes; the limit is {} bytes",
+                attachment.id,
+                data.len(),
+                state.max_attachment_size
+            )));
+        }
+        blobs.push((attachment.id, data));
+    }
+
     let now = chrono::Utc::now().to_rfc3339();
+    // A far-future edit would win every later comparison and block legitimate edits
+    let latest_allowed = chrono::Utc::now() + chrono::Duration::hours(MAX_CLOCK_SKEW_HOURS);
+
+    // Normalize timestamps up front so a malformed one rejects the push before anything is written
+    let mut notes = Vec::with_capacity(push_req.notes.len());
+    for mut note in push_req.notes {
+        let modified_at = parse_timestamp("modifiedAt", &note.modified_at)?;
+        note.created_at = canonical_timestamp(parse_timestamp("createdAt", &note.created_at)?);
+        note.modified_at = canonical_timestamp(modified_at);
+        note.deleted_at = note
+            .deleted_at
+            .as_deref()
+            .map(|at| parse_timestamp("deletedAt", at).map(canonical_timestamp))
+            .transpose()?;
+        note.reminder_at = note
+            .reminder_at
+            .as_deref()
+            .map(|at| parse_timestamp("reminderAt", at).map(canonical_timestamp))
+            .transpose()?;
+        notes.push((note, modified_at));
+    }
 
-    for note in push_req.notes {
+    for (note, modified_at) in notes {
         // Check if note exists
         let existing = sqlx::query!(
             "SELECT modified_at, server_version FROM notes WHERE id = ? AND client_id = ?",
@@ -102,11 +157,16 @@ pub async fn push(
         .fetch_optional(&state.pool)
         .await?;
 
-        let should_accept = match &existing {
+        let skewed = modified_at > latest_allowed;
+        let should_accept = !skewed && match &existing {
             None => true, // New note
             Some(existing_note) => {
-                // Last-Write-Wins: compare modifiedAt
-                note.modified_at > existing_note.modified_at
+                // Last-Write-Wins: compare modifiedAt as instants, since rows stored before
+                // normalization may use a different offset or precision
+                match chrono::DateTime::parse_from_rfc3339(&existing_note.modified_at) {
+                    Ok(existing_at) => modified_at > existing_at,
+                    Err(_) => note.modified_at > existing_note.modified_at,
+                }
             }
         };
 
@@ -132,9 +192,9 @@ pub async fn push(
                 INSERT INTO notes (
                     id, client_id, created_at, modified_at, server_modified_at,
                     content, tags, pinned, deleted, deleted_at, version, server_version,
-                    word_wrap, syntax_language
+                    word_wrap, syntax_language, reminder_at
                 )
-                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
                 ON CONFLICT(id) DO UPDATE SET
                     modified_at = excluded.modified_at,
                     server_modified_at = excluded.server_modified_at,
@@ -146,7 +206,8 @@ pub async fn push(
                     version = excluded.version,
                     server_version = excluded.server_version,
                     word_wrap = excluded.word_wrap,
-                    syntax_language = excluded.syntax_language
+                    syntax_language = excluded.syntax_language,
+                    reminder_at = excluded.reminder_at
                 "#,
                 note.id,
                 client_id,
@@ -161,11 +222,25 @@ pub async fn push(
                 note.version,
                 server_version,
                 word_wrap,
-                note.syntax_language
+                note.syntax_language,
+                note.reminder_at
             )
             .execute(&state.pool)
             .await?;
 
+            // Tombstone a deletion, or drop the tombstone of a note restored from the trash
+            if note.deleted {
+                record_deletion(&state.pool, &note.id, &client_id, &now).await?;
+            } else {
+                sqlx::query!(
+                    "DELETE FROM deletions WHERE id = ? AND client_id = ?",
+                    note.id,
+                    client_id
+                )
+                .execute(&state.pool)
+                .await?;
+            }
+
             accepted.push(SyncAccepted {
                 id: note.id.clone(),
                 server_version,
@@ -193,27 +268,47 @@ pub async fn push(
                 .execute(&state.pool)
                 .await?;
 
+                accepted_attachments.insert(attachment_ref.id.clone());
                 tracing::debug!("Stored attachment metadata: {} for note {}", attachment_ref.id, note.id);
             }
 
             tracing::debug!("Accepted note: {}", note.id);
         } else {
+            let server_note = sqlx::query_as::<_, crate::models::Note>(
+                "SELECT id, client_id, created_at, modified_at, server_modified_at, content, tags, pinned, deleted, deleted_at, version, server_version, word_wrap, syntax_language, reminder_at
+                 FROM notes WHERE id = ? AND client_id = ?",
+            )
+            .bind(&note.id)
+            .bind(&client_id)
+            .fetch_optional(&state.pool)
+            .await?;
+            let server_note = match server_note {
+                Some(db_note) => Some(to_sync_note(&state.pool, db_note).await?),
+                None => None,
+            };
+
+            let reason = if skewed {
+                format!("Clock skew: modifiedAt is more than {} hours ahead of the server", MAX_CLOCK_SKEW_HOURS)
+            } else {
+                "Server version is newer".to_string()
+            };
+            tracing::debug!("Rejected note: {} ({})", note.id, reason);
+
             rejected.push(SyncRejected {
                 id: note.id.clone(),
-                reason: "Server version is newer".to_string(),
-                server_modified_at: existing.unwrap().modified_at,
+                reason,
+                server_modified_at: existing.map(|e| e.modified_at).unwrap_or_else(|| now.clone()),
+                server_note,
             });
-
-            tracing::debug!("Rejected note: {} (conflict)", note.id);
         }
     }
 
     // Store attachment data (binary blobs)
-    for attachment in push_req.attachments {
-        // Decode base64
-        let data = base64::engine::general_purpose::STANDARD
-            .decode(&attachment.data)
-            .map_err(|e| AppError::BadRequest(format!("Invalid base64: {}", e)))?;
+    for (attachment_id, data) in blobs {
+        if !accepted_attachments.contains(&attachment_id) {
+            tracing::debug!("Skipping attachment {} (note rejected or not referenced)", attachment_id);
+            continue;
+        }
 
         // Store in attachments_data
         sqlx::query!(
@@ -222,16 +317,19 @@ pub async fn push(
             VALUES (?, ?, ?)
             ON CONFLICT(id) DO UPDATE SET data = excluded.data
             "#,
-            attachment.id,
+            attachment_id,
             data,
             now
         )
         .execute(&state.pool)
         .await?;
 
-        tracing::debug!("Stored attachment: {}", attachment.id);
+        tracing::debug!("Stored attachment: {}", attachment_id);
     }
 
+    state.metrics.notes_accepted.fetch_add(accepted.len() as u64, Ordering::Relaxed);
+    state.metrics.notes_rejected.fetch_add(rejected.len() as u64, Ordering::Relaxed);
+
     Ok(Json(SyncPushResponse {
         accepted,
         rejected,
@@ -244,7 +342,7 @@ pub async fn pull(
     ClientId(client_id): ClientId,
     Json(pull_req): Json<SyncPullRequest>,
 ) -> AppResult<Json<SyncPullResponse>> {
-
+    state.metrics.pull_requests.fetch_add(1, Ordering::Relaxed);
     tracing::info!(
         "Pull from client {}: lastSyncAt={:?}, {} known IDs",
         client_id,
@@ -252,144 +350,219 @@ pub async fn pull(
         pull_req.known_note_ids.len()
     );
 
-    // Get notes modified after lastSyncAt
-    // We need to build the query string dynamically to avoid type incompatibility
-    let db_notes: Vec<crate::models::Note> = if let Some(last_sync) = &pull_req.last_sync_at {
-        let rows = sqlx::query!(
-            "SELECT id, client_id, created_at, modified_at, server_modified_at, content, tags, pinned, deleted, deleted_at, version, server_version, word_wrap, syntax_language FROM notes WHERE client_id = ? AND server_modified_at > ? ORDER BY server_modified_at",
-            client_id,
-            last_sync
-        )
-        .fetch_all(&state.pool)
-        .await?;
+    let limit = pull_req.limit.unwrap_or(DEFAULT_PULL_LIMIT).clamp(1, MAX_PULL_LIMIT);
+    let (cursor_at, cursor_id) = match &pull_req.cursor {
+        Some(cursor) => {
+            let (at, id) = decode_cursor(cursor)?;
+            (Some(at), Some(id))
+        }
+        None => (None, None),
+    };
+    // One extra row tells whether another page follows
+    let fetch_limit = limit + 1;
+
+    // Get notes modified after lastSyncAt, one page at a time in (server_modified_at, id) order
+    // Deleted notes are reported in `deletions` instead, so clients never process them twice
+    let mut db_notes: Vec<crate::models::Note> = sqlx::query!(
+        "SELECT id, client_id, created_at, modified_at, server_modified_at, content, tags, pinned, deleted, deleted_at, version, server_version, word_wrap, syntax_language, reminder_at
+         FROM notes
+         WHERE client_id = ? AND deleted = 0
+           AND (? IS NULL OR server_modified_at > ?)
+           AND (? IS NULL OR server_modified_at > ? OR (server_modified_at = ? AND id > ?))
+         ORDER BY server_modified_at, id
+         LIMIT ?",
+        client_id,
+        pull_req.last_sync_at,
+        pull_req.last_sync_at,
+        cursor_at,
+        cursor_at,
+        cursor_at,
+        cursor_id,
+        fetch_limit
+    )
+    .fetch_all(&state.pool)
+    .await?
+    .into_iter()
+    .filter_map(|row| Some(crate::models::Note {
+        id: row.id?,
+        client_id: row.client_id,
+        created_at: row.created_at,
+        modified_at: row.modified_at,
+        server_modified_at: row.server_modified_at,
+        content: row.content,
+        tags: row.tags,
+        pinned: row.pinned,
+        deleted: row.deleted,
+        deleted_at: row.deleted_at,
+        version: row.version,
+        server_version: row.server_version,
+        word_wrap: row.word_wrap,
+        syntax_language: row.syntax_language,
+        reminder_at: row.reminder_at,
+    }))
+    .collect();
 
-        rows.into_iter()
-            .filter_map(|row| Some(crate::models::Note {
-                id: row.id?,
-                client_id: row.client_id,
-                created_at: row.created_at,
-                modified_at: row.modified_at,
-                server_modified_at: row.server_modified_at,
-                content: row.content,
-                tags: row.tags,
-                pinned: row.pinned,
-                deleted: row.deleted,
-                deleted_at: row.deleted_at,
-                version: row.version,
-                server_version: row.server_version,
-                word_wrap: row.word_wrap,
-                syntax_language: row.syntax_language,
-            }))
-            .collect()
+    let next_cursor = if db_notes.len() as i64 > limit {
+        db_notes.truncate(limit as usize);
+        db_notes.last().map(|n| encode_cursor(&n.server_modified_at, &n.id))
     } else {
-        let rows = sqlx::query!(
-            "SELECT id, client_id, created_at, modified_at, server_modified_at, content, tags, pinned, deleted, deleted_at, version, server_version, word_wrap, syntax_language FROM notes WHERE client_id = ? ORDER BY server_modified_at",
-            client_id
-        )
-        .fetch_all(&state.pool)
-        .await?;
-
-        rows.into_iter()
-            .filter_map(|row| Some(crate::models::Note {
-                id: row.id?,
-                client_id: row.client_id,
-                created_at: row.created_at,
-                modified_at: row.modified_at,
-                server_modified_at: row.server_modified_at,
-                content: row.content,
-                tags: row.tags,
-                pinned: row.pinned,
-                deleted: row.deleted,
-                deleted_at: row.deleted_at,
-                version: row.version,
-                server_version: row.server_version,
-                word_wrap: row.word_wrap,
-                syntax_language: row.syntax_language,
-            }))
-            .collect()
+        None
     };
 
     let mut notes = Vec::new();
-    let mut needed_attachments = Vec::new();
-
     for db_note in db_notes {
-        // Deserialize tags
-        let tags: Vec<String> = serde_json::from_str(&db_note.tags)
-            .unwrap_or_default();
-
-        // Get attachments for this note
-        let db_attachments = sqlx::query!(
-            "SELECT id, note_id, filename, mime_type, size, created_at FROM attachments_meta WHERE note_id = ?",
-            db_note.id
+        notes.push(to_sync_note(&state.pool, db_note).await?);
+    }
+
+    // Get deletions recorded since lastSyncAt, sent with the first page only.
+    // Tombstones outlive the note rows, so purged notes are still reported
+    let deletions: Vec<SyncDeletion> = if pull_req.cursor.is_some() {
+        Vec::new()
+    } else {
+        sqlx::query!(
+            "SELECT id, deleted_at FROM deletions
+             WHERE client_id = ? AND (? IS NULL OR deleted_at > ?)
+             ORDER BY deleted_at",
+            client_id,
+            pull_req.last_sync_at,
+            pull_req.last_sync_at
         )
         .fetch_all(&state.pool)
-        .await?;
+        .await?
+        .into_iter()
+        .filter_map(|row| Some(SyncDeletion {
+            id: row.id?,
+            deleted_at: row.deleted_at,
+        }))
+        .collect()
+    };
 
-        let attachments = db_attachments
-            .into_iter()
-            .filter_map(|a| {
-                let att_id = a.id?;
-                needed_attachments.push(att_id.clone());
-                Some(crate::models::AttachmentRef {
-                    id: att_id.clone(),
-                    filename: a.filename,  // NOT NULL, so not Optional
-                    mime_type: a.mime_type,  // NOT NULL, so not Optional
-                    size: a.size,  // NOT NULL in schema, so not Optional
-                    data: att_id, // Reference
-                })
-            })
-            .collect();
-
-        notes.push(SyncNote {
-            id: db_note.id,
-            created_at: db_note.created_at,
-            modified_at: db_note.modified_at,
-            content: db_note.content,
-            tags,
-            attachments,
-            pinned: db_note.pinned != 0,
-            deleted: db_note.deleted != 0,
-            deleted_at: db_note.deleted_at,
-            version: db_note.version,
-            word_wrap: db_note.word_wrap.map(|w| w != 0),
-            syntax_language: db_note.syntax_language,
-        });
+    let synced_at = chrono::Utc::now().to_rfc3339();
+
+    tracing::info!(
+        "Pull response: {} notes, {} deletions, more: {}",
+        notes.len(),
+        deletions.len(),
+        next_cursor.is_some()
+    );
+
+    Ok(Json(SyncPullResponse {
+        notes,
+        deletions,
+        synced_at,
+        next_cursor,
+    }))
+}
+
+/// Fetch encrypted attachment blobs by id (kept out of pull pages to bound their size)
+pub async fn attachments(
+    State(state): State<Arc<AppState>>,
+    ClientId(client_id): ClientId,
+    Json(req): Json<SyncAttachmentsRequest>,
+) -> AppResult<Json<SyncAttachmentsResponse>> {
+    if req.ids.len() > MAX_ATTACHMENTS_PER_REQUEST {
+        return Err(AppError::BadRequest(format!(
+            "At most {} attachments can be requested at once",
+            MAX_ATTACHMENTS_PER_REQUEST
+        )));
     }
 
-    // Get attachment data
-    let mut attachments_data = Vec::new();
-    for att_id in needed_attachments {
+    let mut attachments = Vec::new();
+    for att_id in &req.ids {
+        // Only blobs belonging to this client's notes
         if let Some(att_data) = sqlx::query!(
-            "SELECT id, data FROM attachments_data WHERE id = ?",
-            att_id
+            "SELECT d.id, d.data FROM attachments_data d
+             JOIN attachments_meta m ON m.id = d.id
+             JOIN notes n ON n.id = m.note_id
+             WHERE d.id = ? AND n.client_id = ?",
+            att_id,
+            client_id
         )
         .fetch_optional(&state.pool)
         .await?
         {
             if let Some(id) = att_data.id {
-                use base64::Engine;
                 let encoded = base64::engine::general_purpose::STANDARD.encode(&att_data.data);
-                attachments_data.push(SyncAttachmentData {
-                    id,
-                    data: encoded,
-                });
+                attachments.push(SyncAttachmentData { id, data: encoded });
             }
         }
     }
 
-    // Get deletions
-    let deletions = Vec::new(); // Simplified for now
+    tracing::info!("Attachments for client {}: {} of {} found", client_id, attachments.len(), req.ids.len());
 
-    let synced_at = chrono::Utc::now().to_rfc3339();
+    Ok(Json(SyncAttachmentsResponse { attachments }))
+}
 
-    tracing::info!("Pull response: {} notes, {} attachments", notes.len(), attachments_data.len());
+/// Convert a stored note to the sync format, with its attachment references
+async fn to_sync_note(pool: &sqlx::SqlitePool, db_note: crate::models::Note) -> AppResult<SyncNote> {
+    // Deserialize tags
+    let tags: Vec<String> = serde_json::from_str(&db_note.tags)
+        .unwrap_or_default();
 
-    Ok(Json(SyncPullResponse {
-        notes,
-        deletions,
-        attachments: attachments_data,
-        synced_at,
-    }))
+    // Get attachments for this note
+    let db_attachments = sqlx::query!(
+        "SELECT id, note_id, filename, mime_type, size, created_at FROM attachments_meta WHERE note_id = ?",
+        db_note.id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let attachments = db_attachments
+        .into_iter()
+        .filter_map(|a| {
+            let att_id = a.id?;
+            Some(crate::models::AttachmentRef {
+                id: att_id.clone(),
+                filename: a.filename,  // NOT NULL, so not Optional
+                mime_type: a.mime_type,  // NOT NULL, so not Optional
+                size: a.size,  // NOT NULL in schema, so not Optional
+                data: att_id, // Reference
+            })
+        })
+        .collect();
+
+    Ok(SyncNote {
+        id: db_note.id,
+        created_at: db_note.created_at,
+        modified_at: db_note.modified_at,
+        content: db_note.content,
+        tags,
+        attachments,
+        pinned: db_note.pinned != 0,
+        deleted: db_note.deleted != 0,
+        deleted_at: db_note.deleted_at,
+        version: db_note.version,
+        word_wrap: db_note.word_wrap.map(|w| w != 0),
+        syntax_language: db_note.syntax_language,
+        reminder_at: db_note.reminder_at,
+    })
+}
+
+/// Parse a client timestamp, accepting any RFC 3339 offset or fractional precision
+fn parse_timestamp(field: &str, value: &str) -> AppResult<chrono::DateTime<chrono::Utc>> {
+    chrono::DateTime::parse_from_rfc3339(value)
+        .map(|at| at.with_timezone(&chrono::Utc))
+        .map_err(|e| AppError::BadRequest(format!("Invalid {} '{}': {}", field, value, e)))
+}
+
+/// Canonical stored form: UTC with a `Z` suffix and only the fractional digits needed
+fn canonical_timestamp(at: chrono::DateTime<chrono::Utc>) -> String {
+    at.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true)
+}
+
+/// Opaque pull cursor: the (server_modified_at, id) of the last note on the previous page
+fn encode_cursor(server_modified_at: &str, id: &str) -> String {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(format!("{}\n{}", server_modified_at, id))
+}
+
+fn decode_cursor(cursor: &str) -> AppResult<(String, String)> {
+    let invalid = || AppError::BadRequest("Invalid pull cursor".to_string());
+    let bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(cursor)
+        .map_err(|_| invalid())?;
+    let text = String::from_utf8(bytes).map_err(|_| invalid())?;
+    let (at, id) = text.split_once('\n').ok_or_else(invalid)?;
+    Ok((at.to_string(), id.to_string()))
 }
 
 pub async fn delete_note(
@@ -399,15 +572,39 @@ pub async fn delete_note(
 ) -> AppResult<StatusCode> {
 
     // Delete note (cascades to attachments via foreign keys)
-    sqlx::query!(
+    let deleted = sqlx::query!(
         "DELETE FROM notes WHERE id = ? AND client_id = ?",
         note_id,
         client_id
     )
     .execute(&state.pool)
-    .await?;
+    .await?
+    .rows_affected();
+
+    // Missing notes and other clients' notes look the same to the caller
+    if deleted == 0 {
+        return Err(AppError::NotFound(format!("Note {} not found", note_id)));
+    }
+
+    // Other devices only learn about a hard delete through its tombstone
+    let now = chrono::Utc::now().to_rfc3339();
+    record_deletion(&state.pool, &note_id, &client_id, &now).await?;
 
     tracing::info!("Deleted note: {} for client: {}", note_id, client_id);
 
     Ok(StatusCode::NO_CONTENT)
 }
+
+/// Record (or refresh) the tombstone of a deleted note
+async fn record_deletion(pool: &sqlx::SqlitePool, note_id: &str, client_id: &str, deleted_at: &str) -> AppResult<()> {
+    sqlx::query!(
+        "INSERT INTO deletions (id, client_id, deleted_at) VALUES (?, ?, ?)
+         ON CONFLICT(id) DO UPDATE SET deleted_at = excluded.deleted_at",
+        note_id,
+        client_id,
+        deleted_at
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
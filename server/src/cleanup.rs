@@ -0,0 +1,138 @@
+use sqlx::SqlitePool;
+use std::time::Duration;
+
+/// How often the purge job runs
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Spawn the background job that hard-deletes old soft-deleted notes
+pub fn spawn_purge_task(pool: SqlitePool, retention_days: u32) {
+    if retention_days == 0 {
+        tracing::info!("Deleted note purge disabled");
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match purge_deleted_notes(&pool, retention_days).await {
+                Ok(0) => tracing::debug!("Purge: no deleted notes older than {} days", retention_days),
+                Ok(count) => tracing::info!("Purged {} deleted notes older than {} days", count, retention_days),
+                Err(e) => tracing::error!("Deleted note purge failed: {}", e),
+            }
+        }
+    });
+}
+
+/// Hard-delete notes whose tombstone reached the server more than `retention_days` ago.
+/// Attachments are removed explicitly since foreign keys aren't enforced on every pooled connection.
+pub async fn purge_deleted_notes(pool: &SqlitePool, retention_days: u32) -> Result<u64, sqlx::Error> {
+    // server_modified_at is when the server accepted the delete; the tombstone in
+    // `deletions` is kept, so clients pulling after the purge still see the deletion
+    let cutoff = (chrono::Utc::now() - chrono::Duration::days(retention_days as i64)).to_rfc3339();
+
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        "DELETE FROM attachments_data WHERE id IN (
+             SELECT m.id FROM attachments_meta m
+             JOIN notes n ON n.id = m.note_id
+             WHERE n.deleted = 1 AND n.server_modified_at < ?
+         )",
+        cutoff
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "DELETE FROM attachments_meta WHERE note_id IN (
+             SELECT id FROM notes WHERE deleted = 1 AND server_modified_at < ?
+         )",
+        cutoff
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let purged = sqlx::query!(
+        "DELETE FROM notes WHERE deleted = 1 AND server_modified_at < ?",
+        cutoff
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    tx.commit().await?;
+
+    Ok(purged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn insert_note(pool: &SqlitePool, id: &str, deleted: bool, age_days: i64) {
+        let at = (chrono::Utc::now() - chrono::Duration::days(age_days)).to_rfc3339();
+        sqlx::query(
+            "INSERT INTO notes (id, client_id, created_at, modified_at, server_modified_at, content, tags, deleted)
+             VALUES (?, 'client', ?, ?, ?, '', '[]', ?)",
+        )
+        .bind(id)
+        .bind(&at)
+        .bind(&at)
+        .bind(&at)
+        .bind(deleted as i64)
+        .execute(pool)
+        .await
+        .unwrap();
+
+        let att_id = format!("{}-att", id);
+        sqlx::query("INSERT INTO attachments_meta (id, note_id, filename, mime_type, size, created_at) VALUES (?, ?, 'f', 'text/plain', 1, ?)")
+            .bind(&att_id)
+            .bind(id)
+            .bind(&at)
+            .execute(pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO attachments_data (id, data, created_at) VALUES (?, x'00', ?)")
+            .bind(&att_id)
+            .bind(&at)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    async fn ids(pool: &SqlitePool, table: &str) -> Vec<String> {
+        let mut ids: Vec<String> = sqlx::query_scalar(&format!("SELECT id FROM {}", table))
+            .fetch_all(pool)
+            .await
+            .unwrap();
+        ids.sort();
+        ids
+    }
+
+    #[tokio::test]
+    async fn test_purge_only_removes_old_tombstones() {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+        let now = chrono::Utc::now().to_rfc3339();
+        sqlx::query("INSERT INTO clients (id, api_key, device_name, device_type, created_at, last_seen_at) VALUES ('client', 'key', 'test', 'tui', ?, ?)")
+            .bind(&now)
+            .bind(&now)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        insert_note(&pool, "old-deleted", true, 45).await;
+        insert_note(&pool, "recent-deleted", true, 5).await;
+        insert_note(&pool, "old-live", false, 45).await;
+
+        assert_eq!(purge_deleted_notes(&pool, 30).await.unwrap(), 1);
+        assert_eq!(ids(&pool, "notes").await, vec!["old-live", "recent-deleted"]);
+        assert_eq!(ids(&pool, "attachments_meta").await, vec!["old-live-att", "recent-deleted-att"]);
+        assert_eq!(ids(&pool, "attachments_data").await, vec!["old-live-att", "recent-deleted-att"]);
+    }
+}
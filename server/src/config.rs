@@ -3,9 +3,17 @@ use std::env;
 #[derive(Clone)]
 pub struct Config {
     pub database_url: String,
+    pub db_max_connections: u32,
+    pub db_acquire_timeout_secs: u64,
+    /// 0 keeps idle connections open indefinitely
+    pub db_idle_timeout_secs: u64,
     pub port: u16,
-    #[allow(dead_code)]
     pub max_payload_size: usize,
+    pub max_attachment_size: usize,
+    pub register_rate_limit: u32,
+    pub deleted_note_retention_days: u32,
+    pub metrics_token: Option<String>,
+    pub admin_token: Option<String>,
 }
 
 impl Config {
@@ -15,6 +23,18 @@ impl Config {
         Ok(Config {
             database_url: env::var("DATABASE_URL")
                 .unwrap_or_else(|_| "sqlite:jottery.db".to_string()),
+            db_max_connections: env::var("DB_MAX_CONNECTIONS")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse()
+                .unwrap_or(5),
+            db_acquire_timeout_secs: env::var("DB_ACQUIRE_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .unwrap_or(30),
+            db_idle_timeout_secs: env::var("DB_IDLE_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "600".to_string())
+                .parse()
+                .unwrap_or(600),
             port: env::var("PORT")
                 .unwrap_or_else(|_| "3030".to_string())
                 .parse()
@@ -23,6 +43,20 @@ impl Config {
                 .unwrap_or_else(|_| "10485760".to_string())
                 .parse()
                 .unwrap_or(10_485_760),
+            max_attachment_size: env::var("MAX_ATTACHMENT_SIZE")
+                .unwrap_or_else(|_| "5242880".to_string())
+                .parse()
+                .unwrap_or(5_242_880),
+            register_rate_limit: env::var("REGISTER_RATE_LIMIT")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse()
+                .unwrap_or(5),
+            deleted_note_retention_days: env::var("DELETED_NOTE_RETENTION_DAYS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .unwrap_or(30),
+            metrics_token: env::var("METRICS_TOKEN").ok().filter(|t| !t.is_empty()),
+            admin_token: env::var("ADMIN_TOKEN").ok().filter(|t| !t.is_empty()),
         })
     }
 }
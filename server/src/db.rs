@@ -1,9 +1,28 @@
 use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
+use std::time::Duration;
+
+use crate::config::Config;
+
+/// How long a connection waits on a locked database before failing with SQLITE_BUSY
+const BUSY_TIMEOUT_MS: u32 = 5000;
+
+pub async fn init_pool(config: &Config) -> Result<SqlitePool, sqlx::Error> {
+    let idle_timeout = (config.db_idle_timeout_secs > 0)
+        .then(|| Duration::from_secs(config.db_idle_timeout_secs));
 
-pub async fn init_pool(database_url: &str) -> Result<SqlitePool, sqlx::Error> {
     let pool = SqlitePoolOptions::new()
-        .max_connections(5)
-        .connect(database_url)
+        .max_connections(config.db_max_connections.max(1))
+        .acquire_timeout(Duration::from_secs(config.db_acquire_timeout_secs))
+        .idle_timeout(idle_timeout)
+        .after_connect(|conn, _meta| {
+            Box::pin(async move {
+                sqlx::query(&format!("PRAGMA busy_timeout = {}", BUSY_TIMEOUT_MS))
+                    .execute(conn)
+                    .await?;
+                Ok(())
+            })
+        })
+        .connect(&config.database_url)
         .await?;
 
     // Enable foreign keys
@@ -1,24 +1,49 @@
 use axum::{
+    extract::{DefaultBodyLimit, State},
+    http::{HeaderName, StatusCode},
+    response::{IntoResponse, Response},
     routing::{delete, get, post},
     Router,
 };
 use sqlx::SqlitePool;
+use std::future::IntoFuture;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::compression::CompressionLayer;
+use tower_http::decompression::RequestDecompressionLayer;
 
 mod api;
+mod cleanup;
 mod config;
 mod db;
 mod error;
 mod models;
+mod rate_limit;
 
+use crate::api::metrics::Metrics;
 use crate::config::Config;
+use crate::error::{AppError, AppResult};
+use crate::rate_limit::RateLimiter;
+
+/// How long in-flight requests get to finish after a shutdown signal
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
 
 #[derive(Clone)]
 pub struct AppState {
     pub pool: SqlitePool,
+    /// Largest request body accepted, after gzip decompression (bytes)
+    pub max_payload_size: usize,
+    /// Largest single attachment accepted in a push, decoded (bytes)
+    pub max_attachment_size: usize,
+    /// Per-IP limit on unauthenticated client registrations
+    pub register_limiter: Arc<RateLimiter>,
+    /// Bearer token required by `/metrics`, if set
+    pub metrics_token: Option<String>,
+    /// Secret that lets a client manage every registered device, if set
+    pub admin_token: Option<String>,
+    pub metrics: Arc<Metrics>,
 }
 
 #[tokio::main]
@@ -36,9 +61,20 @@ async fn main() {
     tracing::info!("Starting Jottery Sync Server v{}", env!("CARGO_PKG_VERSION"));
     tracing::info!("Database: {}", config.database_url);
     tracing::info!("Port: {}", config.port);
+    tracing::info!("Max payload size: {} bytes", config.max_payload_size);
+    tracing::info!("Registration limit: {} per minute per IP", config.register_rate_limit);
+    tracing::info!(
+        "Database pool: {} connections, {}s acquire timeout, idle timeout {}",
+        config.db_max_connections.max(1),
+        config.db_acquire_timeout_secs,
+        match config.db_idle_timeout_secs {
+            0 => "disabled".to_string(),
+            secs => format!("{}s", secs),
+        }
+    );
 
     // Initialize database
-    let pool = db::init_pool(&config.database_url)
+    let pool = db::init_pool(&config)
         .await
         .expect("Failed to initialize database");
 
@@ -52,52 +88,746 @@ async fn main() {
 
     tracing::info!("Database migrations complete");
 
+    // Periodically hard-delete old soft-deleted notes
+    cleanup::spawn_purge_task(pool.clone(), config.deleted_note_retention_days);
+
     // Build application state
-    let app_state = Arc::new(AppState { pool });
+    let app_state = Arc::new(AppState {
+        pool: pool.clone(),
+        max_payload_size: config.max_payload_size,
+        max_attachment_size: config.max_attachment_size,
+        register_limiter: Arc::new(RateLimiter::new(
+            config.register_rate_limit,
+            Duration::from_secs(60),
+        )),
+        metrics_token: config.metrics_token.clone(),
+        admin_token: config.admin_token.clone(),
+        metrics: Arc::new(Metrics::default()),
+    });
+    let app = build_router(app_state);
+
+    // Start server
+    let addr = SocketAddr::from(([0, 0, 0, 0], config.port));
+    tracing::info!("Listening on {}", addr);
+
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .expect("Failed to bind to address");
+
+    // Peer addresses are needed to rate-limit registration; stop accepting
+    // connections on Ctrl-C/SIGTERM and let in-flight requests drain
+    let (signal_tx, signal_rx) = tokio::sync::oneshot::channel();
+    let server = axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(async move {
+            shutdown_signal().await;
+            let _ = signal_tx.send(());
+        })
+        .into_future();
+    let mut server = std::pin::pin!(server);
+
+    tokio::select! {
+        result = &mut server => result.expect("Server failed"),
+        Ok(()) = signal_rx => {
+            tracing::info!("Shutting down gracefully");
+            match tokio::time::timeout(SHUTDOWN_TIMEOUT, &mut server).await {
+                Ok(result) => result.expect("Server failed"),
+                Err(_) => tracing::warn!(
+                    "Connections still open after {}s, shutting down anyway",
+                    SHUTDOWN_TIMEOUT.as_secs()
+                ),
+            }
+        }
+    }
+
+    pool.close().await;
+    tracing::info!("Database connections closed");
+}
+
+/// Resolve on Ctrl-C, or SIGTERM on Unix (the stop signal under systemd/Docker)
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl-C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// Build the application router with its middleware stack
+fn build_router(app_state: Arc<AppState>) -> Router {
+    let max_payload_size = app_state.max_payload_size;
 
     // Build protected sync routes with auth middleware
     let sync_routes = Router::new()
         .route("/api/v1/sync/status", get(api::sync::get_status))
         .route("/api/v1/sync/push", post(api::sync::push))
         .route("/api/v1/sync/pull", post(api::sync::pull))
+        .route("/api/v1/sync/attachments", post(api::sync::attachments))
         .route("/api/v1/sync/notes/:id", delete(api::sync::delete_note))
+        .route("/api/v1/clients", get(api::clients::list_clients))
+        .route("/api/v1/clients/rotate", post(api::clients::rotate_key))
+        .route("/api/v1/clients/:id", delete(api::clients::revoke_client))
         .layer(axum::middleware::from_fn_with_state(
             app_state.clone(),
             api::middleware::auth_middleware,
         ));
 
     // Build main router
-    let app = Router::new()
-        // Health check (no auth required)
+    Router::new()
+        // Readiness and liveness checks (no auth required)
         .route("/health", get(health_check))
+        .route("/livez", get(livez))
+        // Prometheus metrics (optionally gated by METRICS_TOKEN)
+        .route("/metrics", get(api::metrics::metrics))
         // Auth routes (no auth required)
-        .route("/api/v1/auth/register", post(api::auth::register))
+        .route(
+            "/api/v1/auth/register",
+            post(api::auth::register).route_layer(axum::middleware::from_fn_with_state(
+                app_state.clone(),
+                api::middleware::register_rate_limit,
+            )),
+        )
         // Merge protected sync routes
         .merge(sync_routes)
         // Add state
         .with_state(app_state)
         // Add middleware
+        .layer(axum::middleware::map_response(move |response: Response| async move {
+            payload_too_large_json(response, max_payload_size)
+        }))
+        .layer(DefaultBodyLimit::max(max_payload_size))
         .layer(CompressionLayer::new())
+        // Accept `Content-Encoding: gzip` request bodies (e.g. large pushes)
+        .layer(RequestDecompressionLayer::new())
         .layer(
             CorsLayer::new()
                 .allow_origin(Any)
                 .allow_methods(Any)
-                .allow_headers(Any),
-        );
+                .allow_headers(Any)
+                .expose_headers([HeaderName::from_static(api::middleware::REQUEST_ID_HEADER)]),
+        )
+        // Outermost, so the span covers every other layer and the handler
+        .layer(axum::middleware::from_fn(api::middleware::request_id))
+}
 
-    // Start server
-    let addr = SocketAddr::from(([0, 0, 0, 0], config.port));
-    tracing::info!("Listening on {}", addr);
+/// Readiness: healthy only while the database answers
+async fn health_check(State(state): State<Arc<AppState>>) -> AppResult<&'static str> {
+    sqlx::query("SELECT 1").execute(&state.pool).await.map_err(|e| {
+        tracing::warn!("Health check failed: {}", e);
+        AppError::ServiceUnavailable("Database unavailable".to_string())
+    })?;
+    Ok("OK")
+}
 
-    let listener = tokio::net::TcpListener::bind(addr)
-        .await
-        .expect("Failed to bind to address");
+/// Liveness: the process is up, whatever the state of the database
+async fn livez() -> &'static str {
+    "OK"
+}
 
-    axum::serve(listener, app)
-        .await
-        .expect("Server failed");
+/// Replace the plain-text 413 produced by the body limit with the usual JSON error
+fn payload_too_large_json(response: Response, max_payload_size: usize) -> Response {
+    let is_json = response
+        .headers()
+        .get(axum::http::header::CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"application/json"));
+    if response.status() != StatusCode::PAYLOAD_TOO_LARGE || is_json {
+        return response;
+    }
+
+    AppError::PayloadTooLarge(format!(
+        "Request body exceeds the {} byte limit",
+        max_payload_size
+    ))
+    .into_response()
 }
 
-async fn health_check() -> &'static str {
-    "OK"
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::{to_bytes, Body},
+        http::{header, Request, StatusCode},
+    };
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+    use tower::Service;
+
+    async fn test_state() -> AppState {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+        AppState {
+            pool,
+            max_payload_size: 10_485_760,
+            max_attachment_size: 5_242_880,
+            register_limiter: Arc::new(RateLimiter::new(100, Duration::from_secs(60))),
+            metrics_token: None,
+            admin_token: None,
+            metrics: Arc::new(Metrics::default()),
+        }
+    }
+
+    async fn test_app() -> Router {
+        build_router(Arc::new(test_state().await))
+    }
+
+    async fn body_json(response: axum::response::Response) -> serde_json::Value {
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    async fn register(app: &Router) -> String {
+        let register = Request::post("/api/v1/auth/register")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"deviceName":"test","deviceType":"tui"}"#))
+            .unwrap();
+        let response = app.clone().call(register).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        body_json(response).await["apiKey"].as_str().unwrap().to_string()
+    }
+
+    async fn post_json(app: &Router, uri: &str, api_key: &str, body: serde_json::Value) -> serde_json::Value {
+        let request = Request::post(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::AUTHORIZATION, format!("Bearer {}", api_key))
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = app.clone().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        body_json(response).await
+    }
+
+    fn test_notes(count: usize, content: &str) -> Vec<serde_json::Value> {
+        let now = chrono::Utc::now().to_rfc3339();
+        (0..count)
+            .map(|i| {
+                serde_json::json!({
+                    "id": uuid::Uuid::new_v4().to_string(),
+                    "createdAt": now,
+                    "modifiedAt": now,
+                    "content": format!("{}{}", i, content),
+                    "tags": [],
+                    "attachments": [],
+                    "pinned": false,
+                    "deleted": false,
+                    "deletedAt": null,
+                    "version": 1,
+                })
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_gzip_push_is_decoded() {
+        let app = test_app().await;
+        let api_key = register(&app).await;
+
+        let notes = test_notes(200, &"encrypted-content ".repeat(500));
+        let json = serde_json::to_vec(&serde_json::json!({ "notes": notes, "attachments": [] })).unwrap();
+        assert!(json.len() > 1_000_000);
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&json).unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert!(compressed.len() < json.len() / 10);
+
+        let push = Request::post("/api/v1/sync/push")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::CONTENT_ENCODING, "gzip")
+            .header(header::AUTHORIZATION, format!("Bearer {}", api_key))
+            .body(Body::from(compressed))
+            .unwrap();
+        let response = app.clone().call(push).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_json(response).await;
+        assert_eq!(body["accepted"].as_array().unwrap().len(), 200);
+        assert!(body["rejected"].as_array().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_oversized_payloads_are_rejected() {
+        let app = build_router(Arc::new(AppState {
+            max_payload_size: 16 * 1024,
+            max_attachment_size: 1024,
+            ..test_state().await
+        }));
+        let api_key = register(&app).await;
+
+        let push = |body: serde_json::Value| {
+            Request::post("/api/v1/sync/push")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::AUTHORIZATION, format!("Bearer {}", api_key))
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        // Whole body over the limit
+        let notes = test_notes(10, &"x".repeat(4096));
+        let response = app.clone().call(push(serde_json::json!({ "notes": notes, "attachments": [] }))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(body_json(response).await["error"].as_str().unwrap().contains("16384 byte limit"));
+
+        // Body within the limit, but one attachment over the per-attachment cap
+        use base64::Engine;
+        let blob = base64::engine::general_purpose::STANDARD.encode(vec![7u8; 2048]);
+        let response = app
+            .clone()
+            .call(push(serde_json::json!({ "notes": [], "attachments": [{ "id": "big", "data": blob }] })))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(body_json(response).await["error"].as_str().unwrap().contains("big"));
+    }
+
+    #[tokio::test]
+    async fn test_registration_is_rate_limited_per_ip() {
+        let app = build_router(Arc::new(AppState {
+            register_limiter: Arc::new(RateLimiter::new(2, Duration::from_secs(60))),
+            ..test_state().await
+        }));
+
+        let register_from = |ip: &str| {
+            let addr: SocketAddr = format!("{}:40000", ip).parse().unwrap();
+            Request::post("/api/v1/auth/register")
+                .header(header::CONTENT_TYPE, "application/json")
+                .extension(axum::extract::ConnectInfo(addr))
+                .body(Body::from(r#"{"deviceName":"test","deviceType":"tui"}"#))
+                .unwrap()
+        };
+
+        let first = app.clone().call(register_from("10.0.0.1")).await.unwrap();
+        assert_eq!(first.status(), StatusCode::CREATED);
+        let api_key = body_json(first).await["apiKey"].as_str().unwrap().to_string();
+        assert_eq!(app.clone().call(register_from("10.0.0.1")).await.unwrap().status(), StatusCode::CREATED);
+
+        let limited = app.clone().call(register_from("10.0.0.1")).await.unwrap();
+        assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(body_json(limited).await["error"].as_str().is_some());
+
+        // Other peers and authenticated routes are unaffected
+        assert_eq!(app.clone().call(register_from("10.0.0.2")).await.unwrap().status(), StatusCode::CREATED);
+        let status = Request::get("/api/v1/sync/status")
+            .header(header::AUTHORIZATION, format!("Bearer {}", api_key))
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(app.clone().call(status).await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_metrics_report_counts() {
+        let app = build_router(Arc::new(AppState {
+            metrics_token: Some("scrape".to_string()),
+            ..test_state().await
+        }));
+        let api_key = register(&app).await;
+        post_json(&app, "/api/v1/sync/push", &api_key, serde_json::json!({ "notes": test_notes(2, "x"), "attachments": [] })).await;
+        post_json(&app, "/api/v1/sync/pull", &api_key, serde_json::json!({ "lastSyncAt": null, "knownNoteIds": [] })).await;
+
+        let scrape = |token: &str| {
+            Request::get("/metrics")
+                .header(header::AUTHORIZATION, format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap()
+        };
+        assert_eq!(app.clone().call(scrape("wrong")).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+
+        let response = app.clone().call(scrape("scrape")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let text = String::from_utf8(bytes.to_vec()).unwrap();
+        for line in [
+            "jottery_notes{state=\"live\"} 2",
+            "jottery_clients{state=\"active\"} 1",
+            "jottery_attachment_bytes 0",
+            "jottery_push_requests_total 1",
+            "jottery_pull_requests_total 1",
+            "jottery_notes_accepted_total 2",
+        ] {
+            assert!(text.lines().any(|l| l == line), "missing {:?} in:\n{}", line, text);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_revoked_client_is_rejected() {
+        let app = build_router(Arc::new(AppState {
+            admin_token: Some("admin-secret".to_string()),
+            ..test_state().await
+        }));
+        let first_key = register(&app).await;
+        let second_key = register(&app).await;
+
+        let list = |api_key: &str| {
+            Request::get("/api/v1/clients")
+                .header(header::AUTHORIZATION, format!("Bearer {}", api_key))
+                .header("X-Admin-Token", "admin-secret")
+                .body(Body::empty())
+                .unwrap()
+        };
+        let clients = body_json(app.clone().call(list(&first_key)).await.unwrap()).await;
+        let clients = clients.as_array().unwrap();
+        assert_eq!(clients.len(), 2);
+        let second_id = clients
+            .iter()
+            .find(|c| c["current"] == false)
+            .and_then(|c| c["id"].as_str())
+            .unwrap()
+            .to_string();
+
+        let revoke = Request::delete(format!("/api/v1/clients/{}", second_id))
+            .header(header::AUTHORIZATION, format!("Bearer {}", first_key))
+            .header("X-Admin-Token", "admin-secret")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(app.clone().call(revoke).await.unwrap().status(), StatusCode::NO_CONTENT);
+
+        assert_eq!(app.clone().call(list(&second_key)).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+        let clients = body_json(app.clone().call(list(&first_key)).await.unwrap()).await;
+        assert!(clients.as_array().unwrap().iter().any(|c| c["id"] == second_id.as_str() && c["isActive"] == false));
+    }
+
+    #[tokio::test]
+    async fn test_clients_cannot_manage_each_other_without_admin_token() {
+        let app = build_router(Arc::new(AppState {
+            admin_token: Some("admin-secret".to_string()),
+            ..test_state().await
+        }));
+        let a_key = register(&app).await;
+        let b_key = register(&app).await;
+
+        let list = |api_key: &str| {
+            Request::get("/api/v1/clients")
+                .header(header::AUTHORIZATION, format!("Bearer {}", api_key))
+                .body(Body::empty())
+                .unwrap()
+        };
+        let revoke = |api_key: &str, target_id: &str, admin_token: Option<&str>| {
+            let mut request = Request::delete(format!("/api/v1/clients/{}", target_id))
+                .header(header::AUTHORIZATION, format!("Bearer {}", api_key));
+            if let Some(token) = admin_token {
+                request = request.header("X-Admin-Token", token);
+            }
+            request.body(Body::empty()).unwrap()
+        };
+
+        // Each client only sees itself
+        let a_clients = body_json(app.clone().call(list(&a_key)).await.unwrap()).await;
+        let a_clients = a_clients.as_array().unwrap();
+        assert_eq!(a_clients.len(), 1);
+        assert_eq!(a_clients[0]["current"], true);
+        let a_id = a_clients[0]["id"].as_str().unwrap().to_string();
+
+        // B can't revoke A, with no token or a wrong one
+        for token in [None, Some("guess")] {
+            let response = app.clone().call(revoke(&b_key, &a_id, token)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        }
+        assert_eq!(app.clone().call(list(&a_key)).await.unwrap().status(), StatusCode::OK);
+
+        // A client may still revoke itself
+        assert_eq!(app.clone().call(revoke(&a_key, &a_id, None)).await.unwrap().status(), StatusCode::NO_CONTENT);
+        assert_eq!(app.clone().call(list(&a_key)).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_pull_pages_through_cursor() {
+        let app = test_app().await;
+        let api_key = register(&app).await;
+
+        let notes = test_notes(5, "content");
+        let mut expected: Vec<String> = notes.iter().map(|n| n["id"].as_str().unwrap().to_string()).collect();
+        let pushed = post_json(&app, "/api/v1/sync/push", &api_key, serde_json::json!({ "notes": notes, "attachments": [] })).await;
+        assert_eq!(pushed["accepted"].as_array().unwrap().len(), 5);
+
+        let mut pulled = Vec::new();
+        let mut cursor = serde_json::Value::Null;
+        let mut pages = 0;
+        loop {
+            let page = post_json(
+                &app,
+                "/api/v1/sync/pull",
+                &api_key,
+                serde_json::json!({ "lastSyncAt": null, "knownNoteIds": [], "cursor": cursor, "limit": 2 }),
+            )
+            .await;
+            pages += 1;
+            pulled.extend(page["notes"].as_array().unwrap().iter().map(|n| n["id"].as_str().unwrap().to_string()));
+            cursor = page["nextCursor"].clone();
+            if cursor.is_null() {
+                break;
+            }
+        }
+
+        assert_eq!(pages, 3);
+        pulled.sort();
+        expected.sort();
+        assert_eq!(pulled, expected);
+    }
+
+    #[tokio::test]
+    async fn test_deletions_outlive_purged_notes() {
+        let state = Arc::new(test_state().await);
+        let app = build_router(Arc::clone(&state));
+        let api_key = register(&app).await;
+        let pull_deletions = || async {
+            let page = post_json(&app, "/api/v1/sync/pull", &api_key, serde_json::json!({ "lastSyncAt": null, "knownNoteIds": [] })).await;
+            page["deletions"].as_array().unwrap().iter().map(|d| d["id"].as_str().unwrap().to_string()).collect::<Vec<_>>()
+        };
+
+        let mut notes = test_notes(2, "content");
+        post_json(&app, "/api/v1/sync/push", &api_key, serde_json::json!({ "notes": notes, "attachments": [] })).await;
+        let (trashed, removed) = (notes[0]["id"].as_str().unwrap().to_string(), notes[1]["id"].as_str().unwrap().to_string());
+
+        // Soft delete through a push, then purge the note row
+        notes[0]["deleted"] = true.into();
+        notes[0]["modifiedAt"] = chrono::Utc::now().to_rfc3339().into();
+        post_json(&app, "/api/v1/sync/push", &api_key, serde_json::json!({ "notes": [notes[0]], "attachments": [] })).await;
+        assert_eq!(crate::cleanup::purge_deleted_notes(&state.pool, 0).await.unwrap(), 1);
+        assert_eq!(pull_deletions().await, vec![trashed.clone()]);
+
+        // Hard delete through the endpoint
+        let delete = Request::delete(format!("/api/v1/sync/notes/{}", removed))
+            .header(header::AUTHORIZATION, format!("Bearer {}", api_key))
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(app.clone().call(delete).await.unwrap().status(), StatusCode::NO_CONTENT);
+        let delete_again = Request::delete(format!("/api/v1/sync/notes/{}", removed))
+            .header(header::AUTHORIZATION, format!("Bearer {}", api_key))
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(app.clone().call(delete_again).await.unwrap().status(), StatusCode::NOT_FOUND);
+        let mut deletions = pull_deletions().await;
+        deletions.sort();
+        let mut expected = vec![trashed.clone(), removed];
+        expected.sort();
+        assert_eq!(deletions, expected);
+
+        // Restoring a note drops its tombstone
+        notes[0]["deleted"] = false.into();
+        notes[0]["modifiedAt"] = (chrono::Utc::now() + chrono::Duration::seconds(1)).to_rfc3339().into();
+        post_json(&app, "/api/v1/sync/push", &api_key, serde_json::json!({ "notes": [notes[0]], "attachments": [] })).await;
+        assert!(!pull_deletions().await.contains(&trashed));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_push_includes_server_copy() {
+        let app = test_app().await;
+        let api_key = register(&app).await;
+
+        let mut notes = test_notes(1, "server");
+        post_json(&app, "/api/v1/sync/push", &api_key, serde_json::json!({ "notes": notes, "attachments": [] })).await;
+
+        // An edit based on an older version loses to the stored copy
+        notes[0]["content"] = "stale".into();
+        notes[0]["modifiedAt"] = (chrono::Utc::now() - chrono::Duration::hours(1)).to_rfc3339().into();
+        let pushed = post_json(&app, "/api/v1/sync/push", &api_key, serde_json::json!({ "notes": notes, "attachments": [] })).await;
+
+        let rejected = &pushed["rejected"][0];
+        assert_eq!(rejected["id"], notes[0]["id"]);
+        assert_eq!(rejected["serverNote"]["id"], notes[0]["id"]);
+        assert_eq!(rejected["serverNote"]["content"], "0server");
+        assert!(pushed["accepted"].as_array().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_health_checks_database() {
+        let state = Arc::new(test_state().await);
+        let app = build_router(Arc::clone(&state));
+        let get = |uri: &str| Request::get(uri).body(Body::empty()).unwrap();
+
+        assert_eq!(app.clone().call(get("/health")).await.unwrap().status(), StatusCode::OK);
+
+        state.pool.close().await;
+        let response = app.clone().call(get("/health")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body_json(response).await["error"], "Database unavailable");
+        assert_eq!(app.clone().call(get("/livez")).await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_repeated_registration_is_refused() {
+        let app = test_app().await;
+        let register_with = |idempotency_key: &str| {
+            let request = Request::post("/api/v1/auth/register")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header("Idempotency-Key", idempotency_key)
+                .body(Body::from(r#"{"deviceName":"test","deviceType":"tui"}"#))
+                .unwrap();
+            let app = app.clone();
+            async move {
+                let response = app.clone().call(request).await.unwrap();
+                let status = response.status();
+                (status, body_json(response).await)
+            }
+        };
+        let status = |api_key: &str| {
+            let request = Request::get("/api/v1/sync/status")
+                .header(header::AUTHORIZATION, format!("Bearer {}", api_key))
+                .body(Body::empty())
+                .unwrap();
+            let app = app.clone();
+            async move { app.clone().call(request).await.unwrap().status() }
+        };
+
+        let (created, first) = register_with("retry-1").await;
+        assert_eq!(created, StatusCode::CREATED);
+
+        // A repeat neither mints a key for the first client nor creates a second one
+        let (refused, body) = register_with("retry-1").await;
+        assert_eq!(refused, StatusCode::CONFLICT);
+        assert!(body.get("apiKey").is_none());
+        assert_eq!(status(first["apiKey"].as_str().unwrap()).await, StatusCode::OK);
+
+        // Racing requests with one key register a single client
+        let (a, b) = tokio::join!(register_with("retry-2"), register_with("retry-2"));
+        let mut statuses = [a.0, b.0];
+        statuses.sort();
+        assert_eq!(statuses, [StatusCode::CREATED, StatusCode::CONFLICT]);
+    }
+
+    #[tokio::test]
+    async fn test_rotated_key_replaces_old_one() {
+        let app = test_app().await;
+        let old_key = register(&app).await;
+        post_json(&app, "/api/v1/sync/push", &old_key, serde_json::json!({ "notes": test_notes(1, "kept"), "attachments": [] })).await;
+
+        let pull = |api_key: &str| {
+            Request::post("/api/v1/sync/pull")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::AUTHORIZATION, format!("Bearer {}", api_key))
+                .body(Body::from(r#"{"lastSyncAt":null,"knownNoteIds":[]}"#))
+                .unwrap()
+        };
+
+        // A rotation whose response never arrived: the old key still works and can rotate again
+        let lost = post_json(&app, "/api/v1/clients/rotate", &old_key, serde_json::json!({})).await;
+        let lost_key = lost["apiKey"].as_str().unwrap().to_string();
+        assert_eq!(app.clone().call(pull(&old_key)).await.unwrap().status(), StatusCode::OK);
+
+        let rotated = post_json(&app, "/api/v1/clients/rotate", &old_key, serde_json::json!({})).await;
+        let new_key = rotated["apiKey"].as_str().unwrap();
+        assert_ne!(new_key, old_key);
+        assert_eq!(rotated["clientId"], lost["clientId"]);
+        assert_eq!(app.clone().call(pull(&lost_key)).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+
+        // Same client, same notes; using the new key retires the old one
+        let response = app.clone().call(pull(new_key)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_json(response).await["notes"][0]["content"], "0kept");
+        assert_eq!(app.clone().call(pull(&old_key)).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(app.clone().call(pull(new_key)).await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_responses_carry_request_id() {
+        let app = test_app().await;
+        let request_id = |response: &axum::response::Response| {
+            response.headers()[api::middleware::REQUEST_ID_HEADER].to_str().unwrap().to_string()
+        };
+
+        let first = app.clone().call(Request::get("/livez").body(Body::empty()).unwrap()).await.unwrap();
+        let unauthorized = app.clone().call(Request::get("/api/v1/clients").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(unauthorized.status(), StatusCode::UNAUTHORIZED);
+
+        assert!(uuid::Uuid::parse_str(&request_id(&first)).is_ok());
+        assert_ne!(request_id(&first), request_id(&unauthorized));
+    }
+
+    #[tokio::test]
+    async fn test_future_dated_push_is_rejected() {
+        let app = test_app().await;
+        let api_key = register(&app).await;
+
+        let mut notes = test_notes(2, "note");
+        post_json(&app, "/api/v1/sync/push", &api_key, serde_json::json!({ "notes": [notes[0]], "attachments": [] })).await;
+
+        // Both an edit and a new note from a clock running days ahead
+        let future = (chrono::Utc::now() + chrono::Duration::days(3)).to_rfc3339();
+        for note in &mut notes {
+            note["modifiedAt"] = future.clone().into();
+        }
+        let pushed = post_json(&app, "/api/v1/sync/push", &api_key, serde_json::json!({ "notes": notes, "attachments": [] })).await;
+        assert!(pushed["accepted"].as_array().unwrap().is_empty());
+        let rejected = pushed["rejected"].as_array().unwrap();
+        assert_eq!(rejected.len(), 2);
+        assert!(rejected.iter().all(|r| r["reason"].as_str().unwrap().starts_with("Clock skew")));
+        assert_eq!(rejected[0]["serverNote"]["content"], "0note");
+
+        // A slightly fast clock is still accepted
+        notes[1]["modifiedAt"] = (chrono::Utc::now() + chrono::Duration::hours(1)).to_rfc3339().into();
+        let pushed = post_json(&app, "/api/v1/sync/push", &api_key, serde_json::json!({ "notes": [notes[1]], "attachments": [] })).await;
+        assert_eq!(pushed["accepted"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_equivalent_timestamps_compare_as_instants() {
+        let app = test_app().await;
+        let api_key = register(&app).await;
+
+        // 10:30 UTC, written with a +02:00 offset
+        let mut notes = test_notes(1, "note");
+        notes[0]["createdAt"] = "2025-03-12T12:30:00+02:00".into();
+        notes[0]["modifiedAt"] = "2025-03-12T12:30:00+02:00".into();
+        post_json(&app, "/api/v1/sync/push", &api_key, serde_json::json!({ "notes": notes, "attachments": [] })).await;
+
+        let pulled =
+            post_json(&app, "/api/v1/sync/pull", &api_key, serde_json::json!({ "lastSyncAt": null, "knownNoteIds": [] })).await;
+        assert_eq!(pulled["notes"][0]["modifiedAt"], "2025-03-12T10:30:00Z");
+        assert_eq!(pulled["notes"][0]["createdAt"], "2025-03-12T10:30:00Z");
+
+        // The same instant in another format is not newer
+        notes[0]["modifiedAt"] = "2025-03-12T10:30:00.000+00:00".into();
+        let pushed = post_json(&app, "/api/v1/sync/push", &api_key, serde_json::json!({ "notes": notes, "attachments": [] })).await;
+        assert_eq!(pushed["rejected"][0]["reason"], "Server version is newer");
+
+        // Later, though it sorts before "12:30:00+02:00" as a string
+        notes[0]["modifiedAt"] = "2025-03-12T11:00:00.5Z".into();
+        let pushed = post_json(&app, "/api/v1/sync/push", &api_key, serde_json::json!({ "notes": notes, "attachments": [] })).await;
+        assert_eq!(pushed["accepted"].as_array().unwrap().len(), 1);
+
+        // A malformed timestamp fails the push
+        notes[0]["modifiedAt"] = "yesterday".into();
+        let request = Request::post("/api/v1/sync/push")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::AUTHORIZATION, format!("Bearer {}", api_key))
+            .body(Body::from(serde_json::json!({ "notes": notes, "attachments": [] }).to_string()))
+            .unwrap();
+        let response = app.clone().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_reminder_round_trips() {
+        let app = test_app().await;
+        let api_key = register(&app).await;
+
+        let mut notes = test_notes(2, "note");
+        notes[0]["reminderAt"] = "2030-06-01T09:30:00+02:00".into();
+        post_json(&app, "/api/v1/sync/push", &api_key, serde_json::json!({ "notes": notes, "attachments": [] })).await;
+
+        let pulled =
+            post_json(&app, "/api/v1/sync/pull", &api_key, serde_json::json!({ "lastSyncAt": null, "knownNoteIds": [] })).await;
+        let reminder_of = |id: &serde_json::Value| {
+            pulled["notes"].as_array().unwrap().iter().find(|n| &n["id"] == id).unwrap().get("reminderAt").cloned()
+        };
+        assert_eq!(reminder_of(&notes[0]["id"]), Some("2030-06-01T07:30:00Z".into()));
+        assert_eq!(reminder_of(&notes[1]["id"]), None);
+    }
 }
@@ -20,6 +20,33 @@ pub struct RegisterRequest {
     pub device_type: String,
 }
 
+/// A registered device as listed by the client-management API
+#[derive(Debug, Serialize)]
+pub struct ClientInfo {
+    pub id: String,
+    #[serde(rename = "deviceName")]
+    pub device_name: String,
+    #[serde(rename = "deviceType")]
+    pub device_type: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    #[serde(rename = "lastSeenAt")]
+    pub last_seen_at: String,
+    #[serde(rename = "isActive")]
+    pub is_active: bool,
+    /// Whether this is the device making the request
+    pub current: bool,
+}
+
+/// A newly issued API key for the calling client
+#[derive(Debug, Serialize)]
+pub struct RotateKeyResponse {
+    #[serde(rename = "apiKey")]
+    pub api_key: String,
+    #[serde(rename = "clientId")]
+    pub client_id: String,
+}
+
 #[derive(Debug, Serialize)]
 pub struct RegisterResponse {
     #[serde(rename = "apiKey")]
@@ -26,6 +26,8 @@ pub struct SyncNote {
     pub word_wrap: Option<bool>,
     #[serde(rename = "syntaxLanguage")]
     pub syntax_language: Option<String>,
+    #[serde(rename = "reminderAt", default, skip_serializing_if = "Option::is_none")]
+    pub reminder_at: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -67,6 +69,9 @@ pub struct SyncRejected {
     pub reason: String,
     #[serde(rename = "serverModifiedAt")]
     pub server_modified_at: String,
+    /// The server's current copy, so clients can show both sides of the conflict
+    #[serde(rename = "serverNote", skip_serializing_if = "Option::is_none")]
+    pub server_note: Option<SyncNote>,
 }
 
 // Sync pull request
@@ -76,6 +81,12 @@ pub struct SyncPullRequest {
     pub last_sync_at: Option<String>,
     #[serde(rename = "knownNoteIds")]
     pub known_note_ids: Vec<String>,
+    /// `nextCursor` from the previous page; omitted for the first page
+    #[serde(default)]
+    pub cursor: Option<String>,
+    /// Notes per page (server default and cap apply)
+    #[serde(default)]
+    pub limit: Option<i64>,
 }
 
 // Sync pull response
@@ -83,9 +94,11 @@ pub struct SyncPullRequest {
 pub struct SyncPullResponse {
     pub notes: Vec<SyncNote>,
     pub deletions: Vec<SyncDeletion>,
-    pub attachments: Vec<SyncAttachmentData>,
     #[serde(rename = "syncedAt")]
     pub synced_at: String,
+    /// Cursor for the next page, or null on the last page
+    #[serde(rename = "nextCursor")]
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -101,6 +114,17 @@ pub struct SyncAttachmentData {
     pub data: String, // Base64
 }
 
+// Attachment blob request (blobs are not included in pull pages)
+#[derive(Debug, Deserialize)]
+pub struct SyncAttachmentsRequest {
+    pub ids: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SyncAttachmentsResponse {
+    pub attachments: Vec<SyncAttachmentData>,
+}
+
 // Sync status response
 #[derive(Debug, Serialize)]
 pub struct SyncStatusResponse {
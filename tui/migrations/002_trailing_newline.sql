@@ -0,0 +1,5 @@
+-- Trailing newline normalization applied when saving notes
+-- single: ensure exactly one, none: strip, verbatim: leave as typed
+ALTER TABLE settings ADD COLUMN trailing_newline TEXT NOT NULL DEFAULT 'single';
+
+INSERT INTO schema_version (version, applied_at) VALUES (2, datetime('now'));
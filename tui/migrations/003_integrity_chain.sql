@@ -0,0 +1,13 @@
+-- Tamper-evidence hash chain over stored notes
+-- Each note's chain_hash is a keyed hash of its stored (encrypted) row and the previous note's chain_hash
+ALTER TABLE notes ADD COLUMN chain_hash TEXT;
+
+-- Head of the chain, so removing trailing notes is also detected
+CREATE TABLE IF NOT EXISTS integrity_chain (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    head TEXT NOT NULL,
+    note_count INTEGER NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+INSERT INTO schema_version (version, applied_at) VALUES (3, datetime('now'));
@@ -0,0 +1,4 @@
+-- Pull-only sync: apply remote changes but never push local edits
+ALTER TABLE settings ADD COLUMN sync_pull_only INTEGER NOT NULL DEFAULT 0;
+
+INSERT INTO schema_version (version, applied_at) VALUES (4, datetime('now'));
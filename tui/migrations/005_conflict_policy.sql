@@ -0,0 +1,4 @@
+-- How sync resolves notes edited on both sides: newest, local, remote or ask
+ALTER TABLE settings ADD COLUMN conflict_policy TEXT NOT NULL DEFAULT 'newest';
+
+INSERT INTO schema_version (version, applied_at) VALUES (5, datetime('now'));
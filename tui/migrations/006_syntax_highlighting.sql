@@ -0,0 +1,4 @@
+-- Colour code notes in the preview pane according to their syntax language
+ALTER TABLE settings ADD COLUMN syntax_highlighting INTEGER NOT NULL DEFAULT 1;
+
+INSERT INTO schema_version (version, applied_at) VALUES (6, datetime('now'));
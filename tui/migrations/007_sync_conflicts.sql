@@ -0,0 +1,4 @@
+-- Keep the server's copy of a note left for manual conflict resolution (encrypted sync JSON)
+ALTER TABLE note_sync_metadata ADD COLUMN remote_version TEXT;
+
+INSERT INTO schema_version (version, applied_at) VALUES (7, datetime('now'));
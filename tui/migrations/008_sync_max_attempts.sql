@@ -0,0 +1,4 @@
+-- How many times sync tries a request before giving up on transient failures
+ALTER TABLE settings ADD COLUMN sync_max_attempts INTEGER NOT NULL DEFAULT 3;
+
+INSERT INTO schema_version (version, applied_at) VALUES (8, datetime('now'));
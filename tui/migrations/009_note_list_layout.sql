@@ -0,0 +1,5 @@
+-- Width of the note list pane and whether the preview pane is shown beside it
+ALTER TABLE settings ADD COLUMN note_list_width INTEGER NOT NULL DEFAULT 42;
+ALTER TABLE settings ADD COLUMN show_preview INTEGER NOT NULL DEFAULT 1;
+
+INSERT INTO schema_version (version, applied_at) VALUES (9, datetime('now'));
@@ -0,0 +1,4 @@
+-- Mark pinned notes and attachments in the note list with emoji (0 = ASCII markers)
+ALTER TABLE settings ADD COLUMN emoji_icons INTEGER NOT NULL DEFAULT 1;
+
+INSERT INTO schema_version (version, applied_at) VALUES (10, datetime('now'));
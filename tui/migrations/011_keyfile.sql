@@ -0,0 +1,4 @@
+-- Whether the content key is derived from the password combined with a keyfile
+ALTER TABLE encryption_metadata ADD COLUMN keyfile_required INTEGER NOT NULL DEFAULT 0;
+
+INSERT INTO schema_version (version, applied_at) VALUES (11, datetime('now'));
@@ -0,0 +1,5 @@
+-- Encrypted start of each note's content, so the note list can skip decrypting the rest
+-- (NULL until the note is next saved or the database is unlocked)
+ALTER TABLE notes ADD COLUMN preview TEXT;
+
+INSERT INTO schema_version (version, applied_at) VALUES (12, datetime('now'));
@@ -0,0 +1,4 @@
+-- Seconds of editor inactivity before unsaved changes are written (0 = only save on leaving)
+ALTER TABLE settings ADD COLUMN autosave_seconds INTEGER NOT NULL DEFAULT 5;
+
+INSERT INTO schema_version (version, applied_at) VALUES (13, datetime('now'));
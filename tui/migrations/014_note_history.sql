@@ -0,0 +1,14 @@
+-- Earlier versions of notes, recorded before an update changes their content or tags
+-- (encrypted exactly as they were stored; NoteRepository keeps the newest few per note)
+CREATE TABLE IF NOT EXISTS note_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    note_id TEXT NOT NULL,
+    version INTEGER NOT NULL,
+    modified_at TEXT NOT NULL,
+    content TEXT NOT NULL,            -- Encrypted (JSON: EncryptedData)
+    tags TEXT NOT NULL                -- Encrypted (JSON: EncryptedData of string array)
+);
+
+CREATE INDEX IF NOT EXISTS idx_note_history_note ON note_history(note_id, id DESC);
+
+INSERT INTO schema_version (version, applied_at) VALUES (14, datetime('now'));
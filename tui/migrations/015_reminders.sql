@@ -0,0 +1,6 @@
+-- Optional reminder time per note (RFC 3339, UTC), compared as text to find overdue notes
+ALTER TABLE notes ADD COLUMN reminder_at TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_notes_reminder_at ON notes(reminder_at) WHERE reminder_at IS NOT NULL;
+
+INSERT INTO schema_version (version, applied_at) VALUES (15, datetime('now'));
@@ -0,0 +1,4 @@
+-- Most notes that can be pinned at once (0 = no limit)
+ALTER TABLE settings ADD COLUMN pin_limit INTEGER NOT NULL DEFAULT 0;
+
+INSERT INTO schema_version (version, applied_at) VALUES (16, datetime('now'));
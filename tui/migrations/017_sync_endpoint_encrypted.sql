@@ -0,0 +1,14 @@
+-- The sync endpoint is kept only in sync_metadata, where it is encrypted. Carry over one
+-- set in the settings table but not there, marked as plaintext so the next unlock seals it.
+INSERT OR IGNORE INTO sync_metadata (id, sync_enabled, sync_endpoint)
+SELECT 1, 0, '' FROM settings WHERE id = 1 AND COALESCE(sync_endpoint, '') != '';
+
+UPDATE sync_metadata
+SET sync_endpoint = 'PLAINTEXT:' || (SELECT sync_endpoint FROM settings WHERE id = 1)
+WHERE id = 1
+  AND COALESCE(sync_endpoint, '') = ''
+  AND COALESCE((SELECT sync_endpoint FROM settings WHERE id = 1), '') != '';
+
+UPDATE settings SET sync_endpoint = NULL;
+
+INSERT INTO schema_version (version, applied_at) VALUES (17, datetime('now'));
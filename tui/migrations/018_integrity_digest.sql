@@ -0,0 +1,17 @@
+-- Per-note integrity hashes: each chain_hash now covers only its own row, and the head
+-- records the XOR of all of them, so a save rehashes one note instead of the whole chain.
+-- The old chained hashes can't be converted without the key; drop them so the next
+-- unlock seals the notes afresh.
+DROP TABLE IF EXISTS integrity_chain;
+
+CREATE TABLE integrity_chain (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    digest TEXT NOT NULL,
+    note_count INTEGER NOT NULL,
+    head TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+UPDATE notes SET chain_hash = NULL;
+
+INSERT INTO schema_version (version, applied_at) VALUES (18, datetime('now'));
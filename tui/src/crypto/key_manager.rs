@@ -1,10 +1,11 @@
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
+use zeroize::Zeroize;
 
 const KEY_LENGTH: usize = 32;
 
 /// Master key stored in memory
-/// Never persisted to disk
+/// Never persisted to disk; zeroized when dropped
 #[derive(Clone)]
 pub struct MasterKey {
     key: [u8; KEY_LENGTH],
@@ -12,10 +13,10 @@ pub struct MasterKey {
 }
 
 impl MasterKey {
-    /// Create a new master key
-    pub fn new(key: [u8; KEY_LENGTH]) -> Self {
+    /// Create a new master key (copies the bytes; the caller keeps ownership of `key`)
+    pub fn new(key: &[u8; KEY_LENGTH]) -> Self {
         Self {
-            key,
+            key: *key,
             derived_at: Instant::now(),
         }
     }
@@ -36,6 +37,12 @@ impl MasterKey {
     }
 }
 
+impl Drop for MasterKey {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
 /// Key manager for in-memory master key storage
 /// Handles auto-lock functionality
 #[derive(Clone)]
@@ -78,20 +85,16 @@ impl KeyManager {
     }
 
     /// Set the master key (on unlock)
-    pub fn set_master_key(&self, key: [u8; KEY_LENGTH]) {
+    pub fn set_master_key(&self, key: &[u8; KEY_LENGTH]) {
         let mut inner = self.inner.lock().unwrap();
         inner.master_key = Some(MasterKey::new(key));
         inner.last_activity = Instant::now();
     }
 
     /// Clear the master key (on lock)
-    /// Overwrites the key with zeros before dropping
+    /// The key is zeroized as it is dropped
     pub fn clear_master_key(&self) {
         let mut inner = self.inner.lock().unwrap();
-        if let Some(ref mut key) = inner.master_key {
-            // Overwrite key with zeros for security
-            key.key = [0u8; KEY_LENGTH];
-        }
         inner.master_key = None;
     }
 
@@ -174,7 +177,7 @@ mod tests {
         assert!(manager.is_locked());
 
         let key = [1u8; KEY_LENGTH];
-        manager.set_master_key(key);
+        manager.set_master_key(&key);
         assert!(!manager.is_locked());
 
         let retrieved = manager.get_master_key().unwrap();
@@ -184,13 +187,21 @@ mod tests {
         assert!(manager.is_locked());
     }
 
+    #[test]
+    fn test_master_key_zeroized_on_drop() {
+        let mut key = std::mem::ManuallyDrop::new(MasterKey::new(&[7u8; KEY_LENGTH]));
+        // SAFETY: dropped exactly once; the plain byte array is still readable afterwards
+        unsafe { std::ptr::drop_in_place(&mut *key) };
+        assert_eq!(key.key, [0u8; KEY_LENGTH]);
+    }
+
     #[test]
     fn test_auto_lock() {
         let manager = KeyManager::new();
         manager.set_auto_lock(0); // 0 = disabled
 
         let key = [1u8; KEY_LENGTH];
-        manager.set_master_key(key);
+        manager.set_master_key(&key);
         assert!(!manager.is_locked());
 
         // With auto-lock disabled, should not lock
@@ -218,7 +229,7 @@ mod tests {
         }
 
         let key = [1u8; KEY_LENGTH];
-        manager.set_master_key(key);
+        manager.set_master_key(&key);
 
         // Register activity before timeout
         thread::sleep(Duration::from_millis(50));
@@ -239,7 +250,7 @@ mod tests {
         manager.set_auto_lock(1); // 1 minute
 
         let key = [1u8; KEY_LENGTH];
-        manager.set_master_key(key);
+        manager.set_master_key(&key);
 
         let time_left = manager.time_until_lock().unwrap();
         assert!(time_left.as_secs() <= 60);
@@ -255,7 +266,7 @@ mod tests {
         }
 
         let key = [1u8; KEY_LENGTH];
-        manager.set_master_key(key);
+        manager.set_master_key(&key);
 
         assert!(!manager.should_lock());
 
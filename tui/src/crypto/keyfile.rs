@@ -0,0 +1,71 @@
+//! Optional keyfile combined with the password as the content key's PBKDF2 input
+//! The same combination keys the SQLCipher file (`unlock::file_key`), so the file doesn't open without it
+
+use anyhow::{Context, Result};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+/// HMAC key the keyfile is hashed under, so any file size maps to a fixed-length suffix
+const KEYFILE_HMAC_KEY: &[u8] = b"jottery-keyfile-v1";
+
+/// Read a keyfile, refusing empty files (an empty keyfile adds nothing)
+pub fn read_keyfile(path: &Path) -> Result<Zeroizing<Vec<u8>>> {
+    let bytes = Zeroizing::new(
+        std::fs::read(path).with_context(|| format!("Failed to read keyfile {}", path.display()))?,
+    );
+    if bytes.is_empty() {
+        anyhow::bail!("Keyfile {} is empty", path.display());
+    }
+    Ok(bytes)
+}
+
+/// `password || HMAC-SHA256(keyfile)`, hex-encoded so it can stand in for the password
+pub fn combine_with_keyfile(password: &str, keyfile: &[u8]) -> Zeroizing<String> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(KEYFILE_HMAC_KEY)
+        .expect("HMAC accepts keys of any length");
+    mac.update(keyfile);
+    Zeroizing::new(format!("{}{}", password, hex::encode(mac.finalize().into_bytes())))
+}
+
+/// PBKDF2 input for `password`: the password alone, or combined with the keyfile at
+/// `keyfile` when the database was set up with one
+///
+/// Fails clearly when a required keyfile was not given, or one was given for a
+/// database that doesn't use it.
+pub fn kdf_input(password: &str, keyfile_required: bool, keyfile: Option<&Path>) -> Result<Zeroizing<String>> {
+    match (keyfile_required, keyfile) {
+        (true, Some(path)) => Ok(combine_with_keyfile(password, &read_keyfile(path)?)),
+        (true, None) => anyhow::bail!("This database requires its keyfile; start jottery with --keyfile <path>"),
+        (false, Some(_)) => anyhow::bail!("This database was not set up with a keyfile; drop --keyfile"),
+        (false, None) => Ok(Zeroizing::new(password.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kdf_input_requires_matching_keyfile() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("jottery.key");
+        std::fs::write(&path, b"keyfile bytes").unwrap();
+
+        let combined = kdf_input("pw", true, Some(&path)).unwrap();
+        assert!(combined.starts_with("pw"));
+        assert_eq!(combined.len(), 2 + 64);
+        assert_eq!(*combined, *combine_with_keyfile("pw", b"keyfile bytes"));
+        assert_ne!(*combined, *combine_with_keyfile("pw", b"other bytes"));
+        assert_eq!(*kdf_input("pw", false, None).unwrap(), "pw");
+
+        let missing = kdf_input("pw", true, None).err().unwrap();
+        assert!(missing.to_string().contains("requires its keyfile"), "{}", missing);
+        assert!(kdf_input("pw", false, Some(&path)).is_err());
+        assert!(kdf_input("pw", true, Some(&dir.path().join("absent.key"))).is_err());
+
+        std::fs::write(&path, b"").unwrap();
+        assert!(kdf_input("pw", true, Some(&path)).is_err());
+    }
+}
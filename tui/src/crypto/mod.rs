@@ -3,10 +3,16 @@
 /// Matches web app implementation (src/lib/services/crypto.ts)
 
 mod service;
+mod keyfile;
 mod key_manager;
+mod strength;
+mod stream;
 
 pub use service::*;
+pub use keyfile::*;
 pub use key_manager::*;
+pub use strength::*;
+pub use stream::*;
 
 // Re-export commonly used types
 pub use crate::models::encryption::EncryptedData;
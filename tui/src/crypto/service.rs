@@ -1,29 +1,54 @@
 use aes_gcm::{
-    aead::{Aead, AeadCore, KeyInit, OsRng, rand_core::RngCore},
+    aead::{Aead, KeyInit, OsRng, rand_core::RngCore},
     Aes256Gcm, Nonce,
 };
 use anyhow::{Context, Result};
 use base64::{engine::general_purpose, Engine as _};
+use hmac::{Hmac, Mac};
 use pbkdf2::pbkdf2_hmac;
 use sha2::{Digest, Sha256};
+use std::sync::Mutex;
+use tracing::warn;
+use zeroize::Zeroizing;
 
-use crate::models::encryption::EncryptedData;
+use crate::models::encryption::{EncryptedData, EncryptionMetadata};
 
 // Constants matching web app
 const KEY_LENGTH: usize = 32; // 256 bits
-const NONCE_LENGTH: usize = 12; // 96 bits for GCM
+pub(super) const NONCE_LENGTH: usize = 12; // 96 bits for GCM
+pub(super) const TAG_LENGTH: usize = 16; // 128-bit GCM authentication tag
 const SALT_LENGTH: usize = 32; // 256 bits
-const DEFAULT_ITERATIONS: u32 = 100_000; // Match web app
 
 type NonceType = [u8; NONCE_LENGTH];
 
 /// Cryptography service for encryption/decryption operations
-pub struct CryptoService;
+pub struct CryptoService {
+    /// Injected RNG for deterministic tests (None = OsRng)
+    rng: Option<Mutex<Box<dyn RngCore + Send>>>,
+}
 
 impl CryptoService {
     /// Create a new crypto service instance
     pub fn new() -> Self {
-        Self
+        Self { rng: None }
+    }
+
+    /// Create a crypto service drawing salts and nonces from `rng`
+    ///
+    /// Test-only: lets interop tests produce fixed ciphertext for known vectors
+    #[cfg(test)]
+    pub fn with_rng<R: RngCore + Send + 'static>(rng: R) -> Self {
+        Self {
+            rng: Some(Mutex::new(Box::new(rng))),
+        }
+    }
+
+    /// Fill `dest` from the injected RNG, or OsRng in production
+    fn fill_random(&self, dest: &mut [u8]) {
+        match &self.rng {
+            Some(rng) => rng.lock().unwrap_or_else(|e| e.into_inner()).fill_bytes(dest),
+            None => OsRng.fill_bytes(dest),
+        }
     }
 
     /// Derive a 256-bit AES key from password using PBKDF2
@@ -31,25 +56,31 @@ impl CryptoService {
     /// # Arguments
     /// * `password` - User password
     /// * `salt` - Random salt (32 bytes)
-    /// * `iterations` - PBKDF2 iterations (default: 100,000)
+    /// * `iterations` - PBKDF2 iterations, as stored in `EncryptionMetadata` (raised to at least 100,000)
     pub fn derive_key(
         &self,
         password: &str,
         salt: &[u8],
         iterations: u32,
-    ) -> Result<[u8; KEY_LENGTH]> {
+    ) -> Result<Zeroizing<[u8; KEY_LENGTH]>> {
         if salt.len() < SALT_LENGTH {
             anyhow::bail!("Salt must be at least {} bytes", SALT_LENGTH);
         }
 
-        let iterations = if iterations < 100_000 {
-            DEFAULT_ITERATIONS
+        let iterations = if iterations < EncryptionMetadata::MIN_ITERATIONS {
+            warn!(
+                "{} PBKDF2 iterations is below the minimum; deriving with {}",
+                iterations,
+                EncryptionMetadata::MIN_ITERATIONS
+            );
+            EncryptionMetadata::MIN_ITERATIONS
         } else {
             iterations
         };
 
-        let mut key = [0u8; KEY_LENGTH];
-        pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, iterations, &mut key);
+        // Derived straight into the zeroizing buffer, so no unprotected copy is left behind
+        let mut key = Zeroizing::new([0u8; KEY_LENGTH]);
+        pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, iterations, key.as_mut());
 
         Ok(key)
     }
@@ -81,22 +112,7 @@ impl CryptoService {
     /// * `encrypted` - Encrypted data
     /// * `key` - 256-bit AES key
     pub fn decrypt_text(&self, encrypted: &EncryptedData, key: &[u8; KEY_LENGTH]) -> Result<String> {
-        let cipher = Aes256Gcm::new(key.into());
-
-        let ciphertext = general_purpose::STANDARD
-            .decode(&encrypted.ciphertext)
-            .context("Invalid base64 ciphertext")?;
-
-        let nonce_bytes = general_purpose::STANDARD
-            .decode(&encrypted.nonce)
-            .context("Invalid base64 nonce")?;
-
-        let nonce = Nonce::from_slice(&nonce_bytes);
-
-        let plaintext = cipher
-            .decrypt(nonce, ciphertext.as_ref())
-            .map_err(|_| anyhow::anyhow!("Decryption failed. Invalid key or corrupted data."))?;
-
+        let plaintext = self.decrypt_bytes(encrypted, key)?;
         String::from_utf8(plaintext).context("Decrypted data is not valid UTF-8")
     }
 
@@ -127,35 +143,60 @@ impl CryptoService {
     /// * `encrypted` - Encrypted data
     /// * `key` - 256-bit AES key
     pub fn decrypt_binary(&self, encrypted: &EncryptedData, key: &[u8; KEY_LENGTH]) -> Result<Vec<u8>> {
+        self.decrypt_bytes(encrypted, key)
+    }
+
+    /// Decrypt AES-GCM data with the tag either embedded in the ciphertext or in `tag`
+    fn decrypt_bytes(&self, encrypted: &EncryptedData, key: &[u8; KEY_LENGTH]) -> Result<Vec<u8>> {
         let cipher = Aes256Gcm::new(key.into());
 
-        let ciphertext = general_purpose::STANDARD
+        let mut ciphertext = general_purpose::STANDARD
             .decode(&encrypted.ciphertext)
             .context("Invalid base64 ciphertext")?;
 
+        // A separate tag is appended to get the embedded layout aes-gcm expects
+        if !encrypted.tag.is_empty() {
+            let tag = general_purpose::STANDARD
+                .decode(&encrypted.tag)
+                .context("Invalid base64 tag")?;
+            if tag.len() != TAG_LENGTH {
+                anyhow::bail!("Invalid tag length: {} bytes (expected {})", tag.len(), TAG_LENGTH);
+            }
+            ciphertext.extend_from_slice(&tag);
+        }
+
         let nonce_bytes = general_purpose::STANDARD
             .decode(&encrypted.nonce)
             .context("Invalid base64 nonce")?;
 
+        // Nonce::from_slice panics on the wrong length, so check first
+        if nonce_bytes.len() != NONCE_LENGTH {
+            anyhow::bail!(
+                "Invalid nonce length: {} bytes (expected {})",
+                nonce_bytes.len(),
+                NONCE_LENGTH
+            );
+        }
+
         let nonce = Nonce::from_slice(&nonce_bytes);
 
-        let plaintext = cipher
+        cipher
             .decrypt(nonce, ciphertext.as_ref())
-            .map_err(|_| anyhow::anyhow!("Decryption failed. Invalid key or corrupted data."))?;
-
-        Ok(plaintext)
+            .map_err(|_| anyhow::anyhow!("Decryption failed. Invalid key or corrupted data."))
     }
 
     /// Generate random salt for key derivation (32 bytes)
     pub fn generate_salt(&self) -> Vec<u8> {
         let mut salt = vec![0u8; SALT_LENGTH];
-        OsRng.fill_bytes(&mut salt);
+        self.fill_random(&mut salt);
         salt
     }
 
     /// Generate initialization vector/nonce for encryption (12 bytes)
-    fn generate_nonce(&self) -> NonceType {
-        Aes256Gcm::generate_nonce(&mut OsRng).into()
+    pub(super) fn generate_nonce(&self) -> NonceType {
+        let mut nonce = [0u8; NONCE_LENGTH];
+        self.fill_random(&mut nonce);
+        nonce
     }
 
     /// Hash data using SHA-256 (for sync conflict detection)
@@ -166,6 +207,14 @@ impl CryptoService {
         general_purpose::STANDARD.encode(result)
     }
 
+    /// Keyed hash (HMAC-SHA256, hex) that cannot be recomputed without the key
+    pub fn keyed_hash(&self, data: &[u8], key: &[u8; KEY_LENGTH]) -> String {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key)
+            .expect("HMAC accepts keys of any length");
+        mac.update(data);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
     /// Encrypt JSON data (helper)
     pub fn encrypt_json<T: serde::Serialize>(
         &self,
@@ -197,6 +246,53 @@ impl Default for CryptoService {
 mod tests {
     use super::*;
 
+    /// Deterministic RNG yielding 0, 1, 2, ... (wrapping)
+    struct CountingRng(u8);
+
+    impl RngCore for CountingRng {
+        fn next_u32(&mut self) -> u32 {
+            let mut bytes = [0u8; 4];
+            self.fill_bytes(&mut bytes);
+            u32::from_le_bytes(bytes)
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            let mut bytes = [0u8; 8];
+            self.fill_bytes(&mut bytes);
+            u64::from_le_bytes(bytes)
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            for byte in dest {
+                *byte = self.0;
+                self.0 = self.0.wrapping_add(1);
+            }
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), aes_gcm::aead::rand_core::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_deterministic_rng_golden_vector() {
+        // Vector cross-checked against an independent AES-256-GCM implementation
+        let service = CryptoService::with_rng(CountingRng(0));
+        let key = [0x42u8; 32];
+
+        let encrypted = service.encrypt_text("Hello, Jottery!", &key).unwrap();
+        assert_eq!(encrypted.nonce, "AAECAwQFBgcICQoL");
+        assert_eq!(encrypted.ciphertext, "Tbv9sd4A0kHycjB2o6R6Y/oTYSQbHoB+LcI87TnMxQ==");
+        assert_eq!(service.decrypt_text(&encrypted, &key).unwrap(), "Hello, Jottery!");
+
+        // Salt continues the same sequence
+        assert_eq!(
+            hex::encode(service.generate_salt()),
+            "0c0d0e0f101112131415161718191a1b1c1d1e1f202122232425262728292a2b"
+        );
+    }
+
     #[test]
     fn test_key_derivation() {
         let service = CryptoService::new();
@@ -302,4 +398,124 @@ mod tests {
         // Hash should be base64
         assert!(general_purpose::STANDARD.decode(&hash1).is_ok());
     }
+
+    // Samples below were produced the way the web app does it: PBKDF2-SHA256 (100,000
+    // iterations) for the key, Web Crypto AES-GCM with the tag appended to the ciphertext,
+    // and JSON using `iv` for the nonce.
+    const WEB_PASSWORD: &str = "correct horse battery staple";
+    const WEB_SALT_B64: &str = "ZGVmZ2hpamtsbW5vcHFyc3R1dnd4eXp7fH1+f4CBgoM=";
+
+    fn web_key(service: &CryptoService) -> Zeroizing<[u8; KEY_LENGTH]> {
+        let salt = general_purpose::STANDARD.decode(WEB_SALT_B64).unwrap();
+        service.derive_key(WEB_PASSWORD, &salt, 100_000).unwrap()
+    }
+
+    #[test]
+    fn test_web_app_key_derivation() {
+        let service = CryptoService::new();
+        assert_eq!(
+            hex::encode(web_key(&service)),
+            "048bb79154715b4e0f75b2afbe4b9326cdc510104b2472ab7be3ab5254dc0b34"
+        );
+    }
+
+    #[test]
+    fn test_decrypt_web_app_text() {
+        let service = CryptoService::new();
+        let key = web_key(&service);
+
+        let samples = [
+            (
+                r#"{"ciphertext":"7iFpsMH59L0kRKLIVv4UULVMTriYAJdF7ykR5HptDgHM0J9jN+B923yiUg==","iv":"oaKjpKWmp6ipqqus"}"#,
+                "Shopping list\n- milk\n- eggs",
+            ),
+            (
+                r#"{"ciphertext":"ftVoAx7zWWJg62KbwGiqUD6yhzUOgV+KFm6SLCNKbdFMnkxHoltyyY2CtlMyKQ==","iv":"oaKjpKWmp6ipqqus"}"#,
+                "Ünïcødé ✓ 日本語 🎉",
+            ),
+            (
+                r#"{"ciphertext":"yiZ0qxFVHQhRhCyi0V8I3a879m8=","iv":"oaKjpKWmp6ipqqus"}"#,
+                "work",
+            ),
+        ];
+
+        for (json, expected) in samples {
+            let encrypted: EncryptedData = serde_json::from_str(json).unwrap();
+            assert!(encrypted.tag.is_empty());
+            assert_eq!(service.decrypt_text(&encrypted, &key).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_decrypt_web_app_binary() {
+        let service = CryptoService::new();
+        let key = web_key(&service);
+
+        let encrypted: EncryptedData = serde_json::from_str(
+            r#"{"ciphertext":"vUgEP0+otLm6RctIji/eS2zegJw6","iv":"oaKjpKWmp6ipqqus"}"#,
+        )
+        .unwrap();
+        assert_eq!(service.decrypt_binary(&encrypted, &key).unwrap(), vec![0, 1, 2, 255, 254]);
+    }
+
+    #[test]
+    fn test_tui_output_uses_web_app_layout() {
+        let service = CryptoService::new();
+        let key = web_key(&service);
+
+        let encrypted = service.encrypt_text("round trip", &key).unwrap();
+        let json: serde_json::Value = serde_json::to_value(&encrypted).unwrap();
+        assert!(json.get("iv").is_some());
+        assert!(json.get("nonce").is_none());
+        assert!(json.get("tag").is_none());
+
+        // 10 bytes of plaintext + 16 byte GCM tag
+        let ciphertext = general_purpose::STANDARD.decode(&encrypted.ciphertext).unwrap();
+        assert_eq!(ciphertext.len(), 10 + 16);
+    }
+
+    #[test]
+    fn test_invalid_nonce_length_is_an_error() {
+        let service = CryptoService::new();
+        let key = web_key(&service);
+
+        let encrypted: EncryptedData = serde_json::from_str(
+            r#"{"ciphertext":"yiZ0qxFVHQhRhCyi0V8I3a879m8=","iv":"AAEC"}"#,
+        )
+        .unwrap();
+        assert!(service.decrypt_text(&encrypted, &key).is_err());
+    }
+
+    #[test]
+    fn test_decrypt_embedded_and_separate_tag() {
+        let service = CryptoService::new();
+        let key = web_key(&service);
+
+        // Tag appended to ciphertext (TUI and Web Crypto)
+        let embedded = EncryptedData::new(
+            "7iFpsMH59L0kRKLIVv4UULVMTriYAJdF7ykR5HptDgHM0J9jN+B923yiUg==".to_string(),
+            "oaKjpKWmp6ipqqus".to_string(),
+            String::new(),
+        );
+        // Same data with the last 16 bytes split out into `tag`
+        let separate = EncryptedData::new(
+            "7iFpsMH59L0kRKLIVv4UULVMTriYAJdF7ykR".to_string(),
+            "oaKjpKWmp6ipqqus".to_string(),
+            "5HptDgHM0J9jN+B923yiUg==".to_string(),
+        );
+
+        let expected = "Shopping list\n- milk\n- eggs";
+        assert_eq!(service.decrypt_text(&embedded, &key).unwrap(), expected);
+        assert_eq!(service.decrypt_text(&separate, &key).unwrap(), expected);
+
+        // Separate tag survives JSON with the web app field names
+        let json = r#"{"ciphertext":"7iFpsMH59L0kRKLIVv4UULVMTriYAJdF7ykR","iv":"oaKjpKWmp6ipqqus","tag":"5HptDgHM0J9jN+B923yiUg=="}"#;
+        let parsed: EncryptedData = serde_json::from_str(json).unwrap();
+        assert_eq!(service.decrypt_text(&parsed, &key).unwrap(), expected);
+
+        // A truncated tag is rejected rather than silently misparsed
+        let mut bad = separate.clone();
+        bad.tag = general_purpose::STANDARD.encode([0u8; 8]);
+        assert!(service.decrypt_text(&bad, &key).is_err());
+    }
 }
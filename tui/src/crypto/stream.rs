@@ -0,0 +1,195 @@
+//! Chunked AES-GCM for attachment blobs too large to encrypt in one piece
+//!
+//! A chunked blob is a manifest followed by sealed chunks, each `nonce || ciphertext+tag`.
+//! Every chunk authenticates the manifest, its own index and a caller-supplied context
+//! (the attachment id), so chunks cannot be reordered, dropped or moved between blobs.
+
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm, Nonce,
+};
+use anyhow::Result;
+
+use super::service::{CryptoService, NONCE_LENGTH, TAG_LENGTH};
+
+/// Plaintext bytes per chunk
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Bytes added to each chunk by sealing (nonce + GCM tag)
+pub const CHUNK_OVERHEAD: usize = NONCE_LENGTH + TAG_LENGTH;
+
+const MAGIC: &[u8; 4] = b"JTCK";
+const VERSION: u8 = 1;
+
+/// Layout of a chunked blob, stored at its start
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkManifest {
+    pub chunk_size: u32,
+    pub chunk_count: u32,
+    pub plaintext_len: u64,
+}
+
+impl ChunkManifest {
+    /// Encoded length: magic, version, chunk size, chunk count, plaintext length
+    pub const LEN: usize = 4 + 1 + 4 + 4 + 8;
+
+    /// Manifest for `plaintext_len` bytes in `CHUNK_SIZE` chunks
+    ///
+    /// Empty input still gets one (empty) chunk, so a truncated blob never looks valid.
+    pub fn for_len(plaintext_len: u64) -> Result<Self> {
+        let chunk_count = plaintext_len.div_ceil(CHUNK_SIZE as u64).max(1);
+        let chunk_count = u32::try_from(chunk_count)
+            .map_err(|_| anyhow::anyhow!("Attachment too large: {} bytes", plaintext_len))?;
+        Ok(Self {
+            chunk_size: CHUNK_SIZE as u32,
+            chunk_count,
+            plaintext_len,
+        })
+    }
+
+    /// Whether `blob` starts with a chunk manifest
+    pub fn is_chunked(blob: &[u8]) -> bool {
+        blob.starts_with(MAGIC)
+    }
+
+    pub fn to_bytes(self) -> [u8; Self::LEN] {
+        let mut bytes = [0u8; Self::LEN];
+        bytes[..4].copy_from_slice(MAGIC);
+        bytes[4] = VERSION;
+        bytes[5..9].copy_from_slice(&self.chunk_size.to_le_bytes());
+        bytes[9..13].copy_from_slice(&self.chunk_count.to_le_bytes());
+        bytes[13..].copy_from_slice(&self.plaintext_len.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < Self::LEN || !Self::is_chunked(bytes) {
+            anyhow::bail!("Not a chunked attachment");
+        }
+        if bytes[4] != VERSION {
+            anyhow::bail!("Unsupported chunked attachment version {}", bytes[4]);
+        }
+
+        let manifest = Self {
+            chunk_size: u32::from_le_bytes(bytes[5..9].try_into()?),
+            chunk_count: u32::from_le_bytes(bytes[9..13].try_into()?),
+            plaintext_len: u64::from_le_bytes(bytes[13..Self::LEN].try_into()?),
+        };
+
+        let expected = manifest.plaintext_len.div_ceil(manifest.chunk_size.max(1) as u64).max(1);
+        if manifest.chunk_size == 0 || manifest.chunk_count as u64 != expected {
+            anyhow::bail!("Corrupted chunked attachment manifest");
+        }
+        Ok(manifest)
+    }
+
+    /// Plaintext length of chunk `index` (only the last one may be short)
+    pub fn chunk_len(&self, index: u32) -> usize {
+        let start = index as u64 * self.chunk_size as u64;
+        (self.plaintext_len - start).min(self.chunk_size as u64) as usize
+    }
+
+    /// Byte offset of sealed chunk `index` within the blob
+    pub fn chunk_offset(&self, index: u32) -> usize {
+        Self::LEN + index as usize * (self.chunk_size as usize + CHUNK_OVERHEAD)
+    }
+
+    /// Total blob length: manifest plus every sealed chunk
+    pub fn blob_len(&self) -> usize {
+        Self::LEN + self.plaintext_len as usize + self.chunk_count as usize * CHUNK_OVERHEAD
+    }
+
+    /// Additional authenticated data for chunk `index`
+    fn aad(&self, context: &[u8], index: u32) -> Vec<u8> {
+        let mut aad = Vec::with_capacity(Self::LEN + 4 + context.len());
+        aad.extend_from_slice(&self.to_bytes());
+        aad.extend_from_slice(&index.to_le_bytes());
+        aad.extend_from_slice(context);
+        aad
+    }
+}
+
+impl CryptoService {
+    /// Encrypt chunk `index` of a chunked blob under a fresh nonce
+    pub fn seal_chunk(
+        &self,
+        manifest: &ChunkManifest,
+        context: &[u8],
+        index: u32,
+        plaintext: &[u8],
+        key: &[u8; 32],
+    ) -> Result<Vec<u8>> {
+        let cipher = Aes256Gcm::new(key.into());
+        let nonce = self.generate_nonce();
+        let aad = manifest.aad(context, index);
+
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: plaintext, aad: &aad })
+            .map_err(|_| anyhow::anyhow!("Encryption failed"))?;
+
+        let mut sealed = Vec::with_capacity(NONCE_LENGTH + ciphertext.len());
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    /// Decrypt chunk `index` of a chunked blob
+    pub fn open_chunk(
+        &self,
+        manifest: &ChunkManifest,
+        context: &[u8],
+        index: u32,
+        sealed: &[u8],
+        key: &[u8; 32],
+    ) -> Result<Vec<u8>> {
+        if sealed.len() < CHUNK_OVERHEAD {
+            anyhow::bail!("Chunk {} is truncated", index);
+        }
+        let cipher = Aes256Gcm::new(key.into());
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LENGTH);
+        let aad = manifest.aad(context, index);
+
+        cipher
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: &aad })
+            .map_err(|_| anyhow::anyhow!("Decryption failed for chunk {}. Invalid key or corrupted data.", index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_layout() {
+        let manifest = ChunkManifest::for_len(CHUNK_SIZE as u64 * 2 + 5).unwrap();
+        assert_eq!(manifest.chunk_count, 3);
+        assert_eq!(manifest.chunk_len(0), CHUNK_SIZE);
+        assert_eq!(manifest.chunk_len(2), 5);
+        assert_eq!(manifest.chunk_offset(1), ChunkManifest::LEN + CHUNK_SIZE + CHUNK_OVERHEAD);
+        assert_eq!(manifest.blob_len(), ChunkManifest::LEN + CHUNK_SIZE * 2 + 5 + 3 * CHUNK_OVERHEAD);
+        assert_eq!(ChunkManifest::from_bytes(&manifest.to_bytes()).unwrap(), manifest);
+
+        // Empty input still has a chunk to authenticate
+        assert_eq!(ChunkManifest::for_len(0).unwrap().chunk_count, 1);
+
+        // Count inconsistent with the length is rejected
+        let mut bytes = manifest.to_bytes();
+        bytes[9] = 2;
+        assert!(ChunkManifest::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_chunks_are_bound_to_index_and_context() {
+        let service = CryptoService::new();
+        let key = [7u8; 32];
+        let manifest = ChunkManifest::for_len(10).unwrap();
+
+        let sealed = service.seal_chunk(&manifest, b"a", 0, b"0123456789", &key).unwrap();
+        assert_eq!(sealed.len(), 10 + CHUNK_OVERHEAD);
+        assert_eq!(service.open_chunk(&manifest, b"a", 0, &sealed, &key).unwrap(), b"0123456789");
+
+        assert!(service.open_chunk(&manifest, b"b", 0, &sealed, &key).is_err());
+        assert!(service.open_chunk(&manifest, b"a", 1, &sealed, &key).is_err());
+        assert!(service.open_chunk(&manifest, b"a", 0, &sealed, &[8u8; 32]).is_err());
+    }
+}
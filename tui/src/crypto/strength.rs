@@ -0,0 +1,138 @@
+//! Rough password strength estimate for the create-database screen
+//! Counts character classes and length, discounting repeats, sequences and common passwords
+
+/// Environment variable overriding the minimum strength score (0-4) for new passwords
+pub const ENV_MIN_PASSWORD_STRENGTH: &str = "JOTTERY_MIN_PASSWORD_STRENGTH";
+
+/// Minimum score accepted without confirmation when the variable is unset
+pub const DEFAULT_MIN_PASSWORD_SCORE: u8 = 2;
+
+/// Passwords (and bases of passwords) that are guessed first
+const COMMON: &[&str] = &[
+    "password", "passw0rd", "123456", "12345678", "123456789", "qwerty", "qwertyuiop",
+    "letmein", "welcome", "admin", "iloveyou", "monkey", "dragon", "abc123", "111111",
+    "sunshine", "football", "baseball", "master", "secret", "jottery",
+];
+
+/// Strength bands, weakest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PasswordStrength {
+    VeryWeak,
+    Weak,
+    Fair,
+    Strong,
+    VeryStrong,
+}
+
+impl PasswordStrength {
+    /// Estimate the strength of `password`
+    pub fn estimate(password: &str) -> Self {
+        let bits = estimate_bits(password);
+        if bits < 28.0 {
+            Self::VeryWeak
+        } else if bits < 40.0 {
+            Self::Weak
+        } else if bits < 60.0 {
+            Self::Fair
+        } else if bits < 80.0 {
+            Self::Strong
+        } else {
+            Self::VeryStrong
+        }
+    }
+
+    /// Score from 0 (very weak) to 4 (very strong)
+    pub fn score(self) -> u8 {
+        self as u8
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::VeryWeak => "Very weak",
+            Self::Weak => "Weak",
+            Self::Fair => "Fair",
+            Self::Strong => "Strong",
+            Self::VeryStrong => "Very strong",
+        }
+    }
+
+    /// Minimum score from `JOTTERY_MIN_PASSWORD_STRENGTH`, clamped to 0-4
+    pub fn min_score_from_env() -> u8 {
+        std::env::var(ENV_MIN_PASSWORD_STRENGTH)
+            .ok()
+            .and_then(|v| v.trim().parse::<u8>().ok())
+            .map(|score| score.min(Self::VeryStrong.score()))
+            .unwrap_or(DEFAULT_MIN_PASSWORD_SCORE)
+    }
+}
+
+/// Approximate entropy in bits
+fn estimate_bits(password: &str) -> f64 {
+    let lower = password.to_lowercase();
+    if COMMON.iter().any(|common| lower == *common) {
+        return 0.0;
+    }
+
+    let mut pool = 0u32;
+    if password.chars().any(|c| c.is_ascii_lowercase()) {
+        pool += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_uppercase()) {
+        pool += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_digit()) {
+        pool += 10;
+    }
+    if password.chars().any(|c| c.is_ascii_punctuation() || c == ' ') {
+        pool += 33;
+    }
+    if !password.is_ascii() {
+        pool += 100;
+    }
+    if pool == 0 {
+        return 0.0;
+    }
+
+    // Characters that repeat or continue a run (aaa, abc, 321) add little
+    let chars: Vec<char> = password.chars().collect();
+    let mut effective: f64 = 0.0;
+    for (i, &c) in chars.iter().enumerate() {
+        let predictable = i > 0 && {
+            let step = c as i64 - chars[i - 1] as i64;
+            step.abs() <= 1
+        };
+        effective += if predictable { 0.25 } else { 1.0 };
+    }
+
+    // A common password with a few characters tacked on is still weak:
+    // count the embedded word as a single character
+    if let Some(common) = COMMON
+        .iter()
+        .filter(|common| common.len() >= 5 && lower.contains(*common))
+        .max_by_key(|common| common.len())
+    {
+        effective -= common.len() as f64 - 1.0;
+    }
+
+    effective.max(0.0) * (pool as f64).log2()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_orders_passwords() {
+        assert_eq!(PasswordStrength::estimate(""), PasswordStrength::VeryWeak);
+        assert_eq!(PasswordStrength::estimate("Password"), PasswordStrength::VeryWeak);
+        assert_eq!(PasswordStrength::estimate("aaaaaaaaaaaa"), PasswordStrength::VeryWeak);
+        assert_eq!(PasswordStrength::estimate("abcdefgh12345"), PasswordStrength::VeryWeak);
+        assert!(PasswordStrength::estimate("password2024!") < PasswordStrength::Fair);
+        assert!(PasswordStrength::estimate("tulip-river") >= PasswordStrength::Fair);
+        assert!(PasswordStrength::estimate("Kx9#mQ2$vL7!pR4z") >= PasswordStrength::Strong);
+        assert_eq!(
+            PasswordStrength::estimate("correct horse battery staple orbit"),
+            PasswordStrength::VeryStrong
+        );
+    }
+}
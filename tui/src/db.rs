@@ -1,25 +1,184 @@
 use anyhow::{Context, Result};
-use rusqlite::{Connection, OpenFlags};
+use rusqlite::{params, Connection, OpenFlags};
 use std::path::Path;
 use tracing::{debug, info};
 
+/// Schema migrations, applied in order to bring a database up to the latest version
+///
+/// To change the schema, add the next numbered file under `migrations/` and append it
+/// here; never edit a migration that has shipped. The runner records each version.
+const MIGRATIONS: &[(i32, &str)] = &[
+    (1, include_str!("../migrations/001_initial.sql")),
+    (2, include_str!("../migrations/002_trailing_newline.sql")),
+    (3, include_str!("../migrations/003_integrity_chain.sql")),
+    (4, include_str!("../migrations/004_sync_pull_only.sql")),
+    (5, include_str!("../migrations/005_conflict_policy.sql")),
+    (6, include_str!("../migrations/006_syntax_highlighting.sql")),
+    (7, include_str!("../migrations/007_sync_conflicts.sql")),
+    (8, include_str!("../migrations/008_sync_max_attempts.sql")),
+    (9, include_str!("../migrations/009_note_list_layout.sql")),
+    (10, include_str!("../migrations/010_emoji_icons.sql")),
+    (11, include_str!("../migrations/011_keyfile.sql")),
+    (12, include_str!("../migrations/012_note_previews.sql")),
+    (13, include_str!("../migrations/013_autosave.sql")),
+    (14, include_str!("../migrations/014_note_history.sql")),
+    (15, include_str!("../migrations/015_reminders.sql")),
+    (16, include_str!("../migrations/016_pin_limit.sql")),
+    (17, include_str!("../migrations/017_sync_endpoint_encrypted.sql")),
+    (18, include_str!("../migrations/018_integrity_digest.sql")),
+];
+
+/// Full-text index over decrypted notes
+///
+/// Lives in the temp schema (kept in memory) so plaintext never reaches disk; it is
+/// rebuilt on unlock and kept current by `NoteRepository`. Trigram tokens let it match
+/// anywhere inside words, like the in-memory search.
+const SEARCH_INDEX_SQL: &str = "
+    PRAGMA temp_store = MEMORY;
+    CREATE VIRTUAL TABLE IF NOT EXISTS temp.notes_fts USING fts5(note_id UNINDEXED, content, tags, tokenize = 'trigram');
+";
+
+/// SQLCipher settings a database file was created with
+///
+/// A file only opens with the settings it was written under, so `Database::open`
+/// tries each of [`CipherParams::KNOWN`] in turn and keeps the first that reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CipherParams {
+    /// PBKDF2 iterations SQLCipher derives the file key with
+    ///
+    /// Separate from the content key's `EncryptionMetadata::iterations`.
+    pub kdf_iter: u32,
+    pub page_size: u32,
+    /// HMAC-SHA1 pages and PBKDF2-HMAC-SHA1, as written by SQLCipher 3
+    pub sha1: bool,
+}
+
+impl CipherParams {
+    /// Settings new databases are created with (SQLCipher 4 defaults)
+    pub const DEFAULT: Self = Self { kdf_iter: 256_000, page_size: 4096, sha1: false };
+
+    /// Settings tried when opening an existing file, most likely first
+    pub const KNOWN: &'static [Self] = &[
+        Self::DEFAULT,
+        // SQLCipher 4 with the iteration count of older builds
+        Self { kdf_iter: 64_000, page_size: 4096, sha1: false },
+        Self { kdf_iter: 256_000, page_size: 1024, sha1: false },
+        // SQLCipher 3 defaults
+        Self { kdf_iter: 64_000, page_size: 1024, sha1: true },
+    ];
+
+    /// Apply to a connection that has been keyed but not read yet
+    fn apply(&self, conn: &Connection) -> Result<()> {
+        conn.pragma_update(None, "cipher_page_size", self.page_size)?;
+        conn.pragma_update(None, "kdf_iter", self.kdf_iter)?;
+        if self.sha1 {
+            conn.pragma_update(None, "cipher_hmac_algorithm", "HMAC_SHA1")?;
+            conn.pragma_update(None, "cipher_kdf_algorithm", "PBKDF2_HMAC_SHA1")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for CipherParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "kdf_iter={}, cipher_page_size={}", self.kdf_iter, self.page_size)?;
+        if self.sha1 {
+            write!(f, ", SHA1")?;
+        }
+        Ok(())
+    }
+}
+
+/// Schema version after all migrations have run
+pub const LATEST_SCHEMA_VERSION: i32 = MIGRATIONS[MIGRATIONS.len() - 1].0;
+
 /// Database manager for Jottery TUI
 pub struct Database {
     conn: Connection,
+    cipher: CipherParams,
 }
 
 impl Database {
     /// Open or create a new encrypted database
     ///
+    /// An existing file that doesn't open with the default SQLCipher settings is retried
+    /// with the others in [`CipherParams::KNOWN`]; [`cipher_params`](Self::cipher_params)
+    /// reports which one worked. A wrong password therefore costs one attempt per entry.
+    ///
     /// # Arguments
     /// * `path` - Path to the database file
     /// * `password` - Password for SQLCipher encryption (derived from user password)
     pub fn open<P: AsRef<Path>>(path: P, password: &str) -> Result<Self> {
+        Self::open_with(path, password, CipherParams::KNOWN)
+    }
+
+    /// Open or create a database, trying each of `candidates` until one reads the file
+    ///
+    /// New files are always created with the first candidate.
+    pub fn open_with<P: AsRef<Path>>(path: P, password: &str, candidates: &[CipherParams]) -> Result<Self> {
         let path = path.as_ref();
         let is_new = !path.exists();
 
         info!("Opening database at: {}", path.display());
 
+        let candidates = if is_new { &candidates[..candidates.len().min(1)] } else { candidates };
+        let mut last_error = None;
+        let mut opened = None;
+        for &cipher in candidates {
+            match Self::connect(path, password, cipher) {
+                Ok(conn) => {
+                    opened = Some((conn, cipher));
+                    break;
+                }
+                Err(e) => {
+                    debug!("Database did not open with {}: {:#}", cipher, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        let (conn, cipher) = match (opened, last_error) {
+            (Some(opened), _) => opened,
+            (None, Some(e)) => {
+                return Err(e.context("Failed to access database (wrong password or corrupted database)"))
+            }
+            (None, None) => anyhow::bail!("No SQLCipher settings to open the database with"),
+        };
+
+        if cipher == CipherParams::DEFAULT {
+            debug!("SQLCipher encryption enabled");
+        } else {
+            info!("Database opened with non-default SQLCipher settings: {}", cipher);
+        }
+
+        // WAL lets reads proceed during writes and batches fsyncs; it has to be
+        // enabled after keying, since SQLCipher can't read the header before that
+        let journal_mode: String = conn
+            .pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get(0))
+            .context("Failed to enable WAL journal mode")?;
+        if !journal_mode.eq_ignore_ascii_case("wal") {
+            debug!("WAL unavailable, using journal mode: {}", journal_mode);
+        }
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        conn.pragma_update(None, "busy_timeout", 5000)?;
+
+        let mut db = Self { conn, cipher };
+
+        // Create the schema for a new database, or upgrade an existing one
+        if is_new {
+            info!("New database detected, running migrations...");
+        } else {
+            debug!("Existing database opened successfully");
+        }
+        db.run_migrations()?;
+        db.conn
+            .execute_batch(SEARCH_INDEX_SQL)
+            .context("Failed to create search index")?;
+
+        Ok(db)
+    }
+
+    /// Open a connection keyed with `password` under `cipher` and check it can read the file
+    fn connect(path: &Path, password: &str, cipher: CipherParams) -> Result<Connection> {
         let conn = Connection::open_with_flags(
             path,
             OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE,
@@ -29,29 +188,18 @@ impl Database {
         // Enable SQLCipher encryption
         conn.pragma_update(None, "key", password)
             .context("Failed to set database encryption key")?;
-
-        // Configure SQLCipher settings (matching web app security level)
-        // Use SQLCipher 4.x defaults for compatibility
-        conn.pragma_update(None, "cipher_page_size", 4096)?;
-        conn.pragma_update(None, "kdf_iter", 256000)?; // PBKDF2 iterations
+        cipher.apply(&conn)?;
 
         // Verify the database is accessible (this will fail if wrong password)
         conn.pragma_query(None, "user_version", |_| Ok(()))
-            .context("Failed to access database (wrong password or corrupted database)")?;
+            .context("Failed to access database")?;
 
-        debug!("SQLCipher encryption enabled");
-
-        let mut db = Self { conn };
-
-        // Run migrations if this is a new database
-        if is_new {
-            info!("New database detected, running migrations...");
-            db.run_migrations()?;
-        } else {
-            debug!("Existing database opened successfully");
-        }
+        Ok(conn)
+    }
 
-        Ok(db)
+    /// SQLCipher settings the database was opened with
+    pub fn cipher_params(&self) -> CipherParams {
+        self.cipher
     }
 
     /// Create an in-memory database (for testing)
@@ -63,24 +211,49 @@ impl Database {
 
         conn.pragma_update(None, "key", password)?;
 
-        let mut db = Self { conn };
+        let mut db = Self { conn, cipher: CipherParams::DEFAULT };
         db.run_migrations()?;
+        db.conn
+            .execute_batch(SEARCH_INDEX_SQL)
+            .context("Failed to create search index")?;
 
         Ok(db)
     }
 
-    /// Run database migrations
+    /// Run pending database migrations
     fn run_migrations(&mut self) -> Result<()> {
-        info!("Running database migrations...");
-
-        // Read and execute the migration SQL
-        let migration_sql = include_str!("../migrations/001_initial.sql");
+        let has_schema: bool = self
+            .conn
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'schema_version'",
+                [],
+                |row| row.get::<_, i64>(0),
+            )
+            .context("Failed to check schema version")?
+            > 0;
+        let current = if has_schema { self.schema_version()? } else { 0 };
+        if current > LATEST_SCHEMA_VERSION {
+            anyhow::bail!(
+                "Database schema version {} is newer than this build supports ({}); upgrade jottery",
+                current,
+                LATEST_SCHEMA_VERSION
+            );
+        }
 
-        self.conn
-            .execute_batch(migration_sql)
-            .context("Failed to run migrations")?;
+        for (version, migration_sql) in MIGRATIONS.iter().filter(|(v, _)| *v > current) {
+            info!("Running database migration {}...", version);
+
+            let tx = self.conn.transaction()?;
+            tx.execute_batch(migration_sql)
+                .with_context(|| format!("Failed to run migration {}", version))?;
+            tx.execute(
+                "INSERT OR IGNORE INTO schema_version (version, applied_at) VALUES (?1, datetime('now'))",
+                params![version],
+            )?;
+            tx.commit()?;
+        }
 
-        info!("Migrations completed successfully");
+        debug!("Database schema at version {}", LATEST_SCHEMA_VERSION);
         Ok(())
     }
 
@@ -112,6 +285,68 @@ impl Database {
         Ok(count > 0)
     }
 
+    /// Run SQLite's structural check and SQLCipher's page HMAC check,
+    /// returning the problems found (empty when the file is healthy)
+    pub fn integrity_check(&self) -> Result<Vec<String>> {
+        let rows = |sql: &str| -> rusqlite::Result<Vec<String>> {
+            let mut stmt = self.conn.prepare(sql)?;
+            let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+            rows.collect()
+        };
+
+        let mut problems = Vec::new();
+
+        // A badly damaged file can make the check itself fail; that is a finding too
+        match rows("PRAGMA integrity_check") {
+            Ok(rows) => problems.extend(rows.into_iter().filter(|row| row != "ok")),
+            Err(e) => problems.push(format!("integrity_check failed: {}", e)),
+        }
+
+        // One row per page that fails authentication; none when every page is intact
+        match rows("PRAGMA cipher_integrity_check") {
+            Ok(rows) => problems.extend(rows.into_iter().map(|row| format!("cipher: {}", row))),
+            Err(e) => problems.push(format!("cipher_integrity_check failed: {}", e)),
+        }
+
+        Ok(problems)
+    }
+
+    /// Write a consistent copy of the database to `output`, encrypted with `password`
+    ///
+    /// Uses `sqlcipher_export`, which reads a single snapshot of the live database
+    /// (including any pages still in the WAL). `output` must not already exist.
+    pub fn backup_to<P: AsRef<Path>>(&self, output: P, password: &str) -> Result<()> {
+        let output = output.as_ref();
+        if output.exists() {
+            anyhow::bail!("Backup target already exists: {}", output.display());
+        }
+        let target = output
+            .to_str()
+            .ok_or_else(|| anyhow::anyhow!("Backup path is not valid UTF-8: {}", output.display()))?;
+
+        info!("Backing up database to: {}", output.display());
+        self.conn
+            .execute("ATTACH DATABASE ?1 AS backup KEY ?2", params![target, password])
+            .context("Failed to create backup file")?;
+        let exported = self
+            .conn
+            .query_row("SELECT sqlcipher_export('backup')", [], |_| Ok(()))
+            .context("Failed to export database");
+        self.conn
+            .execute("DETACH DATABASE backup", [])
+            .context("Failed to close backup file")?;
+        exported
+    }
+
+    /// Change the SQLCipher encryption key (re-encrypts every page of the file)
+    pub fn rekey(&self, new_password: &str) -> Result<()> {
+        info!("Rekeying database...");
+        self.conn
+            .pragma_update(None, "rekey", new_password)
+            .context("Failed to rekey database")?;
+        Ok(())
+    }
+
     /// Get the inner connection (for repository use)
     pub fn connection(&self) -> &Connection {
         &self.conn
@@ -161,10 +396,19 @@ impl Database {
         self.conn
             .execute("VACUUM", [])
             .context("Failed to vacuum database")?;
+        // In WAL mode the rewritten pages land in the WAL; fold them back so the file shrinks
+        self.checkpoint()?;
         info!("Database vacuumed successfully");
         Ok(())
     }
 
+    /// Copy the write-ahead log into the main file and truncate it
+    pub fn checkpoint(&self) -> Result<()> {
+        self.conn
+            .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+            .context("Failed to checkpoint database")
+    }
+
     /// Get database file size in bytes
     pub fn file_size<P: AsRef<Path>>(path: P) -> Result<u64> {
         let metadata = std::fs::metadata(path.as_ref())
@@ -181,7 +425,7 @@ mod tests {
     fn test_in_memory_database() {
         let db = Database::in_memory("test_password").unwrap();
         assert!(db.is_initialized().unwrap() == false);
-        assert_eq!(db.schema_version().unwrap(), 1);
+        assert_eq!(db.schema_version().unwrap(), LATEST_SCHEMA_VERSION);
         assert_eq!(db.count_notes(false).unwrap(), 0);
     }
 
@@ -200,6 +444,38 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_open_falls_back_to_known_cipher_params() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let older = CipherParams { kdf_iter: 64_000, page_size: 4096, sha1: false };
+
+        {
+            let db = Database::open_with(&db_path, "password", &[older]).unwrap();
+            assert_eq!(db.cipher_params(), older);
+            db.connection()
+                .execute_batch(
+                    "INSERT INTO notes (id, created_at, modified_at, content, tags, attachments)
+                     VALUES ('n1', 'now', 'now', '{}', '{}', '[]')",
+                )
+                .unwrap();
+        }
+
+        assert!(Database::open_with(&db_path, "password", &[CipherParams::DEFAULT]).is_err());
+        assert!(Database::open(&db_path, "wrong_password").is_err());
+
+        let db = Database::open(&db_path, "password").unwrap();
+        assert_eq!(db.cipher_params(), older);
+        assert_eq!(db.count_notes(true).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_new_database_uses_default_cipher_params() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db = Database::open(temp_dir.path().join("test.db"), "password").unwrap();
+        assert_eq!(db.cipher_params(), CipherParams::DEFAULT);
+    }
+
     #[test]
     fn test_database_persistence() {
         let temp_dir = tempfile::tempdir().unwrap();
@@ -208,13 +484,152 @@ mod tests {
         // Create and close database
         {
             let db = Database::open(&db_path, "password").unwrap();
-            assert_eq!(db.schema_version().unwrap(), 1);
+            assert_eq!(db.schema_version().unwrap(), LATEST_SCHEMA_VERSION);
         }
 
         // Reopen and verify
         {
             let db = Database::open(&db_path, "password").unwrap();
-            assert_eq!(db.schema_version().unwrap(), 1);
+            assert_eq!(db.schema_version().unwrap(), LATEST_SCHEMA_VERSION);
+        }
+    }
+
+    #[test]
+    fn test_integrity_check_detects_corrupt_page() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+
+        {
+            let db = Database::open(&db_path, "password").unwrap();
+            assert!(db.integrity_check().unwrap().is_empty());
         }
+
+        // Flip bytes in the middle of the second page
+        let mut bytes = std::fs::read(&db_path).unwrap();
+        for b in &mut bytes[4096 + 2000..4096 + 2016] {
+            *b ^= 0xff;
+        }
+        std::fs::write(&db_path, bytes).unwrap();
+
+        let db = Database::open(&db_path, "password").unwrap();
+        assert!(!db.integrity_check().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_open_uses_wal() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db = Database::open(temp_dir.path().join("test.db"), "password").unwrap();
+        let journal_mode: String = db
+            .connection()
+            .pragma_query_value(None, "journal_mode", |row| row.get(0))
+            .unwrap();
+        assert_eq!(journal_mode, "wal");
+    }
+
+    #[test]
+    fn test_backup_opens_with_same_password() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let backup_path = temp_dir.path().join("backup.db");
+
+        let db = Database::open(&db_path, "password").unwrap();
+        db.connection()
+            .execute_batch(
+                "INSERT INTO notes (id, created_at, modified_at, content, tags, attachments)
+                 VALUES ('n1', 'now', 'now', '{}', '{}', '[]')",
+            )
+            .unwrap();
+        db.backup_to(&backup_path, "password").unwrap();
+        assert!(db.backup_to(&backup_path, "password").is_err());
+
+        assert!(Database::open(&backup_path, "wrong_password").is_err());
+        let backup = Database::open(&backup_path, "password").unwrap();
+        assert_eq!(backup.schema_version().unwrap(), LATEST_SCHEMA_VERSION);
+        assert_eq!(backup.count_notes(true).unwrap(), 1);
+        assert!(backup.integrity_check().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_upgrade_from_initial_schema() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(MIGRATIONS[0].1).unwrap();
+        let mut db = Database { conn, cipher: CipherParams::DEFAULT };
+        assert_eq!(db.schema_version().unwrap(), 1);
+        db.connection()
+            .execute("UPDATE settings SET sync_endpoint = 'https://sync.example.com'", [])
+            .unwrap();
+
+        db.run_migrations().unwrap();
+        assert_eq!(db.schema_version().unwrap(), LATEST_SCHEMA_VERSION);
+
+        // Re-running is a no-op
+        db.run_migrations().unwrap();
+        let trailing_newline: String = db
+            .connection()
+            .query_row("SELECT trailing_newline FROM settings WHERE id = 1", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(trailing_newline, "single");
+
+        // Columns added by later migrations exist with their defaults
+        let sync_max_attempts: i64 = db
+            .connection()
+            .query_row("SELECT sync_max_attempts FROM settings WHERE id = 1", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(sync_max_attempts, 3);
+
+        // The note list layout round-trips through the settings repository
+        let settings_repo = crate::repository::SettingsRepository::new(db.connection());
+        let mut settings = settings_repo.get().unwrap();
+        assert_eq!((settings.note_list_width, settings.show_preview), (42, true));
+        settings.note_list_width = 60;
+        settings.show_preview = false;
+        settings_repo.update(&settings).unwrap();
+        let settings = settings_repo.get().unwrap();
+        assert_eq!((settings.note_list_width, settings.show_preview), (60, false));
+        assert_eq!(settings.autosave_seconds, 5);
+        assert_eq!(settings.pin_limit, 0);
+
+        // The endpoint moved out of the settings table, waiting to be sealed on unlock
+        let endpoints: (Option<String>, String) = db
+            .connection()
+            .query_row(
+                "SELECT settings.sync_endpoint, sync_metadata.sync_endpoint FROM settings, sync_metadata",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(endpoints, (None, "PLAINTEXT:https://sync.example.com".to_string()));
+
+        // Every version is recorded once, with a timestamp
+        let recorded: Vec<(i32, String)> = db
+            .connection()
+            .prepare("SELECT version, applied_at FROM schema_version ORDER BY version")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let expected: Vec<i32> = MIGRATIONS.iter().map(|(v, _)| *v).collect();
+        assert_eq!(recorded.iter().map(|(v, _)| *v).collect::<Vec<_>>(), expected);
+        assert!(recorded.iter().all(|(_, applied_at)| !applied_at.is_empty()));
+    }
+
+    #[test]
+    fn test_migrations_are_numbered_in_order() {
+        for (i, (version, _)) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(*version, i as i32 + 1);
+        }
+    }
+
+    #[test]
+    fn test_newer_schema_is_rejected() {
+        let mut db = Database::in_memory("test_password").unwrap();
+        db.connection()
+            .execute(
+                "INSERT INTO schema_version (version, applied_at) VALUES (?1, datetime('now'))",
+                params![LATEST_SCHEMA_VERSION + 1],
+            )
+            .unwrap();
+        assert!(db.run_migrations().is_err());
     }
 }
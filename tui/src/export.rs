@@ -2,18 +2,31 @@
 /// Matches web app export format
 
 use anyhow::{Context, Result};
-use chrono::Utc;
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{hash_map::Entry, HashMap, HashSet};
 use std::fs::File;
 use std::io::{BufReader, BufWriter};
-use std::path::Path;
+use std::ffi::OsStr;
+use std::path::{Component, Path, PathBuf};
+use uuid::Uuid;
+use zeroize::Zeroizing;
 
 use crate::{
+    crypto::CryptoService,
     db::Database,
-    models::Note,
-    repository::NoteRepository,
+    models::{encryption::{EncryptedData, EncryptionMetadata}, Attachment, Note},
+    repository::{parse_encrypted, AttachmentRepository, NoteRepository},
 };
 
+/// PBKDF2 iterations for keys derived from an export password
+const EXPORT_ITERATIONS: u32 = EncryptionMetadata::DEFAULT_ITERATIONS;
+
+/// Attachments larger than this go to a sidecar folder instead of inline base64
+const INLINE_ATTACHMENT_LIMIT: usize = 64 * 1024;
+
 /// Export format (matches web app)
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ExportData {
@@ -38,6 +51,26 @@ pub struct ExportNote {
     pub word_wrap: Option<bool>,
     #[serde(rename = "syntaxLanguage")]
     pub syntax_language: Option<String>,
+    #[serde(rename = "reminderAt", default, skip_serializing_if = "Option::is_none")]
+    pub reminder_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<ExportAttachment>,
+}
+
+/// Exported attachment: small blobs inline as base64, large ones as a sidecar file
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportAttachment {
+    pub id: String,
+    pub filename: String,
+    #[serde(rename = "mimeType")]
+    pub mime_type: String,
+    pub size: i64,
+    /// Base64-encoded contents
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+    /// Path of the contents relative to the export file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
 }
 
 impl From<&Note> for ExportNote {
@@ -51,23 +84,70 @@ impl From<&Note> for ExportNote {
             pinned: note.pinned,
             word_wrap: Some(note.word_wrap),
             syntax_language: Some(note.syntax_language.to_string()),
+            reminder_at: note.reminder_at.map(|dt| dt.to_rfc3339()),
+            attachments: Vec::new(),
         }
     }
 }
 
+/// Which notes to include in an export (the default includes all)
+#[derive(Debug, Default)]
+pub struct ExportFilter {
+    /// Required tags (case-insensitive)
+    pub tags: Vec<String>,
+    /// Accept notes with any of `tags` instead of all of them
+    pub any_tag: bool,
+    /// Only notes modified at or after this time
+    pub since: Option<DateTime<Utc>>,
+    /// Only notes modified at or before this time
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl ExportFilter {
+    /// Whether `note` passes every configured filter
+    pub fn matches(&self, note: &Note) -> bool {
+        let has_tag = |wanted: &String| note.tags.iter().any(|t| t.eq_ignore_ascii_case(wanted));
+        let tags_ok = self.tags.is_empty()
+            || if self.any_tag {
+                self.tags.iter().any(has_tag)
+            } else {
+                self.tags.iter().all(has_tag)
+            };
+
+        tags_ok
+            && self.since.is_none_or(|since| note.modified_at >= since)
+            && self.until.is_none_or(|until| note.modified_at <= until)
+    }
+}
+
 /// Export notes to JSON file
+///
+/// Attachments over `INLINE_ATTACHMENT_LIMIT` are written to `<name>.attachments/` next to it.
 pub fn export_notes<P: AsRef<Path>>(
     db: &Database,
     key: &[u8; 32],
     path: P,
+    filter: &ExportFilter,
 ) -> Result<usize> {
     let repo = NoteRepository::new(db.connection());
+    let attachment_repo = AttachmentRepository::new(db.connection());
+    let sidecar = sidecar_dir(path.as_ref());
 
     // Load all notes (including deleted for complete backup)
-    let notes = repo.list(true, key)?;
+    let mut notes = repo.list(true, key)?;
+    notes.retain(|note| filter.matches(note));
 
     // Convert to export format
-    let export_notes: Vec<ExportNote> = notes.iter().map(ExportNote::from).collect();
+    let mut export_notes = Vec::with_capacity(notes.len());
+    for note in &notes {
+        let mut export_note = ExportNote::from(note);
+        for attachment in &note.attachments {
+            export_note
+                .attachments
+                .push(export_attachment(&attachment_repo, attachment, key, &sidecar)?);
+        }
+        export_notes.push(export_note);
+    }
 
     let export_data = ExportData {
         version: "1.0".to_string(),
@@ -85,63 +165,927 @@ pub fn export_notes<P: AsRef<Path>>(
     Ok(notes.len())
 }
 
+/// Outcome of a JSON import
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ImportStats {
+    /// Notes created or updated
+    pub imported: usize,
+    /// Notes merged into an existing note with the same content (only with `dedup`)
+    pub duplicates: usize,
+}
+
+/// Shape of a JSON import file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// `ExportData` as written by `export_notes`
+    Jottery,
+    /// The web app's export: attachments without ids or sizes, and content that may
+    /// still be encrypted with the web app's key
+    WebApp,
+}
+
+impl std::str::FromStr for ImportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "jottery" => Ok(Self::Jottery),
+            "webapp" | "web" => Ok(Self::WebApp),
+            _ => Err(format!("Unknown import format: {} (expected jottery or webapp)", s)),
+        }
+    }
+}
+
+/// How `import_notes` reads and merges a file
+#[derive(Default)]
+pub struct ImportOptions {
+    /// File shape; detected from the contents when unset
+    pub format: Option<ImportFormat>,
+    /// Merge notes whose content matches an existing note (see [`merge_duplicate`])
+    pub dedup: bool,
+    /// Web app key, for notes whose content and tags are still encrypted
+    pub source_key: Option<Zeroizing<[u8; 32]>>,
+}
+
 /// Import notes from JSON file
+///
+/// With `dedup`, a note whose content matches an existing or earlier imported note under
+/// another id is merged into it instead of creating a copy (see [`merge_duplicate`]).
 pub fn import_notes<P: AsRef<Path>>(
     db: &Database,
     key: &[u8; 32],
     path: P,
-) -> Result<usize> {
+    options: &ImportOptions,
+) -> Result<ImportStats> {
     let file = File::open(path.as_ref())
         .context("Failed to open import file")?;
     let reader = BufReader::new(file);
 
-    let export_data: ExportData = serde_json::from_reader(reader)
+    let value: serde_json::Value = serde_json::from_reader(reader)
         .context("Failed to parse JSON")?;
+    let export_data = match options.format.unwrap_or_else(|| detect_format(&value)) {
+        ImportFormat::Jottery => ExportData::deserialize(&value).context("Failed to parse JSON")?,
+        ImportFormat::WebApp => WebAppExport::deserialize(&value)
+            .context("Failed to parse web app export")?
+            .into_export_data(options.source_key.as_deref())?,
+    };
 
     let repo = NoteRepository::new(db.connection());
-    let mut imported = 0;
+    let attachment_repo = AttachmentRepository::new(db.connection());
+    let base_dir = path.as_ref().parent().unwrap_or(Path::new("."));
+    let mut stats = ImportStats::default();
+    let mut seen = if options.dedup {
+        Some(ContentIndex::new(repo.list_lenient(false, key)?.notes))
+    } else {
+        None
+    };
 
     for export_note in export_data.notes {
-        // Convert to Note
-        let note = Note {
-            id: export_note.id,
-            created_at: export_note.created_at.parse()?,
-            modified_at: export_note.modified_at.parse()?,
+        for attachment in &export_note.attachments {
+            match (&attachment.data, &attachment.file) {
+                (Some(data), _) => {
+                    let bytes = general_purpose::STANDARD
+                        .decode(data)
+                        .with_context(|| format!("Invalid attachment data for {}", attachment.filename))?;
+                    attachment_repo.store(
+                        &attachment.id,
+                        &attachment.filename,
+                        &attachment.mime_type,
+                        attachment.size,
+                        &bytes,
+                        key,
+                    )?;
+                }
+                // Sidecar files are encrypted as they are read
+                (None, Some(file)) => {
+                    let reader = File::open(sidecar_file(base_dir, file)?)
+                        .with_context(|| format!("Failed to read attachment {}", file))?;
+                    let len = reader.metadata()?.len() as i64;
+                    attachment_repo
+                        .store_stream(
+                            &attachment.id,
+                            &attachment.filename,
+                            &attachment.mime_type,
+                            len,
+                            BufReader::new(reader),
+                            key,
+                        )
+                        .with_context(|| format!("Failed to read attachment {}", file))?;
+                }
+                // Exported without contents (the blob was missing)
+                (None, None) => continue,
+            }
+        }
+
+        let note = export_note.into_note()?;
+        let Some(seen) = seen.as_mut() else {
+            if upsert_note(&repo, &note, key) {
+                stats.imported += 1;
+            }
+            continue;
+        };
+
+        match seen.duplicate_of(&note) {
+            Some(existing) => {
+                match merge_duplicate(existing, note) {
+                    Some(merged) => {
+                        if repo.update(&merged, key).is_ok() {
+                            stats.duplicates += 1;
+                            seen.insert(merged);
+                        }
+                    }
+                    None => stats.duplicates += 1,
+                }
+            }
+            None => {
+                if upsert_note(&repo, &note, key) {
+                    stats.imported += 1;
+                    seen.insert(note);
+                }
+            }
+        }
+    }
+
+    Ok(stats)
+}
+
+/// Notes by content hash, for collapsing duplicates on import
+struct ContentIndex {
+    crypto: CryptoService,
+    by_hash: HashMap<String, Note>,
+    hash_by_id: HashMap<String, String>,
+}
+
+impl ContentIndex {
+    fn new(notes: Vec<Note>) -> Self {
+        let mut index = Self { crypto: CryptoService::new(), by_hash: HashMap::new(), hash_by_id: HashMap::new() };
+        for note in notes {
+            index.insert(note);
+        }
+        index
+    }
+
+    /// Hash of the content with line endings and trailing whitespace normalized, so the
+    /// same note saved by the web app and the TUI compares equal
+    fn content_hash(&self, content: &str) -> String {
+        let normalized: Vec<&str> = content.trim().lines().map(str::trim_end).collect();
+        self.crypto.hash(&normalized.join("\n"))
+    }
+
+    /// The note already holding `note`'s content under a different id
+    fn duplicate_of(&self, note: &Note) -> Option<&Note> {
+        if self.hash_by_id.contains_key(&note.id) {
+            // Same id: a plain update of that note, not a duplicate
+            return None;
+        }
+        self.by_hash.get(&self.content_hash(&note.content))
+    }
+
+    /// Record `note` as the current version of its id
+    fn insert(&mut self, note: Note) {
+        let hash = self.content_hash(&note.content);
+        if let Some(old) = self.hash_by_id.insert(note.id.clone(), hash.clone()) {
+            if old != hash && self.by_hash.get(&old).is_some_and(|n| n.id == note.id) {
+                self.by_hash.remove(&old);
+            }
+        }
+        // The first note with this content keeps it, so later copies merge into one id
+        match self.by_hash.entry(hash) {
+            Entry::Occupied(mut entry) if entry.get().id == note.id => {
+                entry.insert(note);
+            }
+            Entry::Occupied(_) => {}
+            Entry::Vacant(entry) => {
+                entry.insert(note);
+            }
+        }
+    }
+}
+
+/// Merge an imported note into the existing note with the same content
+///
+/// The existing id and earliest creation time are kept; the more recently modified copy
+/// supplies the content and settings, and tags and attachments from both are kept. A merge
+/// that changes the existing note touches it so sync sends it on; `None` when the import
+/// adds nothing the existing note doesn't already have.
+fn merge_duplicate(existing: &Note, imported: Note) -> Option<Note> {
+    let (mut merged, other) = if imported.modified_at > existing.modified_at {
+        (imported, existing.clone())
+    } else {
+        (existing.clone(), imported)
+    };
+    merged.id = existing.id.clone();
+    merged.created_at = existing.created_at.min(other.created_at);
+    merged.version = existing.version;
+    merged.synced_at = existing.synced_at;
+    merged.sync_hash = existing.sync_hash.clone();
+    for tag in other.tags {
+        if !merged.tags.iter().any(|t| t.eq_ignore_ascii_case(&tag)) {
+            merged.tags.push(tag);
+        }
+    }
+    for attachment in other.attachments {
+        if !merged.attachments.iter().any(|a| a.id == attachment.id) {
+            merged.attachments.push(attachment);
+        }
+    }
+    let unchanged = merged.content == existing.content
+        && merged.tags == existing.tags
+        && merged.attachments.iter().map(|a| &a.id).eq(existing.attachments.iter().map(|a| &a.id))
+        && merged.pinned == existing.pinned
+        && merged.word_wrap == existing.word_wrap
+        && merged.syntax_language == existing.syntax_language
+        && merged.reminder_at == existing.reminder_at;
+    if unchanged {
+        return None;
+    }
+    merged.touch();
+    Some(merged)
+}
+
+/// Web app when any note carries something only the web app writes, otherwise Jottery, so
+/// a malformed Jottery export reports its own parse error
+fn detect_format(value: &serde_json::Value) -> ImportFormat {
+    let notes = value.get("notes").and_then(|n| n.as_array()).map(Vec::as_slice).unwrap_or_default();
+    if notes.iter().any(has_webapp_markers) {
+        ImportFormat::WebApp
+    } else {
+        ImportFormat::Jottery
+    }
+}
+
+/// Encrypted text, stored-note fields, or attachments without an id or size
+fn has_webapp_markers(note: &serde_json::Value) -> bool {
+    let encrypted = |text: &serde_json::Value| match text {
+        serde_json::Value::String(text) => looks_encrypted(text),
+        other => other.is_object(),
+    };
+    let list = |field: &str| note.get(field).and_then(|v| v.as_array()).map(Vec::as_slice).unwrap_or_default();
+    note.get("deleted").is_some()
+        || note.get("version").is_some()
+        || note.get("content").is_some_and(encrypted)
+        || list("tags").iter().any(encrypted)
+        || list("attachments").iter().any(|a| {
+            a.get("id").is_none() || a.get("size").is_none() || a.get("filename").is_some_and(encrypted)
+        })
+}
+
+/// Whether `text` is ciphertext JSON as the web app stores it
+fn looks_encrypted(text: &str) -> bool {
+    text.starts_with('{') && parse_encrypted(text).is_ok()
+}
+
+/// The web app's export file (`ExportData` in `src/lib/types/models.ts`)
+#[derive(Debug, Deserialize)]
+struct WebAppExport {
+    notes: Vec<WebAppNote>,
+}
+
+/// A web app note: decrypted as exported, or still encrypted as the web app stores it
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WebAppNote {
+    id: String,
+    created_at: String,
+    modified_at: String,
+    content: WebAppText,
+    #[serde(default)]
+    tags: Vec<WebAppText>,
+    #[serde(default)]
+    attachments: Vec<WebAppAttachment>,
+    #[serde(default)]
+    pinned: bool,
+    #[serde(default)]
+    deleted: bool,
+    word_wrap: Option<bool>,
+    syntax_language: Option<String>,
+}
+
+/// A web app attachment carries its contents inline and has no id or size
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WebAppAttachment {
+    id: Option<String>,
+    filename: WebAppText,
+    mime_type: String,
+    /// Base64 contents in an export, a blob store reference in stored notes
+    data: Option<String>,
+}
+
+/// A string field that is plaintext, or ciphertext as an object or as stored JSON
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum WebAppText {
+    Encrypted(EncryptedData),
+    Plain(String),
+}
+
+impl WebAppText {
+    fn is_encrypted(&self) -> bool {
+        match self {
+            Self::Encrypted(_) => true,
+            Self::Plain(text) => looks_encrypted(text),
+        }
+    }
+
+    /// Plaintext, decrypting with the web app key when needed
+    fn reveal(self, crypto: &CryptoService, key: Option<&[u8; 32]>, what: &str) -> Result<String> {
+        let encrypted = match self {
+            Self::Encrypted(data) => data,
+            Self::Plain(text) if looks_encrypted(&text) => parse_encrypted(&text)?,
+            Self::Plain(text) => return Ok(text),
+        };
+        let key = key.with_context(|| format!("{} is encrypted; pass the web app salt to decrypt it", what))?;
+        crypto
+            .decrypt_text(&encrypted, key)
+            .with_context(|| format!("{} could not be decrypted with the web app key", what))
+    }
+}
+
+impl WebAppExport {
+    fn into_export_data(self, key: Option<&[u8; 32]>) -> Result<ExportData> {
+        let crypto = CryptoService::new();
+        let notes = self
+            .notes
+            .into_iter()
+            .filter(|note| !note.deleted)
+            .map(|note| note.into_export_note(&crypto, key))
+            .collect::<Result<_>>()?;
+        Ok(ExportData { version: "1.0".to_string(), export_date: Utc::now().to_rfc3339(), notes })
+    }
+}
+
+impl WebAppNote {
+    fn into_export_note(self, crypto: &CryptoService, key: Option<&[u8; 32]>) -> Result<ExportNote> {
+        let what = format!("Note {}", self.id);
+        let content = self.content.reveal(crypto, key, &what)?;
+
+        // Stored notes keep all tags as one encrypted JSON array in the first entry
+        let mut tags = Vec::new();
+        for tag in self.tags {
+            let text = tag.reveal(crypto, key, &format!("{} tags", what))?;
+            match serde_json::from_str::<Vec<String>>(&text) {
+                Ok(list) if text.starts_with('[') => tags.extend(list),
+                _ => tags.push(text),
+            }
+        }
+
+        let mut attachments = Vec::new();
+        for (index, attachment) in self.attachments.into_iter().enumerate() {
+            // Encrypted filenames mean a stored note, whose blobs live outside the note data
+            let (Some(data), false) = (attachment.data, attachment.filename.is_encrypted()) else {
+                tracing::warn!("{}: skipping an attachment without inline contents", what);
+                continue;
+            };
+            let filename = attachment.filename.reveal(crypto, key, &what)?;
+            let size = general_purpose::STANDARD
+                .decode(&data)
+                .with_context(|| format!("Invalid attachment data for {}", filename))?
+                .len() as i64;
+            attachments.push(ExportAttachment {
+                id: attachment.id.unwrap_or_else(|| webapp_attachment_id(&self.id, index)),
+                filename,
+                mime_type: attachment.mime_type,
+                size,
+                data: Some(data),
+                file: None,
+            });
+        }
+
+        Ok(ExportNote {
+            id: self.id,
+            created_at: self.created_at,
+            modified_at: self.modified_at,
+            content,
+            tags,
+            pinned: self.pinned,
+            word_wrap: self.word_wrap,
+            syntax_language: self.syntax_language,
+            reminder_at: None,
+            attachments,
+        })
+    }
+}
+
+/// Id for a web app attachment, which has none: derived from its note and position, so
+/// importing the same file again updates the attachment instead of adding a copy
+fn webapp_attachment_id(note_id: &str, index: usize) -> String {
+    let digest = Sha256::digest(format!("{}\0{}", note_id, index));
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    uuid::Builder::from_custom_bytes(bytes).into_uuid().to_string()
+}
+
+/// `backup.json` -> `backup.attachments`
+fn sidecar_dir(path: &Path) -> PathBuf {
+    path.with_extension("attachments")
+}
+
+/// Resolve an imported attachment's `file`, which must name a file inside a sidecar
+/// folder next to the import file
+///
+/// The path comes from the import file, so anything that could reach elsewhere (absolute
+/// paths, `..`, symlinks out of the folder) is refused.
+fn sidecar_file(base_dir: &Path, file: &str) -> Result<PathBuf> {
+    let relative = Path::new(file);
+    let parts = relative
+        .components()
+        .map(|component| match component {
+            Component::Normal(part) => Some(part),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>();
+    let Some([folder, _]) = parts.as_deref() else {
+        anyhow::bail!("Attachment path {} is not inside an attachments folder", file);
+    };
+    if Path::new(folder).extension() != Some(OsStr::new("attachments")) {
+        anyhow::bail!("Attachment path {} is not inside an attachments folder", file);
+    }
+
+    let sidecar = base_dir
+        .join(folder)
+        .canonicalize()
+        .with_context(|| format!("Failed to read attachment {}", file))?;
+    let resolved = base_dir
+        .join(relative)
+        .canonicalize()
+        .with_context(|| format!("Failed to read attachment {}", file))?;
+    if !resolved.starts_with(&sidecar) {
+        anyhow::bail!("Attachment path {} leads outside its attachments folder", file);
+    }
+    Ok(resolved)
+}
+
+/// Decrypt an attachment blob for export, inline or into the sidecar folder
+fn export_attachment(
+    repo: &AttachmentRepository,
+    attachment: &Attachment,
+    key: &[u8; 32],
+    sidecar: &Path,
+) -> Result<ExportAttachment> {
+    let mut exported = ExportAttachment {
+        id: attachment.id.clone(),
+        filename: attachment.filename.clone(),
+        mime_type: attachment.mime_type.clone(),
+        size: attachment.size,
+        data: None,
+        file: None,
+    };
+
+    let Some(size) = repo.get_size(&attachment.id)? else {
+        return Ok(exported);
+    };
+
+    if size <= INLINE_ATTACHMENT_LIMIT as i64 {
+        if let Some((_, _, _, bytes)) = repo.get(&attachment.id, key)? {
+            exported.data = Some(general_purpose::STANDARD.encode(&bytes));
+        }
+    } else {
+        // Large blobs are streamed straight to disk, named by id, so the id must be a plain UUID
+        if Uuid::parse_str(&attachment.id).is_err() {
+            anyhow::bail!("Attachment {} has an invalid id: {}", attachment.filename, attachment.id);
+        }
+        std::fs::create_dir_all(sidecar).context("Failed to create attachments folder")?;
+        let file = File::create(sidecar.join(&attachment.id))
+            .with_context(|| format!("Failed to write attachment {}", attachment.filename))?;
+        repo.read_stream(&attachment.id, key, BufWriter::new(file))
+            .with_context(|| format!("Failed to write attachment {}", attachment.filename))?;
+        let folder = sidecar.file_name().and_then(|n| n.to_str()).unwrap_or("attachments");
+        exported.file = Some(format!("{}/{}", folder, attachment.id));
+    }
+
+    Ok(exported)
+}
+
+impl ExportNote {
+    /// Convert back to a note
+    fn into_note(self) -> Result<Note> {
+        Ok(Note {
+            id: self.id,
+            created_at: self.created_at.parse()?,
+            modified_at: self.modified_at.parse()?,
             synced_at: None,
-            content: export_note.content,
-            tags: export_note.tags,
-            attachments: Vec::new(), // Attachments not supported in basic export
-            pinned: export_note.pinned,
+            content: self.content,
+            tags: self.tags,
+            attachments: self
+                .attachments
+                .into_iter()
+                .map(|a| Attachment {
+                    data: a.id.clone(),
+                    id: a.id,
+                    filename: a.filename,
+                    mime_type: a.mime_type,
+                    size: a.size,
+                })
+                .collect(),
+            pinned: self.pinned,
             deleted: false,
             deleted_at: None,
             sync_hash: None,
             version: 1,
-            word_wrap: export_note.word_wrap.unwrap_or(true),
-            syntax_language: export_note
+            word_wrap: self.word_wrap.unwrap_or(true),
+            syntax_language: self
                 .syntax_language
                 .and_then(|s| s.parse().ok())
                 .unwrap_or_default(),
-        };
+            reminder_at: self.reminder_at.map(|s| s.parse()).transpose()?,
+        })
+    }
+}
+
+/// Create the note, or update it if it already exists; false if both fail
+fn upsert_note(repo: &NoteRepository, note: &Note, key: &[u8; 32]) -> bool {
+    repo.create(note, key).is_ok() || repo.update(note, key).is_ok()
+}
+
+/// Password-protected export bundle
+///
+/// Self-describing: carries the KDF parameters needed to re-derive the key from the
+/// export password, so it can be imported into any database.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EncryptedExport {
+    pub version: String,
+    #[serde(rename = "exportDate")]
+    pub export_date: String,
+    pub algorithm: String,
+    pub kdf: String,
+    /// Base64-encoded PBKDF2 salt
+    pub salt: String,
+    pub iterations: u32,
+    pub notes: Vec<EncryptedExportNote>,
+}
+
+/// Exported note with content and tags encrypted under the export key
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EncryptedExportNote {
+    pub id: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    #[serde(rename = "modifiedAt")]
+    pub modified_at: String,
+    pub content: EncryptedData,
+    pub tags: EncryptedData,
+    pub pinned: bool,
+    #[serde(rename = "wordWrap")]
+    pub word_wrap: Option<bool>,
+    #[serde(rename = "syntaxLanguage")]
+    pub syntax_language: Option<String>,
+    #[serde(rename = "reminderAt", default, skip_serializing_if = "Option::is_none")]
+    pub reminder_at: Option<String>,
+}
+
+/// Export notes to a bundle encrypted with a key derived from `export_password`
+pub fn export_encrypted<P: AsRef<Path>>(
+    db: &Database,
+    source_key: &[u8; 32],
+    path: P,
+    export_password: &str,
+) -> Result<usize> {
+    let crypto = CryptoService::new();
+    let salt = crypto.generate_salt();
+    let export_key = crypto.derive_key(export_password, &salt, EXPORT_ITERATIONS)?;
+
+    let notes = NoteRepository::new(db.connection()).list(true, source_key)?;
+    let encrypted_notes = notes
+        .iter()
+        .map(|note| {
+            let plain = ExportNote::from(note);
+            Ok(EncryptedExportNote {
+                id: plain.id,
+                created_at: plain.created_at,
+                modified_at: plain.modified_at,
+                content: crypto.encrypt_text(&plain.content, &export_key)?,
+                tags: crypto.encrypt_json(&plain.tags, &export_key)?,
+                pinned: plain.pinned,
+                word_wrap: plain.word_wrap,
+                syntax_language: plain.syntax_language,
+                reminder_at: plain.reminder_at,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let bundle = EncryptedExport {
+        version: "1.0".to_string(),
+        export_date: Utc::now().to_rfc3339(),
+        algorithm: "AES-256-GCM".to_string(),
+        kdf: "PBKDF2-SHA256".to_string(),
+        salt: general_purpose::STANDARD.encode(&salt),
+        iterations: EXPORT_ITERATIONS,
+        notes: encrypted_notes,
+    };
+
+    let file = File::create(path.as_ref()).context("Failed to create export file")?;
+    serde_json::to_writer_pretty(BufWriter::new(file), &bundle).context("Failed to write JSON")?;
+
+    Ok(notes.len())
+}
+
+/// Import an encrypted bundle, re-encrypting its notes under this database's key
+pub fn import_encrypted<P: AsRef<Path>>(
+    db: &Database,
+    key: &[u8; 32],
+    path: P,
+    export_password: &str,
+) -> Result<usize> {
+    let file = File::open(path.as_ref()).context("Failed to open import file")?;
+    let bundle: EncryptedExport = serde_json::from_reader(BufReader::new(file))
+        .context("Failed to parse encrypted export")?;
+
+    if bundle.algorithm != "AES-256-GCM" || bundle.kdf != "PBKDF2-SHA256" {
+        anyhow::bail!("Unsupported export encryption: {} / {}", bundle.algorithm, bundle.kdf);
+    }
+
+    let crypto = CryptoService::new();
+    let salt = general_purpose::STANDARD
+        .decode(&bundle.salt)
+        .context("Invalid salt in export")?;
+    let export_key = crypto.derive_key(export_password, &salt, bundle.iterations)?;
+
+    // Decrypt everything first so a wrong password imports nothing
+    let notes = bundle
+        .notes
+        .into_iter()
+        .map(|n| {
+            ExportNote {
+                content: crypto.decrypt_text(&n.content, &export_key)?,
+                tags: crypto.decrypt_json(&n.tags, &export_key)?,
+                id: n.id,
+                created_at: n.created_at,
+                modified_at: n.modified_at,
+                pinned: n.pinned,
+                word_wrap: n.word_wrap,
+                syntax_language: n.syntax_language,
+                reminder_at: n.reminder_at,
+                attachments: Vec::new(),
+            }
+            .into_note()
+        })
+        .collect::<Result<Vec<_>>>()
+        .context("Failed to decrypt export. Check the export password.")?;
+
+    let repo = NoteRepository::new(db.connection());
+    Ok(notes.iter().filter(|note| upsert_note(&repo, note, key)).count())
+}
+
+/// Export each note as a Markdown file with YAML frontmatter, for Obsidian/Logseq
+///
+/// Files are named `<slug-of-title>-<id prefix>.md`; soft-deleted notes are skipped
+/// unless `include_deleted` is set. Returns the number of files written.
+pub fn export_markdown<P: AsRef<Path>>(
+    db: &Database,
+    key: &[u8; 32],
+    dir: P,
+    include_deleted: bool,
+) -> Result<usize> {
+    let dir = dir.as_ref();
+    std::fs::create_dir_all(dir).context("Failed to create export directory")?;
+
+    let notes = NoteRepository::new(db.connection()).list(include_deleted, key)?;
+    let mut used = HashSet::new();
+
+    for note in &notes {
+        let base = format!("{}-{}", slugify(&note.title()), &note.id[..note.id.len().min(8)]);
+        let mut name = format!("{}.md", base);
+        let mut n = 2;
+        while used.contains(&name) || dir.join(&name).exists() {
+            name = format!("{}-{}.md", base, n);
+            n += 1;
+        }
+
+        std::fs::write(dir.join(&name), markdown_document(note))
+            .with_context(|| format!("Failed to write {}", name))?;
+        used.insert(name);
+    }
+
+    Ok(notes.len())
+}
+
+/// Note body preceded by YAML frontmatter
+fn markdown_document(note: &Note) -> String {
+    // JSON strings are valid YAML flow scalars, so tags survive any characters
+    let tags = note
+        .tags
+        .iter()
+        .map(|t| serde_json::to_string(t).unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let mut doc = String::from("---\n");
+    doc.push_str(&format!("created: {}\n", note.created_at.to_rfc3339()));
+    doc.push_str(&format!("modified: {}\n", note.modified_at.to_rfc3339()));
+    doc.push_str(&format!("tags: [{}]\n", tags));
+    doc.push_str(&format!("pinned: {}\n", note.pinned));
+    doc.push_str(&format!("syntaxLanguage: {}\n", note.syntax_language));
+    if let Some(reminder_at) = note.reminder_at {
+        doc.push_str(&format!("reminder: {}\n", reminder_at.to_rfc3339()));
+    }
+    if note.deleted {
+        doc.push_str("deleted: true\n");
+    }
+    doc.push_str("---\n");
+    doc.push_str(&note.content);
+    doc
+}
+
+/// Outcome of a Markdown import
+#[derive(Debug, Default)]
+pub struct MarkdownImportStats {
+    pub imported: usize,
+    /// Files that could not be imported, with the reason
+    pub skipped: Vec<(PathBuf, String)>,
+}
+
+/// Import `.md` files (a single file, or a directory searched recursively) as new notes
+///
+/// Optional YAML frontmatter supplies tags, pinned, language, timestamps and a reminder; files without
+/// it take their tags from inline `#hashtags`. A file that fails is skipped, not fatal. Symlinks
+/// inside the directory are not followed, so a link back up the tree can't loop.
+pub fn import_markdown<P: AsRef<Path>>(
+    db: &Database,
+    key: &[u8; 32],
+    path: P,
+) -> Result<MarkdownImportStats> {
+    let mut files = Vec::new();
+    collect_markdown_files(path.as_ref(), &mut files)?;
+    files.sort();
+
+    let repo = NoteRepository::new(db.connection());
+    let mut stats = MarkdownImportStats::default();
+
+    for file in files {
+        let result = std::fs::read_to_string(&file)
+            .context("Not valid UTF-8 text")
+            .and_then(|text| parse_markdown_note(&text))
+            .and_then(|note| Ok(repo.create(&note, key)?));
+        match result {
+            Ok(()) => stats.imported += 1,
+            Err(e) => stats.skipped.push((file, e.to_string())),
+        }
+    }
+
+    Ok(stats)
+}
+
+fn collect_markdown_files(path: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    if path.is_dir() {
+        let entries = std::fs::read_dir(path)
+            .with_context(|| format!("Failed to read directory {}", path.display()))?;
+        for entry in entries {
+            let entry = entry?;
+            if entry.file_type()?.is_symlink() {
+                continue;
+            }
+            collect_markdown_files(&entry.path(), files)?;
+        }
+    } else if path.is_file() {
+        let is_markdown = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("md") || ext.eq_ignore_ascii_case("markdown"));
+        if is_markdown {
+            files.push(path.to_path_buf());
+        }
+    } else {
+        anyhow::bail!("{} does not exist", path.display());
+    }
+    Ok(())
+}
+
+/// Build a note from a Markdown document with optional frontmatter
+fn parse_markdown_note(text: &str) -> Result<Note> {
+    let (frontmatter, body) = split_frontmatter(text)?;
+    if body.trim().is_empty() {
+        anyhow::bail!("Empty note");
+    }
 
-        // Try to create (will fail if already exists, which is fine)
-        match repo.create(&note, key) {
-            Ok(_) => imported += 1,
-            Err(_) => {
-                // Note already exists, try to update
-                if repo.update(&note, key).is_ok() {
-                    imported += 1;
+    let mut note = Note::new(body.to_string());
+    let Some(frontmatter) = frontmatter else {
+        note.tags = inline_hashtags(body);
+        return Ok(note);
+    };
+
+    let mut lines = frontmatter.lines().peekable();
+    while let Some(line) = lines.next() {
+        let Some((field, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match field.trim() {
+            "tags" if value.is_empty() => {
+                // Block list: `tags:` followed by `- item` lines
+                while let Some(item) = lines.peek().and_then(|l| l.trim().strip_prefix('-')) {
+                    note.tags.push(yaml_scalar(item.trim()));
+                    lines.next();
                 }
             }
+            "tags" => note.tags = yaml_list(value),
+            "pinned" => note.pinned = value == "true",
+            "syntaxLanguage" | "language" => {
+                note.syntax_language = yaml_scalar(value).parse().unwrap_or_default();
+            }
+            "created" => note.created_at = parse_timestamp(&yaml_scalar(value))?,
+            "modified" | "updated" => note.modified_at = parse_timestamp(&yaml_scalar(value))?,
+            "reminder" | "due" => note.reminder_at = Some(parse_timestamp(&yaml_scalar(value))?),
+            "deleted" if value == "true" => {
+                note.deleted = true;
+                note.deleted_at = Some(Utc::now());
+            }
+            _ => {}
         }
     }
+    note.tags.retain(|t| !t.is_empty());
+    note.tags.dedup();
 
-    Ok(imported)
+    Ok(note)
+}
+
+/// Split `---` delimited frontmatter from the body
+fn split_frontmatter(text: &str) -> Result<(Option<&str>, &str)> {
+    let Some(rest) = text.strip_prefix("---\n").or_else(|| text.strip_prefix("---\r\n")) else {
+        return Ok((None, text));
+    };
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            return Ok((Some(&rest[..offset]), &rest[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+    anyhow::bail!("Frontmatter is not closed with ---")
+}
+
+/// `[a, "b c"]` -> ["a", "b c"]
+fn yaml_list(value: &str) -> Vec<String> {
+    let inner = value.trim_start_matches('[').trim_end_matches(']');
+    if let Ok(items) = serde_json::from_str::<Vec<String>>(&format!("[{}]", inner)) {
+        return items;
+    }
+    inner.split(',').map(|item| yaml_scalar(item.trim())).collect()
+}
+
+/// Strip YAML quoting from a scalar
+fn yaml_scalar(value: &str) -> String {
+    if value.starts_with('"') {
+        if let Ok(s) = serde_json::from_str::<String>(value) {
+            return s;
+        }
+    }
+    value.trim_matches(|c| c == '"' || c == '\'').trim_start_matches('#').to_string()
+}
+
+/// RFC 3339 timestamp or a bare `YYYY-MM-DD` date
+fn parse_timestamp(value: &str) -> Result<DateTime<Utc>> {
+    if let Ok(ts) = DateTime::parse_from_rfc3339(value) {
+        return Ok(ts.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .with_context(|| format!("Invalid timestamp: {}", value))?;
+    Ok(date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc())
+}
+
+/// `#tag` words in the body; headings (`# Title`) and numbers (`#1`) are not tags
+fn inline_hashtags(body: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for word in body.split_whitespace() {
+        let Some(tag) = word.strip_prefix('#') else {
+            continue;
+        };
+        let tag: String = tag
+            .chars()
+            .take_while(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '/'))
+            .collect();
+        if tag.chars().any(char::is_alphabetic) && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+/// Lowercase ASCII slug safe for any filesystem (`Hello, World!` -> `hello-world`)
+fn slugify(title: &str) -> String {
+    let mut slug = String::new();
+    for c in title.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+        if slug.len() >= 50 {
+            break;
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        "untitled".to_string()
+    } else {
+        slug.to_string()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::crypto::CryptoService;
     use tempfile::NamedTempFile;
 
     #[test]
@@ -155,7 +1099,8 @@ mod tests {
         let repo = NoteRepository::new(db.connection());
 
         // Create test notes
-        let note1 = Note::new("Test note 1".to_string());
+        let mut note1 = Note::new("Test note 1".to_string());
+        note1.reminder_at = Some(Utc::now() + chrono::Duration::days(1));
         let note2 = Note::new("Test note 2".to_string());
 
         repo.create(&note1, &key).unwrap();
@@ -163,17 +1108,401 @@ mod tests {
 
         // Export
         let export_file = NamedTempFile::new().unwrap();
-        let count = export_notes(&db, &key, export_file.path()).unwrap();
+        let count = export_notes(&db, &key, export_file.path(), &ExportFilter::default()).unwrap();
         assert_eq!(count, 2);
 
         // Create new database and import
         let db2 = Database::in_memory("test_password").unwrap();
-        let imported = import_notes(&db2, &key, export_file.path()).unwrap();
-        assert_eq!(imported, 2);
+        let imported = import_notes(&db2, &key, export_file.path(), &ImportOptions::default()).unwrap();
+        assert_eq!(imported.imported, 2);
 
         // Verify
         let repo2 = NoteRepository::new(db2.connection());
         let notes = repo2.list(false, &key).unwrap();
         assert_eq!(notes.len(), 2);
+        let reminded = notes.iter().find(|n| n.id == note1.id).unwrap();
+        assert_eq!(reminded.reminder_at, note1.reminder_at);
+    }
+
+    #[test]
+    fn test_import_dedup_merges_matching_content() {
+        let key = [7u8; 32];
+        let db = Database::in_memory("test_password").unwrap();
+        let repo = NoteRepository::new(db.connection());
+
+        let mut existing = Note::new("Shopping\nmilk\n".to_string());
+        existing.tags = vec!["home".to_string()];
+        existing.modified_at = Utc::now() - chrono::Duration::days(2);
+        repo.create(&existing, &key).unwrap();
+
+        // The same note from another app (new id, CRLF, newer), twice, plus a distinct note
+        let mut newer = Note::new("Shopping\r\nmilk  \r\n".to_string());
+        newer.tags = vec!["errands".to_string()];
+        newer.pinned = true;
+        let mut older_copy = Note::new("Shopping\nmilk".to_string());
+        older_copy.modified_at = Utc::now() - chrono::Duration::days(5);
+        let other = Note::new("Something else".to_string());
+        let export_data = ExportData {
+            version: "1.0".to_string(),
+            export_date: Utc::now().to_rfc3339(),
+            notes: [&newer, &older_copy, &other].into_iter().map(ExportNote::from).collect(),
+        };
+        let export_file = NamedTempFile::new().unwrap();
+        serde_json::to_writer(File::create(export_file.path()).unwrap(), &export_data).unwrap();
+
+        let stats = import_notes(&db, &key, export_file.path(), &ImportOptions { dedup: true, ..Default::default() }).unwrap();
+        assert_eq!(stats, ImportStats { imported: 1, duplicates: 2 });
+
+        let notes = repo.list(false, &key).unwrap();
+        assert_eq!(notes.len(), 2);
+        let merged = notes.iter().find(|n| n.id == existing.id).unwrap();
+        assert!(merged.modified_at > newer.modified_at);
+        assert!(merged.version > existing.version);
+        assert!(merged.pinned);
+        assert_eq!(merged.tags, ["errands", "home"]);
+
+        // Importing the same file again changes nothing
+        let before = repo.get(&existing.id, &key).unwrap().unwrap();
+        import_notes(&db, &key, export_file.path(), &ImportOptions { dedup: true, ..Default::default() }).unwrap();
+        let after = repo.get(&existing.id, &key).unwrap().unwrap();
+        assert_eq!((after.modified_at, after.version), (before.modified_at, before.version));
+
+        // Without dedup every copy is kept
+        let db2 = Database::in_memory("test_password").unwrap();
+        assert_eq!(import_notes(&db2, &key, export_file.path(), &ImportOptions::default()).unwrap().imported, 3);
+    }
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+    }
+
+    /// Key the web app derives for the encrypted fixture
+    fn webapp_key() -> Zeroizing<[u8; 32]> {
+        let salt: Vec<u8> = (0..32).collect();
+        CryptoService::new().derive_key("web app password", &salt, 100_000).unwrap()
+    }
+
+    #[test]
+    fn test_import_detects_jottery_and_webapp_formats() {
+        let key = [7u8; 32];
+        let db = Database::in_memory("test_password").unwrap();
+        let repo = NoteRepository::new(db.connection());
+        let attachment_repo = AttachmentRepository::new(db.connection());
+
+        let stats = import_notes(&db, &key, fixture("jottery_export.json"), &ImportOptions::default()).unwrap();
+        assert_eq!(stats.imported, 1);
+        let note = repo.get("9dc2b5f1-6fd3-4d5c-853a-4000e13e3b3e", &key).unwrap().unwrap();
+        assert_eq!(note.tags, ["tui"]);
+        let (_, _, _, bytes) = attachment_repo.get(&note.attachments[0].id, &key).unwrap().unwrap();
+        assert_eq!(bytes, b"hello");
+
+        // Web app attachments have no id or size, so only the web app reader accepts them
+        let webapp = fixture("webapp_export.json");
+        let jottery_only = ImportOptions { format: Some(ImportFormat::Jottery), ..Default::default() };
+        assert!(import_notes(&db, &key, &webapp, &jottery_only).is_err());
+
+        let stats = import_notes(&db, &key, &webapp, &ImportOptions::default()).unwrap();
+        assert_eq!(stats.imported, 2);
+        let trip = repo.get("0b5bd2e4-8a43-4c55-9d8f-2a3f6c1e7a10", &key).unwrap().unwrap();
+        assert!(trip.content.starts_with("# Trip checklist"));
+        assert_eq!(trip.tags, ["travel", "todo"]);
+        assert!(trip.pinned && !trip.word_wrap);
+        assert_eq!(trip.syntax_language.to_string(), "markdown");
+        assert_eq!((trip.attachments[0].filename.as_str(), trip.attachments[0].size), ("hello.txt", 22));
+        let (_, _, _, bytes) = attachment_repo.get(&trip.attachments[0].id, &key).unwrap().unwrap();
+        assert_eq!(bytes, b"hello from the web app");
+
+        // Importing it again keeps the same attachment id
+        import_notes(&db, &key, &webapp, &ImportOptions::default()).unwrap();
+        let again = repo.get("0b5bd2e4-8a43-4c55-9d8f-2a3f6c1e7a10", &key).unwrap().unwrap();
+        assert_eq!(again.attachments.len(), 1);
+        assert_eq!(again.attachments[0].id, trip.attachments[0].id);
+
+        let quick = repo.get("6f1c0d7e-2b9a-4e3c-8f51-9d4a7b2c3e88", &key).unwrap().unwrap();
+        assert!(quick.word_wrap && quick.tags.is_empty());
+
+        // A Jottery export missing a field reports that rather than a web app error
+        let broken = NamedTempFile::new().unwrap();
+        std::fs::write(broken.path(), r#"{"version":"1.0","exportDate":"2025-01-01T00:00:00Z","notes":[{"id":"x","createdAt":"2025-01-01T00:00:00Z","modifiedAt":"2025-01-01T00:00:00Z","content":"hi","pinned":false}]}"#).unwrap();
+        let err = import_notes(&db, &key, broken.path(), &ImportOptions::default()).unwrap_err();
+        assert!(format!("{:#}", err).contains("Failed to parse JSON"), "{:#}", err);
+        assert!(format!("{:#}", err).contains("tags"), "{:#}", err);
+    }
+
+    #[test]
+    fn test_import_webapp_encrypted_content() {
+        let key = [7u8; 32];
+        let db = Database::in_memory("test_password").unwrap();
+        let encrypted = fixture("webapp_encrypted_export.json");
+
+        // Detected as web app data, which needs the web app key
+        let err = import_notes(&db, &key, &encrypted, &ImportOptions::default()).unwrap_err();
+        assert!(format!("{:#}", err).contains("web app salt"), "{:#}", err);
+
+        let wrong = ImportOptions { source_key: Some(Zeroizing::new([9u8; 32])), ..Default::default() };
+        assert!(import_notes(&db, &key, &encrypted, &wrong).is_err());
+
+        let options = ImportOptions { source_key: Some(webapp_key()), ..Default::default() };
+        assert_eq!(import_notes(&db, &key, &encrypted, &options).unwrap().imported, 1);
+        let note = NoteRepository::new(db.connection())
+            .get("a3e1f4c2-5d6b-4f7a-9c8e-1b2d3e4f5a6b", &key)
+            .unwrap()
+            .unwrap();
+        assert_eq!(note.content, "Encrypted in the browser");
+        assert_eq!(note.tags, ["secret", "web"]);
+    }
+
+    #[test]
+    fn test_export_markdown_files() {
+        let crypto = CryptoService::new();
+        let salt = crypto.generate_salt();
+        let key = crypto.derive_key("test_password", &salt, 100_000).unwrap();
+
+        let db = Database::in_memory("test_password").unwrap();
+        let repo = NoteRepository::new(db.connection());
+
+        let mut first = Note::new("Plans: a/b <test>\nbody".to_string());
+        first.id = "aaaaaaaa-1".to_string();
+        first.tags = vec!["work".to_string(), "say \"hi\"".to_string()];
+        let mut second = Note::new("Plans: a/b <test>".to_string());
+        second.id = "aaaaaaaa-2".to_string();
+        let mut deleted = Note::new("gone".to_string());
+        deleted.mark_deleted();
+        for note in [&first, &second, &deleted] {
+            repo.create(note, &key).unwrap();
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(export_markdown(&db, &key, dir.path(), false).unwrap(), 2);
+
+        let mut names: Vec<String> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["plans-a-b-test-aaaaaaaa-2.md", "plans-a-b-test-aaaaaaaa.md"]);
+
+        let doc = names
+            .iter()
+            .map(|name| std::fs::read_to_string(dir.path().join(name)).unwrap())
+            .find(|doc| doc.contains("body"))
+            .unwrap();
+        assert!(doc.starts_with("---\ncreated: "));
+        assert!(doc.contains("tags: [\"work\", \"say \\\"hi\\\"\"]\n"));
+        assert!(doc.contains("pinned: false\nsyntaxLanguage: plain\n---\nPlans: a/b <test>\nbody"));
+
+        let all = tempfile::tempdir().unwrap();
+        assert_eq!(export_markdown(&db, &key, all.path(), true).unwrap(), 3);
     }
+
+
+    #[test]
+    fn test_import_markdown_roundtrip_and_hashtags() {
+        let crypto = CryptoService::new();
+        let salt = crypto.generate_salt();
+        let key = crypto.derive_key("test_password", &salt, 100_000).unwrap();
+
+        let db = Database::in_memory("test_password").unwrap();
+        let mut note = Note::new("# Heading\nselect 1".to_string());
+        note.tags = vec!["work".to_string(), "a, b".to_string()];
+        note.pinned = true;
+        note.syntax_language = crate::models::SyntaxLanguage::Sql;
+        note.reminder_at = Some(Utc::now());
+        NoteRepository::new(db.connection()).create(&note, &key).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        export_markdown(&db, &key, dir.path(), false).unwrap();
+        let nested = dir.path().join("inbox");
+        std::fs::create_dir(&nested).unwrap();
+        std::fs::write(nested.join("plain.md"), "# Title\nidea for #project and #2 #work/sub").unwrap();
+        std::fs::write(nested.join("block.md"), "---\ntags:\n  - one\n  - \"two\"\ncreated: 2024-01-02\n---\nbody").unwrap();
+        std::fs::write(nested.join("broken.md"), "---\ntags: [x]\nno closing fence").unwrap();
+        std::fs::write(nested.join("notes.txt"), "ignored").unwrap();
+        // A link back up the tree would otherwise be walked forever
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(dir.path(), nested.join("loop")).unwrap();
+
+        let db2 = Database::in_memory("test_password").unwrap();
+        let stats = import_markdown(&db2, &key, dir.path()).unwrap();
+        assert_eq!(stats.imported, 3);
+        assert_eq!(stats.skipped.len(), 1);
+        assert!(stats.skipped[0].0.ends_with("broken.md"));
+
+        let notes = NoteRepository::new(db2.connection()).list(false, &key).unwrap();
+        let find = |prefix: &str| notes.iter().find(|n| n.content.starts_with(prefix)).unwrap();
+
+        let restored = find("# Heading");
+        assert_eq!(restored.content, note.content);
+        assert_eq!(restored.tags, note.tags);
+        assert!(restored.pinned);
+        assert_eq!(restored.syntax_language, crate::models::SyntaxLanguage::Sql);
+        assert_eq!(restored.created_at.timestamp(), note.created_at.timestamp());
+        assert_eq!(restored.reminder_at, note.reminder_at);
+
+        assert_eq!(find("# Title").tags, ["project", "work/sub"]);
+        let block = find("body");
+        assert_eq!(block.tags, ["one", "two"]);
+        assert_eq!(block.created_at.to_rfc3339(), "2024-01-02T00:00:00+00:00");
+        assert_eq!(block.reminder_at, None);
+    }
+
+
+    #[test]
+    fn test_encrypted_export_roundtrip() {
+        let crypto = CryptoService::new();
+        let key = crypto.derive_key("db_password", &crypto.generate_salt(), 100_000).unwrap();
+        let other_key = crypto.derive_key("other_db", &crypto.generate_salt(), 100_000).unwrap();
+
+        let db = Database::in_memory("db_password").unwrap();
+        let mut note = Note::new("secret plans".to_string());
+        note.tags = vec!["private".to_string()];
+        NoteRepository::new(db.connection()).create(&note, &key).unwrap();
+
+        let export_file = NamedTempFile::new().unwrap();
+        assert_eq!(export_encrypted(&db, &key, export_file.path(), "export pw").unwrap(), 1);
+
+        let raw = std::fs::read_to_string(export_file.path()).unwrap();
+        assert!(!raw.contains("secret plans"));
+        assert!(!raw.contains("private"));
+        assert!(raw.contains("\"algorithm\": \"AES-256-GCM\""));
+
+        let db2 = Database::in_memory("other_db").unwrap();
+        assert!(import_encrypted(&db2, &other_key, export_file.path(), "wrong").is_err());
+        assert_eq!(import_encrypted(&db2, &other_key, export_file.path(), "export pw").unwrap(), 1);
+
+        let imported = NoteRepository::new(db2.connection()).list(false, &other_key).unwrap();
+        assert_eq!(imported[0].content, "secret plans");
+        assert_eq!(imported[0].tags, ["private"]);
+    }
+
+
+    #[test]
+    fn test_export_import_attachments() {
+        let crypto = CryptoService::new();
+        let key = crypto.derive_key("test_password", &crypto.generate_salt(), 100_000).unwrap();
+
+        let db = Database::in_memory("test_password").unwrap();
+        let attachments = AttachmentRepository::new(db.connection());
+        let small: Vec<u8> = (0..=255u8).collect();
+        let large: Vec<u8> = (0..INLINE_ATTACHMENT_LIMIT + 10).map(|i| (i * 7 % 251) as u8).collect();
+
+        let mut note = Note::new("with files".to_string());
+        for (name, bytes) in [("small.bin", &small), ("large.bin", &large)] {
+            let attachment = Attachment::new(name.to_string(), "application/octet-stream".to_string(), bytes.len() as i64, String::new());
+            attachments.store(&attachment.id, name, &attachment.mime_type, attachment.size, bytes, &key).unwrap();
+            note.attachments.push(attachment);
+        }
+        NoteRepository::new(db.connection()).create(&note, &key).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let export_path = dir.path().join("backup.json");
+        export_notes(&db, &key, &export_path, &ExportFilter::default()).unwrap();
+        assert!(dir.path().join("backup.attachments").join(&note.attachments[1].id).exists());
+
+        let db2 = Database::in_memory("test_password").unwrap();
+        assert_eq!(import_notes(&db2, &key, &export_path, &ImportOptions::default()).unwrap().imported, 1);
+
+        let restored = &NoteRepository::new(db2.connection()).list(false, &key).unwrap()[0];
+        assert_eq!(restored.attachments.len(), 2);
+        let attachments2 = AttachmentRepository::new(db2.connection());
+        for (attachment, bytes) in restored.attachments.iter().zip([&small, &large]) {
+            let (filename, _, _, data) = attachments2.get(&attachment.id, &key).unwrap().unwrap();
+            assert_eq!(filename, attachment.filename);
+            assert_eq!(&data, bytes);
+        }
+
+        // The sidecar file was imported as a chunked blob
+        let kind: String = db2
+            .connection()
+            .query_row("SELECT typeof(data) FROM attachments WHERE id = ?1", [&restored.attachments[1].id], |row| row.get(0))
+            .unwrap();
+        assert_eq!(kind, "blob");
+    }
+
+    #[test]
+    fn test_sidecar_paths_stay_in_attachments_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("backup.attachments")).unwrap();
+        std::fs::write(dir.path().join("backup.attachments").join("blob"), b"data").unwrap();
+        std::fs::write(dir.path().join("secret.txt"), b"secret").unwrap();
+
+        assert!(sidecar_file(dir.path(), "backup.attachments/blob").is_ok());
+        for file in [
+            "secret.txt",
+            "backup.attachments/../secret.txt",
+            "../backup.attachments/blob",
+            "/etc/passwd",
+            "other/blob",
+            "backup.attachments/nested/blob",
+        ] {
+            assert!(sidecar_file(dir.path(), file).is_err(), "{} was accepted", file);
+        }
+
+        // A symlink inside the folder can't point out of it
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(dir.path().join("secret.txt"), dir.path().join("backup.attachments/link")).unwrap();
+            assert!(sidecar_file(dir.path(), "backup.attachments/link").is_err());
+        }
+
+        // Exporting an attachment whose id isn't a UUID refuses to use it as a filename
+        let key = [7u8; 32];
+        let db = Database::in_memory("test_password").unwrap();
+        let bytes = vec![1u8; INLINE_ATTACHMENT_LIMIT + 1];
+        let mut note = Note::new("with a file".to_string());
+        let mut attachment = Attachment::new("big.bin".to_string(), "application/octet-stream".to_string(), bytes.len() as i64, String::new());
+        attachment.id = "../../escape".to_string();
+        AttachmentRepository::new(db.connection())
+            .store(&attachment.id, "big.bin", &attachment.mime_type, attachment.size, &bytes, &key)
+            .unwrap();
+        note.attachments.push(attachment);
+        NoteRepository::new(db.connection()).create(&note, &key).unwrap();
+        let out = tempfile::tempdir().unwrap();
+        assert!(export_notes(&db, &key, out.path().join("backup.json"), &ExportFilter::default()).is_err());
+        assert!(!out.path().join("escape").exists());
+    }
+
+
+    #[test]
+    fn test_export_filters_narrow() {
+        let crypto = CryptoService::new();
+        let key = crypto.derive_key("test_password", &crypto.generate_salt(), 100_000).unwrap();
+        let db = Database::in_memory("test_password").unwrap();
+        let repo = NoteRepository::new(db.connection());
+
+        let day = |d: u32| format!("2024-01-{:02}T00:00:00Z", d).parse::<DateTime<Utc>>().unwrap();
+        for (content, tags, modified) in [
+            ("a", vec!["work"], day(1)),
+            ("b", vec!["work", "urgent"], day(10)),
+            ("c", vec!["Home"], day(20)),
+        ] {
+            let mut note = Note::new(content.to_string());
+            note.tags = tags.into_iter().map(String::from).collect();
+            note.modified_at = modified;
+            repo.create(&note, &key).unwrap();
+        }
+
+        let export = |filter: ExportFilter| {
+            let file = NamedTempFile::new().unwrap();
+            export_notes(&db, &key, file.path(), &filter).unwrap();
+            let data: ExportData = serde_json::from_reader(File::open(file.path()).unwrap()).unwrap();
+            let mut contents: Vec<String> = data.notes.into_iter().map(|n| n.content).collect();
+            contents.sort();
+            contents
+        };
+        let tags = |t: &[&str]| t.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(export(ExportFilter { tags: tags(&["work", "urgent"]), ..Default::default() }), ["b"]);
+        assert_eq!(
+            export(ExportFilter { tags: tags(&["urgent", "home"]), any_tag: true, ..Default::default() }),
+            ["b", "c"]
+        );
+        assert_eq!(export(ExportFilter { since: Some(day(5)), until: Some(day(15)), ..Default::default() }), ["b"]);
+        assert_eq!(
+            export(ExportFilter { tags: tags(&["work"]), since: Some(day(5)), ..Default::default() }),
+            ["b"]
+        );
+        assert!(export(ExportFilter { tags: tags(&["missing"]), ..Default::default() }).is_empty());
+    }
+
 }
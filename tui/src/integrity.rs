@@ -0,0 +1,354 @@
+//! Tamper-evidence hashes over stored notes
+//! Each note's `chain_hash` is a keyed hash of its encrypted row, and the head records a keyed
+//! hash of the XOR of all of them plus the note count, so edits, removal or replayed rows
+//! without the key show up on unlock. Saves rehash only the notes they wrote.
+
+use anyhow::Result;
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+use tracing::warn;
+
+use crate::crypto::CryptoService;
+
+/// Result of verifying the stored hashes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntegrityStatus {
+    /// No seal has been recorded yet (new or pre-seal database)
+    Unsealed,
+    /// Every note matches its recorded hash
+    Valid,
+    /// Stored data does not match the seal
+    Tampered {
+        /// Note whose hash does not match, if the mismatch is at a note
+        note_id: Option<String>,
+        reason: String,
+    },
+}
+
+const NOTE_COLUMNS: &str = "id, created_at, modified_at, content, tags, attachments, pinned, deleted,
+    COALESCE(deleted_at, ''), version, word_wrap, syntax_language, chain_hash, reminder_at";
+
+/// Stored note row, hashed as-is without decrypting
+struct ChainRow {
+    id: String,
+    fields: Vec<String>,
+    chain_hash: Option<String>,
+}
+
+fn chain_row(row: &rusqlite::Row) -> rusqlite::Result<ChainRow> {
+    let mut fields: Vec<String> = vec![
+        row.get(1)?,
+        row.get(2)?,
+        row.get(3)?,
+        row.get(4)?,
+        row.get(5)?,
+        row.get::<_, i64>(6)?.to_string(),
+        row.get::<_, i64>(7)?.to_string(),
+        row.get(8)?,
+        row.get::<_, i64>(9)?.to_string(),
+        row.get::<_, i64>(10)?.to_string(),
+        row.get(11)?,
+    ];
+    if let Some(reminder_at) = row.get::<_, Option<String>>(13)? {
+        fields.push(reminder_at);
+    }
+    Ok(ChainRow {
+        id: row.get(0)?,
+        fields,
+        chain_hash: row.get(12)?,
+    })
+}
+
+/// Load every note row
+fn load_rows(conn: &Connection) -> Result<Vec<ChainRow>> {
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM notes ORDER BY id", NOTE_COLUMNS))?;
+    let rows = stmt.query_map([], chain_row)?.collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// Load one note row, if it exists
+fn load_row(conn: &Connection, id: &str) -> Result<Option<ChainRow>> {
+    let row = conn
+        .query_row(
+            &format!("SELECT {} FROM notes WHERE id = ?1", NOTE_COLUMNS),
+            params![id],
+            chain_row,
+        )
+        .optional()?;
+    Ok(row)
+}
+
+/// Compute the hash of a single row
+fn leaf(crypto: &CryptoService, key: &[u8; 32], row: &ChainRow) -> String {
+    let mut data = Vec::new();
+    for part in std::iter::once(row.id.as_str()).chain(row.fields.iter().map(String::as_str)) {
+        data.extend_from_slice(part.as_bytes());
+        data.push(0);
+    }
+    crypto.keyed_hash(&data, key)
+}
+
+/// Fold a hex hash into the running XOR digest. Hashes that aren't valid hex (only
+/// possible if someone wrote them by hand) are folded in by their bytes, which still
+/// leaves the digest wrong.
+fn fold(digest: &mut [u8; 32], hash: &str) {
+    let bytes = hex::decode(hash).unwrap_or_else(|_| hash.as_bytes().to_vec());
+    for (i, byte) in bytes.iter().enumerate() {
+        digest[i % 32] ^= byte;
+    }
+}
+
+fn head_hash(crypto: &CryptoService, key: &[u8; 32], digest: &str, note_count: i64) -> String {
+    crypto.keyed_hash(format!("{}\0{}", digest, note_count).as_bytes(), key)
+}
+
+/// The recorded seal: XOR digest, note count and head
+fn load_seal(conn: &Connection) -> Result<Option<(String, i64, String)>> {
+    let seal = conn
+        .query_row(
+            "SELECT digest, note_count, head FROM integrity_chain WHERE id = 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()?;
+    Ok(seal)
+}
+
+fn store_seal(crypto: &CryptoService, conn: &Connection, key: &[u8; 32], digest: &[u8; 32], note_count: i64) -> Result<()> {
+    let digest = hex::encode(digest);
+    conn.execute(
+        "INSERT OR REPLACE INTO integrity_chain (id, digest, note_count, head, updated_at)
+         VALUES (1, ?1, ?2, ?3, ?4)",
+        params![&digest, note_count, head_hash(crypto, key, &digest, note_count), Utc::now().to_rfc3339()],
+    )?;
+    Ok(())
+}
+
+/// Rehash every note and record a fresh seal, accepting whatever is stored now.
+/// Only for databases that have no seal yet, after the user has reviewed a mismatch, or
+/// after `verify` passed before a bulk write (see [`resealing`]).
+pub fn seal(conn: &Connection, key: &[u8; 32]) -> Result<()> {
+    let crypto = CryptoService::new();
+    let rows = load_rows(conn)?;
+
+    let mut digest = [0u8; 32];
+    for row in &rows {
+        let hash = leaf(&crypto, key, row);
+        if row.chain_hash.as_deref() != Some(hash.as_str()) {
+            conn.execute(
+                "UPDATE notes SET chain_hash = ?1 WHERE id = ?2",
+                params![&hash, &row.id],
+            )?;
+        }
+        fold(&mut digest, &hash);
+    }
+
+    store_seal(&crypto, conn, key, &digest, rows.len() as i64)
+}
+
+/// Rehash the notes in `ids` after writing them and update the seal, leaving every other
+/// note as recorded. Notes that don't exist are skipped; hard deletes go through
+/// [`resealing`]. When the recorded seal itself doesn't verify nothing is changed, so the
+/// mismatch keeps being reported instead of being approved by the next save.
+pub fn seal_notes(conn: &Connection, key: &[u8; 32], ids: &[&str]) -> Result<()> {
+    let crypto = CryptoService::new();
+    let Some((stored_digest, mut note_count, head)) = load_seal(conn)? else {
+        // Nothing recorded yet, so there is nothing to approve by mistake
+        return seal(conn, key);
+    };
+    if head != head_hash(&crypto, key, &stored_digest, note_count) {
+        warn!("Integrity seal does not verify; not updating it");
+        return Ok(());
+    }
+
+    let mut digest = [0u8; 32];
+    fold(&mut digest, &stored_digest);
+    for id in ids {
+        let Some(row) = load_row(conn, id)? else {
+            continue;
+        };
+        let hash = leaf(&crypto, key, &row);
+        match row.chain_hash.as_deref() {
+            Some(old) if old == hash => continue,
+            Some(old) => fold(&mut digest, old),
+            None => note_count += 1,
+        }
+        fold(&mut digest, &hash);
+        conn.execute(
+            "UPDATE notes SET chain_hash = ?1 WHERE id = ?2",
+            params![&hash, &row.id],
+        )?;
+    }
+
+    store_seal(&crypto, conn, key, &digest, note_count)
+}
+
+/// Run a bulk write that can't name the notes it touches (imports, purging the trash) and
+/// reseal afterwards, unless the notes already failed to verify before it; otherwise the
+/// earlier mismatch would be approved along with the write
+pub fn resealing<T>(conn: &Connection, key: &[u8; 32], write: impl FnOnce() -> Result<T>) -> Result<T> {
+    let intact = !matches!(verify(conn, key)?, IntegrityStatus::Tampered { .. });
+    let result = write()?;
+    if intact {
+        seal(conn, key)?;
+    }
+    Ok(result)
+}
+
+/// Check stored notes against the recorded seal
+pub fn verify(conn: &Connection, key: &[u8; 32]) -> Result<IntegrityStatus> {
+    let Some((stored_digest, note_count, head)) = load_seal(conn)? else {
+        return Ok(IntegrityStatus::Unsealed);
+    };
+
+    let crypto = CryptoService::new();
+    let rows = load_rows(conn)?;
+
+    let mut digest = [0u8; 32];
+    for row in &rows {
+        let hash = leaf(&crypto, key, row);
+        if row.chain_hash.as_deref() != Some(hash.as_str()) {
+            return Ok(IntegrityStatus::Tampered {
+                note_id: Some(row.id.clone()),
+                reason: "note does not match its recorded hash".to_string(),
+            });
+        }
+        fold(&mut digest, &hash);
+    }
+
+    if head != head_hash(&crypto, key, &stored_digest, note_count) {
+        return Ok(IntegrityStatus::Tampered {
+            note_id: None,
+            reason: "the recorded seal was altered".to_string(),
+        });
+    }
+    if rows.len() as i64 != note_count {
+        return Ok(IntegrityStatus::Tampered {
+            note_id: None,
+            reason: format!("expected {} notes, found {}", note_count, rows.len()),
+        });
+    }
+    if hex::encode(digest) != stored_digest {
+        return Ok(IntegrityStatus::Tampered {
+            note_id: None,
+            reason: "notes were replaced with ones from another seal".to_string(),
+        });
+    }
+
+    Ok(IntegrityStatus::Valid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{db::Database, models::Note, repository::NoteRepository};
+
+    #[test]
+    fn test_chain_detects_tampering() {
+        let db = Database::in_memory("test_password").unwrap();
+        let conn = db.connection();
+        let key = [7u8; 32];
+
+        assert_eq!(verify(conn, &key).unwrap(), IntegrityStatus::Unsealed);
+
+        let repo = NoteRepository::new(conn);
+        let first = Note::new("First".to_string());
+        let second = Note::new("Second".to_string());
+        repo.create(&first, &key).unwrap();
+        repo.create(&second, &key).unwrap();
+        seal(conn, &key).unwrap();
+        assert_eq!(verify(conn, &key).unwrap(), IntegrityStatus::Valid);
+
+        // Wrong key cannot validate the seal
+        assert!(matches!(verify(conn, &[8u8; 32]).unwrap(), IntegrityStatus::Tampered { .. }));
+
+        // Flipping a flag without the key is detected at that note
+        conn.execute("UPDATE notes SET pinned = 1 WHERE id = ?1", params![&second.id])
+            .unwrap();
+        assert_eq!(
+            verify(conn, &key).unwrap(),
+            IntegrityStatus::Tampered {
+                note_id: Some(second.id.clone()),
+                reason: "note does not match its recorded hash".to_string(),
+            }
+        );
+
+        // Accepting the change reseals
+        seal(conn, &key).unwrap();
+        assert_eq!(verify(conn, &key).unwrap(), IntegrityStatus::Valid);
+
+        // So is adding a reminder
+        conn.execute("UPDATE notes SET reminder_at = '2030-01-01T09:00:00+00:00' WHERE id = ?1", params![&first.id])
+            .unwrap();
+        assert!(matches!(
+            verify(conn, &key).unwrap(),
+            IntegrityStatus::Tampered { note_id: Some(id), .. } if id == first.id
+        ));
+        seal(conn, &key).unwrap();
+
+        // Removing a note is caught by the seal
+        repo.hard_delete(&first.id).unwrap();
+        assert!(matches!(
+            verify(conn, &key).unwrap(),
+            IntegrityStatus::Tampered { note_id: None, .. }
+        ));
+    }
+
+    #[test]
+    fn test_seal_notes_updates_only_the_written_notes() {
+        let db = Database::in_memory("test_password").unwrap();
+        let conn = db.connection();
+        let key = [7u8; 32];
+        let repo = NoteRepository::new(conn);
+
+        let mut first = Note::new("First".to_string());
+        let second = Note::new("Second".to_string());
+        repo.create(&first, &key).unwrap();
+        seal_notes(conn, &key, &[&first.id]).unwrap();
+        repo.create(&second, &key).unwrap();
+        seal_notes(conn, &key, &[&second.id]).unwrap();
+        assert_eq!(verify(conn, &key).unwrap(), IntegrityStatus::Valid);
+
+        first.content = "First, edited".to_string();
+        first.touch();
+        repo.update(&first, &key).unwrap();
+        seal_notes(conn, &key, &[&first.id]).unwrap();
+        assert_eq!(verify(conn, &key).unwrap(), IntegrityStatus::Valid);
+
+        // Replaying an older row along with its hash is caught by the digest
+        conn.execute("CREATE TEMP TABLE old_row AS SELECT * FROM notes WHERE id = ?1", params![&first.id])
+            .unwrap();
+        first.content = "First, edited again".to_string();
+        first.touch();
+        repo.update(&first, &key).unwrap();
+        seal_notes(conn, &key, &[&first.id]).unwrap();
+        conn.execute("INSERT OR REPLACE INTO notes SELECT * FROM temp.old_row", []).unwrap();
+        assert!(matches!(
+            verify(conn, &key).unwrap(),
+            IntegrityStatus::Tampered { note_id: None, .. }
+        ));
+        seal(conn, &key).unwrap();
+
+        // Saving another note doesn't approve a tampered one
+        conn.execute("UPDATE notes SET pinned = 1 WHERE id = ?1", params![&first.id])
+            .unwrap();
+        seal_notes(conn, &key, &[&second.id]).unwrap();
+        assert!(matches!(
+            verify(conn, &key).unwrap(),
+            IntegrityStatus::Tampered { note_id: Some(id), .. } if id == first.id
+        ));
+
+        // Nor does a bulk write once the notes already failed to verify
+        resealing(conn, &key, || repo.purge_deleted().map_err(Into::into)).unwrap();
+        assert!(matches!(verify(conn, &key).unwrap(), IntegrityStatus::Tampered { .. }));
+
+        // A forged seal is left alone
+        seal(conn, &key).unwrap();
+        conn.execute("UPDATE integrity_chain SET note_count = 1", []).unwrap();
+        seal_notes(conn, &key, &[&second.id]).unwrap();
+        assert!(matches!(
+            verify(conn, &key).unwrap(),
+            IntegrityStatus::Tampered { note_id: None, .. }
+        ));
+    }
+}
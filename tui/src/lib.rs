@@ -0,0 +1,28 @@
+//! Jottery's encrypted note storage, usable without the TUI
+//!
+//! [`Vault`] is the supported entry point: unlock a database with its password, then
+//! read, create and search notes without handling encryption keys. `crypto`, `db`,
+//! `models`, `repository` and `export` are the lower-level building blocks. The
+//! remaining modules exist for the `jottery` binary and are not a stable API.
+
+pub mod crypto;
+pub mod db;
+pub mod export;
+pub mod models;
+pub mod repository;
+
+#[doc(hidden)]
+pub mod integrity;
+#[doc(hidden)]
+pub mod rekey;
+#[doc(hidden)]
+pub mod sync;
+#[doc(hidden)]
+pub mod trash;
+#[doc(hidden)]
+pub mod unlock;
+
+mod thumbnail;
+mod vault;
+
+pub use vault::Vault;
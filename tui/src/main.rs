@@ -1,19 +1,21 @@
-mod crypto;
-mod db;
-mod export;
-mod models;
-mod repository;
 mod ui;
 
 use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
 use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use std::io::{IsTerminal, Read};
+use std::path::{Path, PathBuf};
 use std::fs::OpenOptions;
 use std::sync::{Arc, Mutex};
 use tracing::info;
+use zeroize::Zeroizing;
 
-use crypto::CryptoService;
-use db::Database;
+use jottery_tui::{crypto, db, export, integrity, models, rekey, repository, sync, trash, unlock, Vault};
+
+use db::{CipherParams, Database};
+use models::encryption::EncryptionMetadata;
+use repository::{EncryptionRepository, NoteRepository};
+use sync::SyncEngine;
 use ui::{App, EventHandler, Tui};
 
 #[derive(Parser)]
@@ -36,6 +38,10 @@ struct Cli {
     #[arg(long)]
     reset: bool,
 
+    /// Keyfile combined with the password; a new database then always requires it
+    #[arg(long)]
+    keyfile: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -51,6 +57,64 @@ enum Commands {
         /// Password for decryption
         #[arg(short, long)]
         password: String,
+
+        /// Only notes with this tag (repeatable; all must match unless --any-tag)
+        #[arg(short, long = "tag")]
+        tags: Vec<String>,
+
+        /// Match notes with any of the --tag values instead of all
+        #[arg(long)]
+        any_tag: bool,
+
+        /// Only notes modified at or after this RFC 3339 time
+        #[arg(long)]
+        since: Option<DateTime<Utc>>,
+
+        /// Only notes modified at or before this RFC 3339 time
+        #[arg(long)]
+        until: Option<DateTime<Utc>>,
+    },
+    /// Export notes as Markdown files with YAML frontmatter (one file per note)
+    ExportMarkdown {
+        /// Output directory (created if missing)
+        #[arg(short, long)]
+        output_dir: PathBuf,
+
+        /// Password for decryption
+        #[arg(short, long)]
+        password: String,
+
+        /// Also export notes in the recycle bin
+        #[arg(long)]
+        include_deleted: bool,
+    },
+    /// Export notes to a bundle encrypted with a separate export password
+    ExportEncrypted {
+        /// Output file path
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Password for decryption
+        #[arg(short, long)]
+        password: String,
+
+        /// Password protecting the bundle (prompted for if omitted)
+        #[arg(short, long)]
+        export_password: Option<String>,
+    },
+    /// Import notes from an encrypted export bundle
+    ImportEncrypted {
+        /// Input file path
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Password for encryption
+        #[arg(short, long)]
+        password: String,
+
+        /// Password protecting the bundle (prompted for if omitted)
+        #[arg(short, long)]
+        export_password: Option<String>,
     },
     /// Import notes from JSON file
     Import {
@@ -61,6 +125,143 @@ enum Commands {
         /// Password for encryption
         #[arg(short, long)]
         password: String,
+
+        /// Merge notes whose content matches an existing note instead of adding copies
+        #[arg(long)]
+        dedup: bool,
+
+        /// File shape: jottery or webapp (detected from the contents if omitted)
+        #[arg(long)]
+        format: Option<export::ImportFormat>,
+
+        /// Base64 salt of the web app, to decrypt notes it exported still encrypted
+        #[arg(long)]
+        webapp_salt: Option<String>,
+
+        /// Web app password, if it differs from --password
+        #[arg(long)]
+        webapp_password: Option<String>,
+
+        /// PBKDF2 iterations of the web app key
+        #[arg(long, default_value_t = EncryptionMetadata::WEB_APP_ITERATIONS)]
+        webapp_iterations: u32,
+    },
+    /// Import Markdown files (a file or a directory, recursively) as new notes
+    ImportMarkdown {
+        /// Markdown file or directory
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Password for encryption
+        #[arg(short, long)]
+        password: String,
+    },
+    /// Generate a fresh encryption salt and re-encrypt all notes
+    ///
+    /// Useful to undo the 100,000-iteration downgrade applied when pasting web app credentials
+    RotateSalt {
+        /// Password for decryption
+        #[arg(short, long)]
+        password: String,
+
+        /// PBKDF2 iterations for the new key
+        #[arg(short, long, default_value_t = EncryptionMetadata::DEFAULT_ITERATIONS)]
+        iterations: u32,
+    },
+    /// Change the master password, re-encrypting all notes and the database file
+    ChangePassword {
+        /// Current password
+        #[arg(short, long)]
+        password: String,
+
+        /// New password
+        #[arg(short, long)]
+        new_password: String,
+    },
+    /// Write a consistent, still-encrypted copy of the database (safe while the TUI is running)
+    Backup {
+        /// Password for the database (the backup uses the same one)
+        #[arg(short, long)]
+        password: String,
+
+        /// Backup file path
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Overwrite the output file if it exists
+        #[arg(long)]
+        force: bool,
+    },
+    /// Check the database file and every note for corruption
+    ///
+    /// Prints each problem found (or "OK") and exits non-zero if there are any
+    Check {
+        /// Password for decryption
+        #[arg(short, long)]
+        password: String,
+        /// Accept notes changed outside Jottery and reseal them (after reviewing the report)
+        #[arg(long)]
+        accept: bool,
+    },
+    /// Permanently delete every note in the trash and shrink the database file
+    Purge {
+        /// Password for decryption
+        #[arg(short, long)]
+        password: String,
+    },
+    /// Run one sync cycle without the TUI (for cron/headless use)
+    ///
+    /// Endpoint and API key come from the database, or from the
+    /// JOTTERY_SYNC_ENDPOINT / JOTTERY_SYNC_API_KEY environment variables
+    Sync {
+        /// Password (read from stdin if omitted)
+        #[arg(short, long)]
+        password: Option<String>,
+
+        /// Only apply remote changes, never push local edits
+        #[arg(long)]
+        pull_only: bool,
+    },
+    /// Create a note without launching the TUI and print its id
+    ///
+    /// Content comes from --content, from stdin with --stdin, or from $EDITOR otherwise
+    New {
+        /// Password for encryption
+        #[arg(short, long)]
+        password: String,
+
+        /// Note content
+        #[arg(short, long, conflicts_with = "stdin")]
+        content: Option<String>,
+
+        /// Tag to add (repeatable)
+        #[arg(short, long = "tag")]
+        tags: Vec<String>,
+
+        /// Read the note content from stdin
+        #[arg(long)]
+        stdin: bool,
+    },
+    /// Print notes, optionally filtered like the TUI search
+    ///
+    /// Exits 0 with no output when nothing matches; a wrong password is an error
+    #[command(alias = "search")]
+    List {
+        /// Password for decryption
+        #[arg(short, long)]
+        password: String,
+
+        /// Only notes with this tag
+        #[arg(short, long)]
+        tag: Option<String>,
+
+        /// Search query (words, `#tag`, `-word`)
+        #[arg(short, long)]
+        query: Option<String>,
+
+        /// Print a JSON array instead of a table
+        #[arg(long)]
+        json: bool,
     },
 }
 
@@ -112,32 +313,271 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
+    let keyfile = cli.keyfile.as_deref();
+
     // Handle subcommands
     match cli.command {
-        Some(Commands::Export { output, password }) => {
+        Some(Commands::Export { output, password, tags, any_tag, since, until }) => {
             info!("Exporting notes to: {}", output.display());
-            let db = Database::open(&db_path, &password)
-                .context("Failed to open database. Check your password.")?;
+            let filter = export::ExportFilter { tags, any_tag, since, until };
+            let count = export_json(&db_path, &password, keyfile, &output, &filter)?;
+            println!("✓ Exported {} notes to {}", count, output.display());
+            eprintln!("⚠ {} is NOT encrypted; use `export-encrypted` for a password-protected backup", output.display());
+            return Ok(());
+        }
+        Some(Commands::ExportEncrypted { output, password, export_password }) => {
+            info!("Exporting encrypted notes to: {}", output.display());
+            let (db, key) = open_unlocked(&db_path, &password, keyfile)?;
+            let export_password = match export_password {
+                Some(export_password) => export_password,
+                None => prompt_new_password("Export password: ")?,
+            };
+            if export_password.is_empty() {
+                anyhow::bail!("Export password cannot be empty");
+            }
 
-            let crypto = CryptoService::new();
-            let salt = crypto.generate_salt();
-            let key = crypto.derive_key(&password, &salt, 256_000)?;
+            let count = export::export_encrypted(&db, &key, &output, &export_password)?;
+            println!("✓ Exported {} encrypted notes to {}", count, output.display());
+            return Ok(());
+        }
+        Some(Commands::ImportEncrypted { input, password, export_password }) => {
+            info!("Importing encrypted notes from: {}", input.display());
+            let (db, key) = open_unlocked(&db_path, &password, keyfile)?;
+            let export_password = match export_password {
+                Some(export_password) => export_password,
+                None => prompt_password("Export password: ")?,
+            };
 
-            let count = export::export_notes(&db, &key, &output)?;
-            println!("✓ Exported {} notes to {}", count, output.display());
+            let count = integrity::resealing(db.connection(), &key, || {
+                export::import_encrypted(&db, &key, &input, &export_password)
+            })?;
+            println!("✓ Imported {} notes from {}", count, input.display());
+            return Ok(());
+        }
+        Some(Commands::ExportMarkdown { output_dir, password, include_deleted }) => {
+            info!("Exporting notes as Markdown to: {}", output_dir.display());
+            let (db, key) = open_unlocked(&db_path, &password, keyfile)?;
+
+            let count = export::export_markdown(&db, &key, &output_dir, include_deleted)?;
+            println!("✓ Exported {} notes to {}", count, output_dir.display());
             return Ok(());
         }
-        Some(Commands::Import { input, password }) => {
+        Some(Commands::Import { input, password, dedup, format, webapp_salt, webapp_password, webapp_iterations }) => {
             info!("Importing notes from: {}", input.display());
-            let db = Database::open(&db_path, &password)
-                .context("Failed to open database. Check your password.")?;
+            let source_key = match webapp_salt {
+                Some(salt) => {
+                    let metadata = EncryptionMetadata::from_base64_salt(&salt, webapp_iterations)
+                        .map_err(|e| anyhow::anyhow!("Invalid web app salt: {}", e))?;
+                    let password = webapp_password.as_deref().unwrap_or(&password);
+                    Some(crypto::CryptoService::new().derive_key(password, &metadata.salt, metadata.iterations)?)
+                }
+                None => None,
+            };
+            let options = export::ImportOptions { format, dedup, source_key };
+            let stats = import_json(&db_path, &password, keyfile, &input, &options)?;
+            if dedup {
+                println!(
+                    "✓ Imported {} notes from {} ({} duplicates collapsed)",
+                    stats.imported,
+                    input.display(),
+                    stats.duplicates
+                );
+            } else {
+                println!("✓ Imported {} notes from {}", stats.imported, input.display());
+            }
+            return Ok(());
+        }
+        Some(Commands::ImportMarkdown { input, password }) => {
+            info!("Importing Markdown from: {}", input.display());
+            let (db, key) = open_unlocked(&db_path, &password, keyfile)?;
 
-            let crypto = CryptoService::new();
-            let salt = crypto.generate_salt();
-            let key = crypto.derive_key(&password, &salt, 256_000)?;
+            let stats = integrity::resealing(db.connection(), &key, || export::import_markdown(&db, &key, &input))?;
+            for (file, reason) in &stats.skipped {
+                eprintln!("  skipped {}: {}", file.display(), reason);
+            }
+            println!(
+                "✓ Imported {} notes from {} ({} skipped)",
+                stats.imported,
+                input.display(),
+                stats.skipped.len()
+            );
+            return Ok(());
+        }
+        Some(Commands::RotateSalt { password, iterations }) => {
+            if !(EncryptionMetadata::MIN_ITERATIONS..=EncryptionMetadata::MAX_ITERATIONS).contains(&iterations) {
+                anyhow::bail!(
+                    "Iterations must be between {} and {}",
+                    EncryptionMetadata::MIN_ITERATIONS,
+                    EncryptionMetadata::MAX_ITERATIONS
+                );
+            }
+            info!("Rotating encryption salt ({} iterations)", iterations);
+            let db = unlock::open_database(&db_path, &password, keyfile)?;
 
-            let count = export::import_notes(&db, &key, &input)?;
-            println!("✓ Imported {} notes from {}", count, input.display());
+            let (_, stats) = rekey::rotate_salt(&db, &password, keyfile, iterations)?;
+            println!(
+                "✓ Re-encrypted {} notes and {} attachments with a new salt ({} iterations)",
+                stats.notes, stats.attachments, iterations
+            );
+            return Ok(());
+        }
+        Some(Commands::ChangePassword { password, new_password }) => {
+            if new_password.is_empty() {
+                anyhow::bail!("New password cannot be empty");
+            }
+            let (db, key) = open_unlocked(&db_path, &password, keyfile)?;
+
+            let (_, stats) = rekey::change_password(&db, &key, &new_password, keyfile)?;
+            println!(
+                "✓ Password changed; re-encrypted {} notes and {} attachments",
+                stats.notes, stats.attachments
+            );
+            return Ok(());
+        }
+        Some(Commands::Backup { password, output, force }) => {
+            if output.exists() && !force {
+                anyhow::bail!("{} already exists; use --force to overwrite it", output.display());
+            }
+            if !db_path.exists() {
+                anyhow::bail!("Database does not exist: {}", db_path.display());
+            }
+            let db = unlock::open_database(&db_path, &password, keyfile)?;
+            let file_key = unlock::file_key(&password, keyfile)?;
+            let note_count = db.count_notes(true)?;
+
+            // Export next to the target and only replace it once the copy checks out
+            let mut partial = output.clone().into_os_string();
+            partial.push(".partial");
+            let partial = PathBuf::from(partial);
+            let _ = std::fs::remove_file(&partial);
+
+            let verified = db.backup_to(&partial, &file_key).and_then(|_| {
+                let backup = Database::open(&partial, &file_key)
+                    .context("Backup does not open with the database password")?;
+                let problems = backup.integrity_check()?;
+                if !problems.is_empty() {
+                    anyhow::bail!("Backup failed its integrity check: {}", problems.join("; "));
+                }
+                if backup.count_notes(true)? != note_count {
+                    anyhow::bail!("Backup is missing notes");
+                }
+                Ok(())
+            });
+            if let Err(e) = verified {
+                let _ = std::fs::remove_file(&partial);
+                return Err(e);
+            }
+
+            if output.exists() {
+                std::fs::remove_file(&output).context("Failed to replace existing backup")?;
+            }
+            std::fs::rename(&partial, &output).context("Failed to move backup into place")?;
+            println!("✓ Backed up {} notes to {}", note_count, output.display());
+            return Ok(());
+        }
+        Some(Commands::Check { password, accept }) => {
+            let (db, key) = open_unlocked(&db_path, &password, keyfile)?;
+            if accept {
+                integrity::seal(db.connection(), &key)?;
+                println!("✓ Resealed the current notes");
+            }
+
+            let mut problems = db.integrity_check()?;
+            for (id, error) in NoteRepository::new(db.connection()).find_undecryptable(&key)? {
+                problems.push(format!("note {} does not decrypt: {}", id, error));
+            }
+            if let integrity::IntegrityStatus::Tampered { note_id, reason } =
+                integrity::verify(db.connection(), &key)?
+            {
+                match note_id {
+                    Some(id) => problems.push(format!("note {} does not match the integrity chain: {}", id, reason)),
+                    None => problems.push(format!("integrity chain mismatch: {}", reason)),
+                }
+            }
+
+            if problems.is_empty() {
+                println!("✓ OK");
+                return Ok(());
+            }
+            for problem in &problems {
+                println!("✗ {}", problem);
+            }
+            anyhow::bail!("{} problem(s) found", problems.len());
+        }
+        Some(Commands::Purge { password }) => {
+            let (mut db, key) = open_unlocked(&db_path, &password, keyfile)?;
+            let report = trash::empty(&mut db, &db_path, &key)?;
+            if report.notes == 0 {
+                println!("Trash is empty");
+            } else {
+                println!("✓ {}", report);
+            }
+            return Ok(());
+        }
+        Some(Commands::Sync { password, pull_only }) => {
+            let password = match password {
+                Some(password) => password,
+                None => read_password_stdin()?,
+            };
+            let (db, key) = open_unlocked(&db_path, &password, keyfile)?;
+
+            let result = SyncEngine::new(&db, &key)
+                .with_env_overrides()
+                .with_pull_only(pull_only)
+                .run()
+                .context("Sync failed")?;
+            println!("✓ Sync complete: {}", result.phase_summary());
+            for change in &result.changes {
+                println!("  {:<9} {}", change.kind, change.title);
+            }
+            return Ok(());
+        }
+        Some(Commands::New { password, content, tags, stdin }) => {
+            let vault = open_vault(&db_path, &password, keyfile)?;
+
+            let content = match content {
+                Some(content) => content,
+                None if stdin => {
+                    let mut content = String::new();
+                    std::io::stdin()
+                        .read_to_string(&mut content)
+                        .context("Failed to read note content from stdin")?;
+                    content
+                }
+                None => compose_in_editor()?,
+            };
+
+            let tags: Vec<&str> = tags.iter().map(String::as_str).collect();
+            let note = vault.create_note(&content, &tags)?;
+
+            println!("{}", note.id);
+            return Ok(());
+        }
+        Some(Commands::List { password, tag, query, json }) => {
+            let vault = open_vault(&db_path, &password, keyfile)?;
+
+            // The tag must match exactly, unlike a `#tag` term in the query
+            let query = query.unwrap_or_default();
+            let tag = tag.as_deref().map(|t| t.trim_start_matches('#'));
+            let tagged = |tags: &[String]| tag.is_none_or(|tag| has_tag(tags, tag));
+
+            if json {
+                let mut notes = vault.search(&query)?;
+                notes.retain(|note| tagged(&note.tags));
+                println!("{}", serde_json::to_string_pretty(&notes)?);
+            } else {
+                // Only titles are printed, so whole notes needn't be decrypted
+                for note in vault.previews(&query)?.iter().filter(|note| tagged(&note.tags)) {
+                    let tags = note.tags.iter().map(|t| format!("#{}", t)).collect::<Vec<_>>().join(" ");
+                    println!(
+                        "{}  {:<40}  {:<20}  {}",
+                        note.meta.id,
+                        truncate(&note.title(), 40),
+                        tags,
+                        note.meta.modified_at.format("%Y-%m-%d %H:%M")
+                    );
+                }
+            }
             return Ok(());
         }
         None => {
@@ -157,12 +597,15 @@ fn main() -> Result<()> {
         None
     };
 
+    // Restore the terminal before printing any panic
+    ui::terminal::install_panic_hook();
+
     // Create TUI
     let mut tui = Tui::new()?;
     tui.enter()?;
 
     // Create app
-    let mut app = App::new(db_path, debug_log)?;
+    let mut app = App::new(db_path, debug_log)?.with_keyfile(cli.keyfile);
 
     // Event handler
     let events = EventHandler::default();
@@ -182,7 +625,11 @@ fn main() -> Result<()> {
             ui::Event::Resize(_, _) => {
                 // Terminal resized, will redraw on next iteration
             }
-            ui::Event::Tick | ui::Event::Mouse(_) => {
+            ui::Event::Tick => {
+                // Auto-lock must fire even when no keys are pressed
+                app.tick();
+            }
+            ui::Event::Mouse(_) => {
                 // Ignore for now
             }
         }
@@ -193,3 +640,222 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Open the database and derive the content key from the stored salt and iterations
+fn open_unlocked(db_path: &Path, password: &str, keyfile: Option<&Path>) -> Result<(Database, Zeroizing<[u8; 32]>)> {
+    let db = unlock::open_database(db_path, password, keyfile)?;
+    if db.cipher_params() != CipherParams::DEFAULT {
+        eprintln!("Note: database opened with non-default SQLCipher settings ({})", db.cipher_params());
+    }
+
+    let metadata = EncryptionRepository::new(db.connection())
+        .get()?
+        .ok_or_else(|| anyhow::anyhow!("Database has not been set up yet. Unlock it in the TUI first."))?;
+
+    let input = crypto::kdf_input(password, metadata.keyfile_required, keyfile)?;
+    let checked = unlock::derive_checked_key(db.connection(), &input, &metadata)?;
+    if let Some(recorded) = checked.recorded_iterations {
+        eprintln!(
+            "Warning: notes are encrypted with {} PBKDF2 iterations, not the recorded {}; the record has been updated",
+            checked.iterations, recorded
+        );
+    }
+    Ok((db, checked.key))
+}
+
+/// Unlock the database as a `Vault`, for subcommands that only need notes
+fn open_vault(db_path: &Path, password: &str, keyfile: Option<&Path>) -> Result<Vault> {
+    match keyfile {
+        Some(keyfile) => Vault::open_with_keyfile(db_path, password, keyfile),
+        None => Vault::open(db_path, password),
+    }
+}
+
+/// `export` subcommand: write notes matching `filter` to a plaintext JSON file
+fn export_json(
+    db_path: &Path,
+    password: &str,
+    keyfile: Option<&Path>,
+    output: &Path,
+    filter: &export::ExportFilter,
+) -> Result<usize> {
+    let (db, key) = open_unlocked(db_path, password, keyfile)?;
+    export::export_notes(&db, &key, output, filter)
+}
+
+/// `import` subcommand: merge notes from a JSON export and reseal the database
+fn import_json(
+    db_path: &Path,
+    password: &str,
+    keyfile: Option<&Path>,
+    input: &Path,
+    options: &export::ImportOptions,
+) -> Result<export::ImportStats> {
+    let (db, key) = open_unlocked(db_path, password, keyfile)?;
+    integrity::resealing(db.connection(), &key, || export::import_notes(&db, &key, input, options))
+}
+
+/// Write a new note in $EDITOR (default vi) and return what was saved
+fn compose_in_editor() -> Result<String> {
+    let temp_file = tempfile::NamedTempFile::new().context("Failed to create temporary file")?;
+    let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
+
+    let status = std::process::Command::new(&editor)
+        .arg(temp_file.path())
+        .status()
+        .context(format!("Failed to launch editor: {}", editor))?;
+    if !status.success() {
+        anyhow::bail!("Editor exited with non-zero status");
+    }
+
+    std::fs::read_to_string(temp_file.path()).context("Failed to read note content")
+}
+
+/// Whether `tags` has `tag`, ignoring case
+fn has_tag(tags: &[String], tag: &str) -> bool {
+    tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+}
+
+/// Shorten `text` to at most `max` chars, marking the cut with an ellipsis
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        text.to_string()
+    } else {
+        format!("{}…", text.chars().take(max - 1).collect::<String>())
+    }
+}
+
+/// Prompt on stderr, then read a password from stdin
+fn prompt_password(prompt: &str) -> Result<String> {
+    eprint!("{}", prompt);
+    read_password_stdin()
+}
+
+/// Prompt for a password that protects new data, asking twice when typed at a terminal
+fn prompt_new_password(prompt: &str) -> Result<String> {
+    let password = prompt_password(prompt)?;
+    if std::io::stdin().is_terminal() && prompt_password("Repeat to confirm: ")? != password {
+        anyhow::bail!("Passwords do not match");
+    }
+    Ok(password)
+}
+
+/// Read a password from stdin, without echo when it is a terminal; piped input is read as
+/// its first line
+fn read_password_stdin() -> Result<String> {
+    if std::io::stdin().is_terminal() {
+        return rpassword::read_password().context("Failed to read password");
+    }
+    let mut line = String::new();
+    std::io::stdin()
+        .read_line(&mut line)
+        .context("Failed to read password from stdin")?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    const PASSWORD: &str = "correct horse battery staple 42";
+
+    fn press(app: &mut App, code: KeyCode) {
+        app.handle_key(KeyEvent::new(code, KeyModifiers::NONE)).unwrap();
+    }
+
+    fn type_text(app: &mut App, text: &str) {
+        for c in text.chars() {
+            press(app, KeyCode::Char(c));
+        }
+    }
+
+    /// Create a database the way a first TUI session does, with one note per entry
+    fn create_with_tui(db_path: &Path, keyfile: Option<&Path>, notes: &[&str]) {
+        let mut app = App::new(db_path.to_path_buf(), None)
+            .unwrap()
+            .with_keyfile(keyfile.map(Path::to_path_buf));
+        type_text(&mut app, PASSWORD);
+        press(&mut app, KeyCode::Tab);
+        type_text(&mut app, PASSWORD);
+        press(&mut app, KeyCode::Enter);
+        assert!(app.error.is_none(), "{:?}", app.error);
+
+        for content in notes {
+            press(&mut app, KeyCode::Char('n'));
+            type_text(&mut app, content);
+            press(&mut app, KeyCode::Esc);
+            press(&mut app, KeyCode::Esc);
+        }
+    }
+
+    #[test]
+    fn test_list_tag_matches_whole_tags() {
+        let tags = ["Work".to_string(), "home".to_string()];
+        assert!(has_tag(&tags, "work"));
+        assert!(has_tag(&tags, "HOME"));
+        assert!(!has_tag(&tags, "wor"));
+        assert!(!has_tag(&["workshop".to_string()], "work"));
+    }
+
+    #[test]
+    fn test_export_import_subcommands_use_stored_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("source.db");
+        create_with_tui(&source, None, &["Secret plans", "Shopping list"]);
+
+        let output = dir.path().join("backup.json");
+        assert_eq!(export_json(&source, PASSWORD, None, &output, &export::ExportFilter::default()).unwrap(), 2);
+        let exported = std::fs::read_to_string(&output).unwrap();
+        assert!(exported.contains("Secret plans"));
+        assert!(exported.contains("Shopping list"));
+
+        // Into a second database with its own salt
+        let target = dir.path().join("target.db");
+        create_with_tui(&target, None, &[]);
+        assert_eq!(import_json(&target, PASSWORD, None, &output, &export::ImportOptions::default()).unwrap().imported, 2);
+
+        let (db, key) = open_unlocked(&target, PASSWORD, None).unwrap();
+        let mut contents: Vec<String> = NoteRepository::new(db.connection())
+            .list(false, &key)
+            .unwrap()
+            .into_iter()
+            .map(|note| note.content)
+            .collect();
+        contents.sort();
+        // The TUI saved them with the default single trailing newline
+        assert_eq!(contents, ["Secret plans\n", "Shopping list\n"]);
+        assert!(matches!(
+            integrity::verify(db.connection(), &key).unwrap(),
+            integrity::IntegrityStatus::Valid
+        ));
+    }
+
+    #[test]
+    fn test_keyfile_database_needs_keyfile() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("keyfile.db");
+        let keyfile = dir.path().join("jottery.key");
+        std::fs::write(&keyfile, [0x5a; 64]).unwrap();
+        create_with_tui(&db_path, Some(&keyfile), &["Behind two factors"]);
+
+        let (db, key) = open_unlocked(&db_path, PASSWORD, Some(&keyfile)).unwrap();
+        let notes = NoteRepository::new(db.connection()).list(false, &key).unwrap();
+        assert_eq!(notes[0].content, "Behind two factors\n");
+
+        // Without the keyfile the file doesn't open, and the error points at it...
+        let error = open_unlocked(&db_path, PASSWORD, None).err().unwrap();
+        assert!(error.to_string().contains("--keyfile"), "{}", error);
+
+        // ...and the password alone does not derive a key the notes decrypt with
+        let metadata = EncryptionRepository::new(db.connection()).get().unwrap().unwrap();
+        assert!(metadata.keyfile_required);
+        assert!(unlock::derive_checked_key(db.connection(), PASSWORD, &metadata).is_err());
+
+        // Neither does another keyfile
+        let other = dir.path().join("other.key");
+        std::fs::write(&other, [0xa5; 64]).unwrap();
+        let error = open_unlocked(&db_path, PASSWORD, Some(&other)).err().unwrap();
+        assert!(error.to_string().contains("keyfile is the one"), "{}", error);
+    }
+}
@@ -4,12 +4,17 @@ use base64::{Engine as _, engine::general_purpose};
 
 /// Encryption metadata stored per user
 /// Contains information needed for key derivation
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Salt is held as raw bytes: hex-encoded in the database, base64 in JSON (web app format)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct EncryptionMetadata {
-    pub salt: String,        // Base64-encoded salt for PBKDF2
-    pub iterations: i32,     // PBKDF2 iterations (minimum 100,000, default 256,000)
+    #[serde(with = "salt_base64")]
+    pub salt: Vec<u8>,       // Raw PBKDF2 salt (at least 32 bytes)
+    pub iterations: u32,     // PBKDF2 iterations (minimum 100,000, default 256,000)
     pub created_at: DateTime<Utc>,
     pub algorithm: EncryptionAlgorithm,
+    /// Content key derived from the password combined with a keyfile (TUI only)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub keyfile_required: bool,
 }
 
 /// Encryption algorithm identifier
@@ -33,50 +38,126 @@ impl std::fmt::Display for EncryptionAlgorithm {
     }
 }
 
+impl std::str::FromStr for EncryptionAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "AES-256-GCM" => Ok(Self::Aes256Gcm),
+            _ => Err(format!("Unknown encryption algorithm: {}", s)),
+        }
+    }
+}
+
 impl EncryptionMetadata {
+    /// Minimum PBKDF2 iterations accepted
+    pub const MIN_ITERATIONS: u32 = 100_000;
+
+    /// Maximum PBKDF2 iterations accepted (unlock takes seconds well before this)
+    pub const MAX_ITERATIONS: u32 = 10_000_000;
+
+    /// Iterations for databases created by the TUI
+    pub const DEFAULT_ITERATIONS: u32 = 256_000;
+
+    /// Iterations the web app derives its key with (kept when its salt is pasted in)
+    pub const WEB_APP_ITERATIONS: u32 = 100_000;
+
+    /// Iteration counts notes may have been encrypted under, tried on unlock
+    pub const KNOWN_ITERATIONS: [u32; 2] = [Self::DEFAULT_ITERATIONS, Self::WEB_APP_ITERATIONS];
+
+    /// Minimum salt length in bytes
+    pub const MIN_SALT_LENGTH: usize = 32;
+
     /// Create new encryption metadata with default iterations
-    pub fn new(salt: String) -> Self {
+    pub fn new(salt: Vec<u8>) -> Self {
         Self {
             salt,
-            iterations: 256_000, // Match web app and SQLCipher
+            iterations: Self::DEFAULT_ITERATIONS,
             created_at: Utc::now(),
             algorithm: EncryptionAlgorithm::Aes256Gcm,
+            keyfile_required: false,
         }
     }
 
     /// Create encryption metadata with custom iterations
-    pub fn with_iterations(salt: String, iterations: i32) -> Result<Self, String> {
-        if iterations < 100_000 {
-            return Err("Iterations must be at least 100,000".to_string());
-        }
-
-        Ok(Self {
+    pub fn with_iterations(salt: Vec<u8>, iterations: u32) -> Result<Self, String> {
+        let metadata = Self {
             salt,
             iterations,
             created_at: Utc::now(),
             algorithm: EncryptionAlgorithm::Aes256Gcm,
-        })
+            keyfile_required: false,
+        };
+        metadata.validate()?;
+        Ok(metadata)
+    }
+
+    /// Create encryption metadata from a base64 salt (web app sync credentials)
+    pub fn from_base64_salt(salt_b64: &str, iterations: u32) -> Result<Self, String> {
+        let salt = general_purpose::STANDARD
+            .decode(salt_b64)
+            .map_err(|_| "Salt must be valid base64".to_string())?;
+        Self::with_iterations(salt, iterations)
+    }
+
+    /// Decode a hex salt as stored in the database
+    pub fn decode_hex_salt(salt_hex: &str) -> Result<Vec<u8>, String> {
+        hex::decode(salt_hex).map_err(|_| "Salt must be valid hex".to_string())
+    }
+
+    /// Salt encoded for database storage
+    pub fn salt_hex(&self) -> String {
+        hex::encode(&self.salt)
+    }
+
+    /// Salt encoded for the web app
+    pub fn salt_base64(&self) -> String {
+        general_purpose::STANDARD.encode(&self.salt)
     }
 
     /// Validate encryption metadata
     pub fn validate(&self) -> Result<(), String> {
-        if self.iterations < 100_000 {
+        if self.iterations < Self::MIN_ITERATIONS {
             return Err("Iterations must be at least 100,000".to_string());
         }
 
+        if self.iterations > Self::MAX_ITERATIONS {
+            return Err("Iterations must be at most 10,000,000".to_string());
+        }
+
         if self.salt.is_empty() {
             return Err("Salt cannot be empty".to_string());
         }
 
-        // Validate salt is valid base64
-        if general_purpose::STANDARD.decode(&self.salt).is_err() {
-            return Err("Salt must be valid base64".to_string());
+        if self.salt.len() < Self::MIN_SALT_LENGTH {
+            return Err(format!(
+                "Salt must be at least {} bytes (got {})",
+                Self::MIN_SALT_LENGTH,
+                self.salt.len()
+            ));
         }
 
         Ok(())
     }
 }
 
+/// Serde helper encoding the salt as base64, matching the web app
+mod salt_base64 {
+    use base64::{Engine as _, engine::general_purpose};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(salt: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&general_purpose::STANDARD.encode(salt))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
 /// Encrypted data structure
 /// Used for storing encrypted content
 /// Compatible with both TUI format (nonce+tag) and web app format (iv only)
@@ -134,7 +215,7 @@ mod tests {
 
     #[test]
     fn test_encryption_metadata_creation() {
-        let salt = general_purpose::STANDARD.encode(b"test_salt_16byte");
+        let salt = vec![7u8; 32];
         let metadata = EncryptionMetadata::new(salt.clone());
         assert_eq!(metadata.salt, salt);
         assert_eq!(metadata.iterations, 256_000);
@@ -143,8 +224,7 @@ mod tests {
 
     #[test]
     fn test_encryption_metadata_validation() {
-        let salt = general_purpose::STANDARD.encode(b"test_salt");
-        let metadata = EncryptionMetadata::new(salt);
+        let metadata = EncryptionMetadata::new(vec![7u8; 32]);
         assert!(metadata.validate().is_ok());
 
         // Invalid iterations
@@ -152,10 +232,39 @@ mod tests {
         bad_metadata.iterations = 50_000;
         assert!(bad_metadata.validate().is_err());
 
-        // Invalid salt (not base64)
+        // Salt too short
         let mut bad_metadata = metadata.clone();
-        bad_metadata.salt = "not valid base64!!!".to_string();
+        bad_metadata.salt = b"test_salt".to_vec();
         assert!(bad_metadata.validate().is_err());
+
+        // Invalid base64
+        assert!(EncryptionMetadata::from_base64_salt("not valid base64!!!", 100_000).is_err());
+    }
+
+    #[test]
+    fn test_salt_encoding_roundtrip() {
+        let salt: Vec<u8> = (0..32).collect();
+        let metadata = EncryptionMetadata::with_iterations(salt.clone(), 100_000).unwrap();
+
+        // Hex (database)
+        assert_eq!(EncryptionMetadata::decode_hex_salt(&metadata.salt_hex()).unwrap(), salt);
+
+        // Base64 (web app)
+        let from_b64 = EncryptionMetadata::from_base64_salt(&metadata.salt_base64(), 100_000).unwrap();
+        assert_eq!(from_b64.salt, salt);
+
+        // JSON carries the salt as base64
+        let json = serde_json::to_value(&metadata).unwrap();
+        assert_eq!(json["salt"], metadata.salt_base64());
+        let parsed: EncryptionMetadata = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, metadata);
+    }
+
+    #[test]
+    fn test_algorithm_roundtrip() {
+        let algorithm = EncryptionAlgorithm::Aes256Gcm;
+        assert_eq!(algorithm.to_string().parse::<EncryptionAlgorithm>().unwrap(), algorithm);
+        assert!("AES-128-CBC".parse::<EncryptionAlgorithm>().is_err());
     }
 
     #[test]
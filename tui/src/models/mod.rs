@@ -5,6 +5,7 @@ pub mod note;
 pub mod settings;
 pub mod encryption;
 pub mod sync;
+pub mod time;
 
 pub use note::*;
 pub use settings::*;
@@ -20,6 +20,79 @@ pub struct Note {
     pub version: i32,
     pub word_wrap: bool,
     pub syntax_language: SyntaxLanguage,
+    /// When to remind about the note, for task-like notes
+    #[serde(default)]
+    pub reminder_at: Option<DateTime<Utc>>,
+}
+
+/// A note's unencrypted columns, listed without the key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NoteMeta {
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    pub modified_at: DateTime<Utc>,
+    pub pinned: bool,
+    pub deleted: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reminder_at: Option<DateTime<Utc>>,
+    /// Ids of the note's attachments, for the list's attachment markers
+    #[serde(skip)]
+    pub attachment_ids: Vec<String>,
+}
+
+/// Characters of content kept in a note's preview
+pub const PREVIEW_CHARS: usize = 120;
+
+/// Hours ahead within which a reminder counts as upcoming
+pub const REMINDER_UPCOMING_HOURS: i64 = 24;
+
+/// How a note's reminder stands relative to now
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReminderState {
+    /// The reminder time has passed
+    Overdue,
+    /// Due within `REMINDER_UPCOMING_HOURS`
+    Upcoming,
+    /// Due later than that
+    Later,
+}
+
+impl ReminderState {
+    /// State of a reminder due at `at`
+    pub fn of(at: DateTime<Utc>, now: DateTime<Utc>) -> Self {
+        if at <= now {
+            Self::Overdue
+        } else if at - now <= chrono::Duration::hours(REMINDER_UPCOMING_HOURS) {
+            Self::Upcoming
+        } else {
+            Self::Later
+        }
+    }
+}
+
+/// Earlier versions kept per note in its history
+pub const NOTE_HISTORY_LIMIT: usize = 20;
+
+/// A note's content and tags as they were before an update replaced them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoteVersion {
+    /// Row id in the history table, increasing with each recorded version
+    pub id: i64,
+    pub note_id: String,
+    /// The note's `version` when it held this content
+    pub version: i32,
+    pub modified_at: DateTime<Utc>,
+    pub content: String,
+    pub tags: Vec<String>,
+}
+
+/// A note's metadata, tags and preview, listed without decrypting its full content
+#[derive(Debug, Clone, Serialize)]
+pub struct NotePreview {
+    #[serde(flatten)]
+    pub meta: NoteMeta,
+    pub tags: Vec<String>,
+    pub preview: String,
 }
 
 /// Syntax highlighting language options
@@ -78,8 +151,39 @@ impl std::str::FromStr for SyntaxLanguage {
     }
 }
 
+impl SyntaxLanguage {
+    /// Every language, in picker order
+    pub const ALL: [Self; 9] = [
+        Self::Plain,
+        Self::Javascript,
+        Self::Python,
+        Self::Markdown,
+        Self::Json,
+        Self::Html,
+        Self::Css,
+        Self::Sql,
+        Self::Bash,
+    ];
+
+    /// File extension external tools recognize the language by
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Plain => "txt",
+            Self::Javascript => "js",
+            Self::Python => "py",
+            Self::Markdown => "md",
+            Self::Json => "json",
+            Self::Html => "html",
+            Self::Css => "css",
+            Self::Sql => "sql",
+            Self::Bash => "sh",
+        }
+    }
+}
+
 /// Represents a file attachment
-/// Filename is encrypted, data is a reference to encrypted blob store
+/// Filename is encrypted, data is a reference to encrypted blob store. Image thumbnails
+/// live beside the blob (see `AttachmentRepository::thumbnail_ids`)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Attachment {
     pub id: String,
@@ -87,7 +191,6 @@ pub struct Attachment {
     pub mime_type: String,
     pub size: i64,
     pub data: String,                // Reference to encrypted blob store
-    pub thumbnail_data: Option<String>, // Optional thumbnail for images
 }
 
 impl Note {
@@ -109,9 +212,18 @@ impl Note {
             version: 1,
             word_wrap: true,
             syntax_language: SyntaxLanguage::Plain,
+            reminder_at: None,
         }
     }
 
+    /// Where the note's reminder stands at `now`; None without a reminder
+    ///
+    /// Notes in the trash have no live reminders.
+    pub fn reminder_state(&self, now: DateTime<Utc>) -> Option<ReminderState> {
+        let at = self.reminder_at.filter(|_| !self.deleted)?;
+        Some(ReminderState::of(at, now))
+    }
+
     /// Update modified timestamp
     pub fn touch(&mut self) {
         self.modified_at = Utc::now();
@@ -137,6 +249,111 @@ impl Note {
         self.pinned = !self.pinned;
         self.touch();
     }
+
+    /// In-memory search: every word must appear in the content, `#tag` must match a tag
+    /// and `-word` must not appear (case-insensitive substring matching)
+    pub fn matches_query(&self, query: &str) -> bool {
+        self.matches_query_with_case(query, false)
+    }
+
+    /// `matches_query`, optionally comparing case exactly (for words and `#tag`s alike)
+    pub fn matches_query_with_case(&self, query: &str, case_sensitive: bool) -> bool {
+        matches_query(&self.content, &self.tags, query, case_sensitive)
+    }
+
+    /// First non-empty line of content, used as a display title
+    pub fn title(&self) -> String {
+        title_of(&self.content)
+    }
+
+    /// Start of the content from its first non-blank character, stored encrypted on its
+    /// own so the list can be shown without decrypting whole notes
+    pub fn preview(&self) -> String {
+        preview_of(&self.content)
+    }
+}
+
+impl NoteVersion {
+    /// First non-empty line of the content, like `Note::title`
+    pub fn title(&self) -> String {
+        title_of(&self.content)
+    }
+}
+
+impl NotePreview {
+    /// First non-empty line of the preview, like `Note::title`
+    pub fn title(&self) -> String {
+        title_of(&self.preview)
+    }
+
+    /// `Note::matches_query_with_case` against the preview, for when the full content
+    /// can't be searched
+    pub fn matches_query_with_case(&self, query: &str, case_sensitive: bool) -> bool {
+        matches_query(&self.preview, &self.tags, query, case_sensitive)
+    }
+}
+
+impl NoteMeta {
+    /// Where the note's reminder stands at `now`, like `Note::reminder_state`
+    pub fn reminder_state(&self, now: DateTime<Utc>) -> Option<ReminderState> {
+        let at = self.reminder_at.filter(|_| !self.deleted)?;
+        Some(ReminderState::of(at, now))
+    }
+}
+
+impl From<&Note> for NotePreview {
+    fn from(note: &Note) -> Self {
+        Self {
+            meta: NoteMeta {
+                id: note.id.clone(),
+                created_at: note.created_at,
+                modified_at: note.modified_at,
+                pinned: note.pinned,
+                deleted: note.deleted,
+                reminder_at: note.reminder_at,
+                attachment_ids: note.attachments.iter().map(|a| a.id.clone()).collect(),
+            },
+            tags: note.tags.clone(),
+            preview: note.preview(),
+        }
+    }
+}
+
+/// Search `content` and `tags` for `query` the way `Note::matches_query_with_case` does
+pub fn matches_query(content: &str, tags: &[String], query: &str, case_sensitive: bool) -> bool {
+    let fold = |text: &str| {
+        if case_sensitive {
+            text.to_string()
+        } else {
+            text.to_lowercase()
+        }
+    };
+    let query = fold(query);
+    let content = fold(content);
+
+    query.split_whitespace().all(|part| {
+        if let Some(tag) = part.strip_prefix('#') {
+            tags.iter().any(|t| fold(t).contains(tag))
+        } else if let Some(neg_word) = part.strip_prefix('-') {
+            !content.contains(neg_word)
+        } else {
+            content.contains(part)
+        }
+    })
+}
+
+/// Up to `PREVIEW_CHARS` of `content`, from its first non-blank character
+pub(crate) fn preview_of(content: &str) -> String {
+    content.trim_start().chars().take(PREVIEW_CHARS).collect()
+}
+
+/// First non-empty line of `text`, trimmed
+fn title_of(text: &str) -> String {
+    text.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or("Untitled")
+        .to_string()
 }
 
 impl Attachment {
@@ -148,7 +365,31 @@ impl Attachment {
             mime_type,
             size,
             data,
-            thumbnail_data: None,
+        }
+    }
+
+    /// MIME type for a file, guessed from its extension
+    pub fn mime_type_for(filename: &str) -> &'static str {
+        let extension = std::path::Path::new(filename)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_lowercase)
+            .unwrap_or_default();
+        match extension.as_str() {
+            "png" => "image/png",
+            "jpg" | "jpeg" => "image/jpeg",
+            "gif" => "image/gif",
+            "bmp" => "image/bmp",
+            "webp" => "image/webp",
+            "svg" => "image/svg+xml",
+            "pdf" => "application/pdf",
+            "zip" => "application/zip",
+            "json" => "application/json",
+            "txt" | "log" => "text/plain",
+            "md" => "text/markdown",
+            "csv" => "text/csv",
+            "html" | "htm" => "text/html",
+            _ => "application/octet-stream",
         }
     }
 }
@@ -157,6 +398,32 @@ impl Attachment {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_reminder_state() {
+        let now = Utc::now();
+        let mut note = Note::new("Call the plumber".to_string());
+        assert_eq!(note.reminder_state(now), None);
+
+        note.reminder_at = Some(now - chrono::Duration::minutes(1));
+        assert_eq!(note.reminder_state(now), Some(ReminderState::Overdue));
+        note.reminder_at = Some(now + chrono::Duration::hours(REMINDER_UPCOMING_HOURS));
+        assert_eq!(note.reminder_state(now), Some(ReminderState::Upcoming));
+        note.reminder_at = Some(now + chrono::Duration::days(3));
+        assert_eq!(note.reminder_state(now), Some(ReminderState::Later));
+
+        note.reminder_at = Some(now - chrono::Duration::days(1));
+        note.mark_deleted();
+        assert_eq!(note.reminder_state(now), None);
+    }
+
+    #[test]
+    fn test_attachment_mime_type_from_extension() {
+        assert_eq!(Attachment::mime_type_for("photo.PNG"), "image/png");
+        assert_eq!(Attachment::mime_type_for("/tmp/report.pdf"), "application/pdf");
+        assert_eq!(Attachment::mime_type_for("notes.md"), "text/markdown");
+        assert_eq!(Attachment::mime_type_for("Makefile"), "application/octet-stream");
+    }
+
     #[test]
     fn test_note_creation() {
         let note = Note::new("Test content".to_string());
@@ -192,6 +459,12 @@ mod tests {
         assert!(note.deleted_at.is_none());
     }
 
+    #[test]
+    fn test_note_title() {
+        assert_eq!(Note::new("\n  Shopping list  \nmilk".to_string()).title(), "Shopping list");
+        assert_eq!(Note::new("   \n".to_string()).title(), "Untitled");
+    }
+
     #[test]
     fn test_syntax_language_from_str() {
         assert_eq!("plain".parse::<SyntaxLanguage>().unwrap(), SyntaxLanguage::Plain);
@@ -1,5 +1,12 @@
 use serde::{Deserialize, Serialize};
 
+/// Narrowest the note list pane can be set to
+pub const MIN_NOTE_LIST_WIDTH: u16 = 20;
+/// Widest the note list pane can be set to
+pub const MAX_NOTE_LIST_WIDTH: u16 = 120;
+/// Highest pin limit that can be set
+pub const MAX_PIN_LIMIT: i32 = 100;
+
 /// User application settings
 /// Stored unencrypted in the database
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -9,7 +16,52 @@ pub struct UserSettings {
     pub sort_order: SortOrder,
     pub auto_lock_timeout: i32, // Minutes
     pub sync_enabled: bool,
+    /// Loaded from the sync metadata, where it is stored encrypted; never saved with the settings
     pub sync_endpoint: Option<String>,
+    #[serde(default)]
+    pub sync_pull_only: bool, // Never push local changes (read-only replica)
+    #[serde(default)]
+    pub conflict_policy: ConflictPolicy,
+    #[serde(default = "default_sync_max_attempts")]
+    pub sync_max_attempts: i32, // Tries per sync request on transient failures (1 = no retries)
+    #[serde(default)]
+    pub trailing_newline: TrailingNewline,
+    #[serde(default = "default_syntax_highlighting")]
+    pub syntax_highlighting: bool, // Colour code notes in the preview pane
+    #[serde(default = "default_note_list_width")]
+    pub note_list_width: u16, // Columns for the note list; the preview takes the rest
+    #[serde(default = "default_show_preview")]
+    pub show_preview: bool, // Off lets the note list use the full width
+    #[serde(default = "default_emoji_icons")]
+    pub emoji_icons: bool, // Off uses ASCII markers for terminals without emoji
+    #[serde(default = "default_autosave_seconds")]
+    pub autosave_seconds: i32, // Editor idle time before unsaved changes are written (0 = off)
+    #[serde(default)]
+    pub pin_limit: i32, // Most notes pinned at once (0 = no limit)
+}
+
+fn default_syntax_highlighting() -> bool {
+    true
+}
+
+fn default_note_list_width() -> u16 {
+    42
+}
+
+fn default_show_preview() -> bool {
+    true
+}
+
+fn default_emoji_icons() -> bool {
+    true
+}
+
+fn default_sync_max_attempts() -> i32 {
+    3
+}
+
+fn default_autosave_seconds() -> i32 {
+    5
 }
 
 /// Theme options
@@ -64,6 +116,65 @@ impl std::fmt::Display for SortOrder {
     }
 }
 
+/// Trailing newline handling applied when saving notes
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TrailingNewline {
+    #[default]
+    Single,    // Ensure exactly one trailing newline
+    None,      // Strip trailing newlines
+    Verbatim,  // Leave content as typed
+}
+
+impl std::fmt::Display for TrailingNewline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Single => write!(f, "single"),
+            Self::None => write!(f, "none"),
+            Self::Verbatim => write!(f, "verbatim"),
+        }
+    }
+}
+
+impl TrailingNewline {
+    /// Normalize the end of note content according to this mode
+    pub fn apply(&self, content: &str) -> String {
+        let trimmed = content.trim_end_matches(['\n', '\r']);
+        match self {
+            Self::Single if trimmed.is_empty() => String::new(),
+            Self::Single => format!("{}\n", trimmed),
+            Self::None => trimmed.to_string(),
+            Self::Verbatim => content.to_string(),
+        }
+    }
+}
+
+/// How sync resolves a note edited both locally and remotely since the last sync
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    #[default]
+    #[serde(rename = "newest")]
+    NewestWins, // Later modified_at wins
+    #[serde(rename = "local")]
+    LocalWins,  // Keep the local version and push it
+    #[serde(rename = "remote")]
+    RemoteWins, // Take the server version
+    #[serde(rename = "ask")]
+    AskMe,      // Leave both untouched for manual resolution
+}
+
+impl std::fmt::Display for ConflictPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NewestWins => write!(f, "newest"),
+            Self::LocalWins => write!(f, "local"),
+            Self::RemoteWins => write!(f, "remote"),
+            Self::AskMe => write!(f, "ask"),
+        }
+    }
+}
+
 impl UserSettings {
     /// Create default settings
     pub fn default() -> Self {
@@ -74,6 +185,16 @@ impl UserSettings {
             auto_lock_timeout: 15, // 15 minutes
             sync_enabled: false,
             sync_endpoint: None,
+            sync_pull_only: false,
+            conflict_policy: ConflictPolicy::NewestWins,
+            sync_max_attempts: default_sync_max_attempts(),
+            trailing_newline: TrailingNewline::Single,
+            syntax_highlighting: true,
+            note_list_width: default_note_list_width(),
+            show_preview: default_show_preview(),
+            emoji_icons: default_emoji_icons(),
+            autosave_seconds: default_autosave_seconds(),
+            pin_limit: 0,
         }
     }
 
@@ -83,6 +204,25 @@ impl UserSettings {
             return Err("Auto-lock timeout must be between 1 and 1440 minutes".to_string());
         }
 
+        if self.sync_max_attempts < 1 || self.sync_max_attempts > 10 {
+            return Err("Sync attempts must be between 1 and 10".to_string());
+        }
+
+        if !(0..=600).contains(&self.autosave_seconds) {
+            return Err("Autosave delay must be between 0 (disabled) and 600 seconds".to_string());
+        }
+
+        if !(0..=MAX_PIN_LIMIT).contains(&self.pin_limit) {
+            return Err(format!("Pin limit must be between 0 (no limit) and {}", MAX_PIN_LIMIT));
+        }
+
+        if !(MIN_NOTE_LIST_WIDTH..=MAX_NOTE_LIST_WIDTH).contains(&self.note_list_width) {
+            return Err(format!(
+                "Note list width must be between {} and {} columns",
+                MIN_NOTE_LIST_WIDTH, MAX_NOTE_LIST_WIDTH
+            ));
+        }
+
         if self.sync_enabled && self.sync_endpoint.is_none() {
             return Err("Sync endpoint is required when sync is enabled".to_string());
         }
@@ -109,6 +249,18 @@ mod tests {
         assert_eq!(settings.sort_order, SortOrder::Recent);
         assert_eq!(settings.auto_lock_timeout, 15);
         assert!(!settings.sync_enabled);
+        assert!(!settings.sync_pull_only);
+        assert_eq!(settings.conflict_policy, ConflictPolicy::NewestWins);
+        assert_eq!(settings.trailing_newline, TrailingNewline::Single);
+    }
+
+    #[test]
+    fn test_trailing_newline() {
+        assert_eq!(TrailingNewline::Single.apply("note"), "note\n");
+        assert_eq!(TrailingNewline::Single.apply("note\n\n\r\n"), "note\n");
+        assert_eq!(TrailingNewline::Single.apply("\n"), "");
+        assert_eq!(TrailingNewline::None.apply("note\n\n"), "note");
+        assert_eq!(TrailingNewline::Verbatim.apply("note\n\n"), "note\n\n");
     }
 
     #[test]
@@ -133,5 +285,12 @@ mod tests {
         // Invalid endpoint protocol
         settings.sync_endpoint = Some("ftp://example.com".to_string());
         assert!(settings.validate().is_err());
+        settings.sync_endpoint = Some("https://example.com".to_string());
+
+        // Pin limit out of range
+        settings.pin_limit = MAX_PIN_LIMIT + 1;
+        assert!(settings.validate().is_err());
+        settings.pin_limit = 5;
+        assert!(settings.validate().is_ok());
     }
 }
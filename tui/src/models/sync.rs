@@ -76,6 +76,22 @@ pub struct SyncStatusDisplay {
     pub sync_endpoint: Option<String>,
 }
 
+impl SyncStatusDisplay {
+    /// One-line health summary such as "Last synced: 3m ago • 2 conflicts"
+    pub fn summary(&self, now: DateTime<Utc>) -> String {
+        let mut text = match self.last_sync_at {
+            None => "Never synced".to_string(),
+            Some(at) => format!("Last synced: {}", super::time::ago(at, now)),
+        };
+        match self.conflict_count {
+            0 => {}
+            1 => text.push_str(" • 1 conflict"),
+            n => text.push_str(&format!(" • {} conflicts", n)),
+        }
+        text
+    }
+}
+
 impl Default for SyncStatusDisplay {
     fn default() -> Self {
         Self {
@@ -114,6 +130,8 @@ pub struct SyncNote {
     pub version: i32,
     pub word_wrap: Option<bool>,
     pub syntax_language: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reminder_at: Option<DateTime<Utc>>,
 }
 
 /// Attachment reference (metadata only)
@@ -166,6 +184,10 @@ pub struct SyncRejected {
 pub struct SyncPullRequest {
     pub last_sync_at: Option<DateTime<Utc>>,
     pub known_note_ids: Vec<String>,
+    /// `next_cursor` of the previous page; `None` for the first page
+    pub cursor: Option<String>,
+    /// Notes per page (the server applies its own default and cap)
+    pub limit: Option<i64>,
 }
 
 /// Pull response from server
@@ -174,8 +196,33 @@ pub struct SyncPullRequest {
 pub struct SyncPullResponse {
     pub notes: Vec<SyncNote>,
     pub deletions: Vec<SyncDeletion>,
+    /// Inline blobs, only sent by servers without the attachment endpoint
+    #[serde(default)]
     pub attachments: Vec<SyncAttachment>,
     pub synced_at: DateTime<Utc>,
+    /// Cursor for the next page; `None` on the last page (and from unpaginated servers)
+    #[serde(default)]
+    pub next_cursor: Option<String>,
+}
+
+/// Attachment blob request (pull pages only reference attachments by id)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncAttachmentsRequest {
+    pub ids: Vec<String>,
+}
+
+/// Attachment blobs returned by the server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncAttachmentsResponse {
+    pub attachments: Vec<SyncAttachment>,
+}
+
+/// A replacement API key issued by the server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RotateKeyResponse {
+    pub api_key: String,
+    pub client_id: String,
 }
 
 /// Deleted note info
@@ -287,6 +334,21 @@ mod tests {
         assert_eq!(decoded.client_id, creds.client_id);
     }
 
+    #[test]
+    fn test_status_display_summary() {
+        let now = Utc::now();
+        let mut status = SyncStatusDisplay::default();
+        assert_eq!(status.summary(now), "Never synced");
+
+        status.last_sync_at = Some(now - chrono::Duration::minutes(3));
+        status.conflict_count = 2;
+        assert_eq!(status.summary(now), "Last synced: 3m ago • 2 conflicts");
+
+        status.last_sync_at = Some(now - chrono::Duration::hours(26));
+        status.conflict_count = 0;
+        assert_eq!(status.summary(now), "Last synced: 1d ago");
+    }
+
     #[test]
     fn test_credentials_from_invalid_base64() {
         let result = SyncCredentials::from_base64("invalid!!!base64");
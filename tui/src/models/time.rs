@@ -0,0 +1,117 @@
+//! Human-friendly timestamps shared by the list preview, the sync status and reminders
+
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+
+/// Compact age of `at` relative to `now`, such as "just now", "5m ago", "3h ago" or "2d ago"
+pub fn ago(at: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    match (now - at).num_minutes().max(0) {
+        0 => "just now".to_string(),
+        m if m < 60 => format!("{}m ago", m),
+        m if m < 60 * 24 => format!("{}h ago", m / 60),
+        m => format!("{}d ago", m / (60 * 24)),
+    }
+}
+
+/// Compact time left until `at`, such as "in 5m", "in 3h" or "in 2d"; past times fall back to [`ago`]
+pub fn until(at: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    if at < now {
+        return ago(at, now);
+    }
+    match (at - now).num_minutes() {
+        0 => "now".to_string(),
+        m if m < 60 => format!("in {}m", m),
+        m if m < 60 * 24 => format!("in {}h", m / 60),
+        m => format!("in {}d", m / (60 * 24)),
+    }
+}
+
+/// Parse a reminder typed in the editor, in local time
+///
+/// Accepts an offset (`+30m`, `+2h`, `+3d`, `+1w`), `YYYY-MM-DD HH:MM`, `YYYY-MM-DD` (09:00 that day)
+/// or `HH:MM` (today, or tomorrow if that time has passed)
+pub fn parse_reminder(input: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>, String> {
+    let input = input.trim();
+    if let Some(offset) = input.strip_prefix('+') {
+        let split = offset.char_indices().last().map_or(0, |(i, _)| i);
+        let (amount, unit) = offset.split_at(split);
+        let amount: i64 = amount.parse().map_err(|_| format!("Invalid offset: {}", input))?;
+        let duration = match unit {
+            "m" => Duration::try_minutes(amount),
+            "h" => Duration::try_hours(amount),
+            "d" => Duration::try_days(amount),
+            "w" => Duration::try_weeks(amount),
+            _ => return Err(format!("Unknown unit in {} (use m, h, d or w)", input)),
+        };
+        return duration
+            .and_then(|duration| now.checked_add_signed(duration))
+            .ok_or_else(|| format!("Offset too large: {}", input));
+    }
+
+    let local = if let Ok(dt) = NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M") {
+        dt
+    } else if let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+        date.and_time(NaiveTime::from_hms_opt(9, 0, 0).unwrap_or_default())
+    } else if let Ok(time) = NaiveTime::parse_from_str(input, "%H:%M") {
+        let today = now.with_timezone(&Local).date_naive().and_time(time);
+        if from_local(today)? <= now { today + Duration::days(1) } else { today }
+    } else {
+        return Err(format!("Unrecognised reminder: {} (try +2h, 18:00 or 2024-06-01 09:30)", input));
+    };
+    from_local(local)
+}
+
+fn from_local(local: NaiveDateTime) -> Result<DateTime<Utc>, String> {
+    Local
+        .from_local_datetime(&local)
+        .earliest()
+        .map(|dt| dt.with_timezone(&Utc))
+        .ok_or_else(|| format!("{} doesn't exist in the local time zone", local))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ago() {
+        let now = Utc::now();
+        assert_eq!(ago(now, now), "just now");
+        assert_eq!(ago(now + Duration::minutes(5), now), "just now");
+        assert_eq!(ago(now - Duration::minutes(59), now), "59m ago");
+        assert_eq!(ago(now - Duration::minutes(150), now), "2h ago");
+        assert_eq!(ago(now - Duration::days(40), now), "40d ago");
+    }
+
+    #[test]
+    fn test_until() {
+        let now = Utc::now();
+        assert_eq!(until(now + Duration::seconds(30), now), "now");
+        assert_eq!(until(now + Duration::minutes(5), now), "in 5m");
+        assert_eq!(until(now + Duration::hours(3), now), "in 3h");
+        assert_eq!(until(now + Duration::days(2), now), "in 2d");
+        assert_eq!(until(now - Duration::hours(3), now), "3h ago");
+    }
+
+    #[test]
+    fn test_parse_reminder() {
+        let now = Utc::now();
+        assert_eq!(parse_reminder("+30m", now), Ok(now + Duration::minutes(30)));
+        assert_eq!(parse_reminder(" +2h ", now), Ok(now + Duration::hours(2)));
+        assert_eq!(parse_reminder("+1w", now), Ok(now + Duration::weeks(1)));
+        assert!(parse_reminder("+2y", now).is_err());
+        assert!(parse_reminder("+h", now).is_err());
+        assert!(parse_reminder("+5é", now).is_err());
+        assert_eq!(parse_reminder("+99999999d", now), Err("Offset too large: +99999999d".to_string()));
+        assert!(parse_reminder("+9223372036854775807w", now).is_err());
+        assert!(parse_reminder("soon", now).is_err());
+
+        let dated = parse_reminder("2030-06-01 18:30", now).unwrap().with_timezone(&Local);
+        assert_eq!(dated.format("%Y-%m-%d %H:%M").to_string(), "2030-06-01 18:30");
+        let day = parse_reminder("2030-06-01", now).unwrap().with_timezone(&Local);
+        assert_eq!(day.format("%H:%M").to_string(), "09:00");
+
+        let at = parse_reminder("12:00", now).unwrap();
+        assert!(at > now && at <= now + Duration::days(1));
+        assert_eq!(at.with_timezone(&Local).format("%H:%M").to_string(), "12:00");
+    }
+}
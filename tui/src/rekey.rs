@@ -0,0 +1,304 @@
+//! Re-encryption of stored content under a new key
+//! Used when rotating the PBKDF2 salt/iterations and when changing the password
+
+use anyhow::{Context, Result};
+use rusqlite::Connection;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+use crate::{
+    crypto::{kdf_input, CryptoService, EncryptedData},
+    db::Database,
+    integrity,
+    models::encryption::EncryptionMetadata,
+    repository::{attachment::AttachmentRepository, sync::{SyncRepository, PLAINTEXT_MARKER}, EncryptionRepository, NoteRepository},
+};
+
+/// Counts of re-encrypted records
+#[derive(Debug, Clone, Default)]
+pub struct ReencryptStats {
+    pub notes: usize,
+    pub attachments: usize,
+}
+
+/// Re-encrypt every note, attachment and the stored sync credentials from `old_key` to `new_key`
+///
+/// Everything is decrypted with the old key first, so a single unreadable record aborts
+/// before anything is written. Callers should run this inside a transaction.
+pub fn reencrypt_all(
+    conn: &Connection,
+    old_key: &[u8; 32],
+    new_key: &[u8; 32],
+) -> Result<ReencryptStats> {
+    let crypto = CryptoService::new();
+    let note_repo = NoteRepository::new(conn);
+    let attachment_repo = AttachmentRepository::new(conn);
+    let sync_repo = SyncRepository::new(conn);
+
+    // Decrypt everything up front
+    let notes = note_repo
+        .list(true, old_key)
+        .context("Failed to decrypt notes with the current key")?;
+
+    // Attachments are only checked here, streaming keeps large ones out of memory
+    let attachments = attachment_repo.ids()?;
+    for id in &attachments {
+        attachment_repo
+            .read_stream(id, old_key, std::io::sink())
+            .with_context(|| format!("Failed to decrypt attachment {}", id))?;
+    }
+
+    let sync_metadata = sync_repo
+        .get_metadata(old_key)
+        .context("Failed to decrypt sync settings with the current key")?;
+
+    // History goes first: it checks every version decrypts before rewriting any
+    note_repo
+        .reencrypt_history(old_key, new_key)
+        .context("Failed to re-encrypt note history")?;
+
+    // Write back under the new key
+    for note in &notes {
+        note_repo.update(note, new_key)?;
+    }
+
+    for id in &attachments {
+        attachment_repo.reencrypt(id, old_key, new_key)?;
+    }
+
+    if let Some(mut metadata) = sync_metadata {
+        if let Some(api_key_json) = &metadata.api_key {
+            // Plaintext keys are encrypted on the next unlock, leave them alone
+            if !api_key_json.starts_with(PLAINTEXT_MARKER) {
+                let encrypted: EncryptedData = serde_json::from_str(api_key_json)?;
+                let api_key = crypto
+                    .decrypt_text(&encrypted, old_key)
+                    .context("Failed to decrypt sync API key with the current key")?;
+                let reencrypted = crypto.encrypt_text(&api_key, new_key)?;
+                metadata.api_key = Some(serde_json::to_string(&reencrypted)?);
+            }
+        }
+        sync_repo.update_metadata(&metadata, new_key)?;
+    }
+
+    Ok(ReencryptStats {
+        notes: notes.len(),
+        attachments: attachments.len(),
+    })
+}
+
+/// Generate a fresh salt at `iterations`, re-encrypt all content and store the new metadata
+///
+/// Runs in a single transaction and verifies every note decrypts with the new key before
+/// committing. `keyfile` must be given for databases set up with one. Returns the new
+/// content key.
+pub fn rotate_salt(
+    db: &Database,
+    password: &str,
+    keyfile: Option<&Path>,
+    iterations: u32,
+) -> Result<(Zeroizing<[u8; 32]>, ReencryptStats)> {
+    let crypto = CryptoService::new();
+    let encryption_repo = EncryptionRepository::new(db.connection());
+
+    let metadata = encryption_repo
+        .get()?
+        .ok_or_else(|| anyhow::anyhow!("Database has not been set up yet"))?;
+    let input = kdf_input(password, metadata.keyfile_required, keyfile)?;
+    let old_key = crypto.derive_key(&input, &metadata.salt, metadata.iterations)?;
+
+    change_iterations(db, &old_key, &input, iterations)
+}
+
+/// Re-derive the content key at `iterations` with a fresh salt and re-encrypt everything
+///
+/// `password` must be the KDF input for the current password (combined with the keyfile, if
+/// any); the SQLCipher key is left alone. Returns the new content key.
+pub fn change_iterations(
+    db: &Database,
+    current_key: &[u8; 32],
+    password: &str,
+    iterations: u32,
+) -> Result<(Zeroizing<[u8; 32]>, ReencryptStats)> {
+    let crypto = CryptoService::new();
+    let new_salt = crypto.generate_salt();
+    let new_key = crypto.derive_key(password, &new_salt, iterations)?;
+
+    let stats = reencrypt_committed(db, current_key, &new_key, new_salt, iterations)?;
+    Ok((new_key, stats))
+}
+
+/// Change the master password
+///
+/// Content is re-encrypted under a key derived from `new_password` and a fresh salt (keeping
+/// the current iterations) and committed first; only then is the SQLCipher key rotated. If
+/// the rekey fails, the content is re-encrypted back so both keys stay on the old password.
+/// A database set up with a keyfile keeps it, so `keyfile` must be given. Returns the new
+/// content key.
+pub fn change_password(
+    db: &Database,
+    current_key: &[u8; 32],
+    new_password: &str,
+    keyfile: Option<&Path>,
+) -> Result<(Zeroizing<[u8; 32]>, ReencryptStats)> {
+    let crypto = CryptoService::new();
+    let old_metadata = EncryptionRepository::new(db.connection())
+        .get()?
+        .ok_or_else(|| anyhow::anyhow!("Database has not been set up yet"))?;
+
+    let new_input = kdf_input(new_password, old_metadata.keyfile_required, keyfile)?;
+    let new_salt = crypto.generate_salt();
+    let new_key = crypto.derive_key(&new_input, &new_salt, old_metadata.iterations)?;
+
+    let stats = reencrypt_committed(db, current_key, &new_key, new_salt, old_metadata.iterations)?;
+
+    // The file key includes the keyfile the same way the KDF input does
+    if let Err(e) = db.rekey(&new_input) {
+        reencrypt_committed(db, &new_key, current_key, old_metadata.salt, old_metadata.iterations)
+            .context("Failed to restore content after a failed database rekey")?;
+        return Err(e);
+    }
+
+    Ok((new_key, stats))
+}
+
+/// Re-encrypt everything from `old_key` to `new_key` and store the new salt, in one transaction
+///
+/// Verifies every note decrypts with the new key before committing.
+fn reencrypt_committed(
+    db: &Database,
+    old_key: &[u8; 32],
+    new_key: &[u8; 32],
+    new_salt: Vec<u8>,
+    iterations: u32,
+) -> Result<ReencryptStats> {
+    let tx = db
+        .connection()
+        .unchecked_transaction()
+        .context("Failed to start transaction")?;
+
+    // Checked under the old key, so a mismatch isn't approved by sealing under the new one
+    let intact = !matches!(integrity::verify(&tx, old_key)?, integrity::IntegrityStatus::Tampered { .. });
+    let stats = reencrypt_all(&tx, old_key, new_key)?;
+
+    // Verify before committing
+    let verified = NoteRepository::new(&tx)
+        .list(true, new_key)
+        .context("Verification failed: notes do not decrypt with the new key")?;
+    if verified.len() != stats.notes {
+        anyhow::bail!(
+            "Verification failed: expected {} notes, found {}",
+            stats.notes,
+            verified.len()
+        );
+    }
+
+    let encryption_repo = EncryptionRepository::new(&tx);
+    let mut new_metadata = EncryptionMetadata::with_iterations(new_salt, iterations)
+        .map_err(anyhow::Error::msg)?;
+    new_metadata.keyfile_required = encryption_repo.get()?.is_some_and(|m| m.keyfile_required);
+    encryption_repo.save(&new_metadata)?;
+    if intact {
+        integrity::seal(&tx, new_key)?;
+    }
+
+    tx.commit().context("Failed to commit re-encryption")?;
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Note;
+
+    #[test]
+    fn test_rotate_salt_reencrypts_notes() {
+        let crypto = CryptoService::new();
+        let db = Database::in_memory("test_password").unwrap();
+
+        let salt = crypto.generate_salt();
+        let metadata = EncryptionMetadata::with_iterations(salt.clone(), 100_000).unwrap();
+        EncryptionRepository::new(db.connection()).save(&metadata).unwrap();
+        let old_key = crypto.derive_key("test_password", &salt, 100_000).unwrap();
+
+        let repo = NoteRepository::new(db.connection());
+        let mut note = Note::new("Earlier draft".to_string());
+        repo.create(&note, &old_key).unwrap();
+        note.content = "Secret note".to_string();
+        note.tags = vec!["work".to_string()];
+        repo.update(&note, &old_key).unwrap();
+        let sync_repo = SyncRepository::new(db.connection());
+        sync_repo
+            .update_metadata(&crate::models::sync::SyncMetadata {
+                client_id: Some("client-1".to_string()),
+                sync_endpoint: "https://sync.example.com".to_string(),
+                ..Default::default()
+            }, &old_key)
+            .unwrap();
+
+        let (new_key, stats) = rotate_salt(&db, "test_password", None, 256_000).unwrap();
+        assert_eq!(stats.notes, 1);
+
+        // Metadata updated
+        let metadata = EncryptionRepository::new(db.connection()).get().unwrap().unwrap();
+        assert_ne!(metadata.salt, salt);
+        assert_eq!(metadata.iterations, 256_000);
+        assert_eq!(
+            crypto.derive_key("test_password", &metadata.salt, metadata.iterations).unwrap(),
+            new_key
+        );
+
+        // Content readable with the new key only
+        let loaded = repo.get(&note.id, &new_key).unwrap().unwrap();
+        assert_eq!(loaded.content, "Secret note");
+        assert_eq!(loaded.tags, vec!["work".to_string()]);
+        assert!(repo.get(&note.id, &old_key).is_err());
+
+        // History moves to the new key without picking up the re-encryption itself
+        let history = repo.history(&note.id, &new_key).unwrap();
+        assert_eq!(history.iter().map(|v| v.content.as_str()).collect::<Vec<_>>(), ["Earlier draft"]);
+
+        // Sync credentials too
+        let sync = sync_repo.get_metadata(&new_key).unwrap().unwrap();
+        assert_eq!(sync.client_id.as_deref(), Some("client-1"));
+        assert_eq!(sync.sync_endpoint, "https://sync.example.com");
+        assert!(sync_repo.get_metadata(&old_key).is_err());
+    }
+
+    #[test]
+    fn test_change_password_rekeys_database() {
+        let crypto = CryptoService::new();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.db");
+
+        let (note, new_key) = {
+            let db = Database::open(&path, "old_password").unwrap();
+            let salt = crypto.generate_salt();
+            let metadata = EncryptionMetadata::with_iterations(salt.clone(), 100_000).unwrap();
+            EncryptionRepository::new(db.connection()).save(&metadata).unwrap();
+            let old_key = crypto.derive_key("old_password", &salt, 100_000).unwrap();
+
+            let note = Note::new("Secret note".to_string());
+            NoteRepository::new(db.connection()).create(&note, &old_key).unwrap();
+
+            let (new_key, stats) = change_password(&db, &old_key, "new_password", None).unwrap();
+            assert_eq!(stats.notes, 1);
+            (note, new_key)
+        };
+
+        // The old password no longer opens the file
+        assert!(Database::open(&path, "old_password").is_err());
+
+        let db = Database::open(&path, "new_password").unwrap();
+        let metadata = EncryptionRepository::new(db.connection()).get().unwrap().unwrap();
+        assert_eq!(metadata.iterations, 100_000);
+        assert_eq!(
+            crypto.derive_key("new_password", &metadata.salt, metadata.iterations).unwrap(),
+            new_key
+        );
+
+        let loaded = NoteRepository::new(db.connection()).get(&note.id, &new_key).unwrap().unwrap();
+        assert_eq!(loaded.content, "Secret note");
+    }
+}
@@ -1,7 +1,12 @@
-use anyhow::Result;
-use rusqlite::{params, Connection, OptionalExtension};
+use std::io::{self, Read, Write};
 
-use crate::crypto::{CryptoService, EncryptedData};
+use rusqlite::{blob::Blob, params, Connection, DatabaseName, OptionalExtension};
+use zeroize::Zeroizing;
+
+use crate::crypto::{ChunkManifest, CryptoService, CHUNK_OVERHEAD};
+use crate::thumbnail;
+use super::error::{RepoError, Result};
+use super::parse_stored;
 
 /// Repository for attachment operations
 pub struct AttachmentRepository<'a> {
@@ -18,7 +23,7 @@ impl<'a> AttachmentRepository<'a> {
         }
     }
 
-    /// Store an attachment (encrypted)
+    /// Store an attachment (encrypted), with an encrypted thumbnail if it is an image
     pub fn store(
         &self,
         id: &str,
@@ -29,18 +34,20 @@ impl<'a> AttachmentRepository<'a> {
         key: &[u8; 32],
     ) -> Result<()> {
         // Encrypt filename and data
-        let encrypted_filename = self.crypto.encrypt_text(filename, key)?;
-        let encrypted_data = self.crypto.encrypt_binary(data, key)?;
+        let encrypted_filename = self.crypto.encrypt_text(filename, key).map_err(RepoError::Encrypt)?;
+        let encrypted_data = self.crypto.encrypt_binary(data, key).map_err(RepoError::Encrypt)?;
+        let thumbnail = self.encrypt_thumbnail(mime_type, data, key)?;
 
         self.conn.execute(
-            "INSERT OR REPLACE INTO attachments (id, filename, mime_type, size, data)
-             VALUES (?1, ?2, ?3, ?4, ?5)",
+            "INSERT OR REPLACE INTO attachments (id, filename, mime_type, size, data, thumbnail_data)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
             params![
                 id,
                 serde_json::to_string(&encrypted_filename)?,
                 mime_type,
                 size,
                 serde_json::to_string(&encrypted_data)?,
+                thumbnail,
             ],
         )?;
 
@@ -49,33 +56,281 @@ impl<'a> AttachmentRepository<'a> {
 
     /// Get an attachment (decrypted)
     pub fn get(&self, id: &str, key: &[u8; 32]) -> Result<Option<(String, String, i64, Vec<u8>)>> {
+        let mut data = Vec::new();
+        let info = self.read_stream(id, key, &mut data)?;
+        Ok(info.map(|(filename, mime_type, size)| (filename, mime_type, size, data)))
+    }
+
+    /// Store an attachment read from `reader` as encrypted chunks
+    ///
+    /// Only one chunk is held in memory at a time, except for images small enough to get a
+    /// thumbnail, which are also collected for it. `reader` must yield exactly `size` bytes.
+    pub fn store_stream<R: Read>(
+        &self,
+        id: &str,
+        filename: &str,
+        mime_type: &str,
+        size: i64,
+        mut reader: R,
+        key: &[u8; 32],
+    ) -> Result<()> {
+        let plaintext_len = u64::try_from(size)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Attachment size cannot be negative"))?;
+        let manifest = ChunkManifest::for_len(plaintext_len).map_err(RepoError::Encrypt)?;
+        let encrypted_filename = self.crypto.encrypt_text(filename, key).map_err(RepoError::Encrypt)?;
+
+        // Reserve the whole blob, then fill it chunk by chunk
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute(
+            "INSERT OR REPLACE INTO attachments (id, filename, mime_type, size, data)
+             VALUES (?1, ?2, ?3, ?4, zeroblob(?5))",
+            params![
+                id,
+                serde_json::to_string(&encrypted_filename)?,
+                mime_type,
+                size,
+                manifest.blob_len() as i64,
+            ],
+        )?;
+        let rowid: i64 = tx.query_row(
+            "SELECT rowid FROM attachments WHERE id = ?1",
+            params![id],
+            |row| row.get(0),
+        )?;
+
+        let mut blob = tx.blob_open(DatabaseName::Main, "attachments", "data", rowid, false)?;
+        blob.write_at(&manifest.to_bytes(), 0)?;
+
+        let mut chunk = Zeroizing::new(Vec::with_capacity(manifest.chunk_size as usize));
+        let mut source = thumbnail::wants_thumbnail(mime_type, size).then(|| Zeroizing::new(Vec::new()));
+        for index in 0..manifest.chunk_count {
+            let len = manifest.chunk_len(index);
+            chunk.clear();
+            (&mut reader).take(len as u64).read_to_end(&mut chunk)?;
+            if chunk.len() != len {
+                let message = format!("Attachment {} is shorter than its size of {} bytes", filename, size);
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, message).into());
+            }
+            let sealed = self
+                .crypto
+                .seal_chunk(&manifest, id.as_bytes(), index, &chunk, key)
+                .map_err(RepoError::Encrypt)?;
+            blob.write_at(&sealed, manifest.chunk_offset(index))?;
+            if let Some(source) = &mut source {
+                source.extend_from_slice(&chunk);
+            }
+        }
+        if reader.read(&mut [0u8; 1])? != 0 {
+            let message = format!("Attachment {} is longer than its size of {} bytes", filename, size);
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message).into());
+        }
+
+        drop(blob);
+        if let Some(source) = source {
+            let thumbnail = self.encrypt_thumbnail(mime_type, &source, key)?;
+            tx.execute(
+                "UPDATE attachments SET thumbnail_data = ?2 WHERE id = ?1",
+                params![id, thumbnail],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Decrypted PNG thumbnail of an attachment; None if it has none
+    pub fn thumbnail(&self, id: &str, key: &[u8; 32]) -> Result<Option<Vec<u8>>> {
+        let thumbnail_json: Option<String> = self.conn
+            .query_row(
+                "SELECT thumbnail_data FROM attachments WHERE id = ?1",
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten();
+
+        let Some(thumbnail_json) = thumbnail_json else {
+            return Ok(None);
+        };
+        let encrypted = parse_stored(&thumbnail_json, || format!("attachment {}: corrupted thumbnail ciphertext", id))?;
+        let thumbnail = self
+            .crypto
+            .decrypt_binary(&encrypted, key)
+            .map_err(|source| RepoError::Decrypt { what: format!("thumbnail of attachment {}", id), source })?;
+        Ok(Some(thumbnail))
+    }
+
+    /// IDs of the attachments that have a thumbnail
+    pub fn thumbnail_ids(&self) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare("SELECT id FROM attachments WHERE thumbnail_data IS NOT NULL")?;
+        let ids = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+        Ok(ids)
+    }
+
+    /// Thumbnail of `data` encrypted for the `thumbnail_data` column, if it is an image
+    fn encrypt_thumbnail(&self, mime_type: &str, data: &[u8], key: &[u8; 32]) -> Result<Option<String>> {
+        match thumbnail::generate(mime_type, data) {
+            Some(png) => {
+                let encrypted = self.crypto.encrypt_binary(&png, key).map_err(RepoError::Encrypt)?;
+                Ok(Some(serde_json::to_string(&encrypted)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Decrypt an attachment into `writer`, returning its filename, MIME type and size
+    ///
+    /// Chunked attachments are decrypted one chunk at a time; ones stored whole by
+    /// `store` are decrypted in a single piece.
+    pub fn read_stream<W: Write>(
+        &self,
+        id: &str,
+        key: &[u8; 32],
+        mut writer: W,
+    ) -> Result<Option<(String, String, i64)>> {
         let result = self.conn
             .query_row(
-                "SELECT filename, mime_type, size, data FROM attachments WHERE id = ?1",
+                "SELECT rowid, filename, mime_type, size,
+                        CASE WHEN typeof(data) = 'blob' THEN NULL ELSE data END
+                 FROM attachments WHERE id = ?1",
                 params![id],
                 |row| {
                     Ok((
-                        row.get::<_, String>(0)?,
+                        row.get::<_, i64>(0)?,
                         row.get::<_, String>(1)?,
-                        row.get::<_, i64>(2)?,
-                        row.get::<_, String>(3)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, i64>(3)?,
+                        row.get::<_, Option<String>>(4)?,
                     ))
                 },
             )
             .optional()?;
 
-        match result {
-            Some((filename_json, mime_type, size, data_json)) => {
-                let encrypted_filename: EncryptedData = serde_json::from_str(&filename_json)?;
-                let encrypted_data: EncryptedData = serde_json::from_str(&data_json)?;
+        let Some((rowid, filename_json, mime_type, size, data_json)) = result else {
+            return Ok(None);
+        };
 
-                let filename = self.crypto.decrypt_text(&encrypted_filename, key)?;
-                let data = self.crypto.decrypt_binary(&encrypted_data, key)?;
+        let filename = self.decrypt_filename(id, &filename_json, key)?;
 
-                Ok(Some((filename, mime_type, size, data)))
+        match data_json {
+            Some(data_json) => {
+                let encrypted_data = parse_stored(&data_json, || format!("attachment {}: corrupted ciphertext", id))?;
+                let data = self
+                    .crypto
+                    .decrypt_binary(&encrypted_data, key)
+                    .map_err(|source| RepoError::Decrypt { what: format!("attachment {}", id), source })?;
+                let data = Zeroizing::new(data);
+                writer.write_all(&data)?;
+            }
+            None => {
+                let (blob, manifest) = self.open_chunked(rowid, true)?;
+                let mut sealed = vec![0u8; manifest.chunk_size as usize + CHUNK_OVERHEAD];
+                for index in 0..manifest.chunk_count {
+                    let sealed = &mut sealed[..manifest.chunk_len(index) + CHUNK_OVERHEAD];
+                    blob.read_at_exact(sealed, manifest.chunk_offset(index))?;
+                    let chunk = self.open_chunk(id, &manifest, index, sealed, key)?;
+                    writer.write_all(&chunk)?;
+                }
             }
-            None => Ok(None),
         }
+        writer.flush()?;
+
+        Ok(Some((filename, mime_type, size)))
+    }
+
+    /// Re-encrypt an attachment from `old_key` to `new_key`
+    ///
+    /// Chunked attachments are rewritten in place, a chunk at a time. Returns false if
+    /// there is no attachment with this id.
+    pub fn reencrypt(&self, id: &str, old_key: &[u8; 32], new_key: &[u8; 32]) -> Result<bool> {
+        let result = self.conn
+            .query_row(
+                "SELECT rowid, filename, typeof(data) = 'blob' FROM attachments WHERE id = ?1",
+                params![id],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, bool>(2)?)),
+            )
+            .optional()?;
+
+        let Some((rowid, filename_json, chunked)) = result else {
+            return Ok(false);
+        };
+
+        if !chunked {
+            if let Some((filename, mime_type, size, data)) = self.get(id, old_key)? {
+                let data = Zeroizing::new(data);
+                self.store(id, &filename, &mime_type, size, &data, new_key)?;
+            }
+            return Ok(true);
+        }
+
+        let filename = self.decrypt_filename(id, &filename_json, old_key)?;
+        let reencrypted = self.crypto.encrypt_text(&filename, new_key).map_err(RepoError::Encrypt)?;
+        self.conn.execute(
+            "UPDATE attachments SET filename = ?2 WHERE id = ?1",
+            params![id, serde_json::to_string(&reencrypted)?],
+        )?;
+        if let Some(thumbnail) = self.thumbnail(id, old_key)? {
+            let reencrypted = self.crypto.encrypt_binary(&thumbnail, new_key).map_err(RepoError::Encrypt)?;
+            self.conn.execute(
+                "UPDATE attachments SET thumbnail_data = ?2 WHERE id = ?1",
+                params![id, serde_json::to_string(&reencrypted)?],
+            )?;
+        }
+
+        // Sealed chunks keep their length, so each one is overwritten where it is
+        let (mut blob, manifest) = self.open_chunked(rowid, false)?;
+        let mut sealed = vec![0u8; manifest.chunk_size as usize + CHUNK_OVERHEAD];
+        for index in 0..manifest.chunk_count {
+            let sealed = &mut sealed[..manifest.chunk_len(index) + CHUNK_OVERHEAD];
+            let offset = manifest.chunk_offset(index);
+            blob.read_at_exact(sealed, offset)?;
+            let chunk = self.open_chunk(id, &manifest, index, sealed, old_key)?;
+            let resealed = self
+                .crypto
+                .seal_chunk(&manifest, id.as_bytes(), index, &chunk, new_key)
+                .map_err(RepoError::Encrypt)?;
+            blob.write_at(&resealed, offset)?;
+        }
+
+        Ok(true)
+    }
+
+    /// Decrypt an attachment's stored filename
+    fn decrypt_filename(&self, id: &str, filename_json: &str, key: &[u8; 32]) -> Result<String> {
+        let encrypted = parse_stored(filename_json, || format!("attachment {}: corrupted filename ciphertext", id))?;
+        self.crypto
+            .decrypt_text(&encrypted, key)
+            .map_err(|source| RepoError::Decrypt { what: format!("filename of attachment {}", id), source })
+    }
+
+    /// Decrypt chunk `index` of a chunked attachment
+    fn open_chunk(
+        &self,
+        id: &str,
+        manifest: &ChunkManifest,
+        index: u32,
+        sealed: &[u8],
+        key: &[u8; 32],
+    ) -> Result<Zeroizing<Vec<u8>>> {
+        self.crypto
+            .open_chunk(manifest, id.as_bytes(), index, sealed, key)
+            .map(Zeroizing::new)
+            .map_err(|source| RepoError::Decrypt { what: format!("attachment {} chunk {}", id, index), source })
+    }
+
+    /// Open a chunked attachment blob and read its manifest
+    fn open_chunked(&self, rowid: i64, read_only: bool) -> Result<(Blob<'a>, ChunkManifest)> {
+        let blob = self.conn.blob_open(DatabaseName::Main, "attachments", "data", rowid, read_only)?;
+        let mut header = [0u8; ChunkManifest::LEN];
+        blob.read_at_exact(&mut header, 0)
+            .map_err(|_| RepoError::corrupt("Chunked attachment", "truncated"))?;
+        let manifest = ChunkManifest::from_bytes(&header)
+            .map_err(|e| RepoError::corrupt("Chunked attachment", format_args!("{:#}", e)))?;
+        if blob.len() != manifest.blob_len() {
+            return Err(RepoError::corrupt("Chunked attachment", "truncated or corrupted"));
+        }
+        Ok((blob, manifest))
     }
 
     /// Delete an attachment
@@ -97,6 +352,15 @@ impl<'a> AttachmentRepository<'a> {
         Ok(size)
     }
 
+    /// List all attachment IDs
+    pub fn ids(&self) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare("SELECT id FROM attachments")?;
+        let ids = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+        Ok(ids)
+    }
+
     /// Count all attachments
     pub fn count(&self) -> Result<i64> {
         let count: i64 = self.conn
@@ -113,3 +377,126 @@ impl<'a> AttachmentRepository<'a> {
         Ok(size)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::CHUNK_SIZE;
+    use crate::repository::parse_encrypted;
+    use crate::db::Database;
+
+    fn sample(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_stream_round_trip_across_chunks() {
+        let db = Database::in_memory("test_password").unwrap();
+        let repo = AttachmentRepository::new(db.connection());
+        let key = [7u8; 32];
+        let data = sample(CHUNK_SIZE * 2 + 123);
+
+        repo.store_stream("att-1", "big.bin", "application/octet-stream", data.len() as i64, data.as_slice(), &key)
+            .unwrap();
+
+        let mut out = Vec::new();
+        let (filename, mime_type, size) = repo.read_stream("att-1", &key, &mut out).unwrap().unwrap();
+        assert_eq!(filename, "big.bin");
+        assert_eq!(mime_type, "application/octet-stream");
+        assert_eq!(size, data.len() as i64);
+        assert_eq!(out, data);
+
+        // The whole-blob API reads chunked attachments too
+        assert_eq!(repo.get("att-1", &key).unwrap().unwrap().3, data);
+        assert!(repo.read_stream("att-1", &[8u8; 32], std::io::sink()).is_err());
+    }
+
+    #[test]
+    fn test_store_stream_rejects_wrong_size() {
+        let db = Database::in_memory("test_password").unwrap();
+        let repo = AttachmentRepository::new(db.connection());
+        let key = [7u8; 32];
+        let data = sample(100);
+
+        assert!(repo.store_stream("short", "a", "text/plain", 101, data.as_slice(), &key).is_err());
+        assert!(repo.store_stream("long", "a", "text/plain", 99, data.as_slice(), &key).is_err());
+        // Nothing half-written is left behind
+        assert_eq!(repo.count().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_tampered_chunk_is_rejected() {
+        let db = Database::in_memory("test_password").unwrap();
+        let repo = AttachmentRepository::new(db.connection());
+        let key = [7u8; 32];
+        let data = sample(CHUNK_SIZE + 10);
+        repo.store_stream("att-1", "a", "text/plain", data.len() as i64, data.as_slice(), &key).unwrap();
+
+        // Flip a byte inside the second chunk's ciphertext
+        let manifest = ChunkManifest::for_len(data.len() as u64).unwrap();
+        let offset = manifest.chunk_offset(1) + 20;
+        let mut blob = db.connection().blob_open(DatabaseName::Main, "attachments", "data", 1, false).unwrap();
+        let mut byte = [0u8; 1];
+        blob.read_at_exact(&mut byte, offset).unwrap();
+        blob.write_at(&[byte[0] ^ 0xff], offset).unwrap();
+        drop(blob);
+
+        let err = repo.get("att-1", &key).unwrap_err();
+        assert!(err.to_string().contains("chunk 1"), "{}", err);
+    }
+
+    #[test]
+    fn test_reencrypt_chunked_and_legacy() {
+        let db = Database::in_memory("test_password").unwrap();
+        let repo = AttachmentRepository::new(db.connection());
+        let (old_key, new_key) = ([7u8; 32], [8u8; 32]);
+        let data = sample(CHUNK_SIZE + 10);
+
+        repo.store_stream("chunked", "big.bin", "application/octet-stream", data.len() as i64, data.as_slice(), &old_key)
+            .unwrap();
+        repo.store("legacy", "small.txt", "text/plain", 3, b"abc", &old_key).unwrap();
+
+        assert!(repo.reencrypt("chunked", &old_key, &new_key).unwrap());
+        assert!(repo.reencrypt("legacy", &old_key, &new_key).unwrap());
+        assert!(!repo.reencrypt("missing", &old_key, &new_key).unwrap());
+
+        let (filename, _, _, bytes) = repo.get("chunked", &new_key).unwrap().unwrap();
+        assert_eq!((filename.as_str(), bytes), ("big.bin", data));
+        assert_eq!(repo.get("legacy", &new_key).unwrap().unwrap().3, b"abc");
+        assert!(repo.get("chunked", &old_key).is_err());
+    }
+
+    #[test]
+    fn test_image_attachments_get_encrypted_thumbnails() {
+        let db = Database::in_memory("test_password").unwrap();
+        let repo = AttachmentRepository::new(db.connection());
+        let (old_key, new_key) = ([7u8; 32], [8u8; 32]);
+        let mut png = Vec::new();
+        image::RgbImage::from_fn(512, 256, |x, y| image::Rgb([x as u8, y as u8, 7]))
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+
+        repo.store("whole", "photo.png", "image/png", png.len() as i64, &png, &old_key).unwrap();
+        repo.store_stream("streamed", "photo.png", "image/png", png.len() as i64, png.as_slice(), &old_key)
+            .unwrap();
+        repo.store("text", "notes.txt", "text/plain", 3, b"abc", &old_key).unwrap();
+
+        let mut ids = repo.thumbnail_ids().unwrap();
+        ids.sort();
+        assert_eq!(ids, ["streamed", "whole"]);
+        assert!(repo.thumbnail("text", &old_key).unwrap().is_none());
+
+        // Encrypted at rest, and re-encrypted along with the blob
+        let stored: String = db
+            .connection()
+            .query_row("SELECT thumbnail_data FROM attachments WHERE id = 'whole'", [], |row| row.get(0))
+            .unwrap();
+        assert!(parse_encrypted(&stored).is_ok());
+        for id in ["whole", "streamed"] {
+            assert!(repo.reencrypt(id, &old_key, &new_key).unwrap());
+            assert!(repo.thumbnail(id, &old_key).is_err());
+            let thumbnail = image::load_from_memory(&repo.thumbnail(id, &new_key).unwrap().unwrap()).unwrap();
+            assert_eq!((thumbnail.width(), thumbnail.height()), (128, 64));
+        }
+    }
+}
@@ -1,15 +1,9 @@
 /// Repository for encryption metadata
 use anyhow::{Context, Result};
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use rusqlite::Connection;
 
-/// Encryption metadata stored in database
-#[derive(Debug, Clone)]
-pub struct EncryptionMetadata {
-    pub salt: Vec<u8>,
-    pub iterations: u32,
-    pub algorithm: String,
-}
+pub use crate::models::encryption::EncryptionMetadata;
 
 /// Repository for managing encryption metadata
 pub struct EncryptionRepository<'a> {
@@ -23,45 +17,57 @@ impl<'a> EncryptionRepository<'a> {
 
     /// Get encryption metadata (if exists)
     pub fn get(&self) -> Result<Option<EncryptionMetadata>> {
-        let result = self
-            .conn
-            .query_row(
-                "SELECT salt, iterations, algorithm FROM encryption_metadata WHERE id = 1",
-                [],
-                |row| {
-                    let salt_hex: String = row.get(0)?;
-                    let salt = hex::decode(&salt_hex)
-                        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(
-                            0,
-                            rusqlite::types::Type::Text,
-                            Box::new(e),
-                        ))?;
-
-                    Ok(EncryptionMetadata {
-                        salt,
-                        iterations: row.get(1)?,
-                        algorithm: row.get(2)?,
-                    })
-                },
-            );
-
-        match result {
-            Ok(metadata) => Ok(Some(metadata)),
-            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-            Err(e) => Err(e).context("Failed to load encryption metadata"),
-        }
+        let result = self.conn.query_row(
+            "SELECT salt, iterations, created_at, algorithm, keyfile_required FROM encryption_metadata WHERE id = 1",
+            [],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, u32>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, bool>(4)?,
+                ))
+            },
+        );
+
+        let (salt_hex, iterations, created_at, algorithm, keyfile_required) = match result {
+            Ok(row) => row,
+            Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(None),
+            Err(e) => return Err(e).context("Failed to load encryption metadata"),
+        };
+
+        let metadata = EncryptionMetadata {
+            salt: EncryptionMetadata::decode_hex_salt(&salt_hex).map_err(anyhow::Error::msg)?,
+            iterations,
+            created_at: DateTime::parse_from_rfc3339(&created_at)
+                .context("Invalid encryption metadata timestamp")?
+                .with_timezone(&Utc),
+            algorithm: algorithm.parse().map_err(anyhow::Error::msg)?,
+            keyfile_required,
+        };
+
+        Ok(Some(metadata))
     }
 
-    /// Save encryption metadata (first-time setup)
-    pub fn save(&self, salt: &[u8], iterations: u32) -> Result<()> {
-        let salt_hex = hex::encode(salt);
-        let created_at = Utc::now().to_rfc3339();
+    /// Save encryption metadata (first-time setup or salt change)
+    pub fn save(&self, metadata: &EncryptionMetadata) -> Result<()> {
+        metadata
+            .validate()
+            .map_err(anyhow::Error::msg)
+            .context("Refusing to save invalid encryption metadata")?;
 
         self.conn
             .execute(
-                "INSERT OR REPLACE INTO encryption_metadata (id, salt, iterations, created_at, algorithm)
-                 VALUES (1, ?1, ?2, ?3, 'AES-256-GCM')",
-                (salt_hex, iterations, created_at),
+                "INSERT OR REPLACE INTO encryption_metadata (id, salt, iterations, created_at, algorithm, keyfile_required)
+                 VALUES (1, ?1, ?2, ?3, ?4, ?5)",
+                (
+                    metadata.salt_hex(),
+                    metadata.iterations,
+                    metadata.created_at.to_rfc3339(),
+                    metadata.algorithm.to_string(),
+                    metadata.keyfile_required,
+                ),
             )
             .context("Failed to save encryption metadata")?;
 
@@ -83,13 +89,32 @@ mod tests {
         assert!(repo.get().unwrap().is_none());
 
         // Save metadata
-        let salt = vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16];
-        repo.save(&salt, 256_000).unwrap();
+        let salt: Vec<u8> = (1..=32).collect();
+        let metadata = EncryptionMetadata::new(salt.clone());
+        repo.save(&metadata).unwrap();
 
         // Load and verify
-        let metadata = repo.get().unwrap().unwrap();
-        assert_eq!(metadata.salt, salt);
-        assert_eq!(metadata.iterations, 256_000);
-        assert_eq!(metadata.algorithm, "AES-256-GCM");
+        let loaded = repo.get().unwrap().unwrap();
+        assert_eq!(loaded.salt, salt);
+        assert_eq!(loaded.iterations, 256_000);
+        assert_eq!(loaded.algorithm.to_string(), "AES-256-GCM");
+        assert_eq!(loaded.created_at.timestamp(), metadata.created_at.timestamp());
+
+        // Stored as hex
+        let stored: String = db
+            .connection()
+            .query_row("SELECT salt FROM encryption_metadata WHERE id = 1", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(stored, hex::encode(&salt));
+    }
+
+    #[test]
+    fn test_save_rejects_short_salt() {
+        let db = Database::in_memory("test_password").unwrap();
+        let repo = EncryptionRepository::new(db.connection());
+
+        let metadata = EncryptionMetadata::new(vec![1, 2, 3, 4]);
+        assert!(repo.save(&metadata).is_err());
+        assert!(repo.get().unwrap().is_none());
     }
 }
@@ -0,0 +1,53 @@
+//! Error type shared by the repositories
+//! Lets callers tell a wrong key apart from missing rows, damaged data and SQLite failures
+
+use thiserror::Error;
+
+/// `Result` with [`RepoError`] as the default error
+pub type Result<T, E = RepoError> = std::result::Result<T, E>;
+
+/// Why a repository operation failed
+#[derive(Debug, Error)]
+pub enum RepoError {
+    /// No row with this id
+    #[error("{kind} {id} not found")]
+    NotFound { kind: &'static str, id: String },
+
+    /// Well-formed ciphertext that doesn't decrypt with the key, usually a wrong password
+    #[error("{what} could not be decrypted")]
+    Decrypt {
+        what: String,
+        #[source]
+        source: anyhow::Error,
+    },
+
+    /// Stored data that can't be read back, e.g. malformed ciphertext or a bad timestamp
+    #[error("{what}: {detail}")]
+    Corrupt { what: String, detail: String },
+
+    /// Encrypting data before it is written failed
+    #[error(transparent)]
+    Encrypt(anyhow::Error),
+
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+
+    #[error(transparent)]
+    Serde(#[from] serde_json::Error),
+
+    /// Reading or writing a streamed attachment failed
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl RepoError {
+    /// Whether a row's stored data couldn't be read back, as opposed to the database failing
+    pub fn is_unreadable_data(&self) -> bool {
+        matches!(self, Self::Decrypt { .. } | Self::Corrupt { .. } | Self::Serde(_))
+    }
+
+    /// `Corrupt` error for `what`
+    pub(crate) fn corrupt(what: impl Into<String>, detail: impl std::fmt::Display) -> Self {
+        Self::Corrupt { what: what.into(), detail: detail.to_string() }
+    }
+}
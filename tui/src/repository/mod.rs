@@ -6,7 +6,29 @@ pub mod settings;
 pub mod attachment;
 pub mod sync;
 pub mod encryption;
+pub mod error;
 
 pub use note::NoteRepository;
 pub use settings::SettingsRepository;
 pub use encryption::EncryptionRepository;
+pub use attachment::AttachmentRepository;
+pub use error::RepoError;
+
+use anyhow::{Context, Result};
+
+use crate::crypto::EncryptedData;
+
+/// Parse stored or received ciphertext JSON and check it is well-formed
+///
+/// Catches corrupted base64 before it reaches AES-GCM, where it would only show up as
+/// a generic decryption failure.
+pub(crate) fn parse_encrypted(json: &str) -> Result<EncryptedData> {
+    let encrypted: EncryptedData = serde_json::from_str(json).context("Invalid ciphertext JSON")?;
+    encrypted.validate().map_err(anyhow::Error::msg)?;
+    Ok(encrypted)
+}
+
+/// `parse_encrypted` for a stored column, reporting damage as `RepoError::Corrupt` in `what`
+pub(crate) fn parse_stored(json: &str, what: impl FnOnce() -> String) -> Result<EncryptedData, RepoError> {
+    parse_encrypted(json).map_err(|e| RepoError::corrupt(what(), format_args!("{:#}", e)))
+}
@@ -1,9 +1,159 @@
-use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
-use rusqlite::{params, Connection, OptionalExtension};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::collections::HashSet;
+use tracing::warn;
 
-use crate::crypto::{CryptoService, EncryptedData};
-use crate::models::{Attachment, Note};
+use crate::crypto::CryptoService;
+use crate::models::{matches_query, preview_of, Attachment, Note, NoteMeta, NotePreview, NoteVersion, NOTE_HISTORY_LIMIT};
+use super::error::{RepoError, Result};
+use super::{parse_encrypted, parse_stored};
+
+/// Notes `check_key` tries a key against
+const KEY_CHECK_NOTES: usize = 5;
+
+/// A `notes` row as stored, before decryption
+type NoteRow = (
+    String,         // id
+    String,         // created_at
+    String,         // modified_at
+    Option<String>, // synced_at
+    String,         // content (encrypted)
+    String,         // tags (encrypted)
+    String,         // attachments
+    i32,            // pinned
+    i32,            // deleted
+    Option<String>, // deleted_at
+    Option<String>, // sync_hash
+    i32,            // version
+    i32,            // word_wrap
+    String,         // syntax_language
+    Option<String>, // reminder_at
+);
+
+/// Read the columns selected by `get`, `list`, `list_lenient` and `get_modified_after`
+fn note_row(row: &Row) -> rusqlite::Result<NoteRow> {
+    Ok((
+        row.get(0)?,
+        row.get(1)?,
+        row.get(2)?,
+        row.get(3)?,
+        row.get(4)?,
+        row.get(5)?,
+        row.get(6)?,
+        row.get(7)?,
+        row.get(8)?,
+        row.get(9)?,
+        row.get(10)?,
+        row.get(11)?,
+        row.get(12)?,
+        row.get(13)?,
+        row.get(14)?,
+    ))
+}
+
+/// A `notes` row's unencrypted columns, as selected by `list_meta`
+type MetaRow = (
+    String,         // id
+    String,         // created_at
+    String,         // modified_at
+    bool,           // pinned
+    bool,           // deleted
+    Option<String>, // reminder_at
+    String,         // attachments
+);
+
+/// A `MetaRow` followed by the encrypted tags and preview, as selected by `list_titles`
+type TitleRow = (
+    MetaRow,
+    String, // tags (encrypted)
+    String, // preview, or the content before previews were stored (encrypted)
+    bool,   // whether that is the whole content
+);
+
+/// Read the columns selected by `list_meta`
+fn meta_row(row: &Row) -> rusqlite::Result<MetaRow> {
+    Ok((
+        row.get(0)?,
+        row.get(1)?,
+        row.get(2)?,
+        row.get(3)?,
+        row.get(4)?,
+        row.get(5)?,
+        row.get(6)?,
+    ))
+}
+
+/// Read the columns selected by `list_titles`
+fn title_row(row: &Row) -> rusqlite::Result<TitleRow> {
+    Ok((meta_row(row)?, row.get(7)?, row.get(8)?, row.get(9)?))
+}
+
+/// Parse the timestamps and attachment ids of a `MetaRow`
+fn parse_meta(row: MetaRow) -> Result<NoteMeta> {
+    let (id, created_at, modified_at, pinned, deleted, reminder_at, attachments_json) = row;
+    let attachments: Vec<Attachment> = serde_json::from_str(&attachments_json)?;
+    Ok(NoteMeta {
+        created_at: parse_time(&id, &created_at)?,
+        modified_at: parse_time(&id, &modified_at)?,
+        reminder_at: reminder_at.map(|s| parse_time(&id, &s)).transpose()?,
+        attachment_ids: attachments.into_iter().map(|a| a.id).collect(),
+        id,
+        pinned,
+        deleted,
+    })
+}
+
+/// Parse a stored RFC 3339 timestamp of note `id`
+fn parse_time(id: &str, value: &str) -> Result<DateTime<Utc>> {
+    value
+        .parse()
+        .map_err(|e| RepoError::corrupt(format!("note {}", id), format_args!("invalid timestamp {:?}: {}", value, e)))
+}
+
+/// Translate a search box query into FTS5 expressions: (terms that must match, terms to
+/// exclude), or `None` if a term is shorter than a trigram and the index can't find it
+fn fts_query(query: &str) -> Option<(Option<String>, Option<String>)> {
+    // Quote each term as a phrase, so user input can't inject FTS syntax
+    let term = |column: &str, text: &str| format!("{}:\"{}\"", column, text.replace('"', "\"\""));
+
+    let mut include = Vec::new();
+    let mut exclude = Vec::new();
+    for part in query.split_whitespace() {
+        let (column, text, terms) = if let Some(tag) = part.strip_prefix('#') {
+            ("tags", tag, &mut include)
+        } else if let Some(word) = part.strip_prefix('-') {
+            ("content", word, &mut exclude)
+        } else {
+            ("content", part, &mut include)
+        };
+        match text.chars().count() {
+            // A lone `#` or `-`
+            0 => {}
+            1 | 2 => return None,
+            _ => terms.push(term(column, text)),
+        }
+    }
+
+    let include = (!include.is_empty()).then(|| include.join(" AND "));
+    let exclude = (!exclude.is_empty()).then(|| exclude.join(" OR "));
+    Some((include, exclude))
+}
+
+/// Notes read leniently: those that decrypted, and the ids of those that didn't
+#[derive(Debug, Default)]
+pub struct NoteBatch {
+    pub notes: Vec<Note>,
+    /// Notes whose stored data is damaged or doesn't decrypt with the key
+    pub unreadable: Vec<String>,
+}
+
+/// Titles read leniently, like `NoteBatch`
+#[derive(Debug, Default)]
+pub struct TitleBatch {
+    pub titles: Vec<NotePreview>,
+    /// Notes whose stored data is damaged or doesn't decrypt with the key
+    pub unreadable: Vec<String>,
+}
 
 /// Repository for note operations
 pub struct NoteRepository<'a> {
@@ -23,18 +173,19 @@ impl<'a> NoteRepository<'a> {
     /// Create a new note (encrypted)
     pub fn create(&self, note: &Note, key: &[u8; 32]) -> Result<()> {
         // Encrypt content and tags
-        let encrypted_content = self.crypto.encrypt_text(&note.content, key)?;
-        let encrypted_tags = self.crypto.encrypt_json(&note.tags, key)?;
+        let encrypted_content = self.crypto.encrypt_text(&note.content, key).map_err(RepoError::Encrypt)?;
+        let encrypted_tags = self.crypto.encrypt_json(&note.tags, key).map_err(RepoError::Encrypt)?;
+        let encrypted_preview = self.crypto.encrypt_text(&note.preview(), key).map_err(RepoError::Encrypt)?;
 
         // Serialize attachments
-        let attachments_json = serde_json::to_string(&note.attachments)
-            .context("Failed to serialize attachments")?;
+        let attachments_json = serde_json::to_string(&note.attachments)?;
 
         self.conn.execute(
             "INSERT INTO notes (
                 id, created_at, modified_at, synced_at, content, tags, attachments,
-                pinned, deleted, deleted_at, sync_hash, version, word_wrap, syntax_language
-            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+                pinned, deleted, deleted_at, sync_hash, version, word_wrap, syntax_language, preview,
+                reminder_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
             params![
                 &note.id,
                 note.created_at.to_rfc3339(),
@@ -50,8 +201,11 @@ impl<'a> NoteRepository<'a> {
                 note.version,
                 note.word_wrap as i32,
                 note.syntax_language.to_string(),
+                serde_json::to_string(&encrypted_preview)?,
+                note.reminder_at.map(|dt| dt.to_rfc3339()),
             ],
         )?;
+        self.index_note(note)?;
 
         Ok(())
     }
@@ -60,93 +214,38 @@ impl<'a> NoteRepository<'a> {
     pub fn get(&self, id: &str, key: &[u8; 32]) -> Result<Option<Note>> {
         let mut stmt = self.conn.prepare(
             "SELECT id, created_at, modified_at, synced_at, content, tags, attachments,
-                    pinned, deleted, deleted_at, sync_hash, version, word_wrap, syntax_language
+                    pinned, deleted, deleted_at, sync_hash, version, word_wrap, syntax_language, reminder_at
              FROM notes WHERE id = ?1"
         )?;
 
-        let note = stmt
-            .query_row(params![id], |row| {
-                Ok((
-                    row.get::<_, String>(0)?,      // id
-                    row.get::<_, String>(1)?,      // created_at
-                    row.get::<_, String>(2)?,      // modified_at
-                    row.get::<_, Option<String>>(3)?, // synced_at
-                    row.get::<_, String>(4)?,      // content (encrypted)
-                    row.get::<_, String>(5)?,      // tags (encrypted)
-                    row.get::<_, String>(6)?,      // attachments
-                    row.get::<_, i32>(7)?,         // pinned
-                    row.get::<_, i32>(8)?,         // deleted
-                    row.get::<_, Option<String>>(9)?, // deleted_at
-                    row.get::<_, Option<String>>(10)?, // sync_hash
-                    row.get::<_, i32>(11)?,        // version
-                    row.get::<_, i32>(12)?,        // word_wrap
-                    row.get::<_, String>(13)?,     // syntax_language
-                ))
-            })
-            .optional()?;
-
-        match note {
-            Some((
-                id,
-                created_at,
-                modified_at,
-                synced_at,
-                content_json,
-                tags_json,
-                attachments_json,
-                pinned,
-                deleted,
-                deleted_at,
-                sync_hash,
-                version,
-                word_wrap,
-                syntax_language,
-            )) => {
-                // Decrypt content and tags
-                let encrypted_content: EncryptedData = serde_json::from_str(&content_json)?;
-                let encrypted_tags: EncryptedData = serde_json::from_str(&tags_json)?;
-
-                let content = self.crypto.decrypt_text(&encrypted_content, key)?;
-                let tags: Vec<String> = self.crypto.decrypt_json(&encrypted_tags, key)?;
-
-                // Deserialize attachments
-                let attachments: Vec<Attachment> = serde_json::from_str(&attachments_json)?;
-
-                Ok(Some(Note {
-                    id,
-                    created_at: created_at.parse()?,
-                    modified_at: modified_at.parse()?,
-                    synced_at: synced_at.map(|s| s.parse()).transpose()?,
-                    content,
-                    tags,
-                    attachments,
-                    pinned: pinned != 0,
-                    deleted: deleted != 0,
-                    deleted_at: deleted_at.map(|s| s.parse()).transpose()?,
-                    sync_hash,
-                    version,
-                    word_wrap: word_wrap != 0,
-                    syntax_language: syntax_language.parse().unwrap_or_default(),
-                }))
-            }
-            None => Ok(None),
-        }
+        let row = stmt.query_row(params![id], note_row).optional()?;
+        row.map(|row| self.decrypt_row(row, key)).transpose()
     }
 
-    /// Update a note
+    /// Update a note; fails with `RepoError::NotFound` if it isn't stored
     pub fn update(&self, note: &Note, key: &[u8; 32]) -> Result<()> {
+        self.record_history(note, key)?;
+        self.update_unrecorded(note, key)
+    }
+
+    /// Update a note without recording the version it replaces in the history
+    ///
+    /// For editor saves after the first in an editing session, so autosaves don't push
+    /// earlier versions out of the history.
+    pub fn update_unrecorded(&self, note: &Note, key: &[u8; 32]) -> Result<()> {
         // Encrypt content and tags
-        let encrypted_content = self.crypto.encrypt_text(&note.content, key)?;
-        let encrypted_tags = self.crypto.encrypt_json(&note.tags, key)?;
+        let encrypted_content = self.crypto.encrypt_text(&note.content, key).map_err(RepoError::Encrypt)?;
+        let encrypted_tags = self.crypto.encrypt_json(&note.tags, key).map_err(RepoError::Encrypt)?;
+        let encrypted_preview = self.crypto.encrypt_text(&note.preview(), key).map_err(RepoError::Encrypt)?;
 
         let attachments_json = serde_json::to_string(&note.attachments)?;
 
-        self.conn.execute(
+        let updated = self.conn.execute(
             "UPDATE notes SET
                 modified_at = ?1, synced_at = ?2, content = ?3, tags = ?4, attachments = ?5,
                 pinned = ?6, deleted = ?7, deleted_at = ?8, sync_hash = ?9, version = ?10,
-                word_wrap = ?11, syntax_language = ?12
-             WHERE id = ?13",
+                word_wrap = ?11, syntax_language = ?12, preview = ?13, reminder_at = ?14
+             WHERE id = ?15",
             params![
                 note.modified_at.to_rfc3339(),
                 note.synced_at.map(|dt| dt.to_rfc3339()),
@@ -160,13 +259,29 @@ impl<'a> NoteRepository<'a> {
                 note.version,
                 note.word_wrap as i32,
                 note.syntax_language.to_string(),
+                serde_json::to_string(&encrypted_preview)?,
+                note.reminder_at.map(|dt| dt.to_rfc3339()),
                 &note.id,
             ],
         )?;
+        if updated == 0 {
+            return Err(RepoError::NotFound { kind: "note", id: note.id.clone() });
+        }
+        self.index_note(note)?;
 
         Ok(())
     }
 
+    /// Update several notes in one transaction, so a failure leaves all of them unchanged
+    pub fn update_all(&self, notes: &[Note], key: &[u8; 32]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        for note in notes {
+            self.update(note, key)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
     /// Delete a note (soft delete)
     pub fn delete(&self, id: &str) -> Result<()> {
         let now = Utc::now();
@@ -179,87 +294,538 @@ impl<'a> NoteRepository<'a> {
 
     /// Hard delete a note
     pub fn hard_delete(&self, id: &str) -> Result<()> {
+        self.conn.execute("DELETE FROM note_history WHERE note_id = ?1", params![id])?;
         self.conn.execute("DELETE FROM notes WHERE id = ?1", params![id])?;
+        self.conn.execute("DELETE FROM temp.notes_fts WHERE note_id = ?1", params![id])?;
         Ok(())
     }
 
+    /// Whether any note, in the trash or not, still refers to an attachment
+    pub fn references_attachment(&self, attachment_id: &str) -> Result<bool> {
+        let mut stmt = self.conn.prepare("SELECT attachments FROM notes")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        for json in rows {
+            let attachments: Vec<Attachment> = serde_json::from_str(&json?).unwrap_or_default();
+            if attachments.iter().any(|a| a.id == attachment_id) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Permanently remove every soft-deleted note, returning how many were purged
+    ///
+    /// Also drops their sync metadata and any attachment data no remaining note refers to.
+    /// With sync enabled, deletions made since the last sync are kept until a sync has sent
+    /// them, or other devices would never learn the note was deleted.
+    pub fn purge_deleted(&self) -> Result<usize> {
+        const PURGEABLE: &str = "deleted = 1 AND NOT EXISTS (
+            SELECT 1 FROM sync_metadata
+            WHERE sync_enabled = 1 AND (last_sync_at IS NULL OR notes.modified_at > last_sync_at)
+        )";
+        let tx = self.conn.unchecked_transaction()?;
+
+        let attachment_ids = |condition: &str| -> Result<HashSet<String>> {
+            let mut stmt = self.conn.prepare(&format!("SELECT attachments FROM notes WHERE {}", condition))?;
+            let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+            let mut ids = HashSet::new();
+            for json in rows {
+                let attachments: Vec<Attachment> = serde_json::from_str(&json?).unwrap_or_default();
+                ids.extend(attachments.into_iter().map(|a| a.id));
+            }
+            Ok(ids)
+        };
+        let kept = attachment_ids(&format!("NOT ({})", PURGEABLE))?;
+        let orphaned: Vec<String> = attachment_ids(PURGEABLE)?.into_iter().filter(|id| !kept.contains(id)).collect();
+
+        for table in ["note_sync_metadata", "temp.notes_fts", "note_history"] {
+            self.conn.execute(
+                &format!("DELETE FROM {} WHERE note_id IN (SELECT id FROM notes WHERE {})", table, PURGEABLE),
+                [],
+            )?;
+        }
+        let purged = self.conn.execute(&format!("DELETE FROM notes WHERE {}", PURGEABLE), [])?;
+        for id in &orphaned {
+            self.conn.execute("DELETE FROM attachments WHERE id = ?1", params![id])?;
+        }
+
+        tx.commit()?;
+        Ok(purged)
+    }
+
     /// List all notes (excluding deleted by default)
     pub fn list(&self, include_deleted: bool, key: &[u8; 32]) -> Result<Vec<Note>> {
         let query = if include_deleted {
             "SELECT id, created_at, modified_at, synced_at, content, tags, attachments,
-                    pinned, deleted, deleted_at, sync_hash, version, word_wrap, syntax_language
+                    pinned, deleted, deleted_at, sync_hash, version, word_wrap, syntax_language, reminder_at
              FROM notes ORDER BY modified_at DESC"
         } else {
             "SELECT id, created_at, modified_at, synced_at, content, tags, attachments,
-                    pinned, deleted, deleted_at, sync_hash, version, word_wrap, syntax_language
+                    pinned, deleted, deleted_at, sync_hash, version, word_wrap, syntax_language, reminder_at
              FROM notes WHERE deleted = 0 ORDER BY modified_at DESC"
         };
 
         let mut stmt = self.conn.prepare(query)?;
-        let rows = stmt.query_map([], |row| {
-            Ok((
-                row.get::<_, String>(0)?,
-                row.get::<_, String>(1)?,
-                row.get::<_, String>(2)?,
-                row.get::<_, Option<String>>(3)?,
-                row.get::<_, String>(4)?,
-                row.get::<_, String>(5)?,
-                row.get::<_, String>(6)?,
-                row.get::<_, i32>(7)?,
-                row.get::<_, i32>(8)?,
-                row.get::<_, Option<String>>(9)?,
-                row.get::<_, Option<String>>(10)?,
-                row.get::<_, i32>(11)?,
-                row.get::<_, i32>(12)?,
-                row.get::<_, String>(13)?,
-            ))
-        })?;
+        let notes = stmt
+            .query_map([], note_row)?
+            .map(|row| self.decrypt_row(row?, key))
+            .collect::<Result<Vec<_>>>()?;
 
-        let mut notes = Vec::new();
-        for row in rows {
-            let (
-                id,
-                created_at,
-                modified_at,
-                synced_at,
-                content_json,
-                tags_json,
-                attachments_json,
-                pinned,
-                deleted,
-                deleted_at,
-                sync_hash,
-                version,
-                word_wrap,
-                syntax_language,
-            ) = row?;
+        Ok(notes)
+    }
+
+    /// Like `list`, but a note that can't be read is reported in the batch rather than
+    /// failing the whole listing
+    pub fn list_lenient(&self, include_deleted: bool, key: &[u8; 32]) -> Result<NoteBatch> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, created_at, modified_at, synced_at, content, tags, attachments,
+                    pinned, deleted, deleted_at, sync_hash, version, word_wrap, syntax_language, reminder_at
+             FROM notes WHERE ?1 OR deleted = 0 ORDER BY modified_at DESC"
+        )?;
 
-            let encrypted_content: EncryptedData = serde_json::from_str(&content_json)?;
-            let encrypted_tags: EncryptedData = serde_json::from_str(&tags_json)?;
+        let mut batch = NoteBatch::default();
+        for row in stmt.query_map(params![include_deleted], note_row)? {
+            let row = row?;
+            let id = row.0.clone();
+            match self.decrypt_row(row, key) {
+                Ok(note) => batch.notes.push(note),
+                Err(e) if e.is_unreadable_data() => {
+                    warn!("Skipping unreadable note {}: {}", id, e);
+                    batch.unreadable.push(id);
+                }
+                Err(e) => return Err(e),
+            }
+        }
 
-            let content = self.crypto.decrypt_text(&encrypted_content, key)?;
-            let tags: Vec<String> = self.crypto.decrypt_json(&encrypted_tags, key)?;
-            let attachments: Vec<Attachment> = serde_json::from_str(&attachments_json)?;
+        Ok(batch)
+    }
+
+    /// A page of note metadata in list order (pinned first, then most recently modified),
+    /// without decrypting
+    ///
+    /// The page starts after `after`, the last note of the previous page, so notes that
+    /// move in the meantime (e.g. when unpinned) don't shift the following pages the way
+    /// an offset would. `usize::MAX` means no limit.
+    pub fn list_meta(&self, include_deleted: bool, after: Option<&NoteMeta>, limit: usize) -> Result<Vec<NoteMeta>> {
+        // Compared as Julian days, so the cursor matches however the timestamp was written
+        let mut stmt = self.conn.prepare(
+            "SELECT id, created_at, modified_at, pinned, deleted, reminder_at, attachments
+             FROM notes
+             WHERE (?1 OR deleted = 0)
+               AND (?2 IS NULL
+                    OR pinned < ?2
+                    OR (pinned = ?2 AND (julianday(modified_at) < julianday(?3)
+                                         OR (julianday(modified_at) = julianday(?3) AND id > ?4))))
+             ORDER BY pinned DESC, julianday(modified_at) DESC, id
+             LIMIT ?5"
+        )?;
 
-            notes.push(Note {
+        // SQLite treats a negative LIMIT as none
+        let limit = i64::try_from(limit).unwrap_or(-1);
+        let rows = stmt
+            .query_map(
+                params![
+                    include_deleted,
+                    after.map(|meta| meta.pinned),
+                    after.map(|meta| meta.modified_at.to_rfc3339()),
+                    after.map(|meta| &meta.id),
+                    limit,
+                ],
+                meta_row,
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        rows.into_iter().map(parse_meta).collect()
+    }
+
+    /// Metadata, tags and previews of every note (most recently modified first), decrypting
+    /// only the tags and previews rather than whole notes
+    ///
+    /// A note saved before previews existed has its content decrypted instead, until
+    /// `fill_previews` has run.
+    pub fn list_titles(&self, include_deleted: bool, key: &[u8; 32]) -> Result<Vec<NotePreview>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, created_at, modified_at, pinned, deleted, reminder_at, attachments,
+                    tags, COALESCE(preview, content), preview IS NULL
+             FROM notes WHERE ?1 OR deleted = 0 ORDER BY modified_at DESC"
+        )?;
+
+        let rows = stmt
+            .query_map(params![include_deleted], title_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        rows.into_iter()
+            .map(|(meta, tags_json, preview_json, whole_content)| {
+                self.decrypt_title(parse_meta(meta)?, tags_json, preview_json, whole_content, key)
+            })
+            .collect()
+    }
+
+    /// Tags and previews of the notes in `metas`, decrypting nothing else
+    ///
+    /// Notes that can't be read are reported in the batch, and notes deleted since
+    /// `metas` was listed are left out.
+    pub fn titles(&self, metas: Vec<NoteMeta>, key: &[u8; 32]) -> Result<TitleBatch> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT tags, COALESCE(preview, content), preview IS NULL FROM notes WHERE id = ?1")?;
+
+        let mut batch = TitleBatch::default();
+        for meta in metas {
+            let Some((tags_json, preview_json, whole_content)) = stmt
+                .query_row(params![&meta.id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+                .optional()?
+            else {
+                continue;
+            };
+            let id = meta.id.clone();
+            match self.decrypt_title(meta, tags_json, preview_json, whole_content, key) {
+                Ok(title) => batch.titles.push(title),
+                Err(e) if e.is_unreadable_data() => {
+                    warn!("Skipping unreadable note {}: {}", id, e);
+                    batch.unreadable.push(id);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(batch)
+    }
+
+    /// Store the encrypted preview of every note that has none yet, returning how many
+    ///
+    /// Run on unlock, for notes saved before the preview column was added.
+    pub fn fill_previews(&self, key: &[u8; 32]) -> Result<usize> {
+        let mut stmt = self.conn.prepare("SELECT id FROM notes WHERE preview IS NULL")?;
+        let ids = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let tx = self.conn.unchecked_transaction()?;
+        let mut filled = 0;
+        for id in &ids {
+            // Unreadable notes keep no preview; they are reported when listed
+            let note = match self.get(id, key) {
+                Ok(note) => note,
+                Err(e) if e.is_unreadable_data() => None,
+                Err(e) => return Err(e),
+            };
+            if let Some(note) = note {
+                let encrypted = self.crypto.encrypt_text(&note.preview(), key).map_err(RepoError::Encrypt)?;
+                tx.execute(
+                    "UPDATE notes SET preview = ?1 WHERE id = ?2",
+                    params![serde_json::to_string(&encrypted)?, id],
+                )?;
+                filled += 1;
+            }
+        }
+        tx.commit()?;
+
+        Ok(filled)
+    }
+
+    /// Copy the stored content and tags of `note` into its history if this update changes them
+    ///
+    /// Metadata-only writes such as pinning aren't recorded, and neither are rows that don't
+    /// decrypt with `key`, which is how re-encryption under a new key looks from here.
+    fn record_history(&self, note: &Note, key: &[u8; 32]) -> Result<()> {
+        let stored = self
+            .conn
+            .query_row(
+                "SELECT content, tags, version, modified_at FROM notes WHERE id = ?1",
+                params![&note.id],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, i32>(2)?, row.get::<_, String>(3)?)),
+            )
+            .optional()?;
+        let Some((content_json, tags_json, version, modified_at)) = stored else {
+            return Ok(());
+        };
+
+        let decrypt = |json: &str| parse_encrypted(json).ok().and_then(|e| self.crypto.decrypt_text(&e, key).ok());
+        let (Some(content), Some(tags)) = (decrypt(&content_json), decrypt(&tags_json)) else {
+            return Ok(());
+        };
+        if content == note.content && serde_json::from_str::<Vec<String>>(&tags).is_ok_and(|t| t == note.tags) {
+            return Ok(());
+        }
+
+        self.conn.execute(
+            "INSERT INTO note_history (note_id, version, modified_at, content, tags) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![&note.id, version, modified_at, content_json, tags_json],
+        )?;
+        self.conn.execute(
+            "DELETE FROM note_history WHERE note_id = ?1 AND id NOT IN (
+                SELECT id FROM note_history WHERE note_id = ?1 ORDER BY id DESC LIMIT ?2
+             )",
+            params![&note.id, NOTE_HISTORY_LIMIT as i64],
+        )?;
+        Ok(())
+    }
+
+    /// Earlier versions of a note, newest first
+    pub fn history(&self, note_id: &str, key: &[u8; 32]) -> Result<Vec<NoteVersion>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, version, modified_at, content, tags FROM note_history
+             WHERE note_id = ?1 ORDER BY id DESC",
+        )?;
+        let rows = stmt.query_map(params![note_id], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, i32>(1)?, row.get::<_, String>(2)?, row.get::<_, String>(3)?, row.get::<_, String>(4)?))
+        })?;
+
+        rows.map(|row| {
+            let (id, version, modified_at, content_json, tags_json) = row?;
+            let what = || format!("note {} version {}", note_id, version);
+            let content = self.open_text(&content_json, key, what)?;
+            let tags = serde_json::from_str(&self.open_text(&tags_json, key, what)?)?;
+            Ok(NoteVersion {
                 id,
-                created_at: created_at.parse()?,
-                modified_at: modified_at.parse()?,
-                synced_at: synced_at.map(|s| s.parse()).transpose()?,
+                note_id: note_id.to_string(),
+                version,
+                modified_at: parse_time(note_id, &modified_at)?,
                 content,
                 tags,
-                attachments,
-                pinned: pinned != 0,
-                deleted: deleted != 0,
-                deleted_at: deleted_at.map(|s| s.parse()).transpose()?,
-                sync_hash,
-                version,
-                word_wrap: word_wrap != 0,
-                syntax_language: syntax_language.parse().unwrap_or_default(),
-            });
+            })
+        })
+        .collect()
+    }
+
+    /// Put an earlier version's content and tags back, recording the current ones in history
+    pub fn restore_version(&self, note_id: &str, history_id: i64, key: &[u8; 32]) -> Result<Note> {
+        let version = self
+            .history(note_id, key)?
+            .into_iter()
+            .find(|v| v.id == history_id)
+            .ok_or_else(|| RepoError::NotFound { kind: "note version", id: history_id.to_string() })?;
+        let mut note = self
+            .get(note_id, key)?
+            .ok_or_else(|| RepoError::NotFound { kind: "note", id: note_id.to_string() })?;
+
+        note.content = version.content;
+        note.tags = version.tags;
+        note.touch();
+        self.update(&note, key)?;
+        Ok(note)
+    }
+
+    /// Re-encrypt every recorded version from `old_key` to `new_key`
+    ///
+    /// Everything is decrypted before anything is written, so an unreadable version leaves
+    /// the history untouched.
+    pub fn reencrypt_history(&self, old_key: &[u8; 32], new_key: &[u8; 32]) -> Result<usize> {
+        let mut stmt = self.conn.prepare("SELECT id, note_id, content, tags FROM note_history")?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, String>(3)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let decrypted = rows
+            .iter()
+            .map(|(id, note_id, content_json, tags_json)| {
+                let what = || format!("history of note {}", note_id);
+                Ok((*id, self.open_text(content_json, old_key, what)?, self.open_text(tags_json, old_key, what)?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        for (id, content, tags) in &decrypted {
+            let content = self.crypto.encrypt_text(content, new_key).map_err(RepoError::Encrypt)?;
+            let tags = self.crypto.encrypt_text(tags, new_key).map_err(RepoError::Encrypt)?;
+            self.conn.execute(
+                "UPDATE note_history SET content = ?1, tags = ?2 WHERE id = ?3",
+                params![serde_json::to_string(&content)?, serde_json::to_string(&tags)?, id],
+            )?;
         }
+        Ok(decrypted.len())
+    }
 
-        Ok(notes)
+    /// Decrypt a stored text column of `what`
+    fn open_text(&self, json: &str, key: &[u8; 32], what: impl Fn() -> String) -> Result<String> {
+        let encrypted = parse_stored(json, || format!("{}: corrupted ciphertext", what()))?;
+        self.crypto
+            .decrypt_text(&encrypted, key)
+            .map_err(|source| RepoError::Decrypt { what: what(), source })
+    }
+
+    /// Decrypt the stored tags and preview of the note described by `meta`
+    fn decrypt_title(
+        &self,
+        meta: NoteMeta,
+        tags_json: String,
+        preview_json: String,
+        whole_content: bool,
+        key: &[u8; 32],
+    ) -> Result<NotePreview> {
+        let id = &meta.id;
+
+        let encrypted_tags = parse_stored(&tags_json, || format!("note {}: corrupted tags ciphertext", id))?;
+        let tags: Vec<String> = self
+            .crypto
+            .decrypt_json(&encrypted_tags, key)
+            .map_err(|source| RepoError::Decrypt { what: format!("tags of note {}", id), source })?;
+
+        let encrypted_preview = parse_stored(&preview_json, || format!("note {}: corrupted preview ciphertext", id))?;
+        let mut preview = self
+            .crypto
+            .decrypt_text(&encrypted_preview, key)
+            .map_err(|source| RepoError::Decrypt { what: format!("preview of note {}", id), source })?;
+        if whole_content {
+            preview = preview_of(&preview);
+        }
+
+        Ok(NotePreview { meta, tags, preview })
+    }
+
+    /// Decrypt a row read by `note_row`
+    fn decrypt_row(&self, row: NoteRow, key: &[u8; 32]) -> Result<Note> {
+        let (
+            id,
+            created_at,
+            modified_at,
+            synced_at,
+            content_json,
+            tags_json,
+            attachments_json,
+            pinned,
+            deleted,
+            deleted_at,
+            sync_hash,
+            version,
+            word_wrap,
+            syntax_language,
+            reminder_at,
+        ) = row;
+
+        // Decrypt content and tags
+        let encrypted_content = parse_stored(&content_json, || format!("note {}: corrupted ciphertext", id))?;
+        let encrypted_tags = parse_stored(&tags_json, || format!("note {}: corrupted tags ciphertext", id))?;
+
+        let content = self
+            .crypto
+            .decrypt_text(&encrypted_content, key)
+            .map_err(|source| RepoError::Decrypt { what: format!("note {}", id), source })?;
+        let tags: Vec<String> = self
+            .crypto
+            .decrypt_json(&encrypted_tags, key)
+            .map_err(|source| RepoError::Decrypt { what: format!("tags of note {}", id), source })?;
+
+        // Deserialize attachments
+        let attachments: Vec<Attachment> = serde_json::from_str(&attachments_json)?;
+
+        Ok(Note {
+            created_at: parse_time(&id, &created_at)?,
+            modified_at: parse_time(&id, &modified_at)?,
+            synced_at: synced_at.map(|s| parse_time(&id, &s)).transpose()?,
+            deleted_at: deleted_at.map(|s| parse_time(&id, &s)).transpose()?,
+            reminder_at: reminder_at.map(|s| parse_time(&id, &s)).transpose()?,
+            id,
+            content,
+            tags,
+            attachments,
+            pinned: pinned != 0,
+            deleted: deleted != 0,
+            sync_hash,
+            version,
+            word_wrap: word_wrap != 0,
+            syntax_language: syntax_language.parse().unwrap_or_default(),
+        })
+    }
+
+    /// Add or replace a note in the in-memory search index
+    fn index_note(&self, note: &Note) -> Result<()> {
+        self.conn.execute("DELETE FROM temp.notes_fts WHERE note_id = ?1", params![&note.id])?;
+        self.conn.execute(
+            "INSERT INTO temp.notes_fts (note_id, content, tags) VALUES (?1, ?2, ?3)",
+            params![&note.id, &note.content, note.tags.join("\n")],
+        )?;
+        Ok(())
+    }
+
+    /// Refill the search index from the notes that decrypt (done on unlock)
+    pub fn rebuild_search_index(&self, key: &[u8; 32]) -> Result<usize> {
+        let notes = self.list_lenient(true, key)?.notes;
+
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM temp.notes_fts", [])?;
+        for note in &notes {
+            self.index_note(note)?;
+        }
+        tx.commit()?;
+
+        Ok(notes.len())
+    }
+
+    /// Search notes, returning matching ids (including deleted notes) best match first
+    ///
+    /// Case-insensitive substring matching, as `Note::matches_query`: words must appear in
+    /// the content, `#tag` in a tag, and `-word` excludes notes containing it. Queries with
+    /// one- or two-letter terms scan the indexed text instead. Builds the index first if
+    /// it is empty.
+    pub fn search(&self, query: &str, key: &[u8; 32]) -> Result<Vec<String>> {
+        self.ensure_indexed(key)?;
+
+        let Some((include, exclude)) = fts_query(query) else {
+            return self.scan(|content, tags| matches_query(content, tags, query, false), key);
+        };
+        let (sql, expression) = match (include, exclude) {
+            (Some(include), Some(exclude)) => (
+                "SELECT note_id FROM temp.notes_fts WHERE notes_fts MATCH ?1 ORDER BY rank",
+                format!("{} NOT ({})", include, exclude),
+            ),
+            (Some(include), None) => (
+                "SELECT note_id FROM temp.notes_fts WHERE notes_fts MATCH ?1 ORDER BY rank",
+                include,
+            ),
+            // FTS5 has no unary NOT, so subtract the excluded notes instead
+            (None, Some(exclude)) => (
+                "SELECT note_id FROM temp.notes_fts
+                 WHERE note_id NOT IN (SELECT note_id FROM temp.notes_fts WHERE notes_fts MATCH ?1)",
+                exclude,
+            ),
+            // Nothing searchable (e.g. a lone "#"), so everything matches
+            (None, None) => {
+                let mut stmt = self.conn.prepare("SELECT note_id FROM temp.notes_fts")?;
+                let ids = stmt
+                    .query_map([], |row| row.get(0))?
+                    .collect::<rusqlite::Result<Vec<String>>>()?;
+                return Ok(ids);
+            }
+        };
+
+        let mut stmt = self.conn.prepare(sql)?;
+        let ids = stmt
+            .query_map(params![expression], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+
+        Ok(ids)
+    }
+
+    /// Ids of the notes (including deleted ones) whose content and tags satisfy `matches`,
+    /// for searches the index can't answer, such as regular expressions
+    ///
+    /// Reads the plaintext kept by the search index rather than decrypting every note.
+    pub fn scan(&self, matches: impl Fn(&str, &[String]) -> bool, key: &[u8; 32]) -> Result<Vec<String>> {
+        self.ensure_indexed(key)?;
+
+        let mut stmt = self.conn.prepare("SELECT note_id, content, tags FROM temp.notes_fts")?;
+        let mut ids = Vec::new();
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let content: String = row.get(1)?;
+            let tags: String = row.get(2)?;
+            let tags: Vec<String> = tags.split('\n').filter(|t| !t.is_empty()).map(str::to_string).collect();
+            if matches(&content, &tags) {
+                ids.push(row.get(0)?);
+            }
+        }
+
+        Ok(ids)
+    }
+
+    /// Build the search index if it is empty
+    fn ensure_indexed(&self, key: &[u8; 32]) -> Result<()> {
+        let indexed: i64 = self
+            .conn
+            .query_row("SELECT COUNT(*) FROM temp.notes_fts", [], |row| row.get(0))?;
+        if indexed == 0 {
+            self.rebuild_search_index(key)?;
+        }
+        Ok(())
     }
 
     /// Get notes modified after a specific timestamp (for sync)
@@ -270,74 +836,56 @@ impl<'a> NoteRepository<'a> {
     ) -> Result<Vec<Note>> {
         let mut stmt = self.conn.prepare(
             "SELECT id, created_at, modified_at, synced_at, content, tags, attachments,
-                    pinned, deleted, deleted_at, sync_hash, version, word_wrap, syntax_language
+                    pinned, deleted, deleted_at, sync_hash, version, word_wrap, syntax_language, reminder_at
              FROM notes WHERE modified_at > ?1 ORDER BY modified_at DESC"
         )?;
 
-        let rows = stmt.query_map(params![timestamp.to_rfc3339()], |row| {
-            Ok((
-                row.get::<_, String>(0)?,
-                row.get::<_, String>(1)?,
-                row.get::<_, String>(2)?,
-                row.get::<_, Option<String>>(3)?,
-                row.get::<_, String>(4)?,
-                row.get::<_, String>(5)?,
-                row.get::<_, String>(6)?,
-                row.get::<_, i32>(7)?,
-                row.get::<_, i32>(8)?,
-                row.get::<_, Option<String>>(9)?,
-                row.get::<_, Option<String>>(10)?,
-                row.get::<_, i32>(11)?,
-                row.get::<_, i32>(12)?,
-                row.get::<_, String>(13)?,
-            ))
-        })?;
+        let notes = stmt
+            .query_map(params![timestamp.to_rfc3339()], note_row)?
+            .map(|row| self.decrypt_row(row?, key))
+            .collect::<Result<Vec<_>>>()?;
 
-        let mut notes = Vec::new();
-        for row in rows {
-            let (
-                id,
-                created_at,
-                modified_at,
-                synced_at,
-                content_json,
-                tags_json,
-                attachments_json,
-                pinned,
-                deleted,
-                deleted_at,
-                sync_hash,
-                version,
-                word_wrap,
-                syntax_language,
-            ) = row?;
-
-            let encrypted_content: EncryptedData = serde_json::from_str(&content_json)?;
-            let encrypted_tags: EncryptedData = serde_json::from_str(&tags_json)?;
+        Ok(notes)
+    }
 
-            let content = self.crypto.decrypt_text(&encrypted_content, key)?;
-            let tags: Vec<String> = self.crypto.decrypt_json(&encrypted_tags, key)?;
-            let attachments: Vec<Attachment> = serde_json::from_str(&attachments_json)?;
+    /// Notes (including deleted ones) that fail to decrypt with `key`, with the error for each
+    pub fn find_undecryptable(&self, key: &[u8; 32]) -> Result<Vec<(String, String)>> {
+        let mut stmt = self.conn.prepare("SELECT id FROM notes ORDER BY id")?;
+        let ids = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
 
-            notes.push(Note {
-                id,
-                created_at: created_at.parse()?,
-                modified_at: modified_at.parse()?,
-                synced_at: synced_at.map(|s| s.parse()).transpose()?,
-                content,
-                tags,
-                attachments,
-                pinned: pinned != 0,
-                deleted: deleted != 0,
-                deleted_at: deleted_at.map(|s| s.parse()).transpose()?,
-                sync_hash,
-                version,
-                word_wrap: word_wrap != 0,
-                syntax_language: syntax_language.parse().unwrap_or_default(),
-            });
+        let mut failures = Vec::new();
+        for id in ids {
+            if let Err(e) = self.get(&id, key) {
+                failures.push((id, format!("{:#}", anyhow::Error::from(e))));
+            }
         }
 
-        Ok(notes)
+        Ok(failures)
+    }
+
+    /// Check `key` against the first few stored notes: passes if any of them decrypts (or
+    /// there are none), otherwise fails with the first note's error, usually `RepoError::Decrypt`
+    ///
+    /// One note alone isn't trusted, as it may have been written under another key elsewhere.
+    pub fn check_key(&self, key: &[u8; 32]) -> Result<()> {
+        let mut stmt = self.conn.prepare("SELECT id FROM notes ORDER BY id LIMIT ?1")?;
+        let ids = stmt
+            .query_map(params![KEY_CHECK_NOTES as i64], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut first_error = None;
+        for id in ids {
+            match self.get(&id, key) {
+                Ok(_) => return Ok(()),
+                Err(e) if e.is_unreadable_data() => {
+                    first_error.get_or_insert(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        first_error.map_or(Ok(()), Err)
     }
 
     /// Count notes
@@ -351,4 +899,494 @@ impl<'a> NoteRepository<'a> {
         let count: i64 = self.conn.query_row(query, [], |row| row.get(0))?;
         Ok(count)
     }
+
+    /// Count notes outside the trash whose reminder is due at or before `now`
+    pub fn count_overdue(&self, now: DateTime<Utc>) -> Result<i64> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM notes
+             WHERE deleted = 0 AND reminder_at IS NOT NULL AND julianday(reminder_at) <= julianday(?1)",
+            params![now.to_rfc3339()],
+            |row| row.get(0),
+        )?;
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::repository::attachment::AttachmentRepository;
+    use crate::repository::sync::SyncRepository;
+
+    fn note(content: &str, tags: &[&str]) -> Note {
+        let mut note = Note::new(content.to_string());
+        note.tags = tags.iter().map(|t| t.to_string()).collect();
+        note
+    }
+
+    fn ids_of(page: &[NoteMeta]) -> Vec<&String> {
+        page.iter().map(|meta| &meta.id).collect()
+    }
+
+    #[test]
+    fn test_find_undecryptable_reports_ids() {
+        let db = Database::in_memory("test_password").unwrap();
+        let key = [7u8; 32];
+        let repo = NoteRepository::new(db.connection());
+
+        let good = note("Readable", &[]);
+        let bad = note("Written with another key", &[]);
+        repo.create(&good, &key).unwrap();
+        repo.create(&bad, &[8u8; 32]).unwrap();
+        assert!(repo.list(true, &key).is_err());
+
+        let failures = repo.find_undecryptable(&key).unwrap();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].0, bad.id);
+    }
+
+    #[test]
+    fn test_list_lenient_skips_unreadable_notes() {
+        let db = Database::in_memory("test_password").unwrap();
+        let key = [7u8; 32];
+        let repo = NoteRepository::new(db.connection());
+
+        let good = note("Readable apples", &[]);
+        let foreign = note("Written with another key", &[]);
+        let damaged = note("Damaged on disk", &[]);
+        repo.create(&good, &key).unwrap();
+        repo.create(&foreign, &[8u8; 32]).unwrap();
+        repo.create(&damaged, &key).unwrap();
+        db.connection()
+            .execute("UPDATE notes SET content = '{}' WHERE id = ?1", params![damaged.id])
+            .unwrap();
+        assert!(repo.list(false, &key).is_err());
+
+        let batch = repo.list_lenient(false, &key).unwrap();
+        assert_eq!(batch.notes.iter().map(|n| &n.id).collect::<Vec<_>>(), [&good.id]);
+        let mut unreadable = batch.unreadable;
+        unreadable.sort();
+        let mut expected = vec![foreign.id.clone(), damaged.id.clone()];
+        expected.sort();
+        assert_eq!(unreadable, expected);
+
+        // Unlock-time work carries on past them too
+        assert_eq!(repo.rebuild_search_index(&key).unwrap(), 1);
+        assert_eq!(repo.search("apples", &key).unwrap(), std::slice::from_ref(&good.id));
+        db.connection().execute("UPDATE notes SET preview = NULL", []).unwrap();
+        assert_eq!(repo.fill_previews(&key).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_corrupted_ciphertext_names_the_note() {
+        let db = Database::in_memory("test_password").unwrap();
+        let key = [7u8; 32];
+        let repo = NoteRepository::new(db.connection());
+
+        let damaged = note("Damaged on disk", &[]);
+        repo.create(&damaged, &key).unwrap();
+        db.connection()
+            .execute(
+                "UPDATE notes SET content = json_set(content, '$.iv', '!!not base64!!') WHERE id = ?1",
+                params![damaged.id],
+            )
+            .unwrap();
+
+        let err = repo.get(&damaged.id, &key).unwrap_err();
+        assert!(matches!(err, RepoError::Corrupt { .. }), "{:?}", err);
+        let err = err.to_string();
+        assert!(err.starts_with(&format!("note {}: corrupted ciphertext", damaged.id)), "{}", err);
+        assert!(err.contains("Nonce must be valid base64"), "{}", err);
+        assert!(repo.list(true, &key).is_err());
+    }
+
+    #[test]
+    fn test_errors_tell_missing_from_undecryptable() {
+        let db = Database::in_memory("test_password").unwrap();
+        let key = [7u8; 32];
+        let repo = NoteRepository::new(db.connection());
+
+        let stored = note("Stored", &[]);
+        repo.create(&stored, &key).unwrap();
+
+        let err = repo.get(&stored.id, &[8u8; 32]).unwrap_err();
+        assert!(matches!(&err, RepoError::Decrypt { what, .. } if *what == format!("note {}", stored.id)), "{:?}", err);
+
+        let missing = note("Never saved", &[]);
+        let err = repo.update(&missing, &key).unwrap_err();
+        assert!(matches!(&err, RepoError::NotFound { kind: "note", id } if *id == missing.id), "{:?}", err);
+        assert!(repo.get(&missing.id, &key).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_list_meta_pages_and_titles() {
+        let db = Database::in_memory("test_password").unwrap();
+        let key = [7u8; 32];
+        let repo = NoteRepository::new(db.connection());
+
+        let mut notes: Vec<Note> = (0..5).map(|i| note(&format!("Note {}", i), &[])).collect();
+        for (i, n) in notes.iter_mut().enumerate() {
+            n.modified_at = Utc::now() - chrono::Duration::minutes(i as i64);
+        }
+        notes[3].pinned = true;
+        notes[1].reminder_at = Some(notes[1].modified_at);
+        notes[1].attachments = vec![Attachment::new("a.txt".into(), "text/plain".into(), 1, String::new())];
+        for n in &notes {
+            repo.create(n, &key).unwrap();
+        }
+        repo.delete(&notes[4].id).unwrap();
+
+        let expected = [&notes[3], &notes[0], &notes[1], &notes[2]].map(|n| &n.id);
+        let first = repo.list_meta(false, None, 2).unwrap();
+        assert_eq!(ids_of(&first), expected[..2]);
+        let second = repo.list_meta(false, first.last(), 2).unwrap();
+        assert_eq!(ids_of(&second), expected[2..]);
+        assert!(repo.list_meta(false, second.last(), 2).unwrap().is_empty());
+        assert_eq!(repo.list_meta(true, None, usize::MAX).unwrap().len(), 5);
+
+        // Titles carry what the list shows besides the preview
+        let batch = repo.titles(second, &key).unwrap();
+        assert_eq!(batch.titles[0].meta, NotePreview::from(&notes[1]).meta);
+        assert_eq!(batch.titles[0].meta.attachment_ids, [notes[1].attachments[0].id.clone()]);
+        assert_eq!(batch.titles[1].preview, "Note 2");
+
+        // Metadata needs no key, so even notes under another key are listed; their titles aren't
+        let other = note("Other key", &[]);
+        repo.create(&other, &[8u8; 32]).unwrap();
+        let meta = repo.list_meta(false, None, usize::MAX).unwrap();
+        assert_eq!(meta.len(), 5);
+        let pinned = meta.iter().find(|m| m.id == notes[3].id).unwrap();
+        assert!(pinned.pinned && !pinned.deleted);
+        assert_eq!(pinned.created_at, notes[3].created_at);
+        let batch = repo.titles(meta, &key).unwrap();
+        assert_eq!(batch.titles.len(), 4);
+        assert_eq!(batch.unreadable, [other.id]);
+    }
+
+    #[test]
+    fn test_list_meta_pages_survive_unpinning() {
+        let db = Database::in_memory("test_password").unwrap();
+        let key = [7u8; 32];
+        let repo = NoteRepository::new(db.connection());
+
+        let mut notes: Vec<Note> = (0..5).map(|i| note(&format!("Note {}", i), &[])).collect();
+        for (i, n) in notes.iter_mut().enumerate() {
+            n.modified_at = Utc::now() - chrono::Duration::minutes(i as i64);
+        }
+        notes[4].pinned = true;
+        notes[0].pinned = true;
+        for n in &notes {
+            repo.create(n, &key).unwrap();
+        }
+
+        // The oldest note drops to the end once unpinned; an offset of 2 would skip note 1
+        let first = repo.list_meta(false, None, 2).unwrap();
+        assert_eq!(ids_of(&first), [&notes[0].id, &notes[4].id]);
+        notes[4].pinned = false;
+        repo.update(&notes[4], &key).unwrap();
+        let rest = repo.list_meta(false, first.last(), usize::MAX).unwrap();
+        assert_eq!(ids_of(&rest), [&notes[1].id, &notes[2].id, &notes[3].id, &notes[4].id]);
+    }
+
+    #[test]
+    fn test_list_titles_and_fill_previews() {
+        let db = Database::in_memory("test_password").unwrap();
+        let key = [7u8; 32];
+        let repo = NoteRepository::new(db.connection());
+
+        let long = note(&format!("\n\n  Long note\n{}", "é".repeat(500)), &["big"]);
+        let legacy = note("Saved before previews", &[]);
+        repo.create(&long, &key).unwrap();
+        repo.create(&legacy, &key).unwrap();
+        db.connection()
+            .execute("UPDATE notes SET preview = NULL WHERE id = ?1", params![legacy.id])
+            .unwrap();
+
+        let titles = repo.list_titles(false, &key).unwrap();
+        let long_preview = titles.iter().find(|t| t.meta.id == long.id).unwrap();
+        assert_eq!(long_preview.title(), "Long note");
+        assert_eq!(long_preview.preview.chars().count(), crate::models::PREVIEW_CHARS);
+        assert_eq!(long_preview.tags, ["big"]);
+        // Notes without a stored preview fall back to their content
+        let legacy_preview = titles.iter().find(|t| t.meta.id == legacy.id).unwrap();
+        assert_eq!(legacy_preview.preview, "Saved before previews");
+
+        assert_eq!(repo.fill_previews(&key).unwrap(), 1);
+        assert_eq!(repo.fill_previews(&key).unwrap(), 0);
+        let stored: String = db
+            .connection()
+            .query_row("SELECT preview FROM notes WHERE id = ?1", params![legacy.id], |row| row.get(0))
+            .unwrap();
+        assert!(!stored.contains("Saved"), "previews are stored encrypted");
+        assert!(repo.list_titles(false, &[8u8; 32]).is_err());
+    }
+
+    #[test]
+    fn test_update_all_is_atomic() {
+        let db = Database::in_memory("test_password").unwrap();
+        let key = [7u8; 32];
+        let repo = NoteRepository::new(db.connection());
+
+        let first = note("First", &[]);
+        let second = note("Second", &[]);
+        repo.create(&first, &key).unwrap();
+        repo.create(&second, &key).unwrap();
+
+        let mut changed = vec![first.clone(), second.clone()];
+        for n in &mut changed {
+            n.tags = vec!["bulk".to_string()];
+            n.pinned = true;
+        }
+
+        // A failure on the second note rolls back the first
+        db.connection()
+            .execute_batch(&format!(
+                "CREATE TEMP TRIGGER fail_second BEFORE UPDATE ON notes WHEN NEW.id = '{}'
+                 BEGIN SELECT RAISE(ABORT, 'boom'); END;",
+                second.id
+            ))
+            .unwrap();
+        assert!(repo.update_all(&changed, &key).is_err());
+        assert!(!repo.get(&first.id, &key).unwrap().unwrap().pinned);
+
+        db.connection().execute_batch("DROP TRIGGER fail_second").unwrap();
+        repo.update_all(&changed, &key).unwrap();
+        for n in &changed {
+            let stored = repo.get(&n.id, &key).unwrap().unwrap();
+            assert!(stored.pinned);
+            assert_eq!(stored.tags, vec!["bulk"]);
+        }
+    }
+
+    #[test]
+    fn test_history_records_replaced_content() {
+        let db = Database::in_memory("test_password").unwrap();
+        let key = [7u8; 32];
+        let repo = NoteRepository::new(db.connection());
+
+        let mut n = note("First draft", &["draft"]);
+        repo.create(&n, &key).unwrap();
+
+        // Pinning leaves content and tags alone, so nothing is recorded
+        n.pinned = true;
+        repo.update(&n, &key).unwrap();
+        assert!(repo.history(&n.id, &key).unwrap().is_empty());
+
+        n.content = "Second draft".to_string();
+        n.touch();
+        repo.update(&n, &key).unwrap();
+        n.tags = vec!["final".to_string()];
+        n.touch();
+        repo.update(&n, &key).unwrap();
+
+        let history = repo.history(&n.id, &key).unwrap();
+        let recorded: Vec<_> = history.iter().map(|v| (v.version, v.content.as_str(), v.tags.clone())).collect();
+        assert_eq!(
+            recorded,
+            [(2, "Second draft", vec!["draft".to_string()]), (1, "First draft", vec!["draft".to_string()])]
+        );
+
+        // Restoring records the replaced content in turn
+        let restored = repo.restore_version(&n.id, history[1].id, &key).unwrap();
+        assert_eq!((restored.content.as_str(), restored.tags.as_slice()), ("First draft", ["draft".to_string()].as_slice()));
+        assert!(restored.pinned);
+        assert_eq!(repo.get(&n.id, &key).unwrap().unwrap().content, "First draft");
+        assert_eq!(repo.history(&n.id, &key).unwrap()[0].content, "Second draft");
+        assert!(matches!(repo.restore_version(&n.id, -1, &key), Err(RepoError::NotFound { .. })));
+
+        repo.hard_delete(&n.id).unwrap();
+        assert!(repo.history(&n.id, &key).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_history_is_capped_per_note() {
+        let db = Database::in_memory("test_password").unwrap();
+        let key = [7u8; 32];
+        let repo = NoteRepository::new(db.connection());
+
+        let mut n = note("v0", &[]);
+        let other = note("other", &[]);
+        repo.create(&n, &key).unwrap();
+        repo.create(&other, &key).unwrap();
+        for i in 1..=NOTE_HISTORY_LIMIT + 5 {
+            n.content = format!("v{}", i);
+            n.touch();
+            repo.update(&n, &key).unwrap();
+        }
+        let mut changed = other.clone();
+        changed.content = "other, edited".to_string();
+        repo.update(&changed, &key).unwrap();
+
+        let history = repo.history(&n.id, &key).unwrap();
+        assert_eq!(history.len(), NOTE_HISTORY_LIMIT);
+        assert_eq!(history[0].content, format!("v{}", NOTE_HISTORY_LIMIT + 4));
+        assert_eq!(history.last().unwrap().content, "v5");
+        assert_eq!(repo.history(&other.id, &key).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_unrecorded_update_keeps_history() {
+        let db = Database::in_memory("test_password").unwrap();
+        let key = [7u8; 32];
+        let repo = NoteRepository::new(db.connection());
+
+        let mut n = note("Before editing", &[]);
+        repo.create(&n, &key).unwrap();
+        for content in ["Typing", "Typing more", "Done typing"] {
+            n.content = content.to_string();
+            n.touch();
+            repo.update_unrecorded(&n, &key).unwrap();
+        }
+
+        assert!(repo.history(&n.id, &key).unwrap().is_empty());
+        assert_eq!(repo.get(&n.id, &key).unwrap().unwrap().content, "Done typing");
+    }
+
+    #[test]
+    fn test_reminder_round_trip_and_overdue_count() {
+        let db = Database::in_memory("test_password").unwrap();
+        let key = [7u8; 32];
+        let repo = NoteRepository::new(db.connection());
+        let now = Utc::now();
+
+        let mut overdue = note("Pay rent", &[]);
+        overdue.reminder_at = Some(now - chrono::Duration::hours(2));
+        let mut upcoming = note("Dentist", &[]);
+        upcoming.reminder_at = Some(now + chrono::Duration::hours(2));
+        let mut trashed = note("Old errand", &[]);
+        trashed.reminder_at = Some(now - chrono::Duration::days(2));
+        for n in [&overdue, &upcoming, &trashed, &note("No reminder", &[])] {
+            repo.create(n, &key).unwrap();
+        }
+        repo.delete(&trashed.id).unwrap();
+
+        let loaded = repo.get(&overdue.id, &key).unwrap().unwrap();
+        assert_eq!(loaded.reminder_at, overdue.reminder_at);
+        assert_eq!(repo.count_overdue(now).unwrap(), 1);
+        assert_eq!(repo.count_overdue(now + chrono::Duration::hours(3)).unwrap(), 2);
+
+        let mut cleared = loaded;
+        cleared.reminder_at = None;
+        repo.update(&cleared, &key).unwrap();
+        assert_eq!(repo.get(&cleared.id, &key).unwrap().unwrap().reminder_at, None);
+        assert_eq!(repo.count_overdue(now).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_purge_deleted() {
+        let db = Database::in_memory("test_password").unwrap();
+        let key = [7u8; 32];
+        let repo = NoteRepository::new(db.connection());
+        let attachments = AttachmentRepository::new(db.connection());
+
+        let shared = Attachment::new("shared.txt".into(), "text/plain".into(), 1, String::new());
+        let own = Attachment::new("own.txt".into(), "text/plain".into(), 1, String::new());
+        for attachment in [&shared, &own] {
+            attachments.store(&attachment.id, &attachment.filename, "text/plain", 1, b"x", &key).unwrap();
+        }
+
+        let mut kept = note("Kept", &[]);
+        kept.attachments = vec![shared.clone()];
+        let mut trashed = note("Trashed", &[]);
+        trashed.attachments = vec![shared.clone(), own.clone()];
+        let other_trashed = note("Also trashed", &[]);
+        for n in [&kept, &trashed, &other_trashed] {
+            repo.create(n, &key).unwrap();
+        }
+        repo.delete(&trashed.id).unwrap();
+        repo.delete(&other_trashed.id).unwrap();
+        // Notes in the trash still count, they can be restored
+        assert!(repo.references_attachment(&own.id).unwrap());
+
+        assert_eq!(repo.purge_deleted().unwrap(), 2);
+        assert_eq!(repo.count(true).unwrap(), 1);
+        assert!(repo.get(&kept.id, &key).unwrap().is_some());
+        assert_eq!(attachments.ids().unwrap(), vec![shared.id.clone()]);
+        assert!(repo.references_attachment(&shared.id).unwrap());
+        assert!(!repo.references_attachment(&own.id).unwrap());
+        assert_eq!(repo.purge_deleted().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_purge_keeps_deletions_not_yet_synced() {
+        let db = Database::in_memory("test_password").unwrap();
+        let key = [7u8; 32];
+        let repo = NoteRepository::new(db.connection());
+        let sync_repo = SyncRepository::new(db.connection());
+
+        let synced = note("Deleted before the last sync", &[]);
+        let unsynced = note("Deleted since", &[]);
+        repo.create(&synced, &key).unwrap();
+        repo.create(&unsynced, &key).unwrap();
+        repo.delete(&synced.id).unwrap();
+        let metadata = crate::models::sync::SyncMetadata { sync_enabled: true, ..Default::default() };
+        sync_repo.update_metadata(&metadata, &key).unwrap();
+
+        // Never synced, so no deletion has reached the server
+        assert_eq!(repo.purge_deleted().unwrap(), 0);
+
+        sync_repo.update_last_sync(Utc::now() + chrono::Duration::seconds(1)).unwrap();
+        let mut later = repo.get(&unsynced.id, &key).unwrap().unwrap();
+        later.deleted = true;
+        later.modified_at = Utc::now() + chrono::Duration::seconds(2);
+        repo.update(&later, &key).unwrap();
+        assert_eq!(repo.purge_deleted().unwrap(), 1);
+        assert!(repo.get(&synced.id, &key).unwrap().is_none());
+        assert!(repo.get(&unsynced.id, &key).unwrap().is_some());
+
+        sync_repo.set_sync_enabled(false).unwrap();
+        assert_eq!(repo.purge_deleted().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_search_operators() {
+        let db = Database::in_memory("test_password").unwrap();
+        let key = [7u8; 32];
+        let repo = NoteRepository::new(db.connection());
+
+        let groceries = note("Groceries: apples and pears", &["home"]);
+        let meeting = note("Meeting notes about apples", &["work"]);
+        let recipe = note("Pear crumble recipe", &["home", "cooking"]);
+        for n in [&groceries, &meeting, &recipe] {
+            repo.create(n, &key).unwrap();
+        }
+
+        let search = |query: &str| {
+            let mut ids = repo.search(query, &key).unwrap();
+            ids.sort();
+            ids
+        };
+        let ids = |notes: &[&Note]| {
+            let mut ids: Vec<String> = notes.iter().map(|n| n.id.clone()).collect();
+            ids.sort();
+            ids
+        };
+
+        assert_eq!(search("apples"), ids(&[&groceries, &meeting]));
+        assert_eq!(search("APP"), ids(&[&groceries, &meeting]));
+        // Substrings match anywhere in a word, and short ones are found by scanning
+        assert_eq!(search("rumb"), ids(&[&recipe]));
+        assert_eq!(search("#ook"), ids(&[&recipe]));
+        assert_eq!(search("ea"), ids(&[&groceries, &recipe]));
+        assert_eq!(search("ar -mb"), ids(&[&groceries]));
+        assert_eq!(search("apples #home"), ids(&[&groceries]));
+        assert_eq!(search("pear -crumble"), ids(&[&groceries]));
+        assert_eq!(search("-apples"), ids(&[&recipe]));
+        assert_eq!(search("\"quoted\" OR"), Vec::<String>::new());
+
+        // The index follows updates and hard deletes
+        let mut edited = recipe.clone();
+        edited.content = "Apple crumble recipe".to_string();
+        repo.update(&edited, &key).unwrap();
+        assert_eq!(search("apple"), ids(&[&groceries, &meeting, &recipe]));
+
+        repo.hard_delete(&meeting.id).unwrap();
+        assert_eq!(search("apple"), ids(&[&groceries, &recipe]));
+
+        // Scans see the indexed text, tags included, with case intact
+        let mut scanned = repo.scan(|content, tags| content.contains("Apple") && tags.contains(&"cooking".to_string()), &key).unwrap();
+        scanned.sort();
+        assert_eq!(scanned, ids(&[&recipe]));
+    }
 }
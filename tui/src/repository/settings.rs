@@ -1,7 +1,7 @@
 use anyhow::Result;
 use rusqlite::{params, Connection, OptionalExtension};
 
-use crate::models::{SortOrder, Theme, UserSettings};
+use crate::models::{ConflictPolicy, SortOrder, Theme, TrailingNewline, UserSettings};
 
 /// Repository for settings operations
 pub struct SettingsRepository<'a> {
@@ -15,10 +15,13 @@ impl<'a> SettingsRepository<'a> {
     }
 
     /// Get user settings (or default if not exists)
+    ///
+    /// The sync endpoint isn't part of the stored settings: it is kept encrypted in the sync
+    /// metadata, so it comes back unset here.
     pub fn get(&self) -> Result<UserSettings> {
         let result = self.conn
             .query_row(
-                "SELECT language, theme, sort_order, auto_lock_timeout, sync_enabled, sync_endpoint
+                "SELECT language, theme, sort_order, auto_lock_timeout, sync_enabled, trailing_newline, sync_pull_only, conflict_policy, syntax_highlighting, sync_max_attempts, note_list_width, show_preview, emoji_icons, autosave_seconds, pin_limit
                  FROM settings WHERE id = 1",
                 [],
                 |row| {
@@ -28,7 +31,17 @@ impl<'a> SettingsRepository<'a> {
                         sort_order: parse_sort_order(&row.get::<_, String>(2)?),
                         auto_lock_timeout: row.get(3)?,
                         sync_enabled: row.get::<_, i32>(4)? != 0,
-                        sync_endpoint: row.get(5)?,
+                        sync_endpoint: None,
+                        trailing_newline: parse_trailing_newline(&row.get::<_, String>(5)?),
+                        sync_pull_only: row.get::<_, i32>(6)? != 0,
+                        conflict_policy: parse_conflict_policy(&row.get::<_, String>(7)?),
+                        syntax_highlighting: row.get::<_, i32>(8)? != 0,
+                        sync_max_attempts: row.get(9)?,
+                        note_list_width: row.get(10)?,
+                        show_preview: row.get::<_, i32>(11)? != 0,
+                        emoji_icons: row.get::<_, i32>(12)? != 0,
+                        autosave_seconds: row.get(13)?,
+                        pin_limit: row.get(14)?,
                     })
                 },
             )
@@ -37,20 +50,29 @@ impl<'a> SettingsRepository<'a> {
         Ok(result.unwrap_or_else(UserSettings::default))
     }
 
-    /// Update user settings
+    /// Update user settings (all but the sync endpoint, see [`get`](Self::get))
     pub fn update(&self, settings: &UserSettings) -> Result<()> {
         settings.validate().map_err(|e| anyhow::anyhow!("{}", e))?;
 
         self.conn.execute(
-            "INSERT OR REPLACE INTO settings (id, language, theme, sort_order, auto_lock_timeout, sync_enabled, sync_endpoint)
-             VALUES (1, ?1, ?2, ?3, ?4, ?5, ?6)",
+            "INSERT OR REPLACE INTO settings (id, language, theme, sort_order, auto_lock_timeout, sync_enabled, trailing_newline, sync_pull_only, conflict_policy, syntax_highlighting, sync_max_attempts, note_list_width, show_preview, emoji_icons, autosave_seconds, pin_limit)
+             VALUES (1, ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
             params![
                 &settings.language,
                 settings.theme.to_string(),
                 settings.sort_order.to_string(),
                 settings.auto_lock_timeout,
                 settings.sync_enabled as i32,
-                &settings.sync_endpoint,
+                settings.trailing_newline.to_string(),
+                settings.sync_pull_only as i32,
+                settings.conflict_policy.to_string(),
+                settings.syntax_highlighting as i32,
+                settings.sync_max_attempts,
+                settings.note_list_width,
+                settings.show_preview as i32,
+                settings.emoji_icons as i32,
+                settings.autosave_seconds,
+                settings.pin_limit,
             ],
         )?;
 
@@ -73,15 +95,6 @@ impl<'a> SettingsRepository<'a> {
         Ok(())
     }
 
-    /// Set sync endpoint
-    pub fn set_sync_endpoint(&self, endpoint: Option<&str>) -> Result<()> {
-        self.conn.execute(
-            "UPDATE settings SET sync_endpoint = ?1 WHERE id = 1",
-            params![endpoint],
-        )?;
-        Ok(())
-    }
-
     /// Set theme
     pub fn set_theme(&self, theme: Theme) -> Result<()> {
         self.conn.execute(
@@ -132,3 +145,22 @@ fn parse_sort_order(s: &str) -> SortOrder {
         _ => SortOrder::Recent,
     }
 }
+
+/// Parse trailing newline mode string
+fn parse_trailing_newline(s: &str) -> TrailingNewline {
+    match s.to_lowercase().as_str() {
+        "none" => TrailingNewline::None,
+        "verbatim" => TrailingNewline::Verbatim,
+        _ => TrailingNewline::Single,
+    }
+}
+
+/// Parse conflict policy string
+fn parse_conflict_policy(s: &str) -> ConflictPolicy {
+    match s.to_lowercase().as_str() {
+        "local" => ConflictPolicy::LocalWins,
+        "remote" => ConflictPolicy::RemoteWins,
+        "ask" => ConflictPolicy::AskMe,
+        _ => ConflictPolicy::NewestWins,
+    }
+}
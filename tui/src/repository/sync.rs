@@ -1,22 +1,111 @@
-use anyhow::Result;
+use super::error::{RepoError, Result};
+use super::parse_encrypted;
 use chrono::{DateTime, Utc};
 use rusqlite::{params, Connection, OptionalExtension};
 
+use crate::crypto::CryptoService;
 use crate::models::sync::{NoteSyncMetadata, SyncMetadata, SyncStatus};
 
+/// Prefix for a credential stored before the key that will encrypt it exists
+///
+/// Pasting credentials that carry the web app's salt changes the key, so they are kept
+/// marked like this until the next unlock seals them with [`SyncRepository::seal_plaintext`].
+pub const PLAINTEXT_MARKER: &str = "PLAINTEXT:";
+
 /// Repository for sync metadata operations
+///
+/// The client id and endpoint are encrypted with the master key like the API key.
+/// The API key is handed out still encrypted; the others are decrypted on load.
 pub struct SyncRepository<'a> {
     conn: &'a Connection,
+    crypto: CryptoService,
 }
 
 impl<'a> SyncRepository<'a> {
     /// Create a new sync repository
     pub fn new(conn: &'a Connection) -> Self {
-        Self { conn }
+        Self {
+            conn,
+            crypto: CryptoService::new(),
+        }
+    }
+
+    /// Get global sync metadata, decrypting the client id and endpoint
+    pub fn get_metadata(&self, key: &[u8; 32]) -> Result<Option<SyncMetadata>> {
+        let Some(mut metadata) = self.get_stored_metadata()? else {
+            return Ok(None);
+        };
+        metadata.client_id = metadata
+            .client_id
+            .map(|client_id| self.open(&client_id, "sync client id", key))
+            .transpose()?;
+        metadata.sync_endpoint = self.open(&metadata.sync_endpoint, "sync endpoint", key)?;
+        Ok(Some(metadata))
+    }
+
+    /// Update global sync metadata, encrypting the client id and endpoint
+    ///
+    /// `api_key` is written as given and must already be encrypted.
+    pub fn update_metadata(&self, metadata: &SyncMetadata, key: &[u8; 32]) -> Result<()> {
+        let sealed = SyncMetadata {
+            client_id: metadata.client_id.as_deref().map(|id| self.seal(id, key)).transpose()?,
+            sync_endpoint: self.seal(&metadata.sync_endpoint, key)?,
+            ..metadata.clone()
+        };
+        self.write_metadata(&sealed)
+    }
+
+    /// Update global sync metadata while no key is available to encrypt it
+    ///
+    /// The client id and endpoint are stored with [`PLAINTEXT_MARKER`] until the next
+    /// [`seal_plaintext`](Self::seal_plaintext).
+    pub fn update_metadata_unsealed(&self, metadata: &SyncMetadata) -> Result<()> {
+        let mark = |value: &str| {
+            if value.is_empty() || value.starts_with(PLAINTEXT_MARKER) {
+                value.to_string()
+            } else {
+                format!("{}{}", PLAINTEXT_MARKER, value)
+            }
+        };
+        let marked = SyncMetadata {
+            client_id: metadata.client_id.as_deref().map(mark),
+            sync_endpoint: mark(&metadata.sync_endpoint),
+            ..metadata.clone()
+        };
+        self.write_metadata(&marked)
+    }
+
+    /// Encrypt any sync credential still stored in plaintext
+    ///
+    /// Covers values carrying [`PLAINTEXT_MARKER`] and client ids and endpoints written
+    /// before they were encrypted. Returns whether anything was rewritten.
+    pub fn seal_plaintext(&self, key: &[u8; 32]) -> Result<bool> {
+        let Some(mut stored) = self.get_stored_metadata()? else {
+            return Ok(false);
+        };
+        let mut sealed = false;
+
+        if let Some(api_key) = stored.api_key.as_deref().and_then(|k| k.strip_prefix(PLAINTEXT_MARKER)) {
+            stored.api_key = Some(self.seal(api_key, key)?);
+            sealed = true;
+        }
+        if let Some(client_id) = stored.client_id.as_deref().filter(|id| is_plaintext(id)) {
+            stored.client_id = Some(self.seal(client_id.trim_start_matches(PLAINTEXT_MARKER), key)?);
+            sealed = true;
+        }
+        if is_plaintext(&stored.sync_endpoint) {
+            stored.sync_endpoint = self.seal(stored.sync_endpoint.trim_start_matches(PLAINTEXT_MARKER), key)?;
+            sealed = true;
+        }
+
+        if sealed {
+            self.write_metadata(&stored)?;
+        }
+        Ok(sealed)
     }
 
-    /// Get global sync metadata
-    pub fn get_metadata(&self) -> Result<Option<SyncMetadata>> {
+    /// Global sync metadata exactly as stored
+    fn get_stored_metadata(&self) -> Result<Option<SyncMetadata>> {
         let result = self.conn
             .query_row(
                 "SELECT last_sync_at, last_push_at, last_pull_at, api_key, client_id,
@@ -56,8 +145,8 @@ impl<'a> SyncRepository<'a> {
         Ok(result)
     }
 
-    /// Update global sync metadata
-    pub fn update_metadata(&self, metadata: &SyncMetadata) -> Result<()> {
+    /// Write global sync metadata exactly as given
+    fn write_metadata(&self, metadata: &SyncMetadata) -> Result<()> {
         self.conn.execute(
             "INSERT OR REPLACE INTO sync_metadata (
                 id, last_sync_at, last_push_at, last_pull_at, api_key, client_id,
@@ -78,6 +167,31 @@ impl<'a> SyncRepository<'a> {
         Ok(())
     }
 
+    /// Encrypt a credential for storage, leaving an unset one empty
+    fn seal(&self, value: &str, key: &[u8; 32]) -> Result<String> {
+        if value.is_empty() {
+            return Ok(String::new());
+        }
+        let encrypted = self.crypto.encrypt_text(value, key).map_err(RepoError::Encrypt)?;
+        Ok(serde_json::to_string(&encrypted)?)
+    }
+
+    /// Read back a credential written by `seal`, `update_metadata_unsealed` or before encryption
+    fn open(&self, value: &str, what: &str, key: &[u8; 32]) -> Result<String> {
+        if let Some(plaintext) = value.strip_prefix(PLAINTEXT_MARKER) {
+            return Ok(plaintext.to_string());
+        }
+        match parse_encrypted(value) {
+            Ok(encrypted) => self
+                .crypto
+                .decrypt_text(&encrypted, key)
+                .map_err(|source| RepoError::Decrypt { what: what.to_string(), source }),
+            // Stored before these fields were encrypted, sealed on the next unlock
+            Err(_) if is_legacy_plaintext(value) => Ok(value.to_string()),
+            Err(e) => Err(RepoError::corrupt(what, format_args!("{:#}", e))),
+        }
+    }
+
     /// Update specific sync metadata fields
     pub fn update_last_sync(&self, timestamp: DateTime<Utc>) -> Result<()> {
         self.conn.execute(
@@ -87,11 +201,11 @@ impl<'a> SyncRepository<'a> {
         Ok(())
     }
 
-    /// Set API key and client ID
-    pub fn set_credentials(&self, api_key: &str, client_id: &str) -> Result<()> {
+    /// Set the (already encrypted) API key and the client ID
+    pub fn set_credentials(&self, api_key: &str, client_id: &str, key: &[u8; 32]) -> Result<()> {
         self.conn.execute(
             "UPDATE sync_metadata SET api_key = ?1, client_id = ?2 WHERE id = 1",
-            params![api_key, client_id],
+            params![api_key, self.seal(client_id, key)?],
         )?;
         Ok(())
     }
@@ -147,6 +261,55 @@ impl<'a> SyncRepository<'a> {
         Ok(())
     }
 
+    /// Record a note's latest sync outcome, creating its metadata row if needed
+    ///
+    /// `synced_at` is only advanced when the status is `Synced`; any stored server
+    /// version is dropped once the note leaves the `Conflict` status.
+    pub fn mark_note_status(&self, note_id: &str, status: SyncStatus, error_message: Option<&str>) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO note_sync_metadata (
+                note_id, synced_at, sync_hash, server_version, last_sync_status, error_message
+            ) VALUES (?1, ?2, '', 0, ?3, ?4)
+            ON CONFLICT(note_id) DO UPDATE SET
+                synced_at = CASE WHEN excluded.last_sync_status = 'synced' THEN excluded.synced_at ELSE synced_at END,
+                last_sync_status = excluded.last_sync_status,
+                error_message = excluded.error_message,
+                remote_version = CASE WHEN excluded.last_sync_status = 'conflict' THEN remote_version ELSE NULL END",
+            params![note_id, Utc::now().to_rfc3339(), status.to_string(), error_message],
+        )?;
+        Ok(())
+    }
+
+    /// Mark a note as conflicted and keep the server's version for manual resolution
+    pub fn store_conflict(&self, note_id: &str, remote_version: &str, reason: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO note_sync_metadata (
+                note_id, synced_at, sync_hash, server_version, last_sync_status, error_message, remote_version
+            ) VALUES (?1, ?2, '', 0, 'conflict', ?3, ?4)
+            ON CONFLICT(note_id) DO UPDATE SET
+                last_sync_status = 'conflict',
+                error_message = excluded.error_message,
+                remote_version = excluded.remote_version",
+            params![note_id, Utc::now().to_rfc3339(), reason, remote_version],
+        )?;
+        Ok(())
+    }
+
+    /// Unresolved conflicts as (note id, server version, reason)
+    pub fn list_conflicts(&self) -> Result<Vec<(String, String, Option<String>)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT note_id, remote_version, error_message FROM note_sync_metadata
+             WHERE last_sync_status = 'conflict' AND remote_version IS NOT NULL
+             ORDER BY note_id"
+        )?;
+
+        let conflicts = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(conflicts)
+    }
+
     /// Get all pending notes (notes that need to sync)
     pub fn get_pending_notes(&self) -> Result<Vec<String>> {
         let mut stmt = self.conn.prepare(
@@ -178,6 +341,17 @@ impl<'a> SyncRepository<'a> {
     }
 }
 
+/// Whether a stored client id or endpoint still has to be encrypted
+fn is_plaintext(value: &str) -> bool {
+    value.starts_with(PLAINTEXT_MARKER) || (!value.is_empty() && is_legacy_plaintext(value) && parse_encrypted(value).is_err())
+}
+
+/// Whether an unparseable value can be a credential stored before encryption; anything
+/// shaped like JSON is damaged ciphertext instead
+fn is_legacy_plaintext(value: &str) -> bool {
+    !value.starts_with('{')
+}
+
 /// Parse sync status string
 fn parse_sync_status(s: &str) -> SyncStatus {
     match s.to_lowercase().as_str() {
@@ -188,3 +362,116 @@ fn parse_sync_status(s: &str) -> SyncStatus {
         _ => SyncStatus::Pending,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    fn stored(conn: &Connection) -> (Option<String>, Option<String>, String) {
+        conn.query_row(
+            "SELECT api_key, client_id, sync_endpoint FROM sync_metadata WHERE id = 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_client_id_and_endpoint_encrypted_at_rest() {
+        let db = Database::in_memory("test_password").unwrap();
+        let key = [7u8; 32];
+        let repo = SyncRepository::new(db.connection());
+
+        repo.update_metadata(&SyncMetadata {
+            client_id: Some("client-1".to_string()),
+            sync_endpoint: "https://sync.example.com".to_string(),
+            ..SyncMetadata::default()
+        }, &key)
+        .unwrap();
+
+        let (_, client_id, endpoint) = stored(db.connection());
+        assert!(!client_id.unwrap().contains("client-1"));
+        assert!(!endpoint.contains("sync.example.com"));
+
+        let metadata = repo.get_metadata(&key).unwrap().unwrap();
+        assert_eq!(metadata.client_id.as_deref(), Some("client-1"));
+        assert_eq!(metadata.sync_endpoint, "https://sync.example.com");
+
+        let err = repo.get_metadata(&[8u8; 32]).unwrap_err();
+        assert!(matches!(err, RepoError::Decrypt { .. }));
+    }
+
+    #[test]
+    fn test_unset_endpoint_stays_empty() {
+        let db = Database::in_memory("test_password").unwrap();
+        let key = [7u8; 32];
+        let repo = SyncRepository::new(db.connection());
+
+        repo.update_metadata(&SyncMetadata::default(), &key).unwrap();
+
+        assert_eq!(stored(db.connection()), (None, None, String::new()));
+        assert!(!repo.seal_plaintext(&key).unwrap());
+    }
+
+    #[test]
+    fn test_seal_plaintext_encrypts_marked_and_legacy_values() {
+        let db = Database::in_memory("test_password").unwrap();
+        let key = [7u8; 32];
+        let repo = SyncRepository::new(db.connection());
+
+        repo.update_metadata_unsealed(&SyncMetadata {
+            api_key: Some(format!("{}secret", PLAINTEXT_MARKER)),
+            client_id: Some("client-1".to_string()),
+            sync_endpoint: "https://sync.example.com".to_string(),
+            ..SyncMetadata::default()
+        })
+        .unwrap();
+        let (_, client_id, endpoint) = stored(db.connection());
+        assert_eq!(client_id.as_deref(), Some("PLAINTEXT:client-1"));
+        assert_eq!(endpoint, "PLAINTEXT:https://sync.example.com");
+
+        // Readable before the key that will seal them exists
+        let metadata = repo.get_metadata(&[8u8; 32]).unwrap().unwrap();
+        assert_eq!(metadata.client_id.as_deref(), Some("client-1"));
+
+        assert!(repo.seal_plaintext(&key).unwrap());
+        assert!(!repo.seal_plaintext(&key).unwrap());
+
+        let metadata = repo.get_metadata(&key).unwrap().unwrap();
+        assert_eq!(metadata.client_id.as_deref(), Some("client-1"));
+        assert_eq!(metadata.sync_endpoint, "https://sync.example.com");
+        let api_key = parse_encrypted(metadata.api_key.as_deref().unwrap()).unwrap();
+        assert_eq!(CryptoService::new().decrypt_text(&api_key, &key).unwrap(), "secret");
+
+        // Rows written before these fields were encrypted
+        db.connection()
+            .execute("UPDATE sync_metadata SET client_id = 'client-2', sync_endpoint = 'https://old.example.com'", [])
+            .unwrap();
+        assert_eq!(repo.get_metadata(&key).unwrap().unwrap().client_id.as_deref(), Some("client-2"));
+        assert!(repo.seal_plaintext(&key).unwrap());
+        let (_, client_id, endpoint) = stored(db.connection());
+        assert!(!client_id.unwrap().contains("client-2"));
+        assert!(!endpoint.contains("old.example.com"));
+        assert_eq!(repo.get_metadata(&key).unwrap().unwrap().sync_endpoint, "https://old.example.com");
+    }
+
+    #[test]
+    fn test_damaged_ciphertext_is_corrupt_not_plaintext() {
+        let db = Database::in_memory("test_password").unwrap();
+        let key = [7u8; 32];
+        let repo = SyncRepository::new(db.connection());
+
+        repo.update_metadata(&SyncMetadata {
+            sync_endpoint: "https://sync.example.com".to_string(),
+            ..SyncMetadata::default()
+        }, &key)
+        .unwrap();
+        db.connection()
+            .execute("UPDATE sync_metadata SET sync_endpoint = substr(sync_endpoint, 1, 20)", [])
+            .unwrap();
+
+        assert!(matches!(repo.get_metadata(&key), Err(RepoError::Corrupt { .. })));
+        assert!(!repo.seal_plaintext(&key).unwrap());
+    }
+}
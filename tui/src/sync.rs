@@ -0,0 +1,1452 @@
+//! Sync engine
+//! Runs one push/pull cycle against a Jottery sync server
+//! Shared by the interactive TUI and the headless `sync` subcommand
+
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, Utc};
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::debug;
+
+use crate::{
+    crypto::CryptoService,
+    db::Database,
+    integrity,
+    models::{
+        sync::{
+            AttachmentRef, RotateKeyResponse, SyncAttachment, SyncAttachmentsRequest, SyncAttachmentsResponse, SyncMetadata, SyncNote,
+            SyncPullRequest, SyncPullResponse, SyncPushRequest, SyncPushResponse, SyncStatus,
+        },
+        Attachment, ConflictPolicy, Note,
+    },
+    repository::{parse_encrypted, sync::SyncRepository, AttachmentRepository, NoteRepository, SettingsRepository},
+};
+
+/// Environment variable overriding the stored sync endpoint
+pub const ENV_SYNC_ENDPOINT: &str = "JOTTERY_SYNC_ENDPOINT";
+
+/// Environment variable overriding the stored (encrypted) API key
+pub const ENV_SYNC_API_KEY: &str = "JOTTERY_SYNC_API_KEY";
+
+/// Notes requested per pull page
+const PULL_PAGE_SIZE: i64 = 200;
+
+/// Attachment blobs requested per call (the server's cap)
+const ATTACHMENT_BATCH_SIZE: usize = 50;
+
+/// Delay before the first retry; doubled for each further attempt
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// Upper bound on a single retry delay, including a server's `Retry-After`
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Limit on a whole request, so a server that stops answering fails the attempt
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Limit on establishing a connection
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often a running sync checks whether it was cancelled
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Kind of change made by a sync cycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncChangeKind {
+    /// Local note sent to and accepted by the server
+    Pushed,
+    /// Remote note created locally
+    Added,
+    /// Local note overwritten by a newer remote version
+    Updated,
+    /// Local note deleted because the server reported it deleted
+    Deleted,
+    /// Note changed on both sides since the last sync, resolved by last-write-wins
+    Conflict,
+}
+
+impl std::fmt::Display for SyncChangeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Pushed => write!(f, "pushed"),
+            Self::Added => write!(f, "added"),
+            Self::Updated => write!(f, "updated"),
+            Self::Deleted => write!(f, "deleted"),
+            Self::Conflict => write!(f, "conflict"),
+        }
+    }
+}
+
+/// A single note affected by a sync cycle
+#[derive(Debug, Clone)]
+pub struct SyncChange {
+    pub kind: SyncChangeKind,
+    pub note_id: String,
+    pub title: String,
+}
+
+/// Stage a running sync cycle has reached
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SyncPhase {
+    #[default]
+    Idle,
+    Pushing,
+    Pulling,
+    Done,
+}
+
+/// Progress of a running sync cycle: phase plus notes processed out of the phase total
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyncProgress {
+    pub phase: SyncPhase,
+    pub done: usize,
+    pub total: usize,
+}
+
+impl std::fmt::Display for SyncProgress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.phase {
+            SyncPhase::Idle => write!(f, "Syncing…"),
+            SyncPhase::Pushing => write!(f, "Pushing {}/{}…", self.done, self.total),
+            SyncPhase::Pulling if self.total == 0 => write!(f, "Pulling…"),
+            SyncPhase::Pulling => write!(f, "Pulling {}/{}…", self.done, self.total),
+            SyncPhase::Done => write!(f, "Sync finished"),
+        }
+    }
+}
+
+/// Progress value shared between the sync engine and whoever displays it
+pub type SharedSyncProgress = Arc<Mutex<SyncProgress>>;
+
+/// Outcome of a sync cycle
+#[derive(Debug, Clone, Default)]
+pub struct SyncResult {
+    /// Every note affected, in the order they were processed
+    pub changes: Vec<SyncChange>,
+    /// Whether the push phase was skipped (pull-only)
+    pub push_skipped: bool,
+    /// Notes sent to the server, including re-pushed local conflict winners
+    pub push_attempted: usize,
+    /// Notes and deletions received from the server
+    pub pulled: usize,
+}
+
+impl SyncResult {
+    fn record(&mut self, kind: SyncChangeKind, note: &Note) {
+        self.changes.push(SyncChange {
+            kind,
+            note_id: note.id.clone(),
+            title: note.title(),
+        });
+    }
+
+    fn has_conflict(&self, note_id: &str) -> bool {
+        self.changes
+            .iter()
+            .any(|c| c.kind == SyncChangeKind::Conflict && c.note_id == note_id)
+    }
+
+    /// Number of changes of the given kind
+    pub fn count(&self, kind: SyncChangeKind) -> usize {
+        self.changes.iter().filter(|c| c.kind == kind).count()
+    }
+
+    /// One-line summary such as "2 pushed, 1 added, 1 conflict"
+    pub fn summary(&self) -> String {
+        let parts: Vec<String> = [
+            SyncChangeKind::Pushed,
+            SyncChangeKind::Added,
+            SyncChangeKind::Updated,
+            SyncChangeKind::Deleted,
+            SyncChangeKind::Conflict,
+        ]
+        .iter()
+        .filter_map(|kind| match self.count(*kind) {
+            0 => None,
+            1 => Some(format!("1 {}", kind)),
+            n if *kind == SyncChangeKind::Conflict => Some(format!("{} conflicts", n)),
+            n => Some(format!("{} {}", n, kind)),
+        })
+        .collect();
+
+        if parts.is_empty() {
+            "already up to date".to_string()
+        } else {
+            parts.join(", ")
+        }
+    }
+
+    /// Per-phase summary such as "pushed 3/4 • pulled 5 (2 added, 3 updated)"
+    pub fn phase_summary(&self) -> String {
+        let push = if self.push_skipped {
+            "push skipped (pull-only)".to_string()
+        } else if self.push_attempted == 0 {
+            "nothing to push".to_string()
+        } else {
+            format!("pushed {}/{}", self.count(SyncChangeKind::Pushed), self.push_attempted)
+        };
+
+        let pull_parts: Vec<String> = [
+            SyncChangeKind::Added,
+            SyncChangeKind::Updated,
+            SyncChangeKind::Deleted,
+            SyncChangeKind::Conflict,
+        ]
+        .iter()
+        .filter_map(|kind| match self.count(*kind) {
+            0 => None,
+            1 => Some(format!("1 {}", kind)),
+            n if *kind == SyncChangeKind::Conflict => Some(format!("{} conflicts", n)),
+            n => Some(format!("{} {}", n, kind)),
+        })
+        .collect();
+        let pull = match (self.pulled, pull_parts.is_empty()) {
+            (0, _) => "nothing to pull".to_string(),
+            (n, true) => format!("pulled {}", n),
+            (n, false) => format!("pulled {} ({})", n, pull_parts.join(", ")),
+        };
+
+        format!("{} • {}", push, pull)
+    }
+}
+
+/// A note left for manual resolution, with both versions decrypted
+#[derive(Debug, Clone)]
+pub struct SyncConflict {
+    pub local: Note,
+    pub remote: Note,
+    /// Why the note was held back (e.g. the server's rejection reason)
+    pub reason: Option<String>,
+}
+
+/// How the user settled a conflict
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictChoice {
+    /// Keep the local version; it is pushed on the next sync
+    KeepLocal,
+    /// Replace the local version with the server's
+    KeepRemote,
+    /// Take the server's version and save the local one as a new note
+    SaveAsNew,
+}
+
+/// Sync engine bound to an unlocked database
+pub struct SyncEngine<'a> {
+    db: &'a Database,
+    key: &'a [u8; 32],
+    crypto: CryptoService,
+    endpoint_override: Option<String>,
+    api_key_override: Option<String>,
+    pull_only: bool,
+    progress: Option<SharedSyncProgress>,
+    cancel: Option<Arc<AtomicBool>>,
+    /// Cleared once the server turns down a gzip body, so later pushes go uncompressed
+    gzip_push: Cell<bool>,
+}
+
+impl<'a> SyncEngine<'a> {
+    /// Create a new sync engine
+    pub fn new(db: &'a Database, key: &'a [u8; 32]) -> Self {
+        Self {
+            db,
+            key,
+            crypto: CryptoService::new(),
+            endpoint_override: None,
+            api_key_override: None,
+            pull_only: false,
+            progress: None,
+            cancel: None,
+            gzip_push: Cell::new(true),
+        }
+    }
+
+    /// Report phase and per-note progress into `progress` while running
+    pub fn with_progress(mut self, progress: SharedSyncProgress) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Stop at the next request once `cancel` is set, failing with "Sync cancelled"
+    pub fn with_cancel(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Resolve once the sync has been cancelled; never without a cancel flag
+    async fn cancelled(&self) {
+        let Some(cancel) = &self.cancel else {
+            return std::future::pending().await;
+        };
+        while !cancel.load(Ordering::Relaxed) {
+            tokio::time::sleep(CANCEL_POLL_INTERVAL).await;
+        }
+    }
+
+    fn report(&self, phase: SyncPhase, done: usize, total: usize) {
+        if let Some(progress) = &self.progress {
+            if let Ok(mut progress) = progress.lock() {
+                *progress = SyncProgress { phase, done, total };
+            }
+        }
+    }
+
+    /// Use this endpoint instead of the one stored in sync metadata
+    pub fn with_endpoint(mut self, endpoint: String) -> Self {
+        self.endpoint_override = Some(endpoint);
+        self
+    }
+
+    /// Use this plaintext API key instead of the encrypted one stored in sync metadata
+    pub fn with_api_key(mut self, api_key: String) -> Self {
+        self.api_key_override = Some(api_key);
+        self
+    }
+
+    /// Skip the push phase even if the stored setting allows pushing
+    pub fn with_pull_only(mut self, pull_only: bool) -> Self {
+        self.pull_only = pull_only;
+        self
+    }
+
+    /// Apply `JOTTERY_SYNC_ENDPOINT` / `JOTTERY_SYNC_API_KEY` overrides if set
+    pub fn with_env_overrides(mut self) -> Self {
+        if let Ok(endpoint) = std::env::var(ENV_SYNC_ENDPOINT) {
+            if !endpoint.is_empty() {
+                self = self.with_endpoint(endpoint);
+            }
+        }
+        if let Ok(api_key) = std::env::var(ENV_SYNC_API_KEY) {
+            if !api_key.is_empty() {
+                self = self.with_api_key(api_key);
+            }
+        }
+        self
+    }
+
+    /// Perform bidirectional sync with the server, blocking until it finishes
+    ///
+    /// Drives `sync` on a single-threaded runtime; call it off the UI thread.
+    pub fn run(&self) -> Result<SyncResult> {
+        block_on(self.sync())
+    }
+
+    /// Perform bidirectional sync with the server
+    pub async fn sync(&self) -> Result<SyncResult> {
+        // Cancelling drops the exchange at its next await, i.e. between or during requests
+        // Each applied note is sealed as it is written, so one applied before a failure stays covered
+        tokio::select! {
+            result = self.exchange() => result,
+            () = self.cancelled() => Err(anyhow::anyhow!("Sync cancelled")),
+        }
+    }
+
+    /// Push local changes, then pull and apply remote ones
+    async fn exchange(&self) -> Result<SyncResult> {
+        let key = self.key;
+        let sync_repo = SyncRepository::new(self.db.connection());
+        let note_repo = NoteRepository::new(self.db.connection());
+
+        // Get sync metadata
+        let mut metadata = sync_repo.get_metadata(key)?.unwrap_or_default();
+
+        let api_key = self.api_key(&metadata)?;
+        let endpoint = self.endpoint(&metadata)?;
+
+        let client = http_client()?;
+        let mut result = SyncResult::default();
+
+        // Pull-only devices never send local edits
+        let settings = SettingsRepository::new(self.db.connection()).get()?;
+        let pull_only = self.pull_only || settings.sync_pull_only;
+        let max_attempts = settings.sync_max_attempts.max(1) as u32;
+        result.push_skipped = pull_only;
+
+        // PUSH: Send local changes to server
+        let last_sync = metadata.last_sync_at;
+
+        // Notes awaiting manual resolution are held out of the cycle until the user decides
+        let unresolved: HashSet<String> = sync_repo
+            .list_conflicts()?
+            .into_iter()
+            .map(|(note_id, _, _)| note_id)
+            .collect();
+
+        let mut notes_to_push = if pull_only {
+            debug!("Push - Skipped (pull-only)");
+            Vec::new()
+        } else if let Some(last_sync) = last_sync {
+            note_repo.get_modified_after(last_sync, key)?
+        } else {
+            note_repo.list(false, key)?
+        };
+        notes_to_push.retain(|note| !unresolved.contains(&note.id));
+
+        if !notes_to_push.is_empty() {
+            result.push_attempted = notes_to_push.len();
+            let push_response = self.push_notes(&client, &endpoint, &api_key, &notes_to_push, max_attempts).await?;
+
+            for note in &notes_to_push {
+                if push_response.accepted.iter().any(|a| a.id == note.id) {
+                    sync_repo.mark_note_status(&note.id, SyncStatus::Synced, None)?;
+                    result.record(SyncChangeKind::Pushed, note);
+                } else if let Some(rejected) = push_response.rejected.iter().find(|r| r.id == note.id) {
+                    // Server has a newer version; the pull below resolves it
+                    sync_repo.mark_note_status(&note.id, SyncStatus::Conflict, Some(&rejected.reason))?;
+                    result.record(SyncChangeKind::Conflict, note);
+                }
+            }
+
+            // Update last push timestamp
+            metadata.last_push_at = Some(Utc::now());
+        }
+
+        // PULL: Get changes from server, one page at a time
+        self.report(SyncPhase::Pulling, 0, 0);
+        let mut known_note_ids: Vec<String> = note_repo
+            .list(false, key)?
+            .into_iter()
+            .map(|n| n.id)
+            .collect();
+        let local_attachment_ids: HashSet<String> =
+            AttachmentRepository::new(self.db.connection()).ids()?.into_iter().collect();
+
+        let pull_url = format!("{}/api/v1/sync/pull", endpoint);
+        let policy = settings.conflict_policy;
+        let mut local_winners = Vec::new();
+        let mut deletions = Vec::new();
+        let mut cursor = None;
+        let mut processed = 0;
+
+        loop {
+            let pull_request = SyncPullRequest {
+                last_sync_at: last_sync,
+                // Only needed once, not with every page
+                known_note_ids: std::mem::take(&mut known_note_ids),
+                cursor: cursor.take(),
+                limit: Some(PULL_PAGE_SIZE),
+            };
+
+            let response = send_with_retry("Pull", max_attempts, || {
+                client
+                    .post(&pull_url)
+                    .header("Authorization", format!("Bearer {}", api_key))
+                    .json(&pull_request)
+                    .send()
+            })
+            .await?;
+
+            let page: SyncPullResponse = response.json().await
+                .context("Failed to parse pull response")?;
+
+            // Apply remote changes
+            debug!("Pull - Received {} notes from server", page.notes.len());
+
+            // Blobs are fetched separately; older servers still send them inline
+            let fetched = self
+                .fetch_attachments(&client, &endpoint, &api_key, &page, &local_attachment_ids, max_attempts)
+                .await?;
+            let blobs: HashMap<&str, &str> = page
+                .attachments
+                .iter()
+                .chain(fetched.iter())
+                .map(|a| (a.id.as_str(), a.data.as_str()))
+                .collect();
+
+            result.pulled += page.notes.len() + page.deletions.len();
+            for (index, remote_note) in page.notes.iter().enumerate() {
+                debug!("Pull - Processing note: {}", remote_note.id);
+                self.report(SyncPhase::Pulling, processed + index + 1, processed + page.notes.len());
+
+                let (content, tags, attachments) = self.decrypt_remote(remote_note)?;
+
+                if unresolved.contains(&remote_note.id) {
+                    // Keep the newest server version alongside the unresolved local one
+                    self.store_remote_attachments(&attachments, &blobs)?;
+                    sync_repo.store_conflict(
+                        &remote_note.id,
+                        &serde_json::to_string(remote_note)?,
+                        "Changed again on the server while unresolved",
+                    )?;
+                    continue;
+                }
+
+                // Check if we have this note locally
+                if let Some(mut local_note) = note_repo.get(&remote_note.id, key)? {
+                    let rejected = result.has_conflict(&local_note.id);
+                    let conflicted = is_conflict(&local_note, remote_note, last_sync, rejected);
+                    if conflicted && !rejected {
+                        result.record(SyncChangeKind::Conflict, &local_note);
+                    }
+
+                    match resolve(&local_note, remote_note, conflicted, rejected, policy) {
+                        Resolution::TakeRemote => {
+                            self.store_remote_attachments(&attachments, &blobs)?;
+                            apply_remote(&mut local_note, remote_note, content, tags, attachments);
+                            note_repo.update(&local_note, key)?;
+                            integrity::seal_notes(self.db.connection(), key, &[&local_note.id])?;
+                            sync_repo.mark_note_status(&local_note.id, SyncStatus::Synced, None)?;
+                            result.record(SyncChangeKind::Updated, &local_note);
+                        }
+                        Resolution::PushLocal => local_winners.push(local_note),
+                        Resolution::KeepLocal => {}
+                        Resolution::Leave => {
+                            debug!("Pull - Leaving conflict on {} for the user", local_note.id);
+                            self.store_remote_attachments(&attachments, &blobs)?;
+                            let reason = if rejected {
+                                "Push rejected by the server; left for manual resolution"
+                            } else {
+                                "Changed on both sides; left for manual resolution"
+                            };
+                            sync_repo.store_conflict(&local_note.id, &serde_json::to_string(remote_note)?, reason)?;
+                        }
+                    }
+                } else {
+                    // New note from server
+                    let mut new_note = Note::new(String::new());
+                    new_note.id = remote_note.id.clone();
+                    new_note.created_at = remote_note.created_at;
+                    self.store_remote_attachments(&attachments, &blobs)?;
+                    apply_remote(&mut new_note, remote_note, content, tags, attachments);
+
+                    note_repo.create(&new_note, key)?;
+                    integrity::seal_notes(self.db.connection(), key, &[&new_note.id])?;
+                    sync_repo.mark_note_status(&new_note.id, SyncStatus::Synced, None)?;
+                    result.record(SyncChangeKind::Added, &new_note);
+                }
+            }
+            processed += page.notes.len();
+            deletions.extend(page.deletions);
+
+            match page.next_cursor {
+                Some(next) if !next.is_empty() => cursor = Some(next),
+                _ => break,
+            }
+        }
+
+        // Local-wins conflicts are re-pushed as the newest version so the server takes them
+        if !local_winners.is_empty() && !pull_only {
+            for note in &mut local_winners {
+                note.touch();
+                note_repo.update(note, key)?;
+                integrity::seal_notes(self.db.connection(), key, &[&note.id])?;
+            }
+            result.push_attempted += local_winners.len();
+            let push_response = self.push_notes(&client, &endpoint, &api_key, &local_winners, max_attempts).await?;
+            for note in local_winners.iter().filter(|n| push_response.accepted.iter().any(|a| a.id == n.id)) {
+                sync_repo.mark_note_status(&note.id, SyncStatus::Synced, None)?;
+                result.record(SyncChangeKind::Pushed, note);
+            }
+        }
+
+        // Handle deletions
+        for deletion in deletions {
+            if unresolved.contains(&deletion.id) {
+                continue;
+            }
+            if let Some(local_note) = note_repo.get(&deletion.id, key)? {
+                if !local_note.deleted {
+                    note_repo.delete(&deletion.id)?;
+                    integrity::seal_notes(self.db.connection(), key, &[&deletion.id])?;
+                    result.record(SyncChangeKind::Deleted, &local_note);
+                }
+            }
+        }
+
+        // Update sync metadata
+        metadata.last_sync_at = Some(Utc::now());
+        metadata.last_pull_at = Some(Utc::now());
+        sync_repo.update_metadata(&metadata, key)?;
+        self.report(SyncPhase::Done, 0, 0);
+
+        Ok(result)
+    }
+
+    /// Replace this device's API key on the server and store the new one
+    ///
+    /// The server keeps accepting the old key until the new one is first used, so a lost
+    /// response leaves the old key stored and working and the rotation can simply be
+    /// repeated. Once the new key is saved, a status request with it retires the old one.
+    pub fn rotate_api_key(&self) -> Result<()> {
+        block_on(self.rotate())
+    }
+
+    async fn rotate(&self) -> Result<()> {
+        let sync_repo = SyncRepository::new(self.db.connection());
+        let mut metadata = sync_repo.get_metadata(self.key)?.unwrap_or_default();
+        let api_key = self.api_key(&metadata)?;
+        let endpoint = self.endpoint(&metadata)?;
+        let url = format!("{}/api/v1/clients/rotate", endpoint);
+        let settings = SettingsRepository::new(self.db.connection()).get()?;
+        let max_attempts = settings.sync_max_attempts.max(1) as u32;
+
+        let client = http_client()?;
+        let response = send_with_retry("Key rotation", max_attempts, || {
+            client
+                .post(&url)
+                .header("Authorization", format!("Bearer {}", api_key))
+                .send()
+        })
+        .await?;
+        let rotated: RotateKeyResponse = response.json().await
+            .context("Failed to parse key rotation response")?;
+
+        let encrypted = self.crypto.encrypt_text(&rotated.api_key, self.key)?;
+        metadata.api_key = Some(serde_json::to_string(&encrypted)?);
+        sync_repo.update_metadata(&metadata, self.key)?;
+        debug!("Rotated API key for client {}", rotated.client_id);
+
+        // Only the first use of the new key retires the old one; failing that, the next sync does
+        let confirmed = client
+            .get(format!("{}/api/v1/sync/status", endpoint))
+            .header("Authorization", format!("Bearer {}", rotated.api_key))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        if let Err(e) = confirmed {
+            debug!("Key rotation - Could not confirm the new key yet: {}", e);
+        }
+
+        Ok(())
+    }
+
+    /// The API key override, or the key stored (encrypted) in sync metadata
+    fn api_key(&self, metadata: &SyncMetadata) -> Result<String> {
+        if let Some(api_key) = &self.api_key_override {
+            return Ok(api_key.clone());
+        }
+
+        let encrypted_api_key = metadata
+            .api_key
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("No API key configured"))?;
+        let api_key_encrypted = parse_encrypted(encrypted_api_key)
+            .context("sync API key: corrupted ciphertext")?;
+        self.crypto.decrypt_text(&api_key_encrypted, self.key)
+    }
+
+    /// The endpoint override, or the endpoint stored in sync metadata
+    fn endpoint(&self, metadata: &SyncMetadata) -> Result<String> {
+        let endpoint = self
+            .endpoint_override
+            .clone()
+            .unwrap_or_else(|| metadata.sync_endpoint.clone());
+        if endpoint.is_empty() {
+            anyhow::bail!("No sync endpoint configured");
+        }
+        Ok(endpoint)
+    }
+
+    /// Download the blobs of attachments in a pull page that are not stored locally yet
+    async fn fetch_attachments(
+        &self,
+        client: &reqwest::Client,
+        endpoint: &str,
+        api_key: &str,
+        page: &SyncPullResponse,
+        local_ids: &HashSet<String>,
+        max_attempts: u32,
+    ) -> Result<Vec<SyncAttachment>> {
+        let inline: HashSet<&str> = page.attachments.iter().map(|a| a.id.as_str()).collect();
+        let missing: Vec<String> = page
+            .notes
+            .iter()
+            .flat_map(|note| note.attachments.iter())
+            .filter(|a| !local_ids.contains(&a.id) && !inline.contains(a.id.as_str()))
+            .map(|a| a.id.clone())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+
+        let url = format!("{}/api/v1/sync/attachments", endpoint);
+        let mut fetched = Vec::new();
+        for ids in missing.chunks(ATTACHMENT_BATCH_SIZE) {
+            let request = SyncAttachmentsRequest { ids: ids.to_vec() };
+            let response = match send_with_retry("Attachment download", max_attempts, || {
+                client
+                    .post(&url)
+                    .header("Authorization", format!("Bearer {}", api_key))
+                    .json(&request)
+                    .send()
+            })
+            .await
+            {
+                Ok(response) => response,
+                // Older servers have no blob endpoint; keep whatever is stored locally
+                Err(e) if e.downcast_ref::<HttpStatusError>().is_some_and(|e| e.status == reqwest::StatusCode::NOT_FOUND) => {
+                    debug!("Pull - Attachment endpoint unavailable: {}", e);
+                    return Ok(fetched);
+                }
+                Err(e) => return Err(e),
+            };
+            let batch: SyncAttachmentsResponse = response.json().await
+                .context("Failed to parse attachment response")?;
+            fetched.extend(batch.attachments);
+        }
+
+        Ok(fetched)
+    }
+
+    /// Notes left for manual resolution, with the stored server version decrypted
+    pub fn conflicts(&self) -> Result<Vec<SyncConflict>> {
+        let sync_repo = SyncRepository::new(self.db.connection());
+        let note_repo = NoteRepository::new(self.db.connection());
+
+        let mut conflicts = Vec::new();
+        for (note_id, remote_version, reason) in sync_repo.list_conflicts()? {
+            let Some(local) = note_repo.get(&note_id, self.key)? else {
+                continue;
+            };
+            let remote_note: SyncNote = serde_json::from_str(&remote_version)
+                .context("Invalid stored server version")?;
+            let (content, tags, attachments) = self.decrypt_remote(&remote_note)?;
+            let mut remote = local.clone();
+            apply_remote(&mut remote, &remote_note, content, tags, attachments);
+            conflicts.push(SyncConflict { local, remote, reason });
+        }
+
+        Ok(conflicts)
+    }
+
+    /// Settle a conflict; returns the id of the new note for `SaveAsNew`
+    pub fn resolve_conflict(&self, note_id: &str, choice: ConflictChoice) -> Result<Option<String>> {
+        let conflict = self
+            .conflicts()?
+            .into_iter()
+            .find(|c| c.local.id == note_id)
+            .ok_or_else(|| anyhow::anyhow!("No unresolved conflict for note {}", note_id))?;
+        let sync_repo = SyncRepository::new(self.db.connection());
+        let note_repo = NoteRepository::new(self.db.connection());
+
+        let mut new_note_id = None;
+        match choice {
+            ConflictChoice::KeepLocal => {
+                // Newer than the server's copy, so the next sync pushes it
+                let mut local = conflict.local;
+                local.touch();
+                note_repo.update(&local, self.key)?;
+                sync_repo.mark_note_status(note_id, SyncStatus::Pending, None)?;
+            }
+            ConflictChoice::KeepRemote => {
+                note_repo.update(&conflict.remote, self.key)?;
+                sync_repo.mark_note_status(note_id, SyncStatus::Synced, None)?;
+            }
+            ConflictChoice::SaveAsNew => {
+                // Attachments stay with the original note, which now holds the server's version
+                let mut copy = Note::new(conflict.local.content.clone());
+                copy.tags = conflict.local.tags.clone();
+                copy.pinned = conflict.local.pinned;
+                copy.word_wrap = conflict.local.word_wrap;
+                copy.syntax_language = conflict.local.syntax_language;
+                copy.reminder_at = conflict.local.reminder_at;
+                note_repo.create(&copy, self.key)?;
+                note_repo.update(&conflict.remote, self.key)?;
+                sync_repo.mark_note_status(note_id, SyncStatus::Synced, None)?;
+                new_note_id = Some(copy.id);
+            }
+        }
+        let mut written = vec![note_id];
+        written.extend(new_note_id.as_deref());
+        integrity::seal_notes(self.db.connection(), self.key, &written)?;
+
+        Ok(new_note_id)
+    }
+
+    /// Send notes to the server
+    async fn push_notes(
+        &self,
+        client: &reqwest::Client,
+        endpoint: &str,
+        api_key: &str,
+        notes: &[Note],
+        max_attempts: u32,
+    ) -> Result<SyncPushResponse> {
+        let sync_notes = notes
+            .iter()
+            .enumerate()
+            .map(|(index, note)| {
+                self.report(SyncPhase::Pushing, index + 1, notes.len());
+                self.to_sync_note(note)
+            })
+            .collect::<Result<Vec<SyncNote>>>()?;
+
+        let push_request = SyncPushRequest {
+            notes: sync_notes,
+            attachments: self.attachment_blobs(notes)?,
+        };
+
+        let push_url = format!("{}/api/v1/sync/push", endpoint);
+
+        let body = serde_json::to_vec(&push_request)?;
+        let push_url = push_url.as_str();
+        let send_push = |body: Vec<u8>, gzip: bool| {
+            send_with_retry("Push", max_attempts, move || {
+                let request = client
+                    .post(push_url)
+                    .header("Authorization", format!("Bearer {}", api_key))
+                    .header(reqwest::header::CONTENT_TYPE, "application/json");
+                let request = if gzip {
+                    request.header(reqwest::header::CONTENT_ENCODING, "gzip")
+                } else {
+                    request
+                };
+                request.body(body.clone()).send()
+            })
+        };
+
+        let response = if self.gzip_push.get() {
+            match send_push(gzip(&body)?, true).await {
+                // Servers without request decompression fail to parse the body
+                Err(e) if e.downcast_ref::<HttpStatusError>().is_some_and(|e| rejects_encoding(e.status)) => {
+                    debug!("Push - Server rejected gzip body ({}), retrying uncompressed", e);
+                    self.gzip_push.set(false);
+                    send_push(body, false).await?
+                }
+                result => result?,
+            }
+        } else {
+            send_push(body, false).await?
+        };
+
+        response.json().await.context("Failed to parse push response")
+    }
+
+    /// Convert a local note to sync format, encrypting content and tags for transmission
+    fn to_sync_note(&self, note: &Note) -> Result<SyncNote> {
+        let encrypted_content = self.crypto.encrypt_text(&note.content, self.key)?;
+        let content_json = serde_json::to_string(&encrypted_content)?;
+
+        let encrypted_tags = note.tags.iter()
+            .map(|tag| {
+                let encrypted_tag = self.crypto.encrypt_text(tag, self.key)?;
+                Ok(serde_json::to_string(&encrypted_tag)?)
+            })
+            .collect::<Result<Vec<String>>>()?;
+
+        let attachments = note.attachments.iter()
+            .map(|attachment| {
+                let encrypted_filename = self.crypto.encrypt_text(&attachment.filename, self.key)?;
+                Ok(AttachmentRef {
+                    id: attachment.id.clone(),
+                    filename: serde_json::to_string(&encrypted_filename)?,
+                    mime_type: attachment.mime_type.clone(),
+                    size: attachment.size,
+                    data: attachment.id.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(SyncNote {
+            id: note.id.clone(),
+            created_at: note.created_at,
+            modified_at: note.modified_at,
+            content: content_json,
+            tags: encrypted_tags,
+            attachments,
+            pinned: note.pinned,
+            deleted: note.deleted,
+            deleted_at: note.deleted_at,
+            version: note.version,
+            word_wrap: Some(note.word_wrap),
+            syntax_language: Some(note.syntax_language.to_string()),
+            reminder_at: note.reminder_at,
+        })
+    }
+
+    /// Encrypted blobs for every attachment of `notes` that is stored locally
+    ///
+    /// Each blob is the JSON `EncryptedData`, base64-encoded; the server stores it opaquely.
+    fn attachment_blobs(&self, notes: &[Note]) -> Result<Vec<SyncAttachment>> {
+        let attachment_repo = AttachmentRepository::new(self.db.connection());
+        let mut blobs = Vec::new();
+
+        for attachment in notes.iter().flat_map(|note| &note.attachments) {
+            let Some((_, _, _, bytes)) = attachment_repo.get(&attachment.id, self.key)? else {
+                debug!("Push - Attachment {} has no local data", attachment.id);
+                continue;
+            };
+            let encrypted = self.crypto.encrypt_binary(&bytes, self.key)?;
+            blobs.push(SyncAttachment {
+                id: attachment.id.clone(),
+                data: general_purpose::STANDARD.encode(serde_json::to_vec(&encrypted)?),
+            });
+        }
+
+        Ok(blobs)
+    }
+
+    /// Decrypt and store the blobs for a remote note's attachments
+    fn store_remote_attachments(&self, attachments: &[Attachment], blobs: &HashMap<&str, &str>) -> Result<()> {
+        let attachment_repo = AttachmentRepository::new(self.db.connection());
+
+        for attachment in attachments {
+            let Some(blob) = blobs.get(attachment.id.as_str()) else {
+                // Unchanged blobs are not resent; keep the local copy
+                continue;
+            };
+            let json = general_purpose::STANDARD
+                .decode(blob)
+                .context("Invalid attachment encoding from server")?;
+            let encrypted = std::str::from_utf8(&json)
+                .map_err(anyhow::Error::from)
+                .and_then(parse_encrypted)
+                .with_context(|| format!("attachment {}: corrupted ciphertext", attachment.id))?;
+            let bytes = self.crypto.decrypt_binary(&encrypted, self.key)?;
+            attachment_repo.store(
+                &attachment.id,
+                &attachment.filename,
+                &attachment.mime_type,
+                attachment.size,
+                &bytes,
+                self.key,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Decrypt content, tags and attachment filenames of a note received from the server
+    fn decrypt_remote(&self, remote_note: &SyncNote) -> Result<(String, Vec<String>, Vec<Attachment>)> {
+        let encrypted_content = parse_encrypted(&remote_note.content)
+            .with_context(|| format!("note {}: corrupted ciphertext", remote_note.id))?;
+        let content = self.crypto.decrypt_text(&encrypted_content, self.key)?;
+
+        let tags = remote_note.tags.iter()
+            .map(|tag_json| {
+                let encrypted_tag = parse_encrypted(tag_json)
+                    .with_context(|| format!("note {}: corrupted tags ciphertext", remote_note.id))?;
+                self.crypto.decrypt_text(&encrypted_tag, self.key)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let attachments = remote_note.attachments.iter()
+            .map(|remote| {
+                let encrypted_filename = parse_encrypted(&remote.filename)
+                    .with_context(|| format!("attachment {}: corrupted filename ciphertext", remote.id))?;
+                Ok(Attachment {
+                    id: remote.id.clone(),
+                    filename: self.crypto.decrypt_text(&encrypted_filename, self.key)?,
+                    mime_type: remote.mime_type.clone(),
+                    size: remote.size,
+                    data: remote.data.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok((content, tags, attachments))
+    }
+}
+
+/// Drive a sync future to completion on a single-threaded runtime
+fn block_on<T>(future: impl std::future::Future<Output = Result<T>>) -> Result<T> {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("Failed to start sync runtime")?
+        .block_on(future)
+}
+
+/// HTTP client for talking to the sync server, with request and connect timeouts
+fn http_client() -> Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .connect_timeout(CONNECT_TIMEOUT)
+        .build()
+        .context("Failed to create HTTP client")
+}
+
+/// Send a request, retrying connection errors, timeouts, 5xx and 429 with exponential backoff
+///
+/// Other failures (e.g. 401) are returned immediately. The error names the attempt count
+/// when retries were made.
+async fn send_with_retry<F>(
+    action: &str,
+    max_attempts: u32,
+    send: impl Fn() -> F,
+) -> Result<reqwest::Response>
+where
+    F: std::future::Future<Output = reqwest::Result<reqwest::Response>>,
+{
+    let mut attempt = 1;
+    loop {
+        let attempts_note = if attempt > 1 { format!(" after {} attempts", attempt) } else { String::new() };
+
+        let retry_after = match send().await {
+            Ok(response) if response.status().is_success() => return Ok(response),
+            Ok(response) => {
+                let status = response.status();
+                if !is_retryable(status) || attempt >= max_attempts {
+                    let body = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+                    return Err(HttpStatusError { action: format!("{} failed{}", action, attempts_note), status, body }.into());
+                }
+                debug!("{} - {} on attempt {}, retrying", action, status, attempt);
+                response
+                    .headers()
+                    .get(reqwest::header::RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| parse_retry_after(value, Utc::now()))
+            }
+            Err(e) => {
+                if !(e.is_connect() || e.is_timeout()) || attempt >= max_attempts {
+                    return Err(anyhow::Error::new(e)
+                        .context(format!("Failed to send {} request{}", action.to_lowercase(), attempts_note)));
+                }
+                debug!("{} - {} on attempt {}, retrying", action, e, attempt);
+                None
+            }
+        };
+
+        tokio::time::sleep(backoff_delay(attempt, retry_after)).await;
+        attempt += 1;
+    }
+}
+
+/// A request the server answered with a non-success status
+#[derive(Debug)]
+struct HttpStatusError {
+    action: String,
+    status: reqwest::StatusCode,
+    body: String,
+}
+
+impl std::fmt::Display for HttpStatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} - {}", self.action, self.status, self.body)
+    }
+}
+
+impl std::error::Error for HttpStatusError {}
+
+/// Whether a failed compressed request should be resent without compression
+fn rejects_encoding(status: reqwest::StatusCode) -> bool {
+    matches!(
+        status,
+        reqwest::StatusCode::BAD_REQUEST | reqwest::StatusCode::UNSUPPORTED_MEDIA_TYPE
+    )
+}
+
+/// Gzip a request body
+fn gzip(body: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(body)?;
+    Ok(encoder.finish()?)
+}
+
+/// Server errors and rate limiting are worth retrying; other client errors are not
+fn is_retryable(status: reqwest::StatusCode) -> bool {
+    status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+}
+
+/// Delay before retrying after `attempt`: the server's `Retry-After` if given,
+/// otherwise exponential backoff with up to 50% random jitter
+fn backoff_delay(attempt: u32, retry_after: Option<Duration>) -> Duration {
+    if let Some(delay) = retry_after {
+        return delay.min(RETRY_MAX_DELAY);
+    }
+
+    let exponential = RETRY_BASE_DELAY.saturating_mul(1 << (attempt - 1).min(16)).min(RETRY_MAX_DELAY);
+    let jitter = exponential.mul_f64(rand::random::<f64>() * 0.5);
+    (exponential + jitter).min(RETRY_MAX_DELAY)
+}
+
+/// Parse a `Retry-After` header: either delay seconds or an HTTP date
+fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some((date.with_timezone(&Utc) - now).to_std().unwrap_or(Duration::ZERO))
+}
+
+/// What to do with a remote note that also exists locally
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Resolution {
+    /// Overwrite the local note with the remote version
+    TakeRemote,
+    /// Keep the local note (it is the same or newer)
+    KeepLocal,
+    /// Keep the local note and push it to override the server
+    PushLocal,
+    /// Keep both untouched for manual resolution
+    Leave,
+}
+
+/// Whether both sides changed the note since the last sync (or it was rejected on push)
+fn is_conflict(local: &Note, remote: &SyncNote, last_sync: Option<DateTime<Utc>>, rejected: bool) -> bool {
+    local.modified_at != remote.modified_at
+        && (rejected
+            || last_sync.is_none_or(|last_sync| {
+                local.modified_at > last_sync && remote.modified_at > last_sync
+            }))
+}
+
+/// Decide how to apply a remote note; only real conflicts follow the policy
+///
+/// A conflict from a rejected push is always left for the user: the server refused the
+/// local edit, and settling it by timestamp could overwrite that edit unseen.
+fn resolve(local: &Note, remote: &SyncNote, conflicted: bool, rejected: bool, policy: ConflictPolicy) -> Resolution {
+    let newest = if remote.modified_at > local.modified_at {
+        Resolution::TakeRemote
+    } else {
+        Resolution::KeepLocal
+    };
+
+    if !conflicted {
+        return newest;
+    }
+    if rejected {
+        return Resolution::Leave;
+    }
+
+    match policy {
+        ConflictPolicy::NewestWins => newest,
+        ConflictPolicy::RemoteWins => Resolution::TakeRemote,
+        ConflictPolicy::LocalWins => Resolution::PushLocal,
+        ConflictPolicy::AskMe => Resolution::Leave,
+    }
+}
+
+/// Copy remote fields onto a local note
+fn apply_remote(
+    local_note: &mut Note,
+    remote_note: &SyncNote,
+    content: String,
+    tags: Vec<String>,
+    attachments: Vec<Attachment>,
+) {
+    local_note.content = content;
+    local_note.tags = tags;
+    local_note.attachments = attachments;
+    local_note.modified_at = remote_note.modified_at;
+    local_note.pinned = remote_note.pinned;
+    local_note.deleted = remote_note.deleted;
+    local_note.deleted_at = remote_note.deleted_at;
+    local_note.version = remote_note.version;
+    local_note.word_wrap = remote_note.word_wrap.unwrap_or(true);
+    local_note.reminder_at = remote_note.reminder_at;
+    if let Some(lang_str) = &remote_note.syntax_language {
+        local_note.syntax_language = lang_str.parse().unwrap_or_default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sync_result_summary() {
+        let mut result = SyncResult::default();
+        assert_eq!(result.summary(), "already up to date");
+
+        let note = Note::new("Groceries\nmilk".to_string());
+        result.record(SyncChangeKind::Pushed, &note);
+        result.record(SyncChangeKind::Pushed, &note);
+        result.record(SyncChangeKind::Added, &note);
+        result.record(SyncChangeKind::Conflict, &note);
+
+        assert_eq!(result.count(SyncChangeKind::Pushed), 2);
+        assert_eq!(result.count(SyncChangeKind::Conflict), 1);
+        assert_eq!(result.summary(), "2 pushed, 1 added, 1 conflict");
+        assert_eq!(result.changes[0].title, "Groceries");
+
+        result.push_attempted = 3;
+        result.pulled = 2;
+        assert_eq!(result.phase_summary(), "pushed 2/3 • pulled 2 (1 added, 1 conflict)");
+    }
+
+    #[test]
+    fn test_sync_progress_display() {
+        let mut progress = SyncProgress { phase: SyncPhase::Pushing, done: 12, total: 40 };
+        assert_eq!(progress.to_string(), "Pushing 12/40…");
+        progress = SyncProgress { phase: SyncPhase::Pulling, done: 0, total: 0 };
+        assert_eq!(progress.to_string(), "Pulling…");
+    }
+
+    fn remote_copy(note: &Note, modified_at: DateTime<Utc>) -> SyncNote {
+        SyncNote {
+            id: note.id.clone(),
+            created_at: note.created_at,
+            modified_at,
+            content: String::new(),
+            tags: vec![],
+            attachments: vec![],
+            pinned: false,
+            deleted: false,
+            deleted_at: None,
+            version: note.version,
+            word_wrap: None,
+            syntax_language: None,
+            reminder_at: None,
+        }
+    }
+
+    #[test]
+    fn test_conflict_policy_resolution() {
+        let last_sync = Utc::now() - chrono::Duration::hours(2);
+        let mut local = Note::new("local".to_string());
+        local.modified_at = last_sync + chrono::Duration::minutes(30);
+        let newer_remote = remote_copy(&local, last_sync + chrono::Duration::minutes(60));
+
+        // Both edited since the last sync
+        assert!(is_conflict(&local, &newer_remote, Some(last_sync), false));
+        assert_eq!(resolve(&local, &newer_remote, true, false, ConflictPolicy::NewestWins), Resolution::TakeRemote);
+        assert_eq!(resolve(&local, &newer_remote, true, false, ConflictPolicy::RemoteWins), Resolution::TakeRemote);
+        assert_eq!(resolve(&local, &newer_remote, true, false, ConflictPolicy::LocalWins), Resolution::PushLocal);
+        assert_eq!(resolve(&local, &newer_remote, true, false, ConflictPolicy::AskMe), Resolution::Leave);
+
+        // Only the remote changed: every policy takes it
+        local.modified_at = last_sync - chrono::Duration::minutes(30);
+        assert!(!is_conflict(&local, &newer_remote, Some(last_sync), false));
+        for policy in [ConflictPolicy::LocalWins, ConflictPolicy::AskMe] {
+            assert_eq!(resolve(&local, &newer_remote, false, false, policy), Resolution::TakeRemote);
+        }
+
+        // Identical timestamps are never a conflict
+        let same = remote_copy(&local, local.modified_at);
+        assert!(!is_conflict(&local, &same, None, true));
+        assert_eq!(resolve(&local, &same, false, false, ConflictPolicy::RemoteWins), Resolution::KeepLocal);
+
+        // A rejected push is left for the user under every policy, even when the server copy is newer
+        assert!(is_conflict(&local, &newer_remote, Some(last_sync), true));
+        for policy in [ConflictPolicy::NewestWins, ConflictPolicy::RemoteWins, ConflictPolicy::LocalWins, ConflictPolicy::AskMe] {
+            assert_eq!(resolve(&local, &newer_remote, true, true, policy), Resolution::Leave);
+        }
+    }
+
+    #[test]
+    fn test_attachments_roundtrip_through_sync_format() {
+        let crypto = CryptoService::new();
+        let key = crypto.derive_key("pw", &crypto.generate_salt(), 100_000).unwrap();
+        let db = Database::in_memory("pw").unwrap();
+        let engine = SyncEngine::new(&db, &key);
+
+        let bytes: Vec<u8> = (0..=255u8).rev().collect();
+        let mut note = Note::new("with a file".to_string());
+        let attachment = Attachment::new("photo.png".to_string(), "image/png".to_string(), bytes.len() as i64, String::new());
+        AttachmentRepository::new(db.connection())
+            .store(&attachment.id, "photo.png", "image/png", attachment.size, &bytes, &key)
+            .unwrap();
+        note.attachments.push(attachment.clone());
+
+        // Push side: metadata on the note, encrypted blob alongside
+        let sync_note = engine.to_sync_note(&note).unwrap();
+        assert_ne!(sync_note.attachments[0].filename, "photo.png");
+        let blobs = engine.attachment_blobs(std::slice::from_ref(&note)).unwrap();
+        assert_eq!(blobs.len(), 1);
+
+        // Pull side into a fresh database, as if the server echoed both back
+        let db2 = Database::in_memory("pw").unwrap();
+        let engine2 = SyncEngine::new(&db2, &key);
+        let (_, _, attachments) = engine2.decrypt_remote(&sync_note).unwrap();
+        assert_eq!(attachments[0].filename, "photo.png");
+
+        let blob_map: HashMap<&str, &str> = blobs.iter().map(|b| (b.id.as_str(), b.data.as_str())).collect();
+        engine2.store_remote_attachments(&attachments, &blob_map).unwrap();
+        let (filename, mime_type, _, data) = AttachmentRepository::new(db2.connection())
+            .get(&attachment.id, &key)
+            .unwrap()
+            .unwrap();
+        assert_eq!((filename.as_str(), mime_type.as_str()), ("photo.png", "image/png"));
+        assert_eq!(data, bytes);
+    }
+
+    #[test]
+    fn test_retry_classification_and_backoff() {
+        assert!(is_retryable(reqwest::StatusCode::SERVICE_UNAVAILABLE));
+        assert!(is_retryable(reqwest::StatusCode::TOO_MANY_REQUESTS));
+        assert!(!is_retryable(reqwest::StatusCode::UNAUTHORIZED));
+        assert!(!is_retryable(reqwest::StatusCode::BAD_REQUEST));
+
+        let first = backoff_delay(1, None);
+        assert!(first >= RETRY_BASE_DELAY && first <= RETRY_BASE_DELAY.mul_f64(1.5));
+        let third = backoff_delay(3, None);
+        assert!(third >= RETRY_BASE_DELAY * 4);
+        assert_eq!(backoff_delay(30, None), RETRY_MAX_DELAY);
+        assert_eq!(backoff_delay(1, Some(Duration::from_secs(7))), Duration::from_secs(7));
+        assert_eq!(backoff_delay(1, Some(Duration::from_secs(600))), RETRY_MAX_DELAY);
+    }
+
+    #[test]
+    fn test_gzip_body_roundtrip() {
+        use std::io::Read;
+
+        let body = serde_json::to_vec(&vec!["encrypted-content"; 1000]).unwrap();
+        let compressed = gzip(&body).unwrap();
+        assert!(compressed.len() < body.len() / 10);
+
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(compressed.as_slice()).read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, body);
+        assert!(rejects_encoding(reqwest::StatusCode::UNSUPPORTED_MEDIA_TYPE));
+        assert!(!rejects_encoding(reqwest::StatusCode::UNAUTHORIZED));
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        let now = DateTime::parse_from_rfc3339("2015-10-21T07:28:00Z").unwrap().with_timezone(&Utc);
+        assert_eq!(parse_retry_after("120", now), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:30 GMT", now), Some(Duration::from_secs(30)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:27:00 GMT", now), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn test_conflict_persisted_and_resolved_as_new_note() {
+        let crypto = CryptoService::new();
+        let key = crypto.derive_key("pw", &crypto.generate_salt(), 100_000).unwrap();
+        let db = Database::in_memory("pw").unwrap();
+        let engine = SyncEngine::new(&db, &key);
+        let note_repo = NoteRepository::new(db.connection());
+
+        let local = Note::new("local edit".to_string());
+        note_repo.create(&local, &key).unwrap();
+        let mut server_copy = local.clone();
+        server_copy.content = "server edit".to_string();
+        let remote = engine.to_sync_note(&server_copy).unwrap();
+        SyncRepository::new(db.connection())
+            .store_conflict(&local.id, &serde_json::to_string(&remote).unwrap(), "rejected")
+            .unwrap();
+
+        let conflicts = engine.conflicts().unwrap();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].local.content, "local edit");
+        assert_eq!(conflicts[0].remote.content, "server edit");
+        assert_eq!(conflicts[0].reason.as_deref(), Some("rejected"));
+
+        let new_id = engine.resolve_conflict(&local.id, ConflictChoice::SaveAsNew).unwrap().unwrap();
+        assert!(engine.conflicts().unwrap().is_empty());
+        assert_eq!(note_repo.get(&local.id, &key).unwrap().unwrap().content, "server edit");
+        assert_eq!(note_repo.get(&new_id, &key).unwrap().unwrap().content, "local edit");
+    }
+
+    #[test]
+    fn test_failed_sync_seals_only_applied_notes() {
+        use crate::integrity::IntegrityStatus;
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+
+        let crypto = CryptoService::new();
+        let key = [7u8; 32];
+        let db = Database::in_memory("pw").unwrap();
+        // Pull only, so the local note isn't pushed first
+        let engine = SyncEngine::new(&db, &key).with_pull_only(true);
+        let server = TcpListener::bind("127.0.0.1:0").unwrap();
+        SyncRepository::new(db.connection())
+            .update_metadata(&SyncMetadata {
+                api_key: Some(serde_json::to_string(&crypto.encrypt_text("secret", &key).unwrap()).unwrap()),
+                sync_endpoint: format!("http://{}", server.local_addr().unwrap()),
+                ..SyncMetadata::default()
+            }, &key)
+            .unwrap();
+        let local = Note::new("local".to_string());
+        NoteRepository::new(db.connection()).create(&local, &key).unwrap();
+        integrity::seal(db.connection(), &key).unwrap();
+        // Changed outside the app before syncing
+        db.connection()
+            .execute("UPDATE notes SET pinned = 1 WHERE id = ?1", rusqlite::params![&local.id])
+            .unwrap();
+
+        // The first note applies, the second can't be decrypted and fails the sync
+        let good = engine.to_sync_note(&Note::new("from the server".to_string())).unwrap();
+        let mut bad = engine.to_sync_note(&Note::new("corrupted".to_string())).unwrap();
+        bad.content = "not ciphertext".to_string();
+        let body = serde_json::to_string(&SyncPullResponse {
+            notes: vec![good.clone(), bad],
+            deletions: Vec::new(),
+            attachments: Vec::new(),
+            synced_at: Utc::now(),
+            next_cursor: None,
+        })
+        .unwrap();
+
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = server.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                let n = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+        });
+
+        assert!(engine.run().is_err());
+        handle.join().unwrap();
+
+        assert!(NoteRepository::new(db.connection()).get(&good.id, &key).unwrap().is_some());
+        // The failure doesn't approve the outside change...
+        assert!(matches!(
+            integrity::verify(db.connection(), &key).unwrap(),
+            IntegrityStatus::Tampered { note_id: Some(id), .. } if id == local.id
+        ));
+        // ...while the applied note is covered
+        db.connection()
+            .execute("UPDATE notes SET pinned = 0 WHERE id = ?1", rusqlite::params![&local.id])
+            .unwrap();
+        assert_eq!(integrity::verify(db.connection(), &key).unwrap(), IntegrityStatus::Valid);
+    }
+
+    #[test]
+    fn test_rotate_api_key_stores_new_key() {
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+
+        let crypto = CryptoService::new();
+        let key = [7u8; 32];
+        let db = Database::in_memory("pw").unwrap();
+        let server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let sync_repo = SyncRepository::new(db.connection());
+        sync_repo
+            .update_metadata(&SyncMetadata {
+                api_key: Some(serde_json::to_string(&crypto.encrypt_text("old-key", &key).unwrap()).unwrap()),
+                sync_endpoint: format!("http://{}", server.local_addr().unwrap()),
+                ..SyncMetadata::default()
+            }, &key)
+            .unwrap();
+
+        // Answers the rotation, then the status request that confirms the new key
+        let handle = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for body in [r#"{"apiKey":"new-key","clientId":"client"}"#, r#"{}"#] {
+                let (mut stream, _) = server.accept().unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                }
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+                .unwrap();
+                requests.push(String::from_utf8(request).unwrap());
+            }
+            requests
+        });
+
+        SyncEngine::new(&db, &key).rotate_api_key().unwrap();
+
+        let requests = handle.join().unwrap();
+        assert!(requests[0].starts_with("POST /api/v1/clients/rotate "));
+        assert!(requests[0].contains("Bearer old-key"));
+        assert!(requests[1].starts_with("GET /api/v1/sync/status "));
+        assert!(requests[1].contains("Bearer new-key"));
+        let metadata = sync_repo.get_metadata(&key).unwrap().unwrap();
+        let stored = parse_encrypted(metadata.api_key.as_deref().unwrap()).unwrap();
+        assert_eq!(crypto.decrypt_text(&stored, &key).unwrap(), "new-key");
+    }
+}
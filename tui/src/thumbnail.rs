@@ -0,0 +1,68 @@
+//! Small PNG previews of image attachments, stored encrypted next to the blob
+//! Thumbnails are optional: anything that isn't a decodable image simply has none
+
+use image::{ImageFormat, ImageReader};
+use std::io::Cursor;
+use tracing::debug;
+
+/// Longest side of a thumbnail in pixels
+pub const THUMBNAIL_SIZE: u32 = 128;
+
+/// Largest attachment a thumbnail is made from; bigger ones are not decoded, and
+/// `store_stream` holds no more than this in memory to make one
+pub const MAX_SOURCE_BYTES: i64 = 4 * 1024 * 1024;
+
+/// Whether an attachment of this type and size gets a thumbnail attempt
+pub fn wants_thumbnail(mime_type: &str, size: i64) -> bool {
+    mime_type.starts_with("image/") && size <= MAX_SOURCE_BYTES
+}
+
+/// PNG thumbnail of `data` fitting in `THUMBNAIL_SIZE` square, or None if it isn't a
+/// supported image
+pub fn generate(mime_type: &str, data: &[u8]) -> Option<Vec<u8>> {
+    if !wants_thumbnail(mime_type, data.len() as i64) {
+        return None;
+    }
+
+    let decoded = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .ok()
+        .and_then(|reader| reader.decode().ok());
+    let Some(image) = decoded else {
+        debug!("No thumbnail for {} attachment: not a supported image", mime_type);
+        return None;
+    };
+
+    let mut png = Vec::new();
+    image
+        .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .ok()?;
+    Some(png)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, RgbImage};
+
+    /// A `width` x `height` PNG
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        RgbImage::from_pixel(width, height, image::Rgb([200, 40, 90]))
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_generate_downscales_images_only() {
+        let thumbnail = generate("image/png", &png(640, 320)).unwrap();
+        let decoded = image::load_from_memory(&thumbnail).unwrap();
+        assert_eq!(decoded.dimensions(), (THUMBNAIL_SIZE, THUMBNAIL_SIZE / 2));
+
+        assert!(generate("application/pdf", &png(640, 320)).is_none());
+        assert!(generate("image/png", b"not an image").is_none());
+        assert!(!wants_thumbnail("image/jpeg", MAX_SOURCE_BYTES + 1));
+    }
+}
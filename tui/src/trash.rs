@@ -0,0 +1,86 @@
+//! Emptying the trash: permanently removes soft-deleted notes and shrinks the database file
+
+use anyhow::Result;
+use std::fmt;
+use std::path::Path;
+
+use crate::{db::Database, integrity, repository::NoteRepository};
+
+/// Outcome of emptying the trash
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PurgeReport {
+    pub notes: usize,
+    pub bytes_reclaimed: u64,
+}
+
+impl fmt::Display for PurgeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let plural = if self.notes == 1 { "" } else { "s" };
+        write!(f, "Purged {} note{}, reclaimed ", self.notes, plural)?;
+        match self.bytes_reclaimed {
+            b if b < 1024 => write!(f, "{} B", b),
+            b if b < 1024 * 1024 => write!(f, "{:.1} KiB", b as f64 / 1024.0),
+            b => write!(f, "{:.1} MiB", b as f64 / (1024.0 * 1024.0)),
+        }
+    }
+}
+
+/// Purge every soft-deleted note from the database at `path`, reseal the integrity hashes
+/// and vacuum the file (skipped when there was nothing to purge)
+pub fn empty(db: &mut Database, path: &Path, key: &[u8; 32]) -> Result<PurgeReport> {
+    // Measure with the WAL folded in, so the sizes compare like with like
+    db.checkpoint()?;
+    let before = Database::file_size(path)?;
+
+    let conn = db.connection();
+    let notes = integrity::resealing(conn, key, || Ok(NoteRepository::new(conn).purge_deleted()?))?;
+    if notes == 0 {
+        return Ok(PurgeReport { notes, bytes_reclaimed: 0 });
+    }
+    db.vacuum()?;
+
+    let after = Database::file_size(path)?;
+    Ok(PurgeReport {
+        notes,
+        bytes_reclaimed: before.saturating_sub(after),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Note;
+
+    #[test]
+    fn test_empty_reclaims_space() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("trash.db");
+        let mut db = Database::open(&path, "test_password").unwrap();
+        let key = [7u8; 32];
+
+        let repo = NoteRepository::new(db.connection());
+        let kept = Note::new("Kept".to_string());
+        repo.create(&kept, &key).unwrap();
+        for i in 0..50 {
+            let note = Note::new(format!("{} {}", i, "filler text ".repeat(500)));
+            repo.create(&note, &key).unwrap();
+            repo.delete(&note.id).unwrap();
+        }
+        integrity::seal(db.connection(), &key).unwrap();
+
+        let report = empty(&mut db, &path, &key).unwrap();
+        assert_eq!(report.notes, 50);
+        assert!(report.bytes_reclaimed > 100 * 1024, "{}", report);
+        assert_eq!(integrity::verify(db.connection(), &key).unwrap(), integrity::IntegrityStatus::Valid);
+
+        assert_eq!(empty(&mut db, &path, &key).unwrap(), PurgeReport { notes: 0, bytes_reclaimed: 0 });
+    }
+
+    #[test]
+    fn test_report_display() {
+        let report = |notes, bytes_reclaimed| PurgeReport { notes, bytes_reclaimed }.to_string();
+        assert_eq!(report(1, 512), "Purged 1 note, reclaimed 512 B");
+        assert_eq!(report(3, 4608), "Purged 3 notes, reclaimed 4.5 KiB");
+        assert_eq!(report(9, 3 * 1024 * 1024), "Purged 9 notes, reclaimed 3.0 MiB");
+    }
+}
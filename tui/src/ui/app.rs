@@ -5,29 +5,76 @@ use crossterm::{
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{
-    layout::{Alignment, Constraint, Direction, Layout},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem, Paragraph, Wrap},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
     Frame,
 };
 use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, BTreeSet, HashSet},
     env,
     fs::File,
     io::{self, Write},
     path::PathBuf,
     process::Command,
     sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
+use regex::{Regex, RegexBuilder};
 use tempfile::NamedTempFile;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+use zeroize::Zeroizing;
 
 use crate::{
-    crypto::{CryptoService, KeyManager},
+    crypto::{kdf_input, CryptoService, KeyManager, PasswordStrength},
     db::Database,
-    models::{Note, UserSettings, sync::SyncCredentials},
-    repository::{EncryptionRepository, NoteRepository, SettingsRepository, sync::SyncRepository},
+    integrity::{self, IntegrityStatus},
+    rekey, trash, unlock,
+    models::{
+        matches_query, Attachment, Note, NoteMeta, NotePreview, NoteVersion, ReminderState, SortOrder, SyntaxLanguage, UserSettings, MAX_NOTE_LIST_WIDTH, MAX_PIN_LIMIT, MIN_NOTE_LIST_WIDTH,
+        encryption::EncryptionMetadata,
+        sync::{SyncCredentials, SyncStatus, SyncStatusDisplay},
+        time,
+    },
+    repository::{attachment::AttachmentRepository, EncryptionRepository, NoteRepository, RepoError, SettingsRepository, sync::{SyncRepository, PLAINTEXT_MARKER}},
+    sync::{ConflictChoice, SharedSyncProgress, SyncConflict, SyncEngine, SyncProgress, SyncResult},
+    ui::{
+        auto_sync::AutoSyncTimer,
+        editor::{self, EditSnapshot, TextStats, UndoHistory},
+        highlight, markdown,
+        sync_worker::{SyncOutcome, SyncWorker},
+    },
 };
 
+/// Columns the note list grows or shrinks by per `<`/`>` press
+const NOTE_LIST_WIDTH_STEP: u16 = 4;
+
+/// Narrowest the preview pane is squeezed to before the note list takes the full width
+const MIN_PREVIEW_WIDTH: u16 = 20;
+
+/// Remaining time under which the note list shows the auto-lock countdown
+const LOCK_COUNTDOWN_THRESHOLD: Duration = Duration::from_secs(60);
+
+/// How often the overdue reminder count in the status bar is refreshed
+const REMINDER_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Search prefix that switches the query to a regular expression over note content
+const REGEX_SEARCH_PREFIX: &str = "re:";
+
+/// A compiled `re:` pattern (or its error), with the case setting it was built for
+type CompiledSearch = (String, bool, Result<Regex, String>);
+
+/// Notes decrypted per page of the note list
+const NOTE_PAGE_SIZE: usize = 500;
+
+/// How close the selection gets to the last loaded note before the next page is fetched
+const NOTE_PAGE_MARGIN: usize = 50;
+
+/// Insert-mode keystrokes after which the editor autosaves even while typing continues
+const AUTOSAVE_KEYSTROKES: usize = 200;
+
 /// Application state
 pub enum AppState {
     /// Locked - password input screen
@@ -46,6 +93,15 @@ pub enum AppState {
         /// Previous state to return to
         previous: Box<AppState>,
     },
+    /// Change password form
+    ChangePassword {
+        /// Previous state to return to
+        previous: Box<AppState>,
+    },
+    /// Side-by-side view of a sync conflict awaiting resolution
+    Conflict,
+    /// Earlier versions of the selected note, to browse and restore
+    History,
     /// Quit
     Quit,
 }
@@ -60,6 +116,23 @@ pub enum InputMode {
     Tag,
     /// Settings edit mode
     SettingsEdit,
+    /// Language mode (picking the note's syntax language)
+    Language,
+    /// Attachments panel (listing, opening and removing the note's attachments)
+    Attachments,
+    /// Typing the path of a file to attach
+    AttachPath,
+    /// Typing when the note's reminder is due (empty clears it)
+    Reminder,
+}
+
+/// What started a sync, which decides how its result is reported
+#[derive(Clone, Copy)]
+enum SyncTrigger {
+    /// Requested with `y`; always reports
+    Manual,
+    /// Fired by the auto-sync timer; quiet unless something changed
+    Auto,
 }
 
 /// Application
@@ -76,36 +149,160 @@ pub struct App {
     pub is_new_database: bool,
     /// Which password field is active (false = password, true = confirm)
     pub password_confirm_focused: bool,
+    /// Lowest strength score accepted for a new password without confirming
+    min_password_score: u8,
+    /// User pressed Enter once on a password below `min_password_score`
+    weak_password_confirmed: bool,
     /// Note content input buffer
     pub note_input: String,
+    /// Cursor in `note_input` (byte offset, always on a char boundary)
+    note_cursor: usize,
+    /// Undo/redo history for the note being edited
+    undo_history: UndoHistory,
+    /// Whether the current insert session already has an undo entry
+    insert_recorded: bool,
     /// Tag input buffer (when adding tags)
     pub tag_input: String,
+    /// Tab completion in progress: the text typed before cycling, and the chosen suggestion
+    tag_cycle: Option<(String, usize)>,
     /// Current tags for the note being edited
     pub current_tags: Vec<String>,
+    /// Syntax language for the note being edited
+    current_language: SyntaxLanguage,
+    /// Highlighted entry in the language picker (index into `SyntaxLanguage::ALL`)
+    language_choice: usize,
+    /// Whether the note being edited soft-wraps long lines
+    current_word_wrap: bool,
+    /// Reminder for the note being edited
+    current_reminder: Option<chrono::DateTime<chrono::Utc>>,
+    /// Attachments of the note being edited, as stored
+    current_attachments: Vec<Attachment>,
+    /// Reminder input buffer (when setting a reminder)
+    reminder_input: String,
+    /// Highlighted entry in the attachments panel
+    attachment_choice: usize,
+    /// Path input buffer (when attaching a file)
+    attach_input: String,
+    /// Decrypted copies of opened attachments, deleted on lock and exit
+    opened_attachments: Vec<NamedTempFile>,
+    /// Content, tags, language, wrap and reminder as last loaded or saved (for the modified marker)
+    saved_content: String,
+    saved_tags: Vec<String>,
+    saved_language: SyntaxLanguage,
+    saved_word_wrap: bool,
+    saved_reminder: Option<chrono::DateTime<chrono::Utc>>,
+    /// Last insert-mode change not yet saved, for the autosave idle timer
+    last_edit_at: Option<Instant>,
+    /// Insert-mode keystrokes since the editor was last saved
+    edits_since_save: usize,
+    /// Whether the buffer matches what autosave last wrote (shown as "saved" in the title)
+    autosaved: bool,
+    /// Whether this editing session already recorded the note's earlier version in its history
+    history_recorded: bool,
+    /// Change password form buffers: current, new, confirm
+    password_change: [String; 3],
+    /// Focused change password field (index into `password_change`)
+    password_change_field: usize,
+    /// Iterations to re-derive the key with; the form then only asks for the current password
+    pending_iterations: Option<u32>,
+    /// PBKDF2 iterations of the unlocked content key
+    key_iterations: u32,
     /// Search input buffer
     pub search_input: String,
     /// Whether search mode is active
     pub search_active: bool,
+    /// Match case exactly in the current search session
+    search_case_sensitive: bool,
+    /// Last compiled `re:` pattern with its case setting, since the list filters every frame
+    search_regex_cache: RefCell<Option<CompiledSearch>>,
+    /// Tags the note list is filtered by (combined with the search)
+    tag_filter: BTreeSet<String>,
+    /// Match notes with any selected tag instead of all of them
+    tag_filter_any: bool,
+    /// Whether the tag panel is open and has focus
+    tag_panel_open: bool,
+    /// Highlighted row in the tag panel
+    tag_panel_index: usize,
+    /// Multi-select mode: Space marks notes for bulk delete, pin and tag
+    multi_select: bool,
+    /// Ids of the notes marked in multi-select mode (kept across re-sorting)
+    marked_note_ids: HashSet<String>,
+    /// Tag being typed for the marked notes, while prompting
+    bulk_tag_input: Option<String>,
+    /// `X` was pressed once; a second press empties the trash
+    purge_confirm: bool,
+    /// `x` was pressed in the attachments panel; `y` removes the highlighted attachment
+    remove_attachment_confirm: bool,
+    /// First visible row of the note list, kept between frames so the viewport only moves
+    /// when the selection leaves it
+    note_list_offset: Cell<usize>,
+    /// First visible row of the editor, kept between frames so the text only scrolls when
+    /// the cursor leaves the view
+    editor_scroll: Cell<usize>,
+    /// Preview scroll offset and the note it belongs to; another selection starts at the top
+    preview_scroll: Option<(String, u16)>,
+    /// Furthest scroll offset and visible height of the preview as last drawn
+    preview_extent: Cell<(u16, u16)>,
     /// Sync status message
     pub sync_status: Option<String>,
+    /// Result of the most recent sync (for the details popup)
+    last_sync: Option<SyncResult>,
+    /// Whether the sync details popup is open
+    show_sync_details: bool,
+    /// Sync running on a worker thread, which holds the database until it reports back
+    sync_worker: Option<(SyncWorker, SyncTrigger)>,
+    /// Phase and note counts of the running sync, shown in the note list status area
+    sync_progress: SharedSyncProgress,
+    /// Whether soft-deleted notes are listed (with strikethrough)
+    show_deleted: bool,
+    /// Notes whose reminder is due, shown in the note list status bar
+    overdue_reminders: usize,
+    /// When `overdue_reminders` was last counted (None while locked)
+    reminders_checked_at: Option<Instant>,
+    /// Auto-sync interval in minutes (0 = disabled), stored in sync metadata
+    auto_sync_interval: i32,
+    /// Last sync time and conflict count, shown in the settings sync section
+    sync_health: SyncStatusDisplay,
+    /// Notes left for manual resolution, with both versions decrypted
+    conflicts: Vec<SyncConflict>,
+    /// Conflict shown in the conflict view
+    conflict_index: usize,
+    /// Earlier versions of the note shown in the history view, newest first
+    history: Vec<NoteVersion>,
+    /// Version highlighted in the history view
+    history_index: usize,
+    /// Background auto-sync timer (running while unlocked and sync is enabled)
+    auto_sync: Option<AutoSyncTimer>,
     /// Current error message
     pub error: Option<String>,
-    /// Selected settings field (0-5: language, theme, sort_order, auto_lock_timeout, sync_enabled, sync_endpoint)
+    /// Selected settings field (0-15: language, theme, sort_order, auto_lock_timeout, trailing_newline, sync_enabled, sync_endpoint, sync_pull_only, conflict_policy, auto_sync_interval, sync_max_attempts, syntax_highlighting, emoji_icons, pin_limit, key_iterations, autosave_seconds)
     pub selected_setting: usize,
     /// Settings input buffer (for string/number fields)
     pub setting_input: String,
     /// Database path
     db_path: PathBuf,
+    /// Keyfile combined with the password (`--keyfile`)
+    keyfile: Option<PathBuf>,
     /// Database connection (when unlocked)
     db: Option<Database>,
-    /// Master key (when unlocked)
-    key: Option<[u8; 32]>,
+    /// Master key (when unlocked); zeroized when dropped
+    key: Option<Zeroizing<[u8; 32]>>,
     /// Key manager
     key_manager: KeyManager,
     /// Crypto service
     crypto: CryptoService,
-    /// Loaded notes
-    notes: Vec<Note>,
+    /// Titles of the loaded notes: the first pages of the list, or every note once `all_notes_loaded`
+    notes: Vec<NotePreview>,
+    /// The selected note in full, for the preview pane; decrypted when the selection changes
+    preview_note: Option<Note>,
+    /// No notes are left to fetch beyond `notes`
+    all_notes_loaded: bool,
+    /// Last note of the pages loaded so far, where the next page starts
+    last_listed: Option<NoteMeta>,
+    /// Notes in the loaded pages that failed to decrypt, left out of `notes`
+    unreadable_note_ids: Vec<String>,
+    /// Attachments with an image thumbnail, marked in the note list
+    thumbnail_ids: HashSet<String>,
     /// Selected note index
     selected_note: usize,
     /// Currently editing note ID (None = creating new note)
@@ -128,21 +325,84 @@ impl App {
             password_confirm: String::new(),
             is_new_database,
             password_confirm_focused: false,
+            min_password_score: PasswordStrength::min_score_from_env(),
+            weak_password_confirmed: false,
             note_input: String::new(),
+            note_cursor: 0,
+            undo_history: UndoHistory::default(),
+            insert_recorded: false,
             tag_input: String::new(),
+            tag_cycle: None,
             current_tags: Vec::new(),
+            current_language: SyntaxLanguage::Plain,
+            language_choice: 0,
+            attachment_choice: 0,
+            attach_input: String::new(),
+            opened_attachments: Vec::new(),
+            current_word_wrap: true,
+            current_reminder: None,
+            current_attachments: Vec::new(),
+            reminder_input: String::new(),
+            saved_content: String::new(),
+            saved_tags: Vec::new(),
+            saved_language: SyntaxLanguage::Plain,
+            saved_word_wrap: true,
+            saved_reminder: None,
+            last_edit_at: None,
+            edits_since_save: 0,
+            autosaved: false,
+            history_recorded: false,
+            password_change: Default::default(),
+            password_change_field: 0,
+            pending_iterations: None,
+            key_iterations: EncryptionMetadata::DEFAULT_ITERATIONS,
             search_input: String::new(),
             search_active: false,
+            search_case_sensitive: false,
+            search_regex_cache: RefCell::new(None),
+            tag_filter: BTreeSet::new(),
+            tag_filter_any: false,
+            tag_panel_open: false,
+            tag_panel_index: 0,
+            multi_select: false,
+            marked_note_ids: HashSet::new(),
+            bulk_tag_input: None,
+            purge_confirm: false,
+            remove_attachment_confirm: false,
+            note_list_offset: Cell::new(0),
+            editor_scroll: Cell::new(0),
+            preview_scroll: None,
+            preview_extent: Cell::new((0, 0)),
             sync_status: None,
+            last_sync: None,
+            show_sync_details: false,
+            sync_worker: None,
+            sync_progress: SharedSyncProgress::default(),
+            show_deleted: false,
+            overdue_reminders: 0,
+            reminders_checked_at: None,
+            auto_sync_interval: 5,
+            sync_health: SyncStatusDisplay::default(),
+            conflicts: Vec::new(),
+            conflict_index: 0,
+            history: Vec::new(),
+            history_index: 0,
+            auto_sync: None,
             error: None,
             selected_setting: 0,
             setting_input: String::new(),
             db_path,
+            keyfile: None,
             db: None,
             key: None,
             key_manager: KeyManager::new(),
             crypto: CryptoService::new(),
             notes: Vec::new(),
+            preview_note: None,
+            all_notes_loaded: true,
+            last_listed: None,
+            unreadable_note_ids: Vec::new(),
+            thumbnail_ids: HashSet::new(),
             selected_note: 0,
             editing_note_id: None,
             settings: UserSettings::default(),
@@ -150,6 +410,12 @@ impl App {
         })
     }
 
+    /// Combine the password with `keyfile` on unlock; a new database is set up to require it
+    pub fn with_keyfile(mut self, keyfile: Option<PathBuf>) -> Self {
+        self.keyfile = keyfile;
+        self
+    }
+
     /// Write to debug log if enabled
     fn debug_log(&self, message: &str) {
         if let Some(log) = &self.debug_log {
@@ -163,6 +429,29 @@ impl App {
 
     /// Handle key events
     pub fn handle_key(&mut self, key: KeyEvent) -> Result<()> {
+        self.dispatch_key(key)?;
+
+        // Scrolling, searching or re-sorting may need notes beyond the loaded pages
+        if matches!(self.state, AppState::NoteList) {
+            self.load_more_notes()?;
+        }
+        Ok(())
+    }
+
+    /// Pass a key to the handler for the current screen
+    fn dispatch_key(&mut self, key: KeyEvent) -> Result<()> {
+        // Any key press resets the auto-lock timer
+        self.key_manager.register_activity();
+
+        // Browsing works without the database; anything else is turned away until the sync
+        // returns it, except quitting, which cancels the sync rather than waiting on the network
+        if self.is_quit_key(&key) {
+            self.cancel_sync();
+        } else if self.sync_worker.is_some() && !self.is_browse_key(&key) {
+            self.error = Some("Sync in progress; try again when it finishes".to_string());
+            return Ok(());
+        }
+
         // Handle help and settings screens separately to avoid borrow issues
         if let AppState::Help { .. } = &self.state {
             return self.handle_help_key(key);
@@ -170,6 +459,15 @@ impl App {
         if let AppState::Settings { .. } = &self.state {
             return self.handle_settings_key(key);
         }
+        if let AppState::ChangePassword { .. } = &self.state {
+            return self.handle_change_password_key(key);
+        }
+        if let AppState::Conflict = &self.state {
+            return self.handle_conflict_key(key);
+        }
+        if let AppState::History = &self.state {
+            return self.handle_history_key(key);
+        }
 
         match &self.state {
             AppState::Locked => self.handle_locked_key(key)?,
@@ -178,10 +476,46 @@ impl App {
             AppState::Quit => {}
             AppState::Settings { .. } => unreachable!(), // Handled above
             AppState::Help { .. } => unreachable!(), // Handled above
+            AppState::ChangePassword { .. } => unreachable!(), // Handled above
+            AppState::Conflict => unreachable!(), // Handled above
+            AppState::History => unreachable!(), // Handled above
         }
         Ok(())
     }
 
+    /// Keys that only move around in memory, so they need no database
+    fn is_browse_key(&self, key: &KeyEvent) -> bool {
+        // A sync started from settings leaves the user there
+        if matches!(self.state, AppState::Settings { .. }) {
+            return matches!(self.input_mode, InputMode::Normal)
+                && matches!(
+                    key.code,
+                    KeyCode::Up | KeyCode::Down | KeyCode::Esc | KeyCode::Char('j' | 'k' | 'q' | 's')
+                );
+        }
+        if !matches!(self.state, AppState::NoteList) || self.tag_panel_open || self.multi_select {
+            return false;
+        }
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+
+        match key.code {
+            KeyCode::Up | KeyCode::Down | KeyCode::PageUp | KeyCode::PageDown => true,
+            KeyCode::Char('d' | 'u') if control => true,
+            // Searching falls back to a linear scan while the index is unavailable
+            KeyCode::Char(_) | KeyCode::Backspace | KeyCode::Esc if self.search_active => !control,
+            KeyCode::Char('j' | 'k' | '/' | '?') => true,
+            _ => false,
+        }
+    }
+
+    /// Whether `key` quits from the note list
+    fn is_quit_key(&self, key: &KeyEvent) -> bool {
+        matches!(self.state, AppState::NoteList)
+            && !(self.show_sync_details || self.tag_panel_open || self.multi_select || self.search_active)
+            && key.code == KeyCode::Char('q')
+            && key.modifiers.contains(KeyModifiers::CONTROL)
+    }
+
     /// Handle key events in locked state
     fn handle_locked_key(&mut self, key: KeyEvent) -> Result<()> {
         match key.code {
@@ -209,10 +543,25 @@ impl App {
                         self.error = Some("Passwords do not match".to_string());
                         return Ok(());
                     }
+
+                    // Weak passwords need a second Enter to go ahead
+                    let strength = PasswordStrength::estimate(&self.password_input);
+                    if strength.score() < self.min_password_score && !self.weak_password_confirmed {
+                        self.weak_password_confirmed = true;
+                        self.error = Some(format!(
+                            "{} password: it protects all your notes. Press Enter again to use it anyway",
+                            strength.label()
+                        ));
+                        return Ok(());
+                    }
                 }
 
                 if let Err(e) = self.unlock() {
-                    self.error = Some(format!("Failed to unlock: {}", e));
+                    if let Some(RepoError::Decrypt { .. }) = e.downcast_ref::<RepoError>() {
+                        // The key was derived but doesn't open the notes; drop it and ask again
+                        self.lock();
+                    }
+                    self.error = Some(unlock_error_message(&e));
                     self.password_input.clear();
                     self.password_confirm.clear();
                 }
@@ -222,6 +571,7 @@ impl App {
                     self.password_confirm.push(c);
                 } else {
                     self.password_input.push(c);
+                    self.weak_password_confirmed = false;
                 }
             }
             KeyCode::Backspace => {
@@ -229,6 +579,7 @@ impl App {
                     self.password_confirm.pop();
                 } else {
                     self.password_input.pop();
+                    self.weak_password_confirmed = false;
                 }
             }
             _ => {}
@@ -238,14 +589,54 @@ impl App {
 
     /// Handle key events in note list state
     fn handle_note_list_key(&mut self, key: KeyEvent) -> Result<()> {
-        // Clear sync status on any key (except 'y' which sets it)
-        if key.code != KeyCode::Char('y') {
+        // Any key closes the sync details popup
+        if self.show_sync_details {
+            self.show_sync_details = false;
+            return Ok(());
+        }
+
+        // Clear status on any key (except those that set or expand it)
+        if !matches!(
+            key.code,
+            KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Char('D') | KeyCode::Char('c')
+        ) {
             self.sync_status = None;
         }
+        self.error = None;
+
+        // Any other key cancels a pending empty-trash confirmation
+        let confirming_purge = std::mem::take(&mut self.purge_confirm);
+
+        if self.tag_panel_open {
+            self.handle_tag_panel_key(key);
+            return Ok(());
+        }
+
+        if self.multi_select {
+            return self.handle_multi_select_key(key);
+        }
+
+        // The preview scrolls the same way while searching
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let scroll_down = match key.code {
+            KeyCode::Char('d') if ctrl => Some(true),
+            KeyCode::Char('u') if ctrl => Some(false),
+            KeyCode::PageDown => Some(true),
+            KeyCode::PageUp => Some(false),
+            _ => None,
+        };
+        if let Some(down) = scroll_down {
+            self.scroll_preview(down);
+            return Ok(());
+        }
 
         // Handle search mode
         if self.search_active {
             match key.code {
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.search_case_sensitive = !self.search_case_sensitive;
+                    self.selected_note = 0;
+                }
                 KeyCode::Esc => {
                     self.search_active = false;
                     self.search_input.clear();
@@ -253,22 +644,9 @@ impl App {
                 }
                 KeyCode::Enter => {
                     // Exit search and open selected note
-                    if !self.filtered_notes().is_empty() {
-                        let filtered = self.filtered_notes();
-                        if self.selected_note < filtered.len() {
-                            // Clone the data we need before modifying self
-                            let content = filtered[self.selected_note].content.clone();
-                            let tags = filtered[self.selected_note].tags.clone();
-                            let note_id = filtered[self.selected_note].id.clone();
-
-                            self.note_input = content;
-                            self.current_tags = tags;
-                            self.editing_note_id = Some(note_id);
-                            self.state = AppState::NoteView;
-                            self.input_mode = InputMode::Normal;
-                            self.search_input.clear();
-                            self.search_active = false;
-                        }
+                    if self.open_selected_note()? {
+                        self.search_input.clear();
+                        self.search_active = false;
                     }
                 }
                 KeyCode::Char(c) => {
@@ -283,11 +661,13 @@ impl App {
                     let filtered_count = self.filtered_notes().len();
                     if filtered_count > 0 && self.selected_note < filtered_count - 1 {
                         self.selected_note += 1;
+                        self.preview_scroll = None;
                     }
                 }
                 KeyCode::Up => {
                     if self.selected_note > 0 {
                         self.selected_note -= 1;
+                        self.preview_scroll = None;
                     }
                 }
                 _ => {}
@@ -320,61 +700,106 @@ impl App {
                     // Sync notes
                     self.trigger_sync();
                 }
+                KeyCode::Char('D') => {
+                    // Toggle showing deleted notes inline
+                    self.show_deleted = !self.show_deleted;
+                    self.load_notes()?;
+                    self.sync_status = Some(if self.show_deleted {
+                        "Showing deleted notes (r: restore, D: hide)".to_string()
+                    } else {
+                        "Deleted notes hidden".to_string()
+                    });
+                }
+                KeyCode::Char('r') => {
+                    // Restore selected note if it is deleted
+                    self.restore_selected_note()?;
+                }
+                KeyCode::Char('c') => {
+                    // Copy selected note into a new one
+                    self.duplicate_selected_note()?;
+                }
+                KeyCode::Char('X') => {
+                    // Empty the trash, after a second press to confirm
+                    self.empty_trash(confirming_purge)?;
+                }
+                KeyCode::Char('Y') if self.last_sync.as_ref().is_some_and(|r| !r.changes.is_empty()) => {
+                    // Show what the last sync changed
+                    self.show_sync_details = true;
+                }
+                KeyCode::Char('C') => {
+                    self.open_conflicts();
+                }
+                KeyCode::Char('H') => {
+                    if let Err(e) = self.open_history() {
+                        self.error = Some(format!("Failed to load history: {}", e));
+                    }
+                }
                 KeyCode::Char('/') => {
-                    // Enter search mode
+                    // Enter search mode (case-insensitive until toggled)
                     self.search_active = true;
+                    self.search_case_sensitive = false;
                     self.search_input.clear();
                 }
+                KeyCode::Char('f') => {
+                    // Open the tag filter panel
+                    self.tag_panel_open = true;
+                    self.tag_panel_index = 0;
+                }
+                KeyCode::Char('V') => {
+                    // Enter multi-select mode
+                    self.multi_select = true;
+                    self.marked_note_ids.clear();
+                }
                 KeyCode::Char('n') => {
                     // New note
                     self.note_input.clear();
+                    self.note_cursor = 0;
+                    self.undo_history.clear();
+                    self.insert_recorded = false;
                     self.current_tags.clear();
+                    self.current_language = SyntaxLanguage::Plain;
+                    self.current_word_wrap = true;
+                    self.current_reminder = None;
+                    self.current_attachments.clear();
                     self.editing_note_id = None;
+                    self.mark_saved();
                     self.state = AppState::NoteView;
                     self.input_mode = InputMode::Insert;
                 }
                 KeyCode::Char('i') | KeyCode::Enter => {
                     // Edit selected note
-                    let filtered = self.filtered_notes();
-                    if !filtered.is_empty() && self.selected_note < filtered.len() {
-                        // Clone data before modifying self
-                        let content = filtered[self.selected_note].content.clone();
-                        let tags = filtered[self.selected_note].tags.clone();
-                        let note_id = filtered[self.selected_note].id.clone();
-
-                        self.note_input = content;
-                        self.current_tags = tags;
-                        self.editing_note_id = Some(note_id);
-                        self.state = AppState::NoteView;
-                        self.input_mode = InputMode::Normal;
-                    }
+                    self.open_selected_note()?;
                 }
                 KeyCode::Down | KeyCode::Char('j') => {
                     let note_count = self.filtered_notes().len();
                     if note_count > 0 && self.selected_note < note_count - 1 {
                         self.selected_note += 1;
+                        self.preview_scroll = None;
                     }
                 }
                 KeyCode::Up | KeyCode::Char('k') => {
                     if self.selected_note > 0 {
                         self.selected_note -= 1;
+                        self.preview_scroll = None;
                     }
                 }
+                KeyCode::Char('<') => self.resize_note_list(false),
+                KeyCode::Char('>') => self.resize_note_list(true),
+                KeyCode::Char('v') => {
+                    // Show/hide the preview pane
+                    self.settings.show_preview = !self.settings.show_preview;
+                    self.save_layout();
+                }
                 KeyCode::Char('p') => {
-                    // Toggle pin on selected note
+                    // Toggle pin on selected note, unless pinning it would pass the pin limit
                     let filtered = self.filtered_notes();
                     if !filtered.is_empty() && self.selected_note < filtered.len() {
-                        let note_id = filtered[self.selected_note].id.clone();
-                        if let Some(note) = self.notes.iter_mut().find(|n| n.id == note_id) {
-                            note.pinned = !note.pinned;
-
-                            // Save to database
-                            if let (Some(db), Some(key)) = (&self.db, &self.key) {
-                                let repo = NoteRepository::new(db.connection());
-                                if let Err(e) = repo.update(note, key) {
-                                    self.error = Some(format!("Failed to update pin status: {}", e));
-                                }
-                            }
+                        let note_id = filtered[self.selected_note].meta.id.clone();
+                        let over_limit = if filtered[self.selected_note].meta.pinned { None } else { self.pin_limit_error(1) };
+                        if let Some(e) = over_limit {
+                            self.error = Some(e);
+                        } else if let Err(e) = self.toggle_pin(&note_id) {
+                            self.error = Some(format!("Failed to update pin status: {}", e));
                         }
                     }
                 }
@@ -384,7 +809,7 @@ impl App {
                     if !filtered.is_empty() && self.selected_note < filtered.len() {
                         // Find the actual note in the full list
                         let note_to_delete = filtered[self.selected_note];
-                        if let Some(pos) = self.notes.iter().position(|n| n.id == note_to_delete.id) {
+                        if let Some(pos) = self.notes.iter().position(|n| n.meta.id == note_to_delete.meta.id) {
                             self.selected_note = pos;
                             self.delete_note()?;
                             // Adjust selection after delete
@@ -412,18 +837,64 @@ impl App {
             InputMode::Normal => match key.code {
                 KeyCode::Char('i') => {
                     self.input_mode = InputMode::Insert;
+                    self.insert_recorded = false;
                 }
                 KeyCode::Char('e') => {
-                    // Edit with external $EDITOR
+                    // Edit with external $EDITOR (one undo entry for the whole change)
                     if let Ok(content) = self.edit_with_external_editor() {
+                        self.record_undo();
                         self.note_input = content;
+                        self.note_cursor = self.note_input.len();
                     }
                 }
+                KeyCode::Char('a') => {
+                    // Open the attachments panel
+                    self.attachment_choice = 0;
+                    self.input_mode = InputMode::Attachments;
+                }
+                KeyCode::Char('v') => {
+                    // Show in an external viewer; nothing is read back
+                    if let Err(e) = self.view_with_external_viewer() {
+                        self.error = Some(format!("{:#}", e));
+                    }
+                }
+                KeyCode::Char('u') => {
+                    self.undo();
+                }
+                KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.redo();
+                }
+                KeyCode::Char('z') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.undo();
+                }
+                KeyCode::Char('y') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.redo();
+                }
                 KeyCode::Char('t') => {
                     // Enter tag mode
                     self.tag_input.clear();
                     self.input_mode = InputMode::Tag;
                 }
+                KeyCode::Char('w') => {
+                    // Toggle soft wrapping for this note
+                    self.current_word_wrap = !self.current_word_wrap;
+                }
+                KeyCode::Char('R') => {
+                    // Set or clear the reminder, starting from the current one
+                    self.reminder_input = self
+                        .current_reminder
+                        .map(|at| at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+                        .unwrap_or_default();
+                    self.input_mode = InputMode::Reminder;
+                }
+                KeyCode::Char('L') => {
+                    // Open the language picker on the current language
+                    self.language_choice = SyntaxLanguage::ALL
+                        .iter()
+                        .position(|&l| l == self.current_language)
+                        .unwrap_or(0);
+                    self.input_mode = InputMode::Language;
+                }
                 KeyCode::Char('?') => {
                     // Show help
                     let prev = std::mem::replace(&mut self.state, AppState::Quit);
@@ -443,242 +914,969 @@ impl App {
                 KeyCode::Esc => {
                     self.input_mode = InputMode::Normal;
                 }
-                KeyCode::Char(c) => {
-                    self.note_input.push(c);
+                KeyCode::Char('z') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.undo();
                 }
-                KeyCode::Backspace => {
-                    self.note_input.pop();
+                KeyCode::Char('y') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.redo();
                 }
-                KeyCode::Delete => {
-                    // For append-only editor, Delete acts like Backspace
-                    self.note_input.pop();
+                KeyCode::Char(c) => {
+                    self.record_insert_session();
+                    self.note_input.insert(self.note_cursor, c);
+                    self.note_cursor += c.len_utf8();
+                    self.note_edited();
                 }
                 KeyCode::Enter => {
-                    self.note_input.push('\n');
+                    self.record_insert_session();
+                    self.note_input.insert(self.note_cursor, '\n');
+                    self.note_cursor += 1;
+                    self.note_edited();
                 }
-                _ => {}
-            },
-            InputMode::Tag => match key.code {
-                KeyCode::Esc => {
-                    // Exit tag mode
-                    self.tag_input.clear();
-                    self.input_mode = InputMode::Normal;
+                KeyCode::Backspace if self.note_cursor > 0 => {
+                    self.record_insert_session();
+                    let start = editor::prev_boundary(&self.note_input, self.note_cursor);
+                    self.note_input.replace_range(start..self.note_cursor, "");
+                    self.note_cursor = start;
+                    self.note_edited();
                 }
-                KeyCode::Enter => {
-                    // Add tag
-                    let tag = self.tag_input.trim().to_string();
-                    if !tag.is_empty() && !self.current_tags.contains(&tag) {
-                        self.current_tags.push(tag);
-                    }
-                    self.tag_input.clear();
+                KeyCode::Delete if self.note_cursor < self.note_input.len() => {
+                    self.record_insert_session();
+                    let end = editor::next_boundary(&self.note_input, self.note_cursor);
+                    self.note_input.replace_range(self.note_cursor..end, "");
+                    self.note_edited();
                 }
-                KeyCode::Char(c) => {
-                    self.tag_input.push(c);
+                KeyCode::Left => {
+                    self.note_cursor = editor::prev_boundary(&self.note_input, self.note_cursor);
                 }
-                KeyCode::Backspace => {
-                    if self.tag_input.is_empty() && !self.current_tags.is_empty() {
-                        // Remove last tag if input is empty
-                        self.current_tags.pop();
-                    } else {
-                        self.tag_input.pop();
-                    }
+                KeyCode::Right => {
+                    self.note_cursor = editor::next_boundary(&self.note_input, self.note_cursor);
+                }
+                KeyCode::Home => {
+                    self.note_cursor = editor::line_start(&self.note_input, self.note_cursor);
+                }
+                KeyCode::End => {
+                    self.note_cursor = editor::line_end(&self.note_input, self.note_cursor);
+                }
+                KeyCode::Up => {
+                    self.note_cursor = editor::move_vertical(&self.note_input, self.note_cursor, true);
+                }
+                KeyCode::Down => {
+                    self.note_cursor = editor::move_vertical(&self.note_input, self.note_cursor, false);
                 }
                 _ => {}
             },
-        }
-        Ok(())
-    }
-
-    /// Handle key events in help screen
-    fn handle_help_key(&mut self, key: KeyEvent) -> Result<()> {
-        match key.code {
-            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('?') => {
-                // Return to previous state
-                if let AppState::Help { previous } = std::mem::replace(&mut self.state, AppState::Quit) {
-                    self.state = *previous;
+            InputMode::Tag => {
+                // Any key other than Tab accepts the current completion
+                if !matches!(key.code, KeyCode::Tab | KeyCode::BackTab) {
+                    self.tag_cycle = None;
                 }
-            }
-            _ => {}
-        }
-        Ok(())
-    }
-
-    /// Handle key events in settings screen
-    fn handle_settings_key(&mut self, key: KeyEvent) -> Result<()> {
-        match self.input_mode {
-            InputMode::Normal => {
-                // Navigation mode
                 match key.code {
-                    KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('s') => {
-                        // Return to previous state
-                        if let AppState::Settings { previous } = std::mem::replace(&mut self.state, AppState::Quit) {
-                            self.state = *previous;
-                        }
-                    }
-                    KeyCode::Down | KeyCode::Char('j') => {
-                        // Move down through settings fields
-                        if self.selected_setting < 5 {
-                            self.selected_setting += 1;
-                        }
+                    KeyCode::Tab => self.complete_tag(true),
+                    KeyCode::BackTab => self.complete_tag(false),
+                    KeyCode::Esc => {
+                        // Exit tag mode
+                        self.tag_input.clear();
+                        self.input_mode = InputMode::Normal;
                     }
-                    KeyCode::Up | KeyCode::Char('k') => {
-                        // Move up through settings fields
-                        if self.selected_setting > 0 {
-                            self.selected_setting -= 1;
+                    KeyCode::Enter => {
+                        // Add tag
+                        let tag = self.tag_input.trim().to_string();
+                        if !tag.is_empty() && !self.current_tags.contains(&tag) {
+                            self.record_undo();
+                            self.current_tags.push(tag);
                         }
+                        self.tag_input.clear();
                     }
-                    KeyCode::Enter | KeyCode::Char('i') | KeyCode::Char(' ') => {
-                        // Edit selected field
-                        self.start_editing_setting();
-                    }
-                    KeyCode::Char('p') => {
-                        // Paste sync credentials from clipboard
-                        if let Err(e) = self.paste_sync_credentials() {
-                            self.error = Some(format!("Failed to paste credentials: {}", e));
-                        } else {
-                            self.sync_status = Some("Sync credentials pasted successfully!".to_string());
-                        }
+                    KeyCode::Char(c) => {
+                        self.tag_input.push(c);
                     }
-                    KeyCode::Char('c') => {
-                        // Copy sync credentials to clipboard
-                        if let Err(e) = self.copy_sync_credentials() {
-                            self.error = Some(format!("Failed to copy credentials: {}", e));
+                    KeyCode::Backspace => {
+                        if self.tag_input.is_empty() && !self.current_tags.is_empty() {
+                            // Remove last tag if input is empty
+                            self.record_undo();
+                            self.current_tags.pop();
                         } else {
-                            self.sync_status = Some("Sync credentials copied to clipboard!".to_string());
+                            self.tag_input.pop();
                         }
                     }
-                    KeyCode::Char('y') => {
-                        // Trigger manual sync
-                        self.trigger_sync();
-                    }
                     _ => {}
                 }
             }
-            InputMode::SettingsEdit => {
-                // Editing mode
+            InputMode::Language => match key.code {
+                KeyCode::Esc => {
+                    self.input_mode = InputMode::Normal;
+                }
+                KeyCode::Down | KeyCode::Char('j') => {
+                    self.language_choice = (self.language_choice + 1) % SyntaxLanguage::ALL.len();
+                }
+                KeyCode::Up | KeyCode::Char('k') => {
+                    self.language_choice = (self.language_choice + SyntaxLanguage::ALL.len() - 1) % SyntaxLanguage::ALL.len();
+                }
+                KeyCode::Enter => {
+                    self.current_language = SyntaxLanguage::ALL[self.language_choice];
+                    self.input_mode = InputMode::Normal;
+                }
+                _ => {}
+            },
+            InputMode::Attachments => {
+                let count = self.editing_attachments().len();
+                // Any key but `y` keeps the attachment
+                let confirming = std::mem::take(&mut self.remove_attachment_confirm);
                 match key.code {
-                    KeyCode::Esc => {
-                        // Cancel editing
-                        self.setting_input.clear();
-                        self.input_mode = InputMode::Normal;
-                    }
-                    KeyCode::Enter => {
-                        // Save edited value
-                        if let Err(e) = self.save_setting_value() {
-                            self.error = Some(format!("Failed to save setting: {}", e));
+                    KeyCode::Char('y') | KeyCode::Char('Y') if confirming => {
+                        if let Err(e) = self.remove_attachment() {
+                            self.error = Some(format!("Failed to remove attachment: {:#}", e));
                         }
-                        self.setting_input.clear();
+                    }
+                    _ if confirming => {}
+                    KeyCode::Esc | KeyCode::Char('q') => {
                         self.input_mode = InputMode::Normal;
                     }
-                    KeyCode::Char(c) => {
-                        // For boolean and enum fields, handle cycling
-                        match self.selected_setting {
-                            1 => {
-                                // Theme: cycle through Light/Dark/Auto
-                                self.cycle_theme();
-                                self.input_mode = InputMode::Normal;
-                            }
-                            2 => {
-                                // Sort order: cycle through Recent/Oldest/Alpha/Created
-                                self.cycle_sort_order();
-                                self.input_mode = InputMode::Normal;
-                            }
-                            4 => {
-                                // Sync enabled: toggle
-                                self.settings.sync_enabled = !self.settings.sync_enabled;
-                                if let Err(e) = self.save_settings() {
-                                    self.error = Some(format!("Failed to save settings: {}", e));
-                                }
-                                self.input_mode = InputMode::Normal;
-                            }
-                            _ => {
-                                // String/number fields: type normally
-                                self.setting_input.push(c);
-                            }
+                    KeyCode::Down | KeyCode::Char('j') if count > 0 => {
+                        self.attachment_choice = (self.attachment_choice + 1) % count;
+                    }
+                    KeyCode::Up | KeyCode::Char('k') if count > 0 => {
+                        self.attachment_choice = (self.attachment_choice + count - 1) % count;
+                    }
+                    KeyCode::Char('a') => {
+                        self.attach_input.clear();
+                        self.input_mode = InputMode::AttachPath;
+                    }
+                    KeyCode::Char('o') | KeyCode::Enter if count > 0 => {
+                        if let Err(e) = self.open_attachment() {
+                            self.error = Some(format!("Failed to open attachment: {:#}", e));
                         }
                     }
-                    KeyCode::Backspace => {
-                        self.setting_input.pop();
+                    KeyCode::Char('x') if count > 0 => {
+                        self.remove_attachment_confirm = true;
                     }
                     _ => {}
                 }
             }
-            _ => {}
+            InputMode::AttachPath => match key.code {
+                KeyCode::Esc => {
+                    self.attach_input.clear();
+                    self.input_mode = InputMode::Attachments;
+                }
+                KeyCode::Enter => {
+                    let path = std::mem::take(&mut self.attach_input);
+                    if !path.trim().is_empty() {
+                        if let Err(e) = self.attach_file(&path) {
+                            self.error = Some(format!("Failed to attach file: {:#}", e));
+                        }
+                    }
+                    self.input_mode = InputMode::Attachments;
+                }
+                KeyCode::Char(c) => {
+                    self.attach_input.push(c);
+                }
+                KeyCode::Backspace => {
+                    self.attach_input.pop();
+                }
+                _ => {}
+            },
+            InputMode::Reminder => match key.code {
+                KeyCode::Esc => {
+                    self.reminder_input.clear();
+                    self.input_mode = InputMode::Normal;
+                }
+                KeyCode::Enter => {
+                    let input = self.reminder_input.trim();
+                    let reminder = if input.is_empty() {
+                        Ok(None)
+                    } else {
+                        time::parse_reminder(input, chrono::Utc::now()).map(Some)
+                    };
+                    match reminder {
+                        Ok(reminder) => {
+                            self.current_reminder = reminder;
+                            self.reminder_input.clear();
+                            self.input_mode = InputMode::Normal;
+                        }
+                        Err(e) => self.error = Some(e),
+                    }
+                }
+                KeyCode::Char(c) => {
+                    self.reminder_input.push(c);
+                }
+                KeyCode::Backspace => {
+                    self.reminder_input.pop();
+                }
+                _ => {}
+            },
         }
         Ok(())
     }
 
-    /// Unlock the database
-    fn unlock(&mut self) -> Result<()> {
-        // Open database
-        let db = Database::open(&self.db_path, &self.password_input)
-            .context("Failed to open database")?;
+    /// Attachments of the note being edited; none until it has been saved
+    fn editing_attachments(&self) -> &[Attachment] {
+        &self.current_attachments
+    }
+
+    /// Encrypt the file at `path` into the attachment store and add it to the note being edited
+    ///
+    /// A new note is saved first so the attachment has something to belong to. Only the
+    /// attachment list is written; unsaved edits stay in the editor.
+    fn attach_file(&mut self, path: &str) -> Result<()> {
+        let path = path.trim();
+        let path = match path.strip_prefix("~/") {
+            Some(rest) => PathBuf::from(env::var("HOME").context("$HOME is not set")?).join(rest),
+            None => PathBuf::from(path),
+        };
+        let file = File::open(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let size = file.metadata()?.len() as i64;
+        let filename = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| anyhow::anyhow!("Not a file: {}", path.display()))?
+            .to_string();
+
+        if self.editing_note_id.is_none() {
+            self.save_note()?;
+        }
+        let note_id = self
+            .editing_note_id
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Write something before attaching a file"))?;
+        let db = self.db.as_ref().ok_or_else(|| anyhow::anyhow!("Database not available"))?;
+        let key = self.key.as_ref().ok_or_else(|| anyhow::anyhow!("Encryption key not available"))?;
+        let note_repo = NoteRepository::new(db.connection());
+        let mut note = note_repo
+            .get(&note_id, key)?
+            .ok_or_else(|| anyhow::anyhow!("Note no longer exists"))?;
+
+        let mime_type = Attachment::mime_type_for(&filename).to_string();
+        let mut attachment = Attachment::new(filename, mime_type, size, String::new());
+        attachment.data = attachment.id.clone();
+        let attachment_repo = AttachmentRepository::new(db.connection());
+        attachment_repo.store_stream(
+            &attachment.id,
+            &attachment.filename,
+            &attachment.mime_type,
+            size,
+            io::BufReader::new(file),
+            key,
+        )?;
+
+        note.attachments.push(attachment.clone());
+        note.touch();
+        let saved = note_repo
+            .update(&note, key)
+            .map_err(anyhow::Error::from)
+            .and_then(|_| integrity::seal_notes(db.connection(), key, &[&note.id]));
+        if let Err(e) = saved {
+            attachment_repo.delete(&attachment.id)?;
+            return Err(e);
+        }
+
+        self.thumbnail_ids = attachment_repo.thumbnail_ids()?.into_iter().collect();
+        self.attachment_choice = note.attachments.len() - 1;
+        self.current_attachments = note.attachments.clone();
+        self.update_listed(&note);
+        self.sync_status = Some(format!("Attached {}", attachment.filename));
+        Ok(())
+    }
+
+    /// Decrypt the highlighted attachment to a temporary file and open it with the desktop's handler
+    fn open_attachment(&mut self) -> Result<()> {
+        let attachment = self
+            .editing_attachments()
+            .get(self.attachment_choice)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("No attachment selected"))?;
+        let db = self.db.as_ref().ok_or_else(|| anyhow::anyhow!("Database not available"))?;
+        let key = self.key.as_ref().ok_or_else(|| anyhow::anyhow!("Encryption key not available"))?;
+
+        // Keep the original name at the end so the handler recognises the file type
+        let name = std::path::Path::new(&attachment.filename)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut temp_file = tempfile::Builder::new()
+            .prefix("jottery-")
+            .suffix(&format!("-{}", name))
+            .tempfile()
+            .context("Failed to create temporary file")?;
+        let found = AttachmentRepository::new(db.connection()).read_stream(&attachment.id, key, temp_file.as_file_mut())?;
+        if found.is_none() {
+            anyhow::bail!("{} has not been downloaded to this device", attachment.filename);
+        }
+
+        let opener = if cfg!(target_os = "macos") { "open" } else { "xdg-open" };
+        let launched = Command::new(opener)
+            .arg(temp_file.path())
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn();
+        match launched {
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => anyhow::bail!("{} not found", opener),
+            Err(e) => return Err(e).context(format!("Failed to launch {}", opener)),
+        }
+
+        // The handler reads the file after we return, so it lives until the vault locks
+        self.opened_attachments.push(temp_file);
+        Ok(())
+    }
+
+    /// Remove the highlighted attachment from the note, dropping its data once no note refers to it
+    fn remove_attachment(&mut self) -> Result<()> {
+        let note_id = self
+            .editing_note_id
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("No attachment selected"))?;
+        let db = self.db.as_ref().ok_or_else(|| anyhow::anyhow!("Database not available"))?;
+        let key = self.key.as_ref().ok_or_else(|| anyhow::anyhow!("Encryption key not available"))?;
+        let note_repo = NoteRepository::new(db.connection());
+        let mut note = note_repo
+            .get(&note_id, key)?
+            .ok_or_else(|| anyhow::anyhow!("Note no longer exists"))?;
+        if self.attachment_choice >= note.attachments.len() {
+            anyhow::bail!("No attachment selected");
+        }
+
+        let removed = note.attachments.remove(self.attachment_choice);
+        note.touch();
+        note_repo.update(&note, key)?;
+        integrity::seal_notes(db.connection(), key, &[&note.id])?;
+
+        // Another note may share the data, e.g. after merging duplicates on import
+        if !note_repo.references_attachment(&removed.id)? {
+            AttachmentRepository::new(db.connection()).delete(&removed.id)?;
+            self.thumbnail_ids.remove(&removed.id);
+        }
+
+        self.attachment_choice = self.attachment_choice.min(note.attachments.len().saturating_sub(1));
+        self.current_attachments = note.attachments.clone();
+        self.update_listed(&note);
+        self.sync_status = Some(format!("Removed {}", removed.filename));
+        Ok(())
+    }
+
+    /// Current editor state, for the undo history
+    fn edit_snapshot(&self) -> EditSnapshot {
+        EditSnapshot {
+            content: self.note_input.clone(),
+            cursor: self.note_cursor,
+            tags: self.current_tags.clone(),
+        }
+    }
+
+    /// Record the current state before an edit
+    fn record_undo(&mut self) {
+        let snapshot = self.edit_snapshot();
+        self.undo_history.record(snapshot);
+    }
+
+    /// Record once per insert session, before its first change
+    fn record_insert_session(&mut self) {
+        if !self.insert_recorded {
+            self.record_undo();
+            self.insert_recorded = true;
+        }
+    }
+
+    /// Restart the autosave idle timer after an insert-mode change; long bursts save every
+    /// `AUTOSAVE_KEYSTROKES` so fast typing is still written without saving per key
+    fn note_edited(&mut self) {
+        self.last_edit_at = Some(Instant::now());
+        self.autosaved = false;
+        self.edits_since_save += 1;
+        if self.settings.autosave_seconds > 0 && self.edits_since_save >= AUTOSAVE_KEYSTROKES {
+            self.autosave();
+        }
+    }
+
+    /// Write the editor buffer without leaving the editor
+    fn autosave(&mut self) {
+        if !self.is_dirty() {
+            self.last_edit_at = None;
+            self.edits_since_save = 0;
+            return;
+        }
+        match self.save_note() {
+            Ok(()) => {
+                self.autosaved = true;
+                self.debug_log("autosave - Saved note");
+            }
+            Err(e) => {
+                // Retry after the next idle period rather than on every tick
+                self.last_edit_at = None;
+                self.edits_since_save = 0;
+                self.error = Some(format!("Autosave failed: {}", e));
+            }
+        }
+    }
+
+    /// Restore an editor snapshot
+    fn apply_snapshot(&mut self, snapshot: EditSnapshot) {
+        self.note_input = snapshot.content;
+        self.note_cursor = snapshot.cursor.min(self.note_input.len());
+        self.current_tags = snapshot.tags;
+        // Typing after an undo/redo starts a new undo entry
+        self.insert_recorded = false;
+    }
+
+    /// Undo the last edit to the note
+    fn undo(&mut self) {
+        let current = self.edit_snapshot();
+        if let Some(snapshot) = self.undo_history.undo(current) {
+            self.apply_snapshot(snapshot);
+        }
+    }
+
+    /// Redo the last undone edit
+    fn redo(&mut self) {
+        let current = self.edit_snapshot();
+        if let Some(snapshot) = self.undo_history.redo(current) {
+            self.apply_snapshot(snapshot);
+        }
+    }
+
+    /// Handle key events in the change password form
+    fn handle_change_password_key(&mut self, key: KeyEvent) -> Result<()> {
+        let fields = if self.pending_iterations.is_some() { 1 } else { 3 };
+        match key.code {
+            KeyCode::Esc => {
+                self.close_change_password();
+            }
+            KeyCode::Tab | KeyCode::Down => {
+                self.password_change_field = (self.password_change_field + 1) % fields;
+            }
+            KeyCode::BackTab | KeyCode::Up => {
+                self.password_change_field = (self.password_change_field + fields - 1) % fields;
+            }
+            KeyCode::Enter if self.password_change_field < fields - 1 => {
+                self.password_change_field += 1;
+            }
+            KeyCode::Enter => {
+                self.error = None;
+                let result = match self.pending_iterations {
+                    Some(iterations) => self
+                        .increase_iterations(iterations)
+                        .map(|count| format!("Key iterations raised to {} ({} notes re-encrypted)", iterations, count)),
+                    None => self
+                        .change_password()
+                        .map(|count| format!("Password changed ({} notes re-encrypted)", count)),
+                };
+                match result {
+                    Ok(status) => {
+                        self.close_change_password();
+                        self.sync_status = Some(status);
+                    }
+                    Err(e) => {
+                        let action = if self.pending_iterations.is_some() { "raise key iterations" } else { "change password" };
+                        self.error = Some(format!("Failed to {}: {}", action, e));
+                        self.password_change = Default::default();
+                        self.password_change_field = 0;
+                    }
+                }
+            }
+            KeyCode::Char(c) => {
+                self.password_change[self.password_change_field].push(c);
+            }
+            KeyCode::Backspace => {
+                self.password_change[self.password_change_field].pop();
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Handle key events in the conflict view
+    fn handle_conflict_key(&mut self, key: KeyEvent) -> Result<()> {
+        let choice = match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.state = AppState::NoteList;
+                return Ok(());
+            }
+            KeyCode::Tab | KeyCode::Char('j') | KeyCode::Down => {
+                self.conflict_index = (self.conflict_index + 1) % self.conflicts.len().max(1);
+                return Ok(());
+            }
+            KeyCode::Char('l') => ConflictChoice::KeepLocal,
+            KeyCode::Char('r') => ConflictChoice::KeepRemote,
+            KeyCode::Char('n') => ConflictChoice::SaveAsNew,
+            _ => return Ok(()),
+        };
+
+        self.error = None;
+        if let Err(e) = self.resolve_conflict(choice) {
+            self.error = Some(format!("Failed to resolve conflict: {}", e));
+        }
+        Ok(())
+    }
+
+    /// Open the conflict view, starting at the selected note if it is conflicted
+    fn open_conflicts(&mut self) {
+        if self.conflicts.is_empty() {
+            self.sync_status = Some("No unresolved sync conflicts".to_string());
+            return;
+        }
+
+        let selected_id = self.filtered_notes().get(self.selected_note).map(|n| n.meta.id.clone());
+        self.conflict_index = selected_id
+            .and_then(|id| self.conflicts.iter().position(|c| c.local.id == id))
+            .unwrap_or(0);
+        self.state = AppState::Conflict;
+    }
+
+    /// Settle the conflict on screen and move on to the next one, if any
+    fn resolve_conflict(&mut self, choice: ConflictChoice) -> Result<()> {
+        let note_id = self
+            .conflicts
+            .get(self.conflict_index)
+            .map(|c| c.local.id.clone())
+            .ok_or_else(|| anyhow::anyhow!("No conflict selected"))?;
+        {
+            let db = self.db.as_ref().ok_or_else(|| anyhow::anyhow!("Database not available"))?;
+            let key = self.key.as_ref().ok_or_else(|| anyhow::anyhow!("Encryption key not available"))?;
+            SyncEngine::new(db, key).resolve_conflict(&note_id, choice)?;
+        }
+
+        self.load_notes()?;
+        if let (Some(db), Some(key)) = (&self.db, &self.key) {
+            NoteRepository::new(db.connection()).rebuild_search_index(key)?;
+        }
+        self.refresh_sync_health()?;
+
+        if self.conflicts.is_empty() {
+            self.state = AppState::NoteList;
+            self.sync_status = Some("All sync conflicts resolved".to_string());
+        } else {
+            self.conflict_index = self.conflict_index.min(self.conflicts.len() - 1);
+        }
+        Ok(())
+    }
+
+    /// Keys in the history view: move between versions, restore one, or go back
+    fn handle_history_key(&mut self, key: KeyEvent) -> Result<()> {
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.history.clear();
+                self.state = AppState::NoteList;
+            }
+            KeyCode::Char('j') | KeyCode::Down if self.history_index + 1 < self.history.len() => {
+                self.history_index += 1;
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                self.history_index = self.history_index.saturating_sub(1);
+            }
+            KeyCode::Char('r') | KeyCode::Enter => {
+                self.error = None;
+                if let Err(e) = self.restore_version() {
+                    self.error = Some(format!("Failed to restore version: {}", e));
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Open the history view for the selected note
+    fn open_history(&mut self) -> Result<()> {
+        let Some(note_id) = self.filtered_notes().get(self.selected_note).map(|n| n.meta.id.clone()) else {
+            return Ok(());
+        };
+        let db = self.db.as_ref().ok_or_else(|| anyhow::anyhow!("Database not available"))?;
+        let key = self.key.as_ref().ok_or_else(|| anyhow::anyhow!("Encryption key not available"))?;
+        let history = NoteRepository::new(db.connection()).history(&note_id, key)?;
+
+        if history.is_empty() {
+            self.sync_status = Some("No earlier versions of this note".to_string());
+            return Ok(());
+        }
+        self.history = history;
+        self.history_index = 0;
+        self.state = AppState::History;
+        Ok(())
+    }
+
+    /// Restore the highlighted version (the replaced content goes into history) and go back
+    fn restore_version(&mut self) -> Result<()> {
+        let version = self
+            .history
+            .get(self.history_index)
+            .ok_or_else(|| anyhow::anyhow!("No version selected"))?;
+        {
+            let db = self.db.as_ref().ok_or_else(|| anyhow::anyhow!("Database not available"))?;
+            let key = self.key.as_ref().ok_or_else(|| anyhow::anyhow!("Encryption key not available"))?;
+            NoteRepository::new(db.connection()).restore_version(&version.note_id, version.id, key)?;
+            integrity::seal_notes(db.connection(), key, &[&version.note_id])?;
+        }
+
+        self.sync_status = Some(format!("Restored version {} of \"{}\"", version.version, version.title()));
+        self.history.clear();
+        self.load_notes()?;
+        self.state = AppState::NoteList;
+        Ok(())
+    }
+
+    /// Clear the change password form and return to the previous screen
+    fn close_change_password(&mut self) {
+        self.password_change = Default::default();
+        self.password_change_field = 0;
+        self.pending_iterations = None;
+        if let AppState::ChangePassword { previous } = std::mem::replace(&mut self.state, AppState::Quit) {
+            self.state = *previous;
+        }
+    }
+
+    /// Verify the current password, then re-encrypt everything and rekey the database
+    ///
+    /// Returns the number of re-encrypted notes.
+    fn change_password(&mut self) -> Result<usize> {
+        let [current, new, confirm] = &self.password_change;
+        if new.is_empty() {
+            anyhow::bail!("New password cannot be empty");
+        }
+        if new != confirm {
+            anyhow::bail!("New passwords do not match");
+        }
+
+        self.verify_current_password(current)?;
+
+        let db = self.db.as_ref().ok_or_else(|| anyhow::anyhow!("Database not unlocked"))?;
+        let key = self.key.clone().ok_or_else(|| anyhow::anyhow!("Encryption key not available"))?;
+
+        let (new_key, stats) = rekey::change_password(db, &key, new, self.keyfile.as_deref())?;
+        self.debug_log(&format!("change_password - Re-encrypted {} notes, {} attachments", stats.notes, stats.attachments));
+
+        self.key_manager.set_master_key(&new_key);
+        self.key = Some(new_key);
+        self.load_notes()?;
+
+        Ok(stats.notes)
+    }
+
+    /// Verify the current password, then re-encrypt everything under a key derived with `iterations`
+    ///
+    /// Returns the number of re-encrypted notes.
+    fn increase_iterations(&mut self, iterations: u32) -> Result<usize> {
+        let input = self.verify_current_password(&self.password_change[0])?;
+
+        let db = self.db.as_ref().ok_or_else(|| anyhow::anyhow!("Database not unlocked"))?;
+        let key = self.key.clone().ok_or_else(|| anyhow::anyhow!("Encryption key not available"))?;
+
+        let (new_key, stats) = rekey::change_iterations(db, &key, &input, iterations)?;
+        self.debug_log(&format!("increase_iterations - {} iterations, re-encrypted {} notes, {} attachments", iterations, stats.notes, stats.attachments));
+
+        self.key_manager.set_master_key(&new_key);
+        self.key = Some(new_key);
+        self.key_iterations = iterations;
+        self.load_notes()?;
+
+        Ok(stats.notes)
+    }
+
+    /// Check `password` against the unlocked key, derived with the stored salt and iterations
+    ///
+    /// Returns the KDF input for `password` (combined with the keyfile, if any).
+    fn verify_current_password(&self, password: &str) -> Result<Zeroizing<String>> {
+        let db = self.db.as_ref().ok_or_else(|| anyhow::anyhow!("Database not unlocked"))?;
+        let key = self.key.as_ref().ok_or_else(|| anyhow::anyhow!("Encryption key not available"))?;
+
+        let metadata = EncryptionRepository::new(db.connection())
+            .get()?
+            .ok_or_else(|| anyhow::anyhow!("Encryption metadata not found"))?;
+        let input = kdf_input(password, metadata.keyfile_required, self.keyfile.as_deref())?;
+        if self.crypto.derive_key(&input, &metadata.salt, metadata.iterations)? != *key {
+            anyhow::bail!("Current password is incorrect");
+        }
+        Ok(input)
+    }
+
+    /// Open the change password form over the current screen
+    fn open_change_password(&mut self) {
+        self.error = None;
+        self.sync_status = None;
+        let previous = std::mem::replace(&mut self.state, AppState::Quit);
+        self.state = AppState::ChangePassword {
+            previous: Box::new(previous),
+        };
+    }
+
+    /// Handle key events in help screen
+    fn handle_help_key(&mut self, key: KeyEvent) -> Result<()> {
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('?') => {
+                // Return to previous state
+                if let AppState::Help { previous } = std::mem::replace(&mut self.state, AppState::Quit) {
+                    self.state = *previous;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Handle key events in settings screen
+    fn handle_settings_key(&mut self, key: KeyEvent) -> Result<()> {
+        match self.input_mode {
+            InputMode::Normal => {
+                // Navigation mode
+                match key.code {
+                    KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('s') => {
+                        // Return to previous state
+                        if let AppState::Settings { previous } = std::mem::replace(&mut self.state, AppState::Quit) {
+                            self.state = *previous;
+                        }
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        // Move down through settings fields
+                        if self.selected_setting < 15 {
+                            self.selected_setting += 1;
+                        }
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        // Move up through settings fields
+                        if self.selected_setting > 0 {
+                            self.selected_setting -= 1;
+                        }
+                    }
+                    KeyCode::Enter | KeyCode::Char('i') | KeyCode::Char(' ') => {
+                        // Edit selected field
+                        self.start_editing_setting();
+                    }
+                    KeyCode::Char('p') => {
+                        // Paste sync credentials from clipboard
+                        if let Err(e) = self.paste_sync_credentials() {
+                            self.error = Some(format!("Failed to paste credentials: {}", e));
+                        } else {
+                            self.sync_status = Some("Sync credentials pasted successfully!".to_string());
+                        }
+                    }
+                    KeyCode::Char('c') => {
+                        // Copy sync credentials to clipboard
+                        if let Err(e) = self.copy_sync_credentials() {
+                            self.error = Some(format!("Failed to copy credentials: {}", e));
+                        } else {
+                            self.sync_status = Some("Sync credentials copied to clipboard!".to_string());
+                        }
+                    }
+                    KeyCode::Char('y') => {
+                        // Trigger manual sync
+                        self.trigger_sync();
+                    }
+                    KeyCode::Char('R') => {
+                        // Replace the sync API key on the server
+                        if let Err(e) = self.rotate_sync_api_key() {
+                            self.error = Some(format!("Failed to rotate API key: {}", e));
+                        } else {
+                            self.sync_status = Some("Sync API key rotated; the old key no longer works".to_string());
+                        }
+                    }
+                    KeyCode::Char('P') => {
+                        // Open the change password form
+                        self.open_change_password();
+                    }
+                    _ => {}
+                }
+            }
+            InputMode::SettingsEdit => {
+                // Editing mode
+                match key.code {
+                    KeyCode::Esc => {
+                        // Cancel editing
+                        self.setting_input.clear();
+                        self.input_mode = InputMode::Normal;
+                    }
+                    KeyCode::Enter => {
+                        // Save edited value
+                        if let Err(e) = self.save_setting_value() {
+                            self.error = Some(format!("Failed to save setting: {}", e));
+                        }
+                        self.setting_input.clear();
+                        self.input_mode = InputMode::Normal;
+                    }
+                    KeyCode::Char(c) => {
+                        // For boolean and enum fields, handle cycling
+                        match self.selected_setting {
+                            1 => {
+                                // Theme: cycle through Light/Dark/Auto
+                                self.cycle_theme();
+                                self.input_mode = InputMode::Normal;
+                            }
+                            2 => {
+                                // Sort order: cycle through Recent/Oldest/Alpha/Created
+                                self.cycle_sort_order();
+                                self.input_mode = InputMode::Normal;
+                            }
+                            4 => {
+                                // Trailing newline: cycle through Single/None/Verbatim
+                                self.cycle_trailing_newline();
+                                self.input_mode = InputMode::Normal;
+                            }
+                            5 => {
+                                // Sync enabled: toggle
+                                self.settings.sync_enabled = !self.settings.sync_enabled;
+                                if let Err(e) = self.save_settings() {
+                                    self.error = Some(format!("Failed to save settings: {}", e));
+                                }
+                                self.input_mode = InputMode::Normal;
+                            }
+                            7 => {
+                                // Pull-only: toggle
+                                self.settings.sync_pull_only = !self.settings.sync_pull_only;
+                                if let Err(e) = self.save_settings() {
+                                    self.error = Some(format!("Failed to save settings: {}", e));
+                                }
+                                self.input_mode = InputMode::Normal;
+                            }
+                            8 => {
+                                // Conflict policy: cycle
+                                self.cycle_conflict_policy();
+                                self.input_mode = InputMode::Normal;
+                            }
+                            11 => {
+                                // Syntax highlighting: toggle
+                                self.settings.syntax_highlighting = !self.settings.syntax_highlighting;
+                                if let Err(e) = self.save_settings() {
+                                    self.error = Some(format!("Failed to save settings: {}", e));
+                                }
+                                self.input_mode = InputMode::Normal;
+                            }
+                            12 => {
+                                // List icons: toggle
+                                self.settings.emoji_icons = !self.settings.emoji_icons;
+                                if let Err(e) = self.save_settings() {
+                                    self.error = Some(format!("Failed to save settings: {}", e));
+                                }
+                                self.input_mode = InputMode::Normal;
+                            }
+                            _ => {
+                                // String/number fields: type normally
+                                self.setting_input.push(c);
+                            }
+                        }
+                    }
+                    KeyCode::Backspace => {
+                        self.setting_input.pop();
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Unlock the database
+    fn unlock(&mut self) -> Result<()> {
+        // Open database
+        let db = unlock::open_database(&self.db_path, &self.password_input, self.keyfile.as_deref())?;
+        self.debug_log(&format!("Unlock - SQLCipher settings: {}", db.cipher_params()));
 
         let encryption_repo = EncryptionRepository::new(db.connection());
 
         // Get or create encryption metadata
-        let (salt, iterations) = if let Some(metadata) = encryption_repo.get()? {
+        let (metadata, is_setup) = if let Some(metadata) = encryption_repo.get()? {
             // Load existing salt from database
-            (metadata.salt, metadata.iterations)
+            (metadata, false)
         } else {
-            // First-time setup: generate new salt and save it
-            let new_salt = self.crypto.generate_salt();
-            let iterations = 256_000;
-            encryption_repo.save(&new_salt, iterations)?;
-            (new_salt.to_vec(), iterations)
+            // First-time setup: generate new salt, requiring the keyfile if one was given
+            let mut metadata = EncryptionMetadata::new(self.crypto.generate_salt());
+            metadata.keyfile_required = self.keyfile.is_some();
+            (metadata, true)
         };
 
+        // Read before saving new metadata, so an unreadable keyfile isn't made required
+        let input = kdf_input(&self.password_input, metadata.keyfile_required, self.keyfile.as_deref())?;
+        if is_setup {
+            encryption_repo.save(&metadata)?;
+        }
+
         // Derive encryption key from password and salt
-        self.debug_log(&format!("Unlock - Password length: {} chars", self.password_input.len()));
+        self.debug_log(&format!("Unlock - Password length: {} chars", self.password_input.chars().count()));
         self.debug_log(&format!("Unlock - Password is empty: {}", self.password_input.is_empty()));
+        self.debug_log(&format!("Unlock - Keyfile required: {}", metadata.keyfile_required));
 
-        let key = self
-            .crypto
-            .derive_key(&self.password_input, &salt, iterations)?;
+        // Checked against the stored notes, in case they use another iteration count
+        let checked = match unlock::derive_checked_key(db.connection(), &input, &metadata) {
+            Ok(checked) => checked,
+            Err(e) => {
+                self.debug_log(&format!("Unlock - {:#}", e));
+                return Err(e);
+            }
+        };
+        let key = checked.key;
+        if let Some(recorded) = checked.recorded_iterations {
+            self.sync_status = Some(format!(
+                "Notes are encrypted with {} PBKDF2 iterations, not the recorded {}; the record has been updated",
+                checked.iterations, recorded
+            ));
+        }
 
         // Debug logging for troubleshooting
-        self.debug_log(&format!("Unlock - Salt (hex): {}", hex::encode(&salt)));
-        self.debug_log(&format!("Unlock - Salt length: {} bytes", salt.len()));
-        self.debug_log(&format!("Unlock - Iterations: {}", iterations));
+        self.debug_log(&format!("Unlock - Salt (hex): {}", metadata.salt_hex()));
+        self.debug_log(&format!("Unlock - Salt length: {} bytes", metadata.salt.len()));
+        self.debug_log(&format!("Unlock - Iterations: {} (recorded {})", checked.iterations, metadata.iterations));
         self.debug_log(&format!("Unlock - Key (first 8 bytes): {}", hex::encode(&key[0..8])));
 
-        self.key_manager.set_master_key(key);
-        self.key = Some(key);
+        self.key_manager.set_master_key(&key);
+        self.key = Some(key.clone());
+        self.key_iterations = checked.iterations;
         self.db = Some(db);
 
-        // Check if API key needs encryption (from paste credentials flow)
+        // Encrypt credentials left in plaintext by the paste credentials flow or older versions
         if let Some(db) = &self.db {
             use crate::repository::sync::SyncRepository;
             let sync_repo = SyncRepository::new(db.connection());
 
-            if let Ok(Some(mut metadata)) = sync_repo.get_metadata() {
-                if let Some(api_key_str) = &metadata.api_key {
-                    // Check if API key is plaintext (prefixed with "PLAINTEXT:")
-                    if let Some(plaintext_key) = api_key_str.strip_prefix("PLAINTEXT:") {
-                        self.debug_log("Unlock - Detected plaintext API key, encrypting with new key");
+            if sync_repo.seal_plaintext(&key)? {
+                self.debug_log("Unlock - Encrypted plaintext sync credentials");
+            }
+            if let Ok(Some(metadata)) = sync_repo.get_metadata(&key) {
+                self.auto_sync_interval = metadata.auto_sync_interval.unwrap_or(5);
+            }
+        }
 
-                        // Encrypt API key with the newly derived key
-                        let encrypted = self.crypto.encrypt_text(plaintext_key, &key)?;
-                        let encrypted_api_key = serde_json::to_string(&encrypted)?;
+        // Load notes
+        self.load_notes()?;
 
-                        // Update metadata with encrypted API key
-                        metadata.api_key = Some(encrypted_api_key);
-                        sync_repo.update_metadata(&metadata)?;
+        // Build the in-memory search index from the decrypted notes, and store previews
+        // for notes saved before they existed
+        if let (Some(db), Some(key)) = (&self.db, &self.key) {
+            let repo = NoteRepository::new(db.connection());
+            repo.rebuild_search_index(key)?;
+            let filled = repo.fill_previews(key)?;
+            if filled > 0 {
+                self.debug_log(&format!("Unlock - Stored previews for {} notes", filled));
+            }
+        }
 
-                        self.debug_log("Unlock - API key encrypted and saved");
-                    }
+        // Check the stored notes have not been modified outside the app
+        if let (Some(db), Some(key)) = (&self.db, &self.key) {
+            match integrity::verify(db.connection(), key)? {
+                IntegrityStatus::Valid => {}
+                IntegrityStatus::Unsealed => integrity::seal(db.connection(), key)?,
+                IntegrityStatus::Tampered { note_id, reason } => {
+                    self.debug_log(&format!("Unlock - Integrity check failed: {} ({:?})", reason, note_id));
+                    let title = note_id
+                        .and_then(|id| self.notes.iter().find(|n| n.meta.id == id))
+                        .map(|n| format!(" at \"{}\"", n.title()))
+                        .unwrap_or_default();
+                    self.error = Some(format!(
+                        "Integrity warning: the database was modified outside Jottery{} ({}). Review it, then run `jottery check --accept` to reseal.",
+                        title, reason
+                    ));
                 }
             }
         }
 
-        // Load notes
-        self.load_notes()?;
-
-        // Load settings
-        if let Some(db) = &self.db {
+        // Load settings, with the endpoint from the sync metadata where it is kept encrypted
+        if let (Some(db), Some(key)) = (&self.db, &self.key) {
             let settings_repo = SettingsRepository::new(db.connection());
             self.settings = settings_repo.get()?;
+            self.settings.sync_endpoint = SyncRepository::new(db.connection())
+                .get_metadata(key)?
+                .map(|metadata| metadata.sync_endpoint)
+                .filter(|endpoint| !endpoint.is_empty());
         }
+        self.key_manager.set_auto_lock(self.settings.auto_lock_timeout as u64);
+        self.restart_auto_sync();
+        self.refresh_sync_health()?;
 
         self.password_input.clear();
         self.state = AppState::NoteList;
@@ -686,99 +1884,605 @@ impl App {
         Ok(())
     }
 
-    /// Load notes from database
+    /// Load notes from database, starting with the first page
     fn load_notes(&mut self) -> Result<()> {
         if let (Some(db), Some(key)) = (&self.db, &self.key) {
             let repo = NoteRepository::new(db.connection());
-            self.notes = repo.list(false, key)?;
+            let page = repo.list_meta(self.show_deleted, None, NOTE_PAGE_SIZE)?;
+            self.all_notes_loaded = page.len() < NOTE_PAGE_SIZE;
+            self.last_listed = page.last().cloned();
+            let batch = repo.titles(page, key)?;
+            self.notes = batch.titles;
+            self.preview_note = None;
+            self.unreadable_note_ids = batch.unreadable;
+            self.thumbnail_ids = AttachmentRepository::new(db.connection()).thumbnail_ids()?.into_iter().collect();
             self.selected_note = 0;
         }
-        Ok(())
+        self.refresh_overdue_reminders();
+        self.load_more_notes()
+    }
+
+    /// Fetch the next page once the selection nears the end of the loaded notes
+    ///
+    /// Pages follow the default pinned-then-recent order, so searching, tag filtering,
+    /// bulk selection and the other sort orders load every remaining note instead.
+    fn load_more_notes(&mut self) -> Result<()> {
+        if self.all_notes_loaded {
+            return Ok(());
+        }
+        let needs_all = !self.search_input.is_empty()
+            || !self.tag_filter.is_empty()
+            || self.tag_panel_open
+            || self.multi_select
+            || self.settings.sort_order != SortOrder::Recent;
+        if !needs_all && self.selected_note + NOTE_PAGE_MARGIN < self.notes.len() {
+            return Ok(());
+        }
+
+        // Not while a sync has the database; the next key press tries again
+        if let (Some(db), Some(key)) = (&self.db, &self.key) {
+            let limit = if needs_all { usize::MAX } else { NOTE_PAGE_SIZE };
+            let repo = NoteRepository::new(db.connection());
+            let page = repo.list_meta(self.show_deleted, self.last_listed.as_ref(), limit)?;
+            self.all_notes_loaded = page.len() < limit;
+            if let Some(last) = page.last() {
+                self.last_listed = Some(last.clone());
+            }
+
+            // Notes unpinned since the last page moved down and are already loaded
+            let loaded: HashSet<String> = self.notes.iter().map(|n| n.meta.id.clone()).collect();
+            let page = page.into_iter().filter(|meta| !loaded.contains(&meta.id)).collect();
+            let batch = repo.titles(page, key)?;
+            self.notes.extend(batch.titles);
+            for id in batch.unreadable {
+                if !self.unreadable_note_ids.contains(&id) {
+                    self.unreadable_note_ids.push(id);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace the listed title of `note` after it was saved
+    fn update_listed(&mut self, note: &Note) {
+        if let Some(slot) = self.notes.iter_mut().find(|n| n.meta.id == note.id) {
+            *slot = NotePreview::from(note);
+        }
+    }
+
+    /// Decrypt the selected note for the preview pane, unless it already is
+    fn load_preview_note(&mut self) {
+        let Some(selected) = self.filtered_notes().get(self.selected_note).map(|n| n.meta.clone()) else {
+            self.preview_note = None;
+            return;
+        };
+        if self.preview_note.as_ref().is_some_and(|note| NotePreview::from(note).meta == selected) {
+            return;
+        }
+        // While a sync holds the database the pane keeps showing the preview text
+        self.preview_note = match (&self.db, &self.key) {
+            (Some(db), Some(key)) => match NoteRepository::new(db.connection()).get(&selected.id, key) {
+                Ok(note) => note,
+                Err(e) => {
+                    self.debug_log(&format!("load_preview_note - Failed to load {}: {}", selected.id, e));
+                    None
+                }
+            },
+            _ => None,
+        };
+    }
+
+    /// Open the selected note in the editor, returning whether there was one
+    fn open_selected_note(&mut self) -> Result<bool> {
+        let Some(note_id) = self.filtered_notes().get(self.selected_note).map(|n| n.meta.id.clone()) else {
+            return Ok(false);
+        };
+        let db = self.db.as_ref().ok_or_else(|| anyhow::anyhow!("Database not available"))?;
+        let key = self.key.as_ref().ok_or_else(|| anyhow::anyhow!("Encryption key not available"))?;
+        let note = NoteRepository::new(db.connection())
+            .get(&note_id, key)?
+            .ok_or_else(|| anyhow::anyhow!("Note no longer exists"))?;
+
+        self.note_input = note.content;
+        self.note_cursor = self.note_input.len();
+        self.undo_history.clear();
+        self.history_recorded = false;
+        self.current_tags = note.tags;
+        self.current_language = note.syntax_language;
+        self.current_word_wrap = note.word_wrap;
+        self.current_reminder = note.reminder_at;
+        self.current_attachments = note.attachments;
+        self.editing_note_id = Some(note_id);
+        self.mark_saved();
+        self.state = AppState::NoteView;
+        self.input_mode = InputMode::Normal;
+        Ok(true)
+    }
+
+    /// Pin or unpin the note with `note_id`
+    fn toggle_pin(&mut self, note_id: &str) -> Result<()> {
+        let (Some(db), Some(key)) = (&self.db, &self.key) else {
+            return Ok(());
+        };
+        let repo = NoteRepository::new(db.connection());
+        let Some(mut note) = repo.get(note_id, key)? else {
+            return Ok(());
+        };
+        note.pinned = !note.pinned;
+        repo.update(&note, key)?;
+        integrity::seal_notes(db.connection(), key, &[&note.id])?;
+        self.update_listed(&note);
+        Ok(())
+    }
+
+    /// Save current note
+    fn save_note(&mut self) -> Result<()> {
+        if let (Some(db), Some(key)) = (&self.db, &self.key) {
+            let repo = NoteRepository::new(db.connection());
+
+            // Normalize the trailing newline so in-app and $EDITOR edits compare equal
+            let content = self.settings.trailing_newline.apply(&self.note_input);
+
+            if !content.is_empty() {
+                if let Some(note_id) = &self.editing_note_id {
+                    // Update existing note
+                    if let Some(mut note) = repo.get(note_id, key)? {
+                        let content_changed = note.content != content || note.tags != self.current_tags;
+                        if note.content == content
+                            && note.tags == self.current_tags
+                            && note.syntax_language == self.current_language
+                            && note.word_wrap == self.current_word_wrap
+                            && note.reminder_at == self.current_reminder
+                        {
+                            // Nothing changed, avoid bumping modified_at
+                            return Ok(());
+                        }
+                        note.content = content;
+                        note.tags = self.current_tags.clone();
+                        note.syntax_language = self.current_language;
+                        note.word_wrap = self.current_word_wrap;
+                        note.reminder_at = self.current_reminder;
+                        note.touch();
+                        // Only the version from before this session goes into the history
+                        if self.history_recorded {
+                            repo.update_unrecorded(&note, key)?;
+                        } else {
+                            repo.update(&note, key)?;
+                            self.history_recorded = content_changed;
+                        }
+                        integrity::seal_notes(db.connection(), key, &[&note.id])?;
+                        self.update_listed(&note);
+                    }
+                } else {
+                    // Create new note
+                    let mut note = Note::new(content);
+                    note.tags = self.current_tags.clone();
+                    note.syntax_language = self.current_language;
+                    note.word_wrap = self.current_word_wrap;
+                    note.reminder_at = self.current_reminder;
+                    repo.create(&note, key)?;
+                    // Later saves (autosave or leaving the editor) update this note, with no earlier version to keep
+                    self.editing_note_id = Some(note.id.clone());
+                    self.history_recorded = true;
+                    integrity::seal_notes(db.connection(), key, &[&note.id])?;
+                    self.notes.insert(0, NotePreview::from(&note));
+                }
+            }
+        }
+        self.mark_saved();
+        Ok(())
+    }
+
+    /// Record the editor buffers as matching what is stored
+    fn mark_saved(&mut self) {
+        self.saved_content = self.settings.trailing_newline.apply(&self.note_input);
+        self.saved_tags = self.current_tags.clone();
+        self.saved_language = self.current_language;
+        self.saved_word_wrap = self.current_word_wrap;
+        self.saved_reminder = self.current_reminder;
+        self.last_edit_at = None;
+        self.edits_since_save = 0;
+        self.autosaved = false;
+    }
+
+    /// Whether the editor buffers differ from what is stored
+    fn is_dirty(&self) -> bool {
+        self.settings.trailing_newline.apply(&self.note_input) != self.saved_content
+            || self.current_tags != self.saved_tags
+            || self.current_language != self.saved_language
+            || self.current_word_wrap != self.saved_word_wrap
+            || self.current_reminder != self.saved_reminder
+    }
+
+    /// The compiled pattern when the search is a `re:` regex search; an invalid pattern
+    /// yields its compile error
+    fn search_regex(&self) -> Option<Result<Regex, String>> {
+        let pattern = self.search_input.strip_prefix(REGEX_SEARCH_PREFIX)?;
+        let mut cache = self.search_regex_cache.borrow_mut();
+        match &*cache {
+            Some((cached, case_sensitive, regex))
+                if cached == pattern && *case_sensitive == self.search_case_sensitive =>
+            {
+                return Some(regex.clone());
+            }
+            _ => {}
+        }
+
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(!self.search_case_sensitive)
+            .build()
+            .map_err(|e| match e {
+                // The parser's message is multi-line with a caret diagram; keep the summary
+                regex::Error::Syntax(message) => message
+                    .lines()
+                    .last()
+                    .unwrap_or_default()
+                    .trim()
+                    .trim_start_matches("error: ")
+                    .to_string(),
+                e => e.to_string(),
+            });
+        *cache = Some((pattern.to_string(), self.search_case_sensitive, regex.clone()));
+        Some(regex)
+    }
+
+    /// Scroll offset of the preview for the selected note
+    fn preview_offset(&self) -> u16 {
+        let filtered = self.filtered_notes();
+        match (filtered.get(self.selected_note), &self.preview_scroll) {
+            (Some(note), Some((id, offset))) if *id == note.meta.id => *offset,
+            _ => 0,
+        }
+    }
+
+    /// Scroll the preview half a page down or up, within the extent last drawn
+    fn scroll_preview(&mut self, down: bool) {
+        let Some(id) = self.filtered_notes().get(self.selected_note).map(|note| note.meta.id.clone()) else {
+            return;
+        };
+        let (max_offset, height) = self.preview_extent.get();
+        let step = (height / 2).max(1);
+        let offset = self.preview_offset();
+        let offset = if down {
+            offset.saturating_add(step).min(max_offset)
+        } else {
+            offset.saturating_sub(step)
+        };
+        self.preview_scroll = Some((id, offset));
+    }
+
+    /// Widen or narrow the note list pane by one step
+    fn resize_note_list(&mut self, wider: bool) {
+        let width = if wider {
+            self.settings.note_list_width.saturating_add(NOTE_LIST_WIDTH_STEP)
+        } else {
+            self.settings.note_list_width.saturating_sub(NOTE_LIST_WIDTH_STEP)
+        };
+        self.settings.note_list_width = width.clamp(MIN_NOTE_LIST_WIDTH, MAX_NOTE_LIST_WIDTH);
+        self.settings.show_preview = true;
+        self.save_layout();
+    }
+
+    /// Persist the note list layout (without restarting auto-lock and auto-sync)
+    fn save_layout(&mut self) {
+        if let Some(db) = &self.db {
+            if let Err(e) = SettingsRepository::new(db.connection()).update(&self.settings) {
+                self.error = Some(format!("Failed to save settings: {}", e));
+            }
+        }
+    }
+
+    /// Columns for the note list out of `available`; the list takes them all when the
+    /// preview is hidden or wouldn't keep its minimum width
+    fn note_list_width(&self, available: u16) -> u16 {
+        if !self.settings.show_preview || available < MIN_NOTE_LIST_WIDTH + MIN_PREVIEW_WIDTH {
+            return available;
+        }
+        self.settings
+            .note_list_width
+            .clamp(MIN_NOTE_LIST_WIDTH, available - MIN_PREVIEW_WIDTH)
+    }
+
+    /// Conflict, pinned, attachment and image preview markers shown before a note in the list
+    fn list_indicators(&self, note: &NotePreview) -> String {
+        let (conflict, pinned, attachment, preview) = if self.settings.emoji_icons {
+            ("⚠", "📌", "📎", "📷")
+        } else {
+            ("!", "*", "@", "%")
+        };
+
+        let mut indicators = String::new();
+        let attachments = &note.meta.attachment_ids;
+        if self.conflicts.iter().any(|c| c.local.id == note.meta.id) {
+            indicators.push_str(&format!("{} ", conflict));
+        }
+        if note.meta.pinned {
+            indicators.push_str(&format!("{} ", pinned));
+        }
+        if !attachments.is_empty() {
+            indicators.push_str(&format!("{}{} ", attachment, attachments.len()));
+        }
+        let previews = attachments.iter().filter(|id| self.thumbnail_ids.contains(*id)).count();
+        if previews > 0 {
+            indicators.push_str(&format!("{}{} ", preview, previews));
+        }
+        indicators
+    }
+
+    /// Created/modified lines for the preview metadata, collapsed to one relative line
+    /// when the full dates don't fit in `width`
+    fn note_date_lines(note: &NoteMeta, now: chrono::DateTime<chrono::Utc>, width: u16) -> Vec<String> {
+        let full = |label: &str, at: chrono::DateTime<chrono::Utc>| {
+            format!(
+                "{}: {} ({})",
+                label,
+                at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"),
+                time::ago(at, now)
+            )
+        };
+        let lines = vec![full("Created", note.created_at), full("Modified", note.modified_at)];
+        if lines.iter().all(|line| line.chars().count() <= width as usize) {
+            lines
+        } else {
+            vec![format!(
+                "Created {} · Modified {}",
+                time::ago(note.created_at, now),
+                time::ago(note.modified_at, now)
+            )]
+        }
+    }
+
+    /// Filter notes based on search query and sort (pinned first, then by modified date)
+    fn filtered_notes(&self) -> Vec<&NotePreview> {
+        let mut notes: Vec<&NotePreview> = match self.search_regex() {
+            _ if self.search_input.is_empty() => self.notes.iter().collect(),
+            // An invalid pattern leaves the list unfiltered; the error shows in the search bar
+            Some(Err(_)) => self.notes.iter().collect(),
+            regex => {
+                // Only titles are held in memory, so the content is searched in the database:
+                // through the full-text index, or by scanning the indexed text for regexes and
+                // case-sensitive searches (the index folds case). Without it, the previews are.
+                let matching: Option<HashSet<String>> = match (&self.db, &self.key) {
+                    (Some(db), Some(key)) => {
+                        let repo = NoteRepository::new(db.connection());
+                        let ids = match &regex {
+                            Some(Ok(regex)) => repo.scan(|content, _| regex.is_match(content), key),
+                            _ if self.search_case_sensitive => repo.scan(
+                                |content, tags| matches_query(content, tags, &self.search_input, true),
+                                key,
+                            ),
+                            _ => repo.search(&self.search_input, key),
+                        };
+                        match ids {
+                            Ok(ids) => Some(ids.into_iter().collect()),
+                            Err(e) => {
+                                self.debug_log(&format!("filtered_notes - Search index failed: {}", e));
+                                None
+                            }
+                        }
+                    }
+                    _ => None,
+                };
+
+                self.notes
+                    .iter()
+                    .filter(|note| match (&matching, &regex) {
+                        (Some(ids), _) => ids.contains(&note.meta.id),
+                        (None, Some(Ok(regex))) => regex.is_match(&note.preview),
+                        (None, _) => note.matches_query_with_case(&self.search_input, self.search_case_sensitive),
+                    })
+                    .collect()
+            }
+        };
+
+        if !self.tag_filter.is_empty() {
+            notes.retain(|note| {
+                let has = |tag: &String| note.tags.contains(tag);
+                if self.tag_filter_any {
+                    self.tag_filter.iter().any(has)
+                } else {
+                    self.tag_filter.iter().all(has)
+                }
+            });
+        }
+
+        sort_notes(&mut notes, self.settings.sort_order);
+
+        notes
+    }
+
+    /// Distinct tags across the listed notes with how many notes carry each, by name
+    fn tag_counts(&self) -> Vec<(String, usize)> {
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for tag in self.notes.iter().flat_map(|note| note.tags.iter()) {
+            *counts.entry(tag.clone()).or_default() += 1;
+        }
+        // Keep selected tags listed (so they can be cleared) even if no note has them now
+        for tag in &self.tag_filter {
+            counts.entry(tag.clone()).or_default();
+        }
+        let mut counts: Vec<_> = counts.into_iter().collect();
+        counts.sort_by_key(|(tag, _)| tag.to_lowercase());
+        counts
+    }
+
+    /// Handle keys while the tag panel has focus
+    fn handle_tag_panel_key(&mut self, key: KeyEvent) {
+        let tag_count = self.tag_counts().len();
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('f') => {
+                self.tag_panel_open = false;
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.tag_panel_index = (self.tag_panel_index + 1).min(tag_count.saturating_sub(1));
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.tag_panel_index = self.tag_panel_index.saturating_sub(1);
+            }
+            KeyCode::Char(' ') | KeyCode::Enter => {
+                if let Some((tag, _)) = self.tag_counts().into_iter().nth(self.tag_panel_index) {
+                    if !self.tag_filter.remove(&tag) {
+                        self.tag_filter.insert(tag);
+                    }
+                    self.selected_note = 0;
+                }
+            }
+            KeyCode::Char('o') => {
+                self.tag_filter_any = !self.tag_filter_any;
+                self.selected_note = 0;
+            }
+            KeyCode::Char('x') => {
+                self.tag_filter.clear();
+                self.selected_note = 0;
+            }
+            _ => {}
+        }
+        // Clearing can drop tags that only stayed listed because they were selected
+        self.tag_panel_index = self.tag_panel_index.min(self.tag_counts().len().saturating_sub(1));
     }
 
-    /// Save current note
-    fn save_note(&mut self) -> Result<()> {
-        if let (Some(db), Some(key)) = (&self.db, &self.key) {
-            let repo = NoteRepository::new(db.connection());
+    /// Handle key events in multi-select mode
+    fn handle_multi_select_key(&mut self, key: KeyEvent) -> Result<()> {
+        if let Some(tag) = &mut self.bulk_tag_input {
+            match key.code {
+                KeyCode::Esc => self.bulk_tag_input = None,
+                KeyCode::Enter => {
+                    let tag = tag.trim().to_string();
+                    self.bulk_tag_input = None;
+                    if !tag.is_empty() {
+                        let count = self.apply_to_marked(|note| {
+                            if !note.tags.contains(&tag) {
+                                note.tags.push(tag.clone());
+                                note.touch();
+                            }
+                        })?;
+                        self.sync_status = Some(format!("Tagged {} notes #{}", count, tag));
+                    }
+                }
+                KeyCode::Char(c) => tag.push(c),
+                KeyCode::Backspace => {
+                    tag.pop();
+                }
+                _ => {}
+            }
+            return Ok(());
+        }
 
-            if !self.note_input.is_empty() {
-                if let Some(note_id) = &self.editing_note_id {
-                    // Update existing note
-                    if let Some(note) = self.notes.iter_mut().find(|n| &n.id == note_id) {
-                        note.content = self.note_input.clone();
-                        note.tags = self.current_tags.clone();
-                        note.touch();
-                        repo.update(note, key)?;
+        let visible: Vec<String> = self.filtered_notes().iter().map(|note| note.meta.id.clone()).collect();
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('V') => {
+                self.multi_select = false;
+                self.marked_note_ids.clear();
+            }
+            KeyCode::Char(' ') => {
+                // Toggle the current note and move on to the next
+                if let Some(id) = visible.get(self.selected_note) {
+                    if !self.marked_note_ids.remove(id) {
+                        self.marked_note_ids.insert(id.clone());
                     }
+                    self.selected_note = (self.selected_note + 1).min(visible.len() - 1);
+                }
+            }
+            KeyCode::Char('a') => {
+                // Mark every listed note, or clear the marks if they all are
+                if visible.iter().all(|id| self.marked_note_ids.contains(id)) {
+                    self.marked_note_ids.clear();
                 } else {
-                    // Create new note
-                    let mut note = Note::new(self.note_input.clone());
-                    note.tags = self.current_tags.clone();
-                    repo.create(&note, key)?;
-                    self.notes.insert(0, note);
+                    self.marked_note_ids.extend(visible);
+                }
+            }
+            KeyCode::Down | KeyCode::Char('j') if self.selected_note + 1 < visible.len() => {
+                self.selected_note += 1;
+                self.preview_scroll = None;
+            }
+            KeyCode::Up | KeyCode::Char('k') if self.selected_note > 0 => {
+                self.selected_note -= 1;
+                self.preview_scroll = None;
+            }
+            _ if self.marked_note_ids.is_empty() => {}
+            KeyCode::Char('d') => {
+                let count = self.apply_to_marked(|note| {
+                    if !note.deleted {
+                        note.mark_deleted();
+                    }
+                })?;
+                if !self.show_deleted {
+                    self.notes.retain(|note| !note.meta.deleted);
                 }
+                self.marked_note_ids.clear();
+                self.selected_note = self.selected_note.min(self.filtered_notes().len().saturating_sub(1));
+                self.sync_status = Some(format!("Deleted {} notes", count));
             }
+            KeyCode::Char('p') => {
+                // Pin them all unless they all are already pinned
+                let unpinned = self
+                    .notes
+                    .iter()
+                    .filter(|note| self.marked_note_ids.contains(&note.meta.id) && !note.meta.pinned)
+                    .count();
+                let pin = unpinned > 0;
+                if let Some(e) = self.pin_limit_error(unpinned) {
+                    self.error = Some(e);
+                    return Ok(());
+                }
+                let count = self.apply_to_marked(|note| {
+                    if note.pinned != pin {
+                        note.pinned = pin;
+                        note.touch();
+                    }
+                })?;
+                let action = if pin { "Pinned" } else { "Unpinned" };
+                self.sync_status = Some(format!("{} {} notes", action, count));
+            }
+            KeyCode::Char('t') => {
+                self.bulk_tag_input = Some(String::new());
+            }
+            _ => {}
         }
         Ok(())
     }
 
-    /// Filter notes based on search query and sort (pinned first, then by modified date)
-    fn filtered_notes(&self) -> Vec<&Note> {
-        let mut notes: Vec<&Note> = if self.search_input.is_empty() {
-            self.notes.iter().collect()
-        } else {
-            let query = self.search_input.to_lowercase();
-            let query_parts: Vec<&str> = query.split_whitespace().collect();
-
-            self.notes
-                .iter()
-                .filter(|note| {
-                    let content_lower = note.content.to_lowercase();
-
-                    // Check each query part
-                    for part in &query_parts {
-                        if part.starts_with('#') {
-                            // Tag search
-                            let tag = &part[1..];
-                            if !note.tags.iter().any(|t| t.to_lowercase().contains(tag)) {
-                                return false;
-                            }
-                        } else if part.starts_with('-') {
-                            // Negation
-                            let neg_word = &part[1..];
-                            if content_lower.contains(neg_word) {
-                                return false;
-                            }
-                        } else {
-                            // Regular text search
-                            if !content_lower.contains(part) {
-                                return false;
-                            }
-                        }
-                    }
+    /// Why pinning `adding` more notes isn't allowed, when it would pass the pin limit
+    fn pin_limit_error(&self, adding: usize) -> Option<String> {
+        let limit = usize::try_from(self.settings.pin_limit).unwrap_or(0);
+        let pinned = self.notes.iter().filter(|note| note.meta.pinned && !note.meta.deleted).count();
+        (limit > 0 && adding > 0 && pinned + adding > limit).then(|| {
+            format!(
+                "Pin limit reached: {} of {} notes pinned (change it under Display Settings)",
+                pinned, limit
+            )
+        })
+    }
 
-                    true
-                })
-                .collect()
+    /// Apply `change` to every marked note and save them in one transaction; the
+    /// listed titles only change once the database has
+    fn apply_to_marked(&mut self, change: impl Fn(&mut Note)) -> Result<usize> {
+        let (Some(db), Some(key)) = (&self.db, &self.key) else {
+            return Ok(0);
         };
+        let repo = NoteRepository::new(db.connection());
+        let mut changed = Vec::new();
+        for note in self.notes.iter().filter(|note| self.marked_note_ids.contains(&note.meta.id)) {
+            changed.extend(repo.get(&note.meta.id, key)?);
+        }
+        for note in &mut changed {
+            change(note);
+        }
 
-        // Sort: pinned first, then by modified_at descending
-        notes.sort_by(|a, b| {
-            match (a.pinned, b.pinned) {
-                (true, false) => std::cmp::Ordering::Less,
-                (false, true) => std::cmp::Ordering::Greater,
-                _ => b.modified_at.cmp(&a.modified_at),
-            }
-        });
+        repo.update_all(&changed, key)?;
+        let ids: Vec<&str> = changed.iter().map(|note| note.id.as_str()).collect();
+        integrity::seal_notes(db.connection(), key, &ids)?;
 
-        notes
+        for note in &changed {
+            self.update_listed(note);
+        }
+        Ok(changed.len())
     }
 
     /// Trigger manual sync
     fn trigger_sync(&mut self) {
         self.debug_log("trigger_sync - Called");
         self.debug_log(&format!("trigger_sync - sync_enabled: {}", self.settings.sync_enabled));
-        self.debug_log(&format!("trigger_sync - sync_endpoint: {:?}", self.settings.sync_endpoint));
 
         // Check if sync is configured
         if !self.settings.sync_enabled {
@@ -789,233 +2493,152 @@ impl App {
 
         if self.settings.sync_endpoint.is_none() {
             self.debug_log("trigger_sync - Sync endpoint not configured, returning");
-            self.sync_status = Some("Sync endpoint not configured. Configure in database settings table.".to_string());
+            self.sync_status = Some("Sync endpoint not configured. Press 's' to set it in settings.".to_string());
+            return;
+        }
+
+        if self.sync_worker.is_some() {
+            self.sync_status = Some("Sync already in progress".to_string());
             return;
         }
 
-        // Perform sync
         self.debug_log("trigger_sync - Starting sync");
         self.sync_status = Some("Syncing...".to_string());
-
-        match self.perform_sync() {
-            Ok(result) => {
-                self.sync_status = Some(format!("Sync complete! {} notes synced", result));
-            }
-            Err(e) => {
-                self.error = Some(format!("Sync failed: {}", e));
-                self.sync_status = None;
-            }
-        }
+        self.start_sync(SyncTrigger::Manual);
     }
 
-    /// Perform bidirectional sync with server
-    fn perform_sync(&mut self) -> Result<usize> {
-        use crate::models::sync::{SyncPushRequest, SyncPullRequest, SyncNote, SyncPushResponse, SyncPullResponse};
-        use crate::repository::sync::SyncRepository;
-        use chrono::Utc;
-
-        let db = self.db.as_ref().ok_or_else(|| anyhow::anyhow!("Database not available"))?;
-        let key = self.key.as_ref().ok_or_else(|| anyhow::anyhow!("Encryption key not available"))?;
-
-        let sync_repo = SyncRepository::new(db.connection());
-        let note_repo = NoteRepository::new(db.connection());
-
-        // Get sync metadata
-        let mut metadata = sync_repo.get_metadata()?.unwrap_or_default();
-
-        // Get API key
-        let encrypted_api_key = metadata.api_key.as_ref()
-            .ok_or_else(|| anyhow::anyhow!("No API key configured"))?;
-        let api_key_encrypted: crate::crypto::EncryptedData = serde_json::from_str(encrypted_api_key)?;
-        let api_key = self.crypto.decrypt_text(&api_key_encrypted, key)?;
-
-        let endpoint = metadata.sync_endpoint.clone();
-
-        // PUSH: Send local changes to server
-        let last_sync = metadata.last_sync_at;
-        let notes_to_push = if let Some(last_sync) = last_sync {
-            note_repo.get_modified_after(last_sync, key)?
-        } else {
-            note_repo.list(false, key)?
+    /// Lend the database to a worker thread and sync there; `tick` picks up the outcome
+    fn start_sync(&mut self, trigger: SyncTrigger) {
+        let (Some(db), Some(key)) = (self.db.take(), self.key.clone()) else {
+            self.error = Some("Sync failed: Database not available".to_string());
+            self.sync_status = None;
+            return;
         };
 
-        let mut sync_count = 0;
-
-        if !notes_to_push.is_empty() {
-            // Convert notes to sync format, encrypting content and tags
-            let sync_notes: Result<Vec<SyncNote>> = notes_to_push.iter().map(|note| {
-                // Encrypt content and tags for transmission to server
-                let encrypted_content = self.crypto.encrypt_text(&note.content, key)?;
-                let content_json = serde_json::to_string(&encrypted_content)?;
-
-                let encrypted_tags: Result<Vec<String>> = note.tags.iter()
-                    .map(|tag| {
-                        let encrypted_tag = self.crypto.encrypt_text(tag, key)?;
-                        Ok(serde_json::to_string(&encrypted_tag)?)
-                    })
-                    .collect();
-
-                Ok(SyncNote {
-                    id: note.id.clone(),
-                    created_at: note.created_at,
-                    modified_at: note.modified_at,
-                    content: content_json,
-                    tags: encrypted_tags?,
-                    attachments: vec![], // TODO: Handle attachments
-                    pinned: note.pinned,
-                    deleted: note.deleted,
-                    deleted_at: note.deleted_at,
-                    version: note.version,
-                    word_wrap: Some(note.word_wrap),
-                    syntax_language: Some(note.syntax_language.to_string()),
-                })
-            }).collect();
-
-            let sync_notes = sync_notes?;
-
-            let push_request = SyncPushRequest {
-                notes: sync_notes,
-                attachments: vec![],
-            };
-
-            // Create HTTP client
-            let client = reqwest::blocking::Client::new();
-            let push_url = format!("{}/api/v1/sync/push", endpoint);
-
-            let response = client
-                .post(&push_url)
-                .header("Authorization", format!("Bearer {}", api_key))
-                .json(&push_request)
-                .send()
-                .context("Failed to send push request")?;
+        self.reset_sync_progress();
+        let worker = SyncWorker::start(db, key, Arc::clone(&self.sync_progress));
+        self.sync_worker = Some((worker, trigger));
+    }
 
-            if !response.status().is_success() {
-                let status = response.status();
-                let error_text = response.text().unwrap_or_else(|_| "Unknown error".to_string());
-                anyhow::bail!("Push failed: {} - {}", status, error_text);
+    /// Cancel a running sync and take the database back; nothing is reported or reloaded
+    fn cancel_sync(&mut self) {
+        if let Some((worker, _)) = self.sync_worker.take() {
+            match worker.cancel() {
+                Ok(SyncOutcome { db, .. }) => self.db = Some(db),
+                Err(e) => self.debug_log(&format!("cancel_sync - {}", e)),
             }
-
-            let push_response: SyncPushResponse = response.json()
-                .context("Failed to parse push response")?;
-
-            sync_count += push_response.accepted.len();
-
-            // Update last push timestamp
-            metadata.last_push_at = Some(Utc::now());
         }
+    }
 
-        // PULL: Get changes from server
-        let known_note_ids: Vec<String> = self.notes.iter().map(|n| n.id.clone()).collect();
-
-        let pull_request = SyncPullRequest {
-            last_sync_at: last_sync,
-            known_note_ids,
+    /// Take the database back from the sync worker and report the result
+    fn finish_sync(&mut self, trigger: SyncTrigger, outcome: Result<SyncOutcome>) {
+        let result = match outcome {
+            Ok(SyncOutcome { db, result }) => {
+                self.db = Some(db);
+                result.and_then(|result| self.reload_after_sync(result))
+            }
+            Err(e) => {
+                // Without the connection nothing can be saved; lock so the next unlock reopens it
+                self.lock();
+                self.error = Some(format!("Sync failed: {}", e));
+                return;
+            }
         };
 
-        let pull_url = format!("{}/api/v1/sync/pull", endpoint);
-        let client = reqwest::blocking::Client::new();
-
-        let response = client
-            .post(&pull_url)
-            .header("Authorization", format!("Bearer {}", api_key))
-            .json(&pull_request)
-            .send()
-            .context("Failed to send pull request")?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().unwrap_or_else(|_| "Unknown error".to_string());
-            anyhow::bail!("Pull failed: {} - {}", status, error_text);
+        match trigger {
+            SyncTrigger::Manual => self.finish_manual_sync(result),
+            SyncTrigger::Auto => self.finish_auto_sync(result),
         }
+    }
 
-        let pull_response: SyncPullResponse = response.json()
-            .context("Failed to parse pull response")?;
-
-        // Apply remote changes
-        self.debug_log(&format!("Pull - Received {} notes from server", pull_response.notes.len()));
-
-        for remote_note in pull_response.notes {
-            self.debug_log(&format!("Pull - Processing note: {}", remote_note.id));
-
-            // Decrypt content and tags from server (they're stored encrypted on server)
-            self.debug_log(&format!("Pull - Encrypted content JSON: {}", &remote_note.content));
-
-            let encrypted_content: crate::crypto::EncryptedData = serde_json::from_str(&remote_note.content)?;
-            self.debug_log(&format!("Pull - Encrypted data - ciphertext len: {}, nonce len: {}, tag len: {}",
-                encrypted_content.ciphertext.len(),
-                encrypted_content.nonce.len(),
-                encrypted_content.tag.len()));
-
-            let decrypted_content = self.crypto.decrypt_text(&encrypted_content, key)?;
-            self.debug_log(&format!("Pull - Successfully decrypted content, length: {} chars", decrypted_content.len()));
+    /// Report a manual sync requested by `trigger_sync`
+    fn finish_manual_sync(&mut self, result: Result<SyncResult>) {
+        match result {
+            Ok(result) => {
+                let details_hint = if !self.conflicts.is_empty() {
+                    " (C: resolve conflicts)"
+                } else if result.changes.is_empty() {
+                    ""
+                } else {
+                    " (Y: details)"
+                };
+                self.sync_status = Some(format!("Sync complete: {}{}", result.phase_summary(), details_hint));
+                self.last_sync = Some(result);
+            }
+            Err(e) => {
+                self.error = Some(format!("Sync failed: {}", e));
+                self.sync_status = None;
+            }
+        }
+    }
 
-            let decrypted_tags: Vec<String> = remote_note.tags.iter()
-                .map(|tag_json| {
-                    let encrypted_tag: crate::crypto::EncryptedData = serde_json::from_str(tag_json)?;
-                    self.crypto.decrypt_text(&encrypted_tag, key)
-                })
-                .collect::<Result<Vec<_>>>()?;
-
-            // Check if we have this note locally
-            if let Some(local_note) = self.notes.iter_mut().find(|n| n.id == remote_note.id) {
-                // Conflict resolution: Last-Write-Wins
-                if remote_note.modified_at > local_note.modified_at {
-                    // Remote is newer, update local with decrypted content
-                    local_note.content = decrypted_content;
-                    local_note.tags = decrypted_tags;
-                    local_note.modified_at = remote_note.modified_at;
-                    local_note.pinned = remote_note.pinned;
-                    local_note.deleted = remote_note.deleted;
-                    local_note.deleted_at = remote_note.deleted_at;
-                    local_note.version = remote_note.version;
-                    local_note.word_wrap = remote_note.word_wrap.unwrap_or(true);
-                    if let Some(lang_str) = &remote_note.syntax_language {
-                        local_note.syntax_language = lang_str.parse().unwrap_or_default();
-                    }
+    fn reset_sync_progress(&self) {
+        if let Ok(mut progress) = self.sync_progress.lock() {
+            *progress = SyncProgress::default();
+        }
+    }
 
-                    note_repo.update(local_note, key)?;
-                    sync_count += 1;
-                }
-            } else {
-                // New note from server, add it with decrypted content
-                let mut new_note = Note::new(decrypted_content);
-                new_note.id = remote_note.id;
-                new_note.created_at = remote_note.created_at;
-                new_note.modified_at = remote_note.modified_at;
-                new_note.tags = decrypted_tags;
-                new_note.pinned = remote_note.pinned;
-                new_note.deleted = remote_note.deleted;
-                new_note.deleted_at = remote_note.deleted_at;
-                new_note.version = remote_note.version;
-                new_note.word_wrap = remote_note.word_wrap.unwrap_or(true);
-                if let Some(lang_str) = &remote_note.syntax_language {
-                    new_note.syntax_language = lang_str.parse().unwrap_or_default();
+    /// Report a sync signalled by the auto-sync timer
+    fn finish_auto_sync(&mut self, result: Result<SyncResult>) {
+        match result {
+            Ok(result) => {
+                // Quiet when nothing changed, so the status line isn't overwritten every interval
+                if !result.changes.is_empty() {
+                    self.sync_status = Some(format!("Auto-sync: {} (Y: details)", result.summary()));
+                    self.last_sync = Some(result);
                 }
-
-                note_repo.create(&new_note, key)?;
-                self.notes.insert(0, new_note);
-                sync_count += 1;
+            }
+            Err(e) => {
+                self.debug_log(&format!("finish_auto_sync - Failed: {}", e));
+                self.sync_status = Some(format!("Auto-sync failed: {}", e));
             }
         }
+    }
 
-        // Handle deletions
-        for deletion in pull_response.deletions {
-            if let Some(pos) = self.notes.iter().position(|n| n.id == deletion.id) {
-                note_repo.delete(&deletion.id)?;
-                self.notes.remove(pos);
-                sync_count += 1;
-            }
+    /// (Re)start the auto-sync timer from the current settings
+    fn restart_auto_sync(&mut self) {
+        // Dropping the old timer stops its thread
+        self.auto_sync = None;
+
+        if self.db.is_some() && self.settings.sync_enabled && self.settings.sync_endpoint.is_some() {
+            self.auto_sync = AutoSyncTimer::start(self.auto_sync_interval.max(0) as u64);
         }
+    }
 
-        // Update sync metadata
-        metadata.last_sync_at = Some(Utc::now());
-        metadata.last_pull_at = Some(Utc::now());
-        sync_repo.update_metadata(&metadata)?;
+    /// Refresh the list and sync health from what a finished sync wrote
+    fn reload_after_sync(&mut self, result: SyncResult) -> Result<SyncResult> {
+        self.debug_log(&format!(
+            "reload_after_sync - {} ({} changes)",
+            result.summary(),
+            result.changes.len()
+        ));
 
         // Reload notes to ensure UI is up to date
         self.load_notes()?;
+        self.refresh_sync_health()?;
+
+        Ok(result)
+    }
 
-        Ok(sync_count)
+    /// Re-read the last sync time and conflict count from sync metadata
+    fn refresh_sync_health(&mut self) -> Result<()> {
+        let db = self.db.as_ref().ok_or_else(|| anyhow::anyhow!("Database not available"))?;
+        let key = self.key.as_ref().ok_or_else(|| anyhow::anyhow!("Encryption key not available"))?;
+        let sync_repo = SyncRepository::new(db.connection());
+        let metadata = sync_repo.get_metadata(key)?.unwrap_or_default();
+
+        self.sync_health = SyncStatusDisplay {
+            is_enabled: self.settings.sync_enabled,
+            last_sync_at: metadata.last_sync_at,
+            pending_notes: sync_repo.count_by_status(SyncStatus::Pending)? as usize,
+            conflict_count: sync_repo.count_by_status(SyncStatus::Conflict)? as usize,
+            client_id: metadata.client_id,
+            sync_endpoint: self.settings.sync_endpoint.clone(),
+            ..SyncStatusDisplay::default()
+        };
+
+        self.conflicts = SyncEngine::new(db, key).conflicts()?;
+        Ok(())
     }
 
     /// Start editing a setting field
@@ -1041,17 +2664,71 @@ impl App {
                 self.input_mode = InputMode::SettingsEdit;
             }
             4 => {
+                // Trailing newline: cycle immediately, no input needed
+                self.cycle_trailing_newline();
+            }
+            5 => {
                 // Sync enabled: toggle immediately
                 self.settings.sync_enabled = !self.settings.sync_enabled;
                 if let Err(e) = self.save_settings() {
                     self.error = Some(format!("Failed to save settings: {}", e));
                 }
             }
-            5 => {
+            6 => {
                 // Sync endpoint
                 self.setting_input = self.settings.sync_endpoint.clone().unwrap_or_default();
                 self.input_mode = InputMode::SettingsEdit;
             }
+            7 => {
+                // Pull-only: toggle immediately
+                self.settings.sync_pull_only = !self.settings.sync_pull_only;
+                if let Err(e) = self.save_settings() {
+                    self.error = Some(format!("Failed to save settings: {}", e));
+                }
+            }
+            8 => {
+                // Conflict policy: cycle immediately, no input needed
+                self.cycle_conflict_policy();
+            }
+            9 => {
+                // Auto-sync interval
+                self.setting_input = self.auto_sync_interval.to_string();
+                self.input_mode = InputMode::SettingsEdit;
+            }
+            10 => {
+                // Sync attempts
+                self.setting_input = self.settings.sync_max_attempts.to_string();
+                self.input_mode = InputMode::SettingsEdit;
+            }
+            11 => {
+                // Syntax highlighting: toggle immediately
+                self.settings.syntax_highlighting = !self.settings.syntax_highlighting;
+                if let Err(e) = self.save_settings() {
+                    self.error = Some(format!("Failed to save settings: {}", e));
+                }
+            }
+            12 => {
+                // List icons: toggle immediately
+                self.settings.emoji_icons = !self.settings.emoji_icons;
+                if let Err(e) = self.save_settings() {
+                    self.error = Some(format!("Failed to save settings: {}", e));
+                }
+            }
+            13 => {
+                // Pin limit
+                self.setting_input = self.settings.pin_limit.to_string();
+                self.input_mode = InputMode::SettingsEdit;
+            }
+            14 => {
+                // Key iterations
+                self.setting_input = self.key_iterations.to_string();
+                self.input_mode = InputMode::SettingsEdit;
+            }
+            15 => {
+                // Autosave delay
+                self.setting_input = self.settings.autosave_seconds.to_string();
+                self.input_mode = InputMode::SettingsEdit;
+            }
             _ => {}
         }
     }
@@ -1075,8 +2752,8 @@ impl App {
                     anyhow::bail!("Invalid number");
                 }
             }
-            5 => {
-                // Sync endpoint
+            6 => {
+                // Sync endpoint (stored encrypted in sync metadata rather than settings)
                 if self.setting_input.is_empty() {
                     self.settings.sync_endpoint = None;
                 } else {
@@ -1085,6 +2762,71 @@ impl App {
                     }
                     self.settings.sync_endpoint = Some(self.setting_input.clone());
                 }
+                if let (Some(db), Some(key)) = (&self.db, &self.key) {
+                    let sync_repo = SyncRepository::new(db.connection());
+                    let mut metadata = sync_repo.get_metadata(key)?.unwrap_or_default();
+                    metadata.sync_endpoint = self.setting_input.clone();
+                    sync_repo.update_metadata(&metadata, key)?;
+                }
+            }
+            9 => {
+                // Auto-sync interval (stored in sync metadata rather than settings)
+                let minutes = self.setting_input.parse::<i32>()
+                    .map_err(|_| anyhow::anyhow!("Invalid number"))?;
+                if !(0..=1440).contains(&minutes) {
+                    anyhow::bail!("Auto-sync interval must be between 0 (disabled) and 1440 minutes");
+                }
+                if let (Some(db), Some(key)) = (&self.db, &self.key) {
+                    let sync_repo = SyncRepository::new(db.connection());
+                    let mut metadata = sync_repo.get_metadata(key)?.unwrap_or_default();
+                    metadata.auto_sync_interval = Some(minutes);
+                    sync_repo.update_metadata(&metadata, key)?;
+                }
+                self.auto_sync_interval = minutes;
+            }
+            10 => {
+                // Sync attempts
+                let attempts = self.setting_input.parse::<i32>()
+                    .map_err(|_| anyhow::anyhow!("Invalid number"))?;
+                if !(1..=10).contains(&attempts) {
+                    anyhow::bail!("Sync attempts must be between 1 and 10");
+                }
+                self.settings.sync_max_attempts = attempts;
+            }
+            13 => {
+                // Pin limit
+                let limit = self.setting_input.parse::<i32>()
+                    .map_err(|_| anyhow::anyhow!("Invalid number"))?;
+                if !(0..=MAX_PIN_LIMIT).contains(&limit) {
+                    anyhow::bail!("Pin limit must be between 0 (no limit) and {}", MAX_PIN_LIMIT);
+                }
+                self.settings.pin_limit = limit;
+            }
+            14 => {
+                // Key iterations (stored in encryption metadata): confirmed with the password first
+                let iterations = self.setting_input.parse::<u32>()
+                    .map_err(|_| anyhow::anyhow!("Invalid number"))?;
+                if iterations == self.key_iterations {
+                    return Ok(());
+                }
+                if iterations < self.key_iterations {
+                    anyhow::bail!("Key iterations can only be increased (currently {})", self.key_iterations);
+                }
+                if iterations > EncryptionMetadata::MAX_ITERATIONS {
+                    anyhow::bail!("Key iterations must be at most {}", EncryptionMetadata::MAX_ITERATIONS);
+                }
+                self.open_change_password();
+                self.pending_iterations = Some(iterations);
+                return Ok(());
+            }
+            15 => {
+                // Autosave delay
+                let seconds = self.setting_input.parse::<i32>()
+                    .map_err(|_| anyhow::anyhow!("Invalid number"))?;
+                if !(0..=600).contains(&seconds) {
+                    anyhow::bail!("Autosave delay must be between 0 (disabled) and 600 seconds");
+                }
+                self.settings.autosave_seconds = seconds;
             }
             _ => {}
         }
@@ -1107,7 +2849,6 @@ impl App {
 
     /// Cycle through sort order options
     fn cycle_sort_order(&mut self) {
-        use crate::models::SortOrder;
         self.settings.sort_order = match self.settings.sort_order {
             SortOrder::Recent => SortOrder::Oldest,
             SortOrder::Oldest => SortOrder::Alpha,
@@ -1119,15 +2860,51 @@ impl App {
         }
     }
 
+    /// Cycle through trailing newline options
+    fn cycle_trailing_newline(&mut self) {
+        use crate::models::TrailingNewline;
+        self.settings.trailing_newline = match self.settings.trailing_newline {
+            TrailingNewline::Single => TrailingNewline::None,
+            TrailingNewline::None => TrailingNewline::Verbatim,
+            TrailingNewline::Verbatim => TrailingNewline::Single,
+        };
+        if let Err(e) = self.save_settings() {
+            self.error = Some(format!("Failed to save settings: {}", e));
+        }
+    }
+
+    /// Cycle through conflict policy options
+    fn cycle_conflict_policy(&mut self) {
+        use crate::models::ConflictPolicy;
+        self.settings.conflict_policy = match self.settings.conflict_policy {
+            ConflictPolicy::NewestWins => ConflictPolicy::LocalWins,
+            ConflictPolicy::LocalWins => ConflictPolicy::RemoteWins,
+            ConflictPolicy::RemoteWins => ConflictPolicy::AskMe,
+            ConflictPolicy::AskMe => ConflictPolicy::NewestWins,
+        };
+        if let Err(e) = self.save_settings() {
+            self.error = Some(format!("Failed to save settings: {}", e));
+        }
+    }
+
     /// Save settings to database
     fn save_settings(&mut self) -> Result<()> {
         if let Some(db) = &self.db {
             let settings_repo = SettingsRepository::new(db.connection());
             settings_repo.update(&self.settings)?;
         }
+        self.key_manager.set_auto_lock(self.settings.auto_lock_timeout as u64);
+        self.restart_auto_sync();
         Ok(())
     }
 
+    /// Have the server issue a new sync API key and store it in place of the old one
+    fn rotate_sync_api_key(&mut self) -> Result<()> {
+        let db = self.db.as_ref().ok_or_else(|| anyhow::anyhow!("Database not unlocked"))?;
+        let key = self.key.as_ref().ok_or_else(|| anyhow::anyhow!("Encryption key not available"))?;
+        SyncEngine::new(db, key).rotate_api_key()
+    }
+
     /// Paste sync credentials from clipboard
     fn paste_sync_credentials(&mut self) -> Result<()> {
         // Get clipboard content
@@ -1150,43 +2927,44 @@ impl App {
         // If web app salt is provided, update it first
         // We'll encrypt the API key AFTER the user unlocks with the new salt
         if let Some(salt_b64) = &creds.salt {
-            use base64::Engine;
-            use crate::repository::encryption::EncryptionRepository;
             let encryption_repo = EncryptionRepository::new(db.connection());
+            if encryption_repo.get()?.is_some_and(|m| m.keyfile_required) {
+                anyhow::bail!("This database uses a keyfile, which the web app can't unlock with");
+            }
 
-            // Decode the base64 salt from web app
-            let salt = base64::engine::general_purpose::STANDARD.decode(salt_b64)
-                .context("Invalid base64 salt from sync credentials")?;
+            // Decode and validate the web app's salt, keeping its iteration count
+            let metadata = EncryptionMetadata::from_base64_salt(salt_b64, EncryptionMetadata::WEB_APP_ITERATIONS)
+                .map_err(anyhow::Error::msg)
+                .context("Web app salt is incompatible with TUI")?;
 
             self.debug_log(&format!("Paste credentials - Salt (base64): {}", salt_b64));
-            self.debug_log(&format!("Paste credentials - Salt (hex): {}", hex::encode(&salt)));
-            self.debug_log(&format!("Paste credentials - Salt length: {} bytes", salt.len()));
-
-            // Validate salt length - must be at least 32 bytes (256 bits) for PBKDF2
-            if salt.len() < 32 {
-                anyhow::bail!("Invalid salt length: {} bytes (expected at least 32 bytes). Web app salt may be incompatible with TUI.", salt.len());
-            }
+            self.debug_log(&format!("Paste credentials - Salt (hex): {}", metadata.salt_hex()));
+            self.debug_log(&format!("Paste credentials - Salt length: {} bytes", metadata.salt.len()));
 
-            // Update encryption metadata with web app's salt AND iteration count
             self.debug_log("Paste credentials - Saving salt with 100,000 iterations");
-            encryption_repo.save(&salt, 100_000)?;
+            encryption_repo.save(&metadata)?;
             self.debug_log("Paste credentials - Salt saved successfully");
         }
 
-        // Save sync metadata with PLAINTEXT API key temporarily
-        // It will be encrypted on next unlock with the new salt
+        // Save sync credentials in plaintext temporarily
+        // They are encrypted on next unlock with the new salt
+        let key = self.key.as_ref().ok_or_else(|| anyhow::anyhow!("Database not unlocked"))?;
         let sync_repo = SyncRepository::new(db.connection());
-        let mut metadata = sync_repo.get_metadata()?.unwrap_or_default();
+        let mut metadata = sync_repo.get_metadata(key)?.unwrap_or_default();
 
-        // Store API key as plaintext temporarily (will be encrypted on next unlock)
-        // We use a special marker to indicate it needs encryption
-        self.debug_log("Paste credentials - Storing API key (will encrypt on next unlock)");
-        metadata.api_key = Some(format!("PLAINTEXT:{}", creds.api_key));
+        // We use a special marker to indicate they need encryption
+        self.debug_log("Paste credentials - Storing credentials (will encrypt on next unlock)");
+        metadata.api_key = Some(format!("{}{}", PLAINTEXT_MARKER, creds.api_key));
         metadata.client_id = Some(creds.client_id);
         metadata.sync_endpoint = creds.endpoint.clone();
         metadata.sync_enabled = true;
 
-        sync_repo.update_metadata(&metadata)?;
+        sync_repo.update_metadata_unsealed(&metadata)?;
+
+        // Without a new salt the current key stays valid, so there is nothing to wait for
+        if creds.salt.is_none() {
+            sync_repo.seal_plaintext(key)?;
+        }
 
         // Update settings
         self.settings.sync_endpoint = Some(creds.endpoint);
@@ -1199,13 +2977,7 @@ impl App {
             self.debug_log("Paste credentials - Locking database to force re-unlock with new salt");
 
             // Automatically lock the database
-            self.key = None;
-            self.notes.clear();
-            self.selected_note = 0;
-            self.password_input.clear();
-            self.password_confirm.clear();
-            self.input_mode = InputMode::Normal;
-            self.state = AppState::Locked;
+            self.lock();
 
             // Show message about what happened
             self.error = Some("Salt synchronized! Please re-enter your password to unlock with the new encryption salt.".to_string());
@@ -1218,8 +2990,9 @@ impl App {
     fn copy_sync_credentials(&mut self) -> Result<()> {
         // Get sync metadata
         if let Some(db) = &self.db {
+            let key = self.key.as_ref().ok_or_else(|| anyhow::anyhow!("Database not unlocked"))?;
             let sync_repo = SyncRepository::new(db.connection());
-            let metadata = sync_repo.get_metadata()?
+            let metadata = sync_repo.get_metadata(key)?
                 .ok_or_else(|| anyhow::anyhow!("No sync configuration found"))?;
 
             // Check if credentials exist
@@ -1229,12 +3002,8 @@ impl App {
                 .ok_or_else(|| anyhow::anyhow!("No client ID found. Enable sync first."))?;
 
             // Decrypt API key
-            let api_key = if let Some(key) = &self.key {
-                let encrypted: crate::crypto::EncryptedData = serde_json::from_str(&encrypted_api_key)?;
-                self.crypto.decrypt_text(&encrypted, key)?
-            } else {
-                anyhow::bail!("Database not unlocked");
-            };
+            let encrypted: crate::crypto::EncryptedData = serde_json::from_str(&encrypted_api_key)?;
+            let api_key = self.crypto.decrypt_text(&encrypted, key)?;
 
             // Create credentials payload
             let creds = SyncCredentials::new(
@@ -1243,34 +3012,178 @@ impl App {
                 client_id,
             );
 
-            // Encode to base64
-            let encoded = creds.to_base64()?;
+            // Encode to base64
+            let encoded = creds.to_base64()?;
+
+            // Copy to clipboard
+            let mut clipboard = arboard::Clipboard::new()
+                .context("Failed to access clipboard")?;
+            clipboard.set_text(&encoded)
+                .context("Failed to write to clipboard")?;
+        } else {
+            anyhow::bail!("Database not available");
+        }
+
+        Ok(())
+    }
+
+    /// Delete selected note
+    fn delete_note(&mut self) -> Result<()> {
+        if let (Some(db), Some(key)) = (&self.db, &self.key) {
+            if !self.notes.is_empty() && self.selected_note < self.notes.len() {
+                let note = &self.notes[self.selected_note];
+                let repo = NoteRepository::new(db.connection());
+                repo.delete(&note.meta.id)?;
+                integrity::seal_notes(db.connection(), key, &[&note.meta.id])?;
+                if self.show_deleted {
+                    // Keep it in the list, shown as deleted
+                    let note = &mut self.notes[self.selected_note];
+                    note.meta.deleted = true;
+                    note.meta.modified_at = chrono::Utc::now();
+                } else {
+                    self.notes.remove(self.selected_note);
+                    if self.selected_note >= self.notes.len() && self.selected_note > 0 {
+                        self.selected_note -= 1;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Permanently delete the notes in the trash; the first call only asks for confirmation
+    fn empty_trash(&mut self, confirmed: bool) -> Result<()> {
+        let (Some(db), Some(key)) = (&mut self.db, &self.key) else {
+            return Ok(());
+        };
+
+        let repo = NoteRepository::new(db.connection());
+        let in_trash = repo.count(true)? - repo.count(false)?;
+        if in_trash == 0 {
+            self.sync_status = Some("Trash is empty".to_string());
+        } else if !confirmed {
+            self.purge_confirm = true;
+            self.sync_status = Some(format!(
+                "Permanently delete {} notes in the trash? Press X again to confirm",
+                in_trash
+            ));
+        } else {
+            let report = trash::empty(db, &self.db_path, key)?;
+            self.notes.retain(|note| !note.meta.deleted);
+            self.selected_note = self.selected_note.min(self.filtered_notes().len().saturating_sub(1));
+            self.sync_status = Some(report.to_string());
+        }
+        Ok(())
+    }
+
+    /// Restore the selected note if it is soft-deleted
+    fn restore_selected_note(&mut self) -> Result<()> {
+        let Some(id) = self
+            .filtered_notes()
+            .get(self.selected_note)
+            .filter(|note| note.meta.deleted)
+            .map(|note| note.meta.id.clone())
+        else {
+            return Ok(());
+        };
+
+        if let (Some(db), Some(key)) = (&self.db, &self.key) {
+            let repo = NoteRepository::new(db.connection());
+            if let Some(mut note) = repo.get(&id, key)? {
+                note.restore();
+                repo.update(&note, key)?;
+                integrity::seal_notes(db.connection(), key, &[&note.id])?;
+                self.update_listed(&note);
+                self.sync_status = Some("Note restored".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Existing tags (from any note) starting with `prefix`, ignoring case, that the
+    /// current note doesn't have yet
+    fn tag_suggestions(&self, prefix: &str) -> Vec<String> {
+        let prefix = prefix.trim().to_lowercase();
+        let mut tags: Vec<String> = self
+            .notes
+            .iter()
+            .flat_map(|note| note.tags.iter())
+            .filter(|tag| tag.to_lowercase().starts_with(&prefix) && !self.current_tags.contains(tag))
+            .cloned()
+            .collect();
+        tags.sort_by_key(|tag| (tag.to_lowercase(), tag.clone()));
+        tags.dedup();
+        tags
+    }
+
+    /// Tab completion: extend to the longest common prefix, then cycle through matches
+    fn complete_tag(&mut self, forward: bool) {
+        if let Some((typed, index)) = &self.tag_cycle {
+            let candidates = self.tag_suggestions(typed);
+            if !candidates.is_empty() {
+                let len = candidates.len();
+                let index = if forward { (index + 1) % len } else { (index + len - 1) % len };
+                self.tag_input = candidates[index].clone();
+                self.tag_cycle = Some((typed.clone(), index));
+            }
+            return;
+        }
 
-            // Copy to clipboard
-            let mut clipboard = arboard::Clipboard::new()
-                .context("Failed to access clipboard")?;
-            clipboard.set_text(&encoded)
-                .context("Failed to write to clipboard")?;
-        } else {
-            anyhow::bail!("Database not available");
+        let candidates = self.tag_suggestions(&self.tag_input);
+        if candidates.is_empty() {
+            return;
         }
 
-        Ok(())
+        let common = candidates[1..].iter().fold(candidates[0].clone(), |common, tag| {
+            common
+                .chars()
+                .zip(tag.chars())
+                .take_while(|(a, b)| a == b)
+                .map(|(a, _)| a)
+                .collect()
+        });
+        if common.chars().count() > self.tag_input.trim().chars().count() {
+            self.tag_input = common;
+            return;
+        }
+
+        // Nothing left to extend: start cycling, skipping the match already typed
+        let index = candidates
+            .iter()
+            .position(|tag| *tag == self.tag_input)
+            .map(|pos| (pos + 1) % candidates.len())
+            .unwrap_or(0);
+        self.tag_cycle = Some((self.tag_input.clone(), index));
+        self.tag_input = candidates[index].clone();
     }
 
-    /// Delete selected note
-    fn delete_note(&mut self) -> Result<()> {
-        if let Some(db) = &self.db {
-            if !self.notes.is_empty() && self.selected_note < self.notes.len() {
-                let note = &self.notes[self.selected_note];
-                let repo = NoteRepository::new(db.connection());
-                repo.delete(&note.id)?;
-                self.notes.remove(self.selected_note);
-                if self.selected_note >= self.notes.len() && self.selected_note > 0 {
-                    self.selected_note -= 1;
-                }
-            }
+    /// Create a new note with the selected note's content and tags, and select it
+    fn duplicate_selected_note(&mut self) -> Result<()> {
+        let Some(source_id) = self.filtered_notes().get(self.selected_note).map(|n| n.meta.id.clone()) else {
+            return Ok(());
+        };
+        let (Some(db), Some(key)) = (&self.db, &self.key) else {
+            return Ok(());
+        };
+        let repo = NoteRepository::new(db.connection());
+        let Some(source) = repo.get(&source_id, key)? else {
+            return Ok(());
+        };
+
+        // Fresh id and timestamps, so sync treats the copy as a separate note
+        let mut copy = Note::new(source.content);
+        copy.tags = source.tags;
+        copy.syntax_language = source.syntax_language;
+        copy.word_wrap = source.word_wrap;
+
+        repo.create(&copy, key)?;
+        integrity::seal_notes(db.connection(), key, &[&copy.id])?;
+
+        self.load_notes()?;
+        if let Some(pos) = self.filtered_notes().iter().position(|n| n.meta.id == copy.id) {
+            self.selected_note = pos;
         }
+        self.sync_status = Some("Note duplicated".to_string());
         Ok(())
     }
 
@@ -1316,14 +3229,62 @@ impl App {
         Ok(content)
     }
 
+    /// Show note content read-only in $JOTTERY_VIEWER or $PAGER
+    fn view_with_external_viewer(&self) -> Result<()> {
+        let viewer = viewer_command(env::var("JOTTERY_VIEWER").ok(), env::var("PAGER").ok());
+        let (program, args) = viewer.split_first().expect("viewer command is never empty");
+
+        // Named after the language so viewers like bat and glow pick the right highlighting
+        let mut temp_file = tempfile::Builder::new()
+            .suffix(&format!(".{}", self.current_language.extension()))
+            .tempfile()
+            .context("Failed to create temporary file")?;
+        temp_file
+            .write_all(self.note_input.as_bytes())
+            .context("Failed to write to temporary file")?;
+        temp_file.flush()?;
+
+        let temp_path = temp_file.path();
+        let mut permissions = std::fs::metadata(temp_path)?.permissions();
+        permissions.set_readonly(true);
+        std::fs::set_permissions(temp_path, permissions)?;
+
+        // Suspend TUI
+        disable_raw_mode().context("Failed to disable raw mode")?;
+        execute!(io::stdout(), LeaveAlternateScreen)
+            .context("Failed to leave alternate screen")?;
+
+        let status = Command::new(program).args(args).arg(temp_path).status();
+
+        // Resume TUI before reporting, whatever happened to the viewer
+        execute!(io::stdout(), EnterAlternateScreen)
+            .context("Failed to enter alternate screen")?;
+        enable_raw_mode().context("Failed to enable raw mode")?;
+
+        match status {
+            Ok(status) if status.success() => Ok(()),
+            Ok(_) => anyhow::bail!("Viewer exited with non-zero status: {}", program),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                anyhow::bail!("Viewer not found: {} (set $JOTTERY_VIEWER or $PAGER)", program)
+            }
+            Err(e) => Err(e).context(format!("Failed to launch viewer: {}", program)),
+        }
+    }
+
     /// Render the UI
     pub fn render(&mut self, frame: &mut Frame) {
+        if matches!(self.state, AppState::NoteList) {
+            self.load_preview_note();
+        }
         match &self.state {
             AppState::Locked => self.render_locked(frame),
             AppState::NoteList => self.render_note_list(frame),
             AppState::NoteView => self.render_note_view(frame),
             AppState::Settings { .. } => self.render_settings(frame),
             AppState::Help { .. } => self.render_help(frame),
+            AppState::ChangePassword { .. } => self.render_change_password(frame),
+            AppState::Conflict => self.render_conflict(frame),
+            AppState::History => self.render_history(frame),
             AppState::Quit => {}
         }
     }
@@ -1345,6 +3306,7 @@ impl App {
         let constraints = if self.is_new_database {
             vec![
                 Constraint::Length(3),  // Password field
+                Constraint::Length(1),  // Strength meter
                 Constraint::Length(3),  // Confirm field
                 Constraint::Length(2),  // Help text
                 Constraint::Length(3),  // Error (if any)
@@ -1375,13 +3337,31 @@ impl App {
             Style::default()
         };
 
-        let password_text = "*".repeat(self.password_input.len());
+        let password_text = "*".repeat(self.password_input.chars().count());
         let password = Paragraph::new(password_text)
             .style(password_style)
             .block(Block::default().title("Password").borders(Borders::ALL));
         frame.render_widget(password, chunks[0]);
 
         if self.is_new_database {
+            // Strength meter (only while creating a password)
+            if !self.password_input.is_empty() {
+                let strength = PasswordStrength::estimate(&self.password_input);
+                let color = match strength {
+                    PasswordStrength::VeryWeak | PasswordStrength::Weak => Color::Red,
+                    PasswordStrength::Fair => Color::Yellow,
+                    PasswordStrength::Strong | PasswordStrength::VeryStrong => Color::Green,
+                };
+                let filled = (strength.score() as usize + 1) * 4;
+                let meter = Line::from(vec![
+                    Span::raw(" Strength: "),
+                    Span::styled("█".repeat(filled), Style::default().fg(color)),
+                    Span::styled("░".repeat(20 - filled), Style::default().fg(Color::DarkGray)),
+                    Span::styled(format!(" {}", strength.label()), Style::default().fg(color)),
+                ]);
+                frame.render_widget(Paragraph::new(meter), chunks[1]);
+            }
+
             // Confirm field
             let confirm_style = if self.password_confirm_focused {
                 Style::default().fg(Color::Yellow)
@@ -1389,27 +3369,27 @@ impl App {
                 Style::default()
             };
 
-            let confirm_text = "*".repeat(self.password_confirm.len());
+            let confirm_text = "*".repeat(self.password_confirm.chars().count());
             let confirm = Paragraph::new(confirm_text)
                 .style(confirm_style)
                 .block(Block::default().title("Confirm Password").borders(Borders::ALL));
-            frame.render_widget(confirm, chunks[1]);
+            frame.render_widget(confirm, chunks[2]);
 
             // Help text
             let help = Paragraph::new("Tab: switch fields | Enter: create")
                 .style(Style::default().fg(Color::DarkGray))
                 .alignment(Alignment::Center);
-            frame.render_widget(help, chunks[2]);
+            frame.render_widget(help, chunks[3]);
 
             // Cursor position
             if self.password_confirm_focused {
                 frame.set_cursor_position((
-                    chunks[1].x + self.password_confirm.len() as u16 + 1,
-                    chunks[1].y + 1,
+                    chunks[2].x + self.password_confirm.chars().count() as u16 + 1,
+                    chunks[2].y + 1,
                 ));
             } else {
                 frame.set_cursor_position((
-                    chunks[0].x + self.password_input.len() as u16 + 1,
+                    chunks[0].x + self.password_input.chars().count() as u16 + 1,
                     chunks[0].y + 1,
                 ));
             }
@@ -1419,12 +3399,12 @@ impl App {
                 let error = Paragraph::new(err.clone())
                     .style(Style::default().fg(Color::Red))
                     .block(Block::default().title("Error").borders(Borders::ALL));
-                frame.render_widget(error, chunks[3]);
+                frame.render_widget(error, chunks[4]);
             }
         } else {
             // Show cursor at end of password input
             frame.set_cursor_position((
-                chunks[0].x + self.password_input.len() as u16 + 1,
+                chunks[0].x + self.password_input.chars().count() as u16 + 1,
                 chunks[0].y + 1,
             ));
 
@@ -1451,23 +3431,70 @@ impl App {
         let content_area = main_layout[0];
         let help_area = main_layout[1];
 
-        // Split content into left (list) and right (preview) panes
-        // Notes pane is fixed width (40 chars), preview takes the rest
+        // Split content into left (list) and right (preview) panes, with the tag
+        // panel in front while it is open
+        // Notes pane width is adjustable (< and >), preview takes the rest
+        let (tag_pane, content_area) = if self.tag_panel_open {
+            let chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Length(24), Constraint::Min(0)])
+                .split(content_area);
+            (Some(chunks[0]), chunks[1])
+        } else {
+            (None, content_area)
+        };
         let main_chunks = Layout::default()
             .direction(Direction::Horizontal)
-            .constraints([Constraint::Length(42), Constraint::Min(0)])
+            .constraints([
+                Constraint::Length(self.note_list_width(content_area.width)),
+                Constraint::Min(0),
+            ])
             .split(content_area);
 
         // Left pane: note list
         let left_pane = main_chunks[0];
         let right_pane = main_chunks[1];
 
+        if let Some(tag_pane) = tag_pane {
+            let items: Vec<ListItem> = self
+                .tag_counts()
+                .into_iter()
+                .enumerate()
+                .map(|(i, (tag, count))| {
+                    let mark = if self.tag_filter.contains(&tag) { "[x]" } else { "[ ]" };
+                    let mut style = Style::default();
+                    if i == self.tag_panel_index {
+                        style = style.fg(Color::Yellow).add_modifier(Modifier::BOLD);
+                    }
+                    ListItem::new(format!("{} #{} ({})", mark, tag, count)).style(style)
+                })
+                .collect();
+            let title = if self.tag_filter_any { "Tags (any)" } else { "Tags (all)" };
+            let panel = List::new(items).block(
+                Block::default()
+                    .title(title)
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(Color::Yellow)),
+            );
+            frame.render_widget(panel, tag_pane);
+        }
+
         // Left pane layout: search bar (optional), list
-        let title = if self.search_active {
-            "Notes (Search)"
-        } else {
-            "Notes"
-        };
+        let mut title = match (self.search_active, self.show_deleted) {
+            (true, true) => "Notes (Search, incl. deleted)",
+            (true, false) => "Notes (Search)",
+            (false, true) => "Notes (incl. deleted)",
+            (false, false) => "Notes",
+        }
+        .to_string();
+        if self.multi_select {
+            title.push_str(&format!(" - {} marked", self.marked_note_ids.len()));
+        }
+        if !self.tag_filter.is_empty() {
+            let separator = if self.tag_filter_any { " | " } else { " " };
+            let tags: Vec<String> = self.tag_filter.iter().map(|t| format!("#{}", t)).collect();
+            title.push_str(&format!(" [{}]", tags.join(separator)));
+        }
 
         let left_constraints = if self.search_active {
             vec![Constraint::Length(3), Constraint::Min(0)]
@@ -1482,10 +3509,25 @@ impl App {
 
         // Render search bar if active
         let list_chunk = if self.search_active {
-            let search_text = format!("Search: {}", self.search_input);
+            let regex = self.search_regex();
+            let search_text = match &regex {
+                Some(Err(error)) => format!("Search: {}  ✗ {}", self.search_input, error),
+                _ => format!("Search: {}", self.search_input),
+            };
+            let search_title = match (regex.is_some(), self.search_case_sensitive) {
+                (true, true) => "Search (regex, Aa: case-sensitive)",
+                (true, false) => "Search (regex)",
+                (false, true) => "Search (Aa: case-sensitive)",
+                (false, false) => "Search",
+            };
+            let search_style = if matches!(regex, Some(Err(_))) {
+                Style::default().fg(Color::Red)
+            } else {
+                Style::default().fg(Color::Yellow)
+            };
             let search_bar = Paragraph::new(search_text)
-                .style(Style::default().fg(Color::Yellow))
-                .block(Block::default().title("Search").borders(Borders::ALL));
+                .style(search_style)
+                .block(Block::default().title(search_title).borders(Borders::ALL));
             frame.render_widget(search_bar, left_chunks[0]);
             left_chunks[1]
         } else {
@@ -1498,76 +3540,136 @@ impl App {
             .borders(Borders::ALL);
 
         let filtered = self.filtered_notes();
-        let items: Vec<ListItem> = filtered
+        let now = chrono::Utc::now();
+        let mut items: Vec<ListItem> = filtered
             .iter()
             .enumerate()
             .map(|(i, note)| {
-                let content = note.content.lines().next().unwrap_or("");
-                let mut preview = if content.len() > 30 {
-                    format!("{}...", &content[..30])
-                } else {
-                    content.to_string()
-                };
-
-                // Add indicators for pinned and attachments
-                let mut indicators = String::new();
-                if note.pinned {
-                    indicators.push_str("📌 ");
+                // Leave room for the borders and indicators
+                let max_width = (left_pane.width as usize).saturating_sub(10).max(8);
+                let first_line = note.preview.lines().next().unwrap_or("");
+                let preview = truncate_to_width(first_line, max_width);
+
+                let mut indicators = self.list_indicators(note);
+                if self.multi_select {
+                    let mark = if self.marked_note_ids.contains(&note.meta.id) { "[x]" } else { "[ ]" };
+                    indicators = format!("{} {}", mark, indicators);
                 }
-                if !note.attachments.is_empty() {
-                    indicators.push_str(&format!("📎{} ", note.attachments.len()));
-                }
-
-                if !indicators.is_empty() {
-                    preview = format!("{}{}", indicators, preview);
+                let mut spans = vec![Span::raw(indicators)];
+                if let Some(state) = note.meta.reminder_state(now) {
+                    let glyph = if self.settings.emoji_icons { "⏰" } else { "R" };
+                    spans.push(Span::styled(format!("{} ", glyph), Style::default().fg(reminder_color(state))));
                 }
+                spans.push(Span::raw(preview));
 
-                let style = if i == self.selected_note {
+                let mut style = if i == self.selected_note {
                     Style::default()
                         .fg(Color::Yellow)
                         .add_modifier(Modifier::BOLD)
                 } else {
                     Style::default()
                 };
+                if note.meta.deleted {
+                    style = style.add_modifier(Modifier::CROSSED_OUT | Modifier::DIM);
+                }
 
-                ListItem::new(preview).style(style)
+                ListItem::new(Line::from(spans)).style(style)
             })
             .collect();
 
+        // A dim divider between the pinned notes and the rest; it is never selected
+        let separator = pinned_separator(&filtered);
+        let mut selected_row = self.selected_note;
+        if let Some(at) = separator {
+            let divider = "─".repeat(list_chunk.width.saturating_sub(2) as usize);
+            items.insert(at, ListItem::new(Line::styled(divider, Style::default().fg(Color::DarkGray))));
+            if selected_row >= at {
+                selected_row += 1;
+            }
+        }
+
+        // The list scrolls to keep the selection in view, including after a resize
         let list = List::new(items).block(list_block);
-        frame.render_widget(list, list_chunk);
+        let mut list_state = ListState::default()
+            .with_offset(self.note_list_offset.get())
+            .with_selected(Some(selected_row));
+        frame.render_stateful_widget(list, list_chunk, &mut list_state);
+        self.note_list_offset.set(list_state.offset());
 
         // Help text (full width at bottom)
-        let status_text = if let Some(ref status) = self.sync_status {
+        let status_text = if let Some(ref err) = self.error {
+            err.clone()
+        } else if matches!(self.sync_worker, Some((_, SyncTrigger::Manual))) {
+            self.sync_progress.lock().map(|p| p.to_string()).unwrap_or_default()
+        } else if let Some(ref status) = self.sync_status {
             status.clone()
+        } else if self.tag_panel_open {
+            "Space: select tag | o: all/any | x: clear | f/Esc: close".to_string()
+        } else if let Some(tag) = &self.bulk_tag_input {
+            format!("Add tag to {} notes: {}_ (Enter: apply | Esc: cancel)", self.marked_note_ids.len(), tag)
+        } else if self.multi_select {
+            "Space: mark | a: all | d: delete | p: pin/unpin | t: add tag | V/Esc: done".to_string()
         } else if self.search_active {
-            "Type: search | Esc: exit | ↑/↓: navigate".to_string()
+            "Type: search (re: for regex) | Ctrl+c: match case | Esc: exit | ↑/↓: navigate".to_string()
+        } else if !self.unreadable_note_ids.is_empty() {
+            let count = self.unreadable_note_ids.len();
+            format!(
+                "{} {} could not be decrypted and {} hidden (run `jottery check`) | /: search | n: new | i: edit",
+                count,
+                if count == 1 { "note" } else { "notes" },
+                if count == 1 { "is" } else { "are" },
+            )
         } else {
-            "/: search | p: pin | y: sync | s: settings | n: new | i: edit".to_string()
+            "/: search | f: tags | p: pin | y: sync | s: settings | n: new | i: edit".to_string()
         };
         let help = Paragraph::new(status_text)
-            .style(if self.sync_status.is_some() {
+            .style(if self.error.is_some() {
+                Style::default().fg(Color::Red)
+            } else if self.sync_status.is_some() {
                 Style::default().fg(Color::Yellow)
             } else {
                 Style::default().fg(Color::DarkGray)
             })
             .alignment(Alignment::Center);
-        frame.render_widget(help, help_area);
+        // Auto-lock countdown, or else overdue reminders, right-aligned beside the help
+        let badge = self
+            .lock_countdown()
+            .map(|countdown| (countdown, Color::Yellow))
+            .or_else(|| self.overdue_badge().map(|overdue| (overdue, Color::Red)));
+        if let Some((badge, color)) = badge {
+            let help_chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Min(0), Constraint::Length(badge.width() as u16)])
+                .split(help_area);
+            frame.render_widget(help, help_chunks[0]);
+            frame.render_widget(
+                Paragraph::new(badge)
+                    .style(Style::default().fg(color))
+                    .alignment(Alignment::Right),
+                help_chunks[1],
+            );
+        } else {
+            frame.render_widget(help, help_area);
+        }
 
         // Right pane: note preview
-        let preview_block = Block::default()
+        let mut preview_block = Block::default()
             .title("Preview")
             .borders(Borders::ALL);
 
         if !filtered.is_empty() && self.selected_note < filtered.len() {
-            let note = filtered[self.selected_note];
+            let title = filtered[self.selected_note];
+            // The full note once decrypted; until then (or while a sync holds the database) the preview text
+            let note = self.preview_note.as_ref().filter(|note| note.id == title.meta.id);
+            let content = note.map_or(title.preview.as_str(), |note| note.content.as_str());
+            let language = note.map_or(SyntaxLanguage::Plain, |note| note.syntax_language);
 
             // Build metadata line (tags and syntax language)
             let mut metadata_parts = Vec::new();
 
             // Show tags (or n/a if none)
-            let tags_str = if !note.tags.is_empty() {
-                note.tags.iter()
+            let tags_str = if !title.tags.is_empty() {
+                title.tags.iter()
                     .map(|t| format!("#{}", t))
                     .collect::<Vec<_>>()
                     .join(" ")
@@ -1577,18 +3679,56 @@ impl App {
             metadata_parts.push(format!("Tags: {}", tags_str));
 
             // Show syntax language
-            metadata_parts.push(format!("Type: {}", note.syntax_language));
+            metadata_parts.push(format!("Type: {}", language));
 
-            let metadata_line = if !metadata_parts.is_empty() {
-                format!("{}\n\n", metadata_parts.join(" | "))
+            let mut preview_lines = vec![Line::from(metadata_parts.join(" | "))];
+            preview_lines.extend(
+                Self::note_date_lines(&title.meta, now, preview_block.inner(right_pane).width)
+                    .into_iter()
+                    .map(|line| Line::styled(line, Style::default().fg(Color::DarkGray))),
+            );
+            if let (Some(at), Some(state)) = (title.meta.reminder_at, title.meta.reminder_state(now)) {
+                preview_lines.push(Line::styled(
+                    format!(
+                        "Reminder: {} ({})",
+                        at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"),
+                        time::until(at, now)
+                    ),
+                    Style::default().fg(reminder_color(state)),
+                ));
+            }
+            preview_lines.push(Line::from(""));
+            if !self.settings.syntax_highlighting {
+                preview_lines.extend(content.lines().map(|line| Line::from(line.to_string())));
+            } else if language == SyntaxLanguage::Markdown {
+                preview_lines.extend(markdown::render(content));
             } else {
-                String::new()
-            };
+                preview_lines.extend(highlight::highlight(content, language));
+            }
 
-            let preview_text = format!("{}{}", metadata_line, note.content);
-            let preview = Paragraph::new(preview_text)
-                .block(preview_block)
-                .wrap(Wrap { trim: false });
+            // Wrapped here rather than by the paragraph, so the scroll range is known
+            let inner = preview_block.inner(right_pane);
+            if note.is_none_or(|note| note.word_wrap) {
+                preview_lines = preview_lines
+                    .into_iter()
+                    .flat_map(|line| wrap_line(line, inner.width as usize))
+                    .collect();
+            }
+            let total = u16::try_from(preview_lines.len()).unwrap_or(u16::MAX);
+            let preview = Paragraph::new(preview_lines);
+
+            // Clamp the scroll to the rendered length and remember it for the scroll keys
+            let max_offset = total.saturating_sub(inner.height);
+            self.preview_extent.set((max_offset, inner.height));
+            let offset = self.preview_offset().min(max_offset);
+            if max_offset > 0 {
+                let last = (offset + inner.height).min(total);
+                preview_block = preview_block.title_bottom(
+                    Line::from(format!(" {}-{}/{} ", offset + 1, last, total)).right_aligned(),
+                );
+            }
+
+            let preview = preview.block(preview_block).scroll((offset, 0));
             frame.render_widget(preview, right_pane);
         } else {
             let preview = Paragraph::new("No notes")
@@ -1596,6 +3736,117 @@ impl App {
                 .alignment(Alignment::Center);
             frame.render_widget(preview, right_pane);
         }
+
+        if self.show_sync_details {
+            if let Some(result) = &self.last_sync {
+                self.render_sync_details(frame, result);
+            }
+        }
+    }
+
+    /// Render popup listing the notes changed by the last sync
+    fn render_sync_details(&self, frame: &mut Frame, result: &SyncResult) {
+        let size = frame.area();
+        let height = (result.changes.len() as u16 + 2).min(size.height.saturating_sub(4)).max(3).min(size.height);
+        let width = 60.min(size.width.saturating_sub(4));
+        let area = Rect::new(
+            size.x + size.width.saturating_sub(width) / 2,
+            size.y + size.height.saturating_sub(height) / 2,
+            width,
+            height,
+        );
+
+        let lines: Vec<Line> = result
+            .changes
+            .iter()
+            .map(|change| {
+                Line::from(vec![
+                    Span::styled(format!("{:<9} ", change.kind), Style::default().fg(Color::Cyan)),
+                    Span::raw(change.title.clone()),
+                ])
+            })
+            .collect();
+
+        let popup = Paragraph::new(lines).block(
+            Block::default()
+                .title(format!("Last sync: {}", result.summary()))
+                .borders(Borders::ALL),
+        );
+        frame.render_widget(Clear, area);
+        frame.render_widget(popup, area);
+    }
+
+    /// Render the syntax language picker centred over `within`
+    fn render_language_picker(&self, frame: &mut Frame, within: Rect) {
+        let height = (SyntaxLanguage::ALL.len() as u16 + 2).min(within.height);
+        let width = 24.min(within.width);
+        let area = Rect::new(
+            within.x + (within.width - width) / 2,
+            within.y + (within.height - height) / 2,
+            width,
+            height,
+        );
+
+        let items: Vec<ListItem> = SyntaxLanguage::ALL
+            .iter()
+            .enumerate()
+            .map(|(i, language)| {
+                let style = if i == self.language_choice {
+                    Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                let prefix = if i == self.language_choice { "→ " } else { "  " };
+                ListItem::new(format!("{}{}", prefix, language)).style(style)
+            })
+            .collect();
+
+        let list = List::new(items).block(Block::default().title("Language").borders(Borders::ALL));
+        frame.render_widget(Clear, area);
+        frame.render_widget(list, area);
+    }
+
+    /// Render the note's attachments, with the path prompt while attaching, centred over `within`
+    fn render_attachments_panel(&self, frame: &mut Frame, within: Rect) {
+        let attachments = self.editing_attachments();
+        let mut items: Vec<ListItem> = attachments
+            .iter()
+            .enumerate()
+            .map(|(i, attachment)| {
+                let selected = i == self.attachment_choice;
+                let style = if selected {
+                    Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                let prefix = if selected { "→ " } else { "  " };
+                ListItem::new(format!("{}{} ({})", prefix, attachment.filename, format_size(attachment.size)))
+                    .style(style)
+            })
+            .collect();
+        if items.is_empty() {
+            items.push(
+                ListItem::new("  (none - press 'a' to attach a file)").style(Style::default().fg(Color::DarkGray)),
+            );
+        }
+        if matches!(self.input_mode, InputMode::AttachPath) {
+            items.push(
+                ListItem::new(format!("Path: {}_", self.attach_input)).style(Style::default().fg(Color::Yellow)),
+            );
+        }
+
+        let height = (items.len() as u16 + 2).min(within.height);
+        let width = 60.min(within.width);
+        let area = Rect::new(
+            within.x + (within.width - width) / 2,
+            within.y + (within.height - height) / 2,
+            width,
+            height,
+        );
+
+        let list = List::new(items).block(Block::default().title("Attachments").borders(Borders::ALL));
+        frame.render_widget(Clear, area);
+        frame.render_widget(list, area);
     }
 
     /// Render note view
@@ -1606,11 +3857,22 @@ impl App {
             InputMode::Normal => "NORMAL",
             InputMode::Insert => "INSERT",
             InputMode::Tag => "TAG",
+            InputMode::Language => "LANGUAGE",
+            InputMode::Attachments | InputMode::AttachPath => "ATTACHMENTS",
+            InputMode::Reminder => "REMINDER",
             InputMode::SettingsEdit => "NORMAL", // Should not happen in note view
         };
 
+        let modified = if self.is_dirty() {
+            " [+]"
+        } else if self.autosaved {
+            " [saved]"
+        } else {
+            ""
+        };
+
         let block = Block::default()
-            .title(format!("Note - {}", mode_text))
+            .title(format!("Note{} - {} - {}", modified, mode_text, self.current_language))
             .borders(Borders::ALL);
 
         let constraints = vec![
@@ -1651,68 +3913,181 @@ impl App {
             Style::default().fg(Color::Blue)
         };
 
-        let tags = Paragraph::new(tags_text)
+        // Matching existing tags on the second line while typing one
+        let mut tag_lines = vec![Line::from(tags_text)];
+        if matches!(self.input_mode, InputMode::Tag) && !self.tag_input.trim().is_empty() {
+            let (typed, selected) = match &self.tag_cycle {
+                Some((typed, index)) => (typed.as_str(), Some(*index)),
+                None => (self.tag_input.as_str(), None),
+            };
+            let suggestions = self.tag_suggestions(typed);
+            if !suggestions.is_empty() {
+                let mut spans = vec![Span::styled("  Tab: ", Style::default().fg(Color::DarkGray))];
+                for (i, tag) in suggestions.iter().enumerate() {
+                    if i > 0 {
+                        spans.push(Span::styled(" · ", Style::default().fg(Color::DarkGray)));
+                    }
+                    let style = if Some(i) == selected {
+                        Style::default().fg(Color::Black).bg(Color::Yellow)
+                    } else {
+                        Style::default().fg(Color::Gray)
+                    };
+                    spans.push(Span::styled(format!("#{}", tag), style));
+                }
+                tag_lines.push(Line::from(spans));
+            }
+        } else if matches!(self.input_mode, InputMode::Reminder) {
+            tag_lines.push(Line::styled(
+                format!("Reminder: {}_", self.reminder_input),
+                Style::default().fg(Color::Yellow),
+            ));
+        } else if let Some(at) = self.current_reminder {
+            let now = chrono::Utc::now();
+            let color = reminder_color(ReminderState::of(at, now));
+            tag_lines.push(Line::styled(
+                format!(
+                    "Reminder: {} ({})",
+                    at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"),
+                    time::until(at, now)
+                ),
+                Style::default().fg(color),
+            ));
+        }
+
+        let tags = Paragraph::new(tag_lines)
             .style(tags_style);
         frame.render_widget(tags, chunks[0]);
 
-        // Render note content
-        let text = Paragraph::new(self.note_input.clone())
+        // Render note content, wrapped here rather than by the paragraph so the cursor is placed
+        // on the same rows; the view scrolls down, and sideways for unwrapped notes, to keep
+        // the cursor in it
+        let inner_width = chunks[1].width.saturating_sub(2) as usize;
+        let inner_height = chunks[1].height.saturating_sub(2) as usize;
+        let wrap_width = if self.current_word_wrap { inner_width } else { usize::MAX };
+        let rows = editor::wrap_rows(&self.note_input, wrap_width);
+        let (cursor_row, cursor_column) =
+            editor::cursor_row_col(&self.note_input, &rows, self.note_cursor, wrap_width);
+        let h_scroll = cursor_column.saturating_sub(inner_width.saturating_sub(1));
+
+        let mut v_scroll = self.editor_scroll.get();
+        if cursor_row < v_scroll {
+            v_scroll = cursor_row;
+        } else if cursor_row >= v_scroll + inner_height.max(1) {
+            v_scroll = cursor_row + 1 - inner_height.max(1);
+        }
+        self.editor_scroll.set(v_scroll);
+
+        let lines: Vec<Line> = rows.iter().map(|row| Line::from(&self.note_input[row.clone()])).collect();
+        let text = Paragraph::new(lines)
             .block(block)
-            .wrap(Wrap { trim: false });
+            .scroll((v_scroll as u16, h_scroll as u16));
         frame.render_widget(text, chunks[1]);
 
         // Help text
         let help = match self.input_mode {
             InputMode::Normal | InputMode::SettingsEdit => {
-                Paragraph::new("i: insert | t: tags | q/Esc: save & quit")
+                Paragraph::new(format!(
+                    "i: insert | t: tags | a: attachments | R: reminder | L: language | w: wrap ({}) | u/Ctrl+r: undo/redo | q/Esc: save & quit",
+                    if self.current_word_wrap { "on" } else { "off" }
+                ))
                     .style(Style::default().fg(Color::DarkGray))
                     .alignment(Alignment::Center)
             }
             InputMode::Insert => {
-                Paragraph::new("Esc: normal mode | Type to edit")
+                Paragraph::new("Esc: normal mode | Type to edit | Arrows/Home/End: move cursor")
                     .style(Style::default().fg(Color::DarkGray))
                     .alignment(Alignment::Center)
             }
             InputMode::Tag => {
-                Paragraph::new("Type tag name | Enter: add | Backspace: remove last | Esc: exit")
+                Paragraph::new("Type tag name | Tab: complete | Enter: add | Backspace: remove last | Esc: exit")
+                    .style(Style::default().fg(Color::DarkGray))
+                    .alignment(Alignment::Center)
+            }
+            InputMode::Language => {
+                Paragraph::new("↑/↓ or j/k: choose language | Enter: apply | Esc: cancel")
+                    .style(Style::default().fg(Color::DarkGray))
+                    .alignment(Alignment::Center)
+            }
+            InputMode::Attachments if self.remove_attachment_confirm => {
+                let filename = self
+                    .editing_attachments()
+                    .get(self.attachment_choice)
+                    .map_or("the attachment", |a| a.filename.as_str());
+                Paragraph::new(format!("Remove {} from the note? (y/N)", filename))
+                    .style(Style::default().fg(Color::Yellow))
+                    .alignment(Alignment::Center)
+            }
+            InputMode::Attachments => {
+                Paragraph::new("↑/↓ or j/k: choose | a: attach file | o/Enter: open | x: remove | Esc: close")
+                    .style(Style::default().fg(Color::DarkGray))
+                    .alignment(Alignment::Center)
+            }
+            InputMode::AttachPath => {
+                Paragraph::new("Type the path of a file | Enter: attach | Esc: cancel")
+                    .style(Style::default().fg(Color::DarkGray))
+                    .alignment(Alignment::Center)
+            }
+            InputMode::Reminder => {
+                Paragraph::new("+30m, +2h, +3d, HH:MM or YYYY-MM-DD [HH:MM] | Enter: set (empty clears) | Esc: cancel")
                     .style(Style::default().fg(Color::DarkGray))
                     .alignment(Alignment::Center)
             }
         };
-        frame.render_widget(help, chunks[2]);
+
+        // Live counts, right-aligned beside the help
+        let stats = TextStats::of(&self.note_input);
+        let counts = format!(
+            "{} words · {} chars · {} lines ",
+            stats.words, stats.chars, stats.lines
+        );
+        let help_chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Min(0), Constraint::Length(counts.chars().count() as u16)])
+            .split(chunks[2]);
+        frame.render_widget(help, help_chunks[0]);
+        frame.render_widget(
+            Paragraph::new(counts)
+                .style(Style::default().fg(Color::DarkGray))
+                .alignment(Alignment::Right),
+            help_chunks[1],
+        );
+
+        if matches!(self.input_mode, InputMode::Language) {
+            self.render_language_picker(frame, chunks[1]);
+        }
+        if matches!(self.input_mode, InputMode::Attachments | InputMode::AttachPath) {
+            self.render_attachments_panel(frame, chunks[1]);
+        }
 
         // Show cursor
         match self.input_mode {
             InputMode::Insert => {
-                // Calculate cursor position at end of text
-                let lines: Vec<&str> = self.note_input.lines().collect();
-                let line_count = if self.note_input.is_empty() {
-                    0
-                } else {
-                    lines.len().saturating_sub(1)
-                };
-                let last_line_len = lines.last().map(|l| l.len()).unwrap_or(0);
-
                 frame.set_cursor_position((
-                    chunks[1].x + 1 + last_line_len as u16,
-                    chunks[1].y + 1 + line_count as u16,
+                    chunks[1].x + 1 + (cursor_column - h_scroll) as u16,
+                    chunks[1].y + 1 + (cursor_row - v_scroll) as u16,
                 ));
             }
             InputMode::Tag => {
                 // Cursor after tag input
-                let tag_prefix_len = if self.current_tags.is_empty() {
-                    "Tags: ".len()
+                let tag_prefix_width = if self.current_tags.is_empty() {
+                    "Tags: ".width()
                 } else {
                     format!("Tags: {} ",
                         self.current_tags.iter().map(|t| format!("#{}", t)).collect::<Vec<_>>().join(" ")
-                    ).len()
+                    ).width()
                 };
 
                 frame.set_cursor_position((
-                    chunks[0].x + tag_prefix_len as u16 + self.tag_input.len() as u16,
+                    chunks[0].x + tag_prefix_width as u16 + self.tag_input.width() as u16,
                     chunks[0].y,
                 ));
             }
+            InputMode::Reminder => {
+                frame.set_cursor_position((
+                    chunks[0].x + "Reminder: ".len() as u16 + self.reminder_input.width() as u16,
+                    chunks[0].y + 1,
+                ));
+            }
             _ => {}
         }
     }
@@ -1736,7 +4111,7 @@ impl App {
             let selected = index == self.selected_setting;
             let editing = selected && matches!(self.input_mode, InputMode::SettingsEdit);
 
-            let display_value = if editing && (index == 0 || index == 3 || index == 5) {
+            let display_value = if editing && is_text_setting(index) {
                 // Show input buffer for editable fields
                 format!("{}_", self.setting_input)
             } else {
@@ -1773,13 +4148,45 @@ impl App {
             field_line(1, "Theme:                 ".to_string(), format!("{} (press Enter to cycle)", self.settings.theme)),
             field_line(2, "Sort Order:            ".to_string(), format!("{} (press Enter to cycle)", self.settings.sort_order)),
             field_line(3, "Auto-lock Timeout:     ".to_string(), format!("{} minutes", self.settings.auto_lock_timeout)),
+            field_line(4, "Trailing Newline:      ".to_string(), format!("{} (press Enter to cycle)", self.settings.trailing_newline)),
             Line::from(""),
             Line::from(vec![
                 Span::styled("Sync Settings", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
             ]),
             Line::from(""),
-            field_line(4, "Sync Enabled:          ".to_string(), format!("{} (press Enter to toggle)", if self.settings.sync_enabled { "Yes" } else { "No" })),
-            field_line(5, "Sync Endpoint:         ".to_string(), self.settings.sync_endpoint.clone().unwrap_or_else(|| "Not configured".to_string())),
+            field_line(5, "Sync Enabled:          ".to_string(), format!("{} (press Enter to toggle)", if self.settings.sync_enabled { "Yes" } else { "No" })),
+            field_line(6, "Sync Endpoint:         ".to_string(), self.settings.sync_endpoint.clone().unwrap_or_else(|| "Not configured".to_string())),
+            field_line(7, "Pull Only:             ".to_string(), format!("{} (press Enter to toggle)", if self.settings.sync_pull_only { "Yes - local edits are never pushed" } else { "No" })),
+            field_line(8, "Conflict Policy:       ".to_string(), format!("{} (press Enter to cycle)", self.settings.conflict_policy)),
+            field_line(9, "Auto-sync Interval:    ".to_string(), if self.auto_sync_interval > 0 { format!("{} minutes", self.auto_sync_interval) } else { "Disabled".to_string() }),
+            field_line(10, "Sync Attempts:         ".to_string(), if self.settings.sync_max_attempts > 1 { format!("{} (retries transient failures)", self.settings.sync_max_attempts) } else { "1 (no retries)".to_string() }),
+            Line::from(vec![
+                Span::raw("  Status:                "),
+                Span::styled(
+                    self.sync_health.summary(chrono::Utc::now()),
+                    Style::default().fg(if self.sync_health.conflict_count > 0 { Color::Red } else { Color::DarkGray }),
+                ),
+            ]),
+            Line::from(""),
+            Line::from(vec![
+                Span::styled("Display Settings", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+            ]),
+            Line::from(""),
+            field_line(11, "Syntax Highlighting:   ".to_string(), format!("{} (press Enter to toggle)", if self.settings.syntax_highlighting { "On" } else { "Off" })),
+            field_line(12, "List Icons:            ".to_string(), format!("{} (press Enter to toggle)", if self.settings.emoji_icons { "Emoji (📌 📎)" } else { "ASCII (* @)" })),
+            field_line(13, "Pin Limit:             ".to_string(), if self.settings.pin_limit > 0 { format!("{} pinned notes", self.settings.pin_limit) } else { "No limit".to_string() }),
+            Line::from(""),
+            Line::from(vec![
+                Span::styled("Security Settings", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+            ]),
+            Line::from(""),
+            field_line(14, "Key Iterations:        ".to_string(), format!("{} (PBKDF2; raising it re-encrypts all notes)", self.key_iterations)),
+            Line::from(""),
+            Line::from(vec![
+                Span::styled("Editor Settings", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+            ]),
+            Line::from(""),
+            field_line(15, "Autosave:              ".to_string(), if self.settings.autosave_seconds > 0 { format!("after {} seconds idle", self.settings.autosave_seconds) } else { "Disabled (saves when leaving the editor)".to_string() }),
             Line::from(""),
             Line::from(""),
             Line::from(vec![
@@ -1795,6 +4202,12 @@ impl App {
             ]),
             Line::from("  • Press 'p' to paste sync credentials from clipboard"),
             Line::from("  • Press 'c' to copy sync credentials to clipboard"),
+            Line::from("  • Press 'R' to rotate the sync API key (the old key stops working)"),
+            Line::from(""),
+            Line::from(vec![
+                Span::styled("Security: ", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+            ]),
+            Line::from("  • Press 'P' to change the master password"),
         ];
 
         // Add status and error messages if present
@@ -1820,21 +4233,205 @@ impl App {
 
         frame.render_widget(paragraph, size);
 
-        // Show cursor when editing text fields
-        if matches!(self.input_mode, InputMode::SettingsEdit) && (self.selected_setting == 0 || self.selected_setting == 3 || self.selected_setting == 5) {
-            // Calculate cursor position based on selected field
-            let line_offset = match self.selected_setting {
-                0 => 2,  // Language is on line 2
-                3 => 5,  // Auto-lock timeout is on line 5
-                5 => 10, // Sync endpoint is on line 10
-                _ => 0,
-            };
+        // Show cursor when editing text fields
+        if matches!(self.input_mode, InputMode::SettingsEdit) && is_text_setting(self.selected_setting) {
+            // Calculate cursor position based on selected field
+            let line_offset = match self.selected_setting {
+                0 => 2,  // Language is on line 2
+                3 => 5,  // Auto-lock timeout is on line 5
+                6 => 11, // Sync endpoint is on line 11
+                9 => 14, // Auto-sync interval is on line 14
+                10 => 15, // Sync attempts is on line 15
+                13 => 22, // Pin limit is on line 22
+                14 => 26, // Key iterations is on line 26
+                15 => 30, // Autosave is on line 30
+                _ => 0,
+            };
+
+            let cursor_x = 26 + self.setting_input.width() as u16; // After label
+            let cursor_y = line_offset + 1; // +1 for border
+
+            frame.set_cursor_position((cursor_x, cursor_y));
+        }
+    }
+
+    /// Render the change password form (just the current password when raising iterations)
+    fn render_change_password(&self, frame: &mut Frame) {
+        let size = frame.area();
+
+        let title = match self.pending_iterations {
+            Some(_) => "Raise Key Iterations - Enter: confirm | Esc: cancel",
+            None => "Change Password - Tab: switch fields | Enter: next/confirm | Esc: cancel",
+        };
+        let block = Block::default()
+            .title(title)
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::Green));
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(2)
+            .constraints([
+                Constraint::Length(3), // Current password
+                Constraint::Length(3), // New password
+                Constraint::Length(3), // Confirm new password
+                Constraint::Length(2), // Help text
+                Constraint::Length(3), // Error (if any)
+                Constraint::Min(0),    // Remaining space
+            ])
+            .split(size);
+
+        frame.render_widget(block, size);
+
+        let titles = ["Current Password", "New Password", "Confirm New Password"];
+        let fields = if self.pending_iterations.is_some() { 1 } else { titles.len() };
+        for (index, title) in titles.iter().enumerate().take(fields) {
+            let style = if index == self.password_change_field {
+                Style::default().fg(Color::Yellow)
+            } else {
+                Style::default()
+            };
+            let field = Paragraph::new("*".repeat(self.password_change[index].chars().count()))
+                .style(style)
+                .block(Block::default().title(*title).borders(Borders::ALL));
+            frame.render_widget(field, chunks[index]);
+        }
+
+        let help_text = match self.pending_iterations {
+            Some(iterations) => format!("All notes are re-encrypted with a key derived with {} iterations; this may take a moment", iterations),
+            None => "All notes are re-encrypted with the new password; this may take a moment".to_string(),
+        };
+        let help = Paragraph::new(help_text)
+            .style(Style::default().fg(Color::DarkGray))
+            .alignment(Alignment::Center);
+        frame.render_widget(help, chunks[3]);
+
+        let focused = chunks[self.password_change_field];
+        frame.set_cursor_position((
+            focused.x + self.password_change[self.password_change_field].chars().count() as u16 + 1,
+            focused.y + 1,
+        ));
+
+        if let Some(err) = &self.error {
+            let error = Paragraph::new(err.clone())
+                .style(Style::default().fg(Color::Red))
+                .block(Block::default().title("Error").borders(Borders::ALL));
+            frame.render_widget(error, chunks[4]);
+        }
+    }
+
+    /// Render the conflict view: local and server versions side by side
+    fn render_conflict(&self, frame: &mut Frame) {
+        let size = frame.area();
+        let Some(conflict) = self.conflicts.get(self.conflict_index) else {
+            return;
+        };
+
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Min(0), Constraint::Length(1)])
+            .split(size);
+        let panes = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(layout[1]);
+
+        let heading = format!(
+            "Sync conflict {}/{}: {}{}",
+            self.conflict_index + 1,
+            self.conflicts.len(),
+            conflict.local.title(),
+            conflict.reason.as_ref().map(|r| format!(" ({})", r)).unwrap_or_default()
+        );
+        frame.render_widget(
+            Paragraph::new(heading).style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+            layout[0],
+        );
+
+        for (pane, (label, note)) in panes.iter().zip([("Local", &conflict.local), ("Server", &conflict.remote)]) {
+            let title = format!(
+                "{} - modified {}{}",
+                label,
+                note.modified_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"),
+                if note.tags.is_empty() { String::new() } else { format!(" - #{}", note.tags.join(" #")) }
+            );
+            let body = Paragraph::new(note.content.clone())
+                .block(Block::default().title(title).borders(Borders::ALL))
+                .wrap(Wrap { trim: false });
+            frame.render_widget(body, *pane);
+        }
+
+        let help_text = match &self.error {
+            Some(err) => err.clone(),
+            None => "l: keep local | r: keep server | n: keep server, save local as new note | Tab: next | Esc: back".to_string(),
+        };
+        let help = Paragraph::new(help_text)
+            .style(Style::default().fg(if self.error.is_some() { Color::Red } else { Color::DarkGray }))
+            .alignment(Alignment::Center);
+        frame.render_widget(help, layout[2]);
+    }
+
+    /// Render the versions of a note: list on the left, the highlighted one on the right
+    fn render_history(&self, frame: &mut Frame) {
+        let size = frame.area();
+        let Some(selected) = self.history.get(self.history_index) else {
+            return;
+        };
 
-            let cursor_x = 26 + self.setting_input.len() as u16; // After label
-            let cursor_y = line_offset + 1; // +1 for border
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Min(0), Constraint::Length(1)])
+            .split(size);
+        let panes = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(35), Constraint::Percentage(65)])
+            .split(layout[1]);
+
+        let current = self.notes.iter().find(|n| n.meta.id == selected.note_id);
+        let heading = format!(
+            "History of {} ({} earlier versions)",
+            current.map(|n| n.title()).unwrap_or_else(|| selected.title()),
+            self.history.len()
+        );
+        frame.render_widget(
+            Paragraph::new(heading).style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+            layout[0],
+        );
+
+        let items: Vec<ListItem> = self
+            .history
+            .iter()
+            .map(|version| {
+                let when = version.modified_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M");
+                ListItem::new(format!("v{} {} {}", version.version, when, version.title()))
+            })
+            .collect();
+        let mut state = ListState::default();
+        state.select(Some(self.history_index));
+        let list = List::new(items)
+            .block(Block::default().title("Versions").borders(Borders::ALL))
+            .highlight_style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))
+            .highlight_symbol("> ");
+        frame.render_stateful_widget(list, panes[0], &mut state);
+
+        let title = format!(
+            "Version {}{}",
+            selected.version,
+            if selected.tags.is_empty() { String::new() } else { format!(" - #{}", selected.tags.join(" #")) }
+        );
+        let body = Paragraph::new(selected.content.clone())
+            .block(Block::default().title(title).borders(Borders::ALL))
+            .wrap(Wrap { trim: false });
+        frame.render_widget(body, panes[1]);
 
-            frame.set_cursor_position((cursor_x, cursor_y));
-        }
+        let help_text = match &self.error {
+            Some(err) => err.clone(),
+            None => "j/k: choose version | r/Enter: restore (current content is kept in history) | Esc: back".to_string(),
+        };
+        let help = Paragraph::new(help_text)
+            .style(Style::default().fg(if self.error.is_some() { Color::Red } else { Color::DarkGray }))
+            .alignment(Alignment::Center);
+        frame.render_widget(help, layout[2]);
     }
 
     /// Render help screen
@@ -1859,21 +4456,33 @@ impl App {
             Line::from(vec![
                 Span::styled("NOTE LIST", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
             ]),
-            Line::from("  /                     Enter search mode"),
+            Line::from("  /                     Enter search mode (re: prefix for regex)"),
+            Line::from("  f                     Filter by tags (Space: select, o: all/any, x: clear)"),
+            Line::from("  V                     Multi-select (Space: mark, a: all, d/p/t: delete/pin/tag)"),
             Line::from("  y                     Sync notes (if configured)"),
+            Line::from("  Y                     Show what the last sync changed"),
+            Line::from("  C                     Resolve sync conflicts (l: local, r: server, n: both)"),
+            Line::from("  H                     Browse and restore earlier versions of the note"),
             Line::from("  s                     Show settings"),
             Line::from("  n                     Create new note"),
             Line::from("  i / Enter             Edit selected note"),
             Line::from("  d                     Delete selected note"),
+            Line::from("  D                     Show/hide deleted notes"),
+            Line::from("  r                     Restore selected deleted note"),
+            Line::from("  c                     Duplicate selected note"),
+            Line::from("  X                     Empty the trash (press twice)"),
             Line::from("  j / ↓                 Move down"),
             Line::from("  k / ↑                 Move up"),
+            Line::from("  Ctrl+d / Ctrl+u       Scroll preview (also PgDn / PgUp)"),
+            Line::from("  < / >                 Narrow/widen the note list"),
+            Line::from("  v                     Show/hide the preview"),
             Line::from("  ?                     Show this help"),
             Line::from("  Ctrl+q                Quit application"),
             Line::from(""),
             Line::from(vec![
                 Span::styled("SEARCH MODE", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
             ]),
-            Line::from("  Type                  Enter search query"),
+            Line::from("  Type                  Enter search query (matches word prefixes)"),
             Line::from("  #tag                  Search by tag"),
             Line::from("  -word                 Exclude word (negation)"),
             Line::from("  word1 word2           Match all words (AND)"),
@@ -1886,7 +4495,14 @@ impl App {
             ]),
             Line::from("  i                     Enter insert mode"),
             Line::from("  e                     Edit with external $EDITOR"),
+            Line::from("  v                     View in $JOTTERY_VIEWER / $PAGER"),
+            Line::from("  a                     Attachments (a: attach, o: open, x: remove)"),
             Line::from("  t                     Enter tag mode"),
+            Line::from("  w                     Toggle word wrap"),
+            Line::from("  L                     Pick syntax language"),
+            Line::from("  R                     Set or clear a reminder (+2h, 18:00, 2024-06-01 09:30)"),
+            Line::from("  u / Ctrl+z            Undo"),
+            Line::from("  Ctrl+r / Ctrl+y       Redo"),
             Line::from("  ?                     Show this help"),
             Line::from("  q / Esc               Save and return to list"),
             Line::from(""),
@@ -1895,7 +4511,10 @@ impl App {
             ]),
             Line::from("  Type                  Edit note content"),
             Line::from("  Enter                 New line"),
-            Line::from("  Backspace / Delete    Delete character"),
+            Line::from("  Backspace / Delete    Delete character before / after cursor"),
+            Line::from("  ←/→/↑/↓               Move cursor"),
+            Line::from("  Home / End            Start / end of line"),
+            Line::from("  Ctrl+z / Ctrl+y       Undo / redo"),
             Line::from("  Esc                   Exit to normal mode"),
             Line::from(""),
             Line::from(vec![
@@ -1916,6 +4535,7 @@ impl App {
             Line::from("  Esc                   Cancel editing (text/number fields)"),
             Line::from("  p                     Paste sync credentials from clipboard"),
             Line::from("  c                     Copy sync credentials to clipboard"),
+            Line::from("  P                     Change master password"),
             Line::from("  s / q                 Close settings panel"),
             Line::from(""),
             Line::from(vec![
@@ -1935,4 +4555,1611 @@ impl App {
     pub fn should_quit(&self) -> bool {
         matches!(self.state, AppState::Quit)
     }
+
+    /// "locks in m:ss" once auto-lock is less than a minute away
+    fn lock_countdown(&self) -> Option<String> {
+        let remaining = self.key_manager.time_until_lock()?;
+        if remaining >= LOCK_COUNTDOWN_THRESHOLD {
+            return None;
+        }
+        let secs = remaining.as_secs_f64().ceil() as u64;
+        Some(format!("locks in {}:{:02} ", secs / 60, secs % 60))
+    }
+
+    /// Overdue reminder count for the status bar, if any are overdue
+    fn overdue_badge(&self) -> Option<String> {
+        let glyph = if self.settings.emoji_icons { "⏰" } else { "R" };
+        match self.overdue_reminders {
+            0 => None,
+            1 => Some(format!("{} 1 overdue reminder ", glyph)),
+            n => Some(format!("{} {} overdue reminders ", glyph, n)),
+        }
+    }
+
+    /// Recount overdue reminders; skipped while a sync holds the database
+    fn refresh_overdue_reminders(&mut self) {
+        if let Some(db) = &self.db {
+            match NoteRepository::new(db.connection()).count_overdue(chrono::Utc::now()) {
+                Ok(count) => self.overdue_reminders = count as usize,
+                Err(e) => self.debug_log(&format!("refresh_overdue_reminders - {}", e)),
+            }
+            self.reminders_checked_at = Some(Instant::now());
+        }
+    }
+
+    /// Periodic check from the event loop; locks after inactivity even without key presses
+    pub fn tick(&mut self) {
+        if let Some((worker, trigger)) = &self.sync_worker {
+            if let Some(outcome) = worker.poll() {
+                let trigger = *trigger;
+                self.sync_worker = None;
+                self.finish_sync(trigger, outcome);
+            }
+        }
+
+        // Only sync from the list, so a pull never races an open editor buffer
+        let sync_due = self.auto_sync.as_ref().is_some_and(|timer| timer.poll());
+        if sync_due && self.sync_worker.is_none() && matches!(self.state, AppState::NoteList) {
+            self.debug_log("tick - Auto-sync timer fired");
+            self.start_sync(SyncTrigger::Auto);
+        }
+
+        if self.reminders_checked_at.is_some_and(|at| at.elapsed() >= REMINDER_CHECK_INTERVAL) {
+            self.refresh_overdue_reminders();
+        }
+
+        let idle = Duration::from_secs(self.settings.autosave_seconds.max(0) as u64);
+        if matches!(self.state, AppState::NoteView)
+            && self.settings.autosave_seconds > 0
+            && self.last_edit_at.is_some_and(|at| at.elapsed() >= idle)
+        {
+            self.autosave();
+        }
+
+        if self.key_manager.should_lock() {
+            self.debug_log("tick - Auto-lock timeout reached");
+
+            // Keep unsaved edits rather than discarding them
+            if matches!(self.state, AppState::NoteView) {
+                if let Err(e) = self.save_note() {
+                    self.debug_log(&format!("tick - Failed to save note before lock: {}", e));
+                }
+            }
+
+            self.lock();
+            self.error = Some(format!(
+                "Locked after {} minutes of inactivity",
+                self.settings.auto_lock_timeout
+            ));
+        }
+    }
+
+    /// Lock the app: drop (and so zeroize) the key, close the database and clear decrypted data
+    fn lock(&mut self) {
+        // Locking must not wait on the network, nor leave a sync running with the key
+        self.cancel_sync();
+        self.key_manager.clear_master_key();
+        self.key = None;
+        self.db = None;
+        self.auto_sync = None;
+
+        self.notes.clear();
+        self.preview_note = None;
+        self.all_notes_loaded = true;
+        self.last_listed = None;
+        self.unreadable_note_ids.clear();
+        self.thumbnail_ids.clear();
+        self.selected_note = 0;
+        self.note_input.clear();
+        self.note_cursor = 0;
+        self.undo_history.clear();
+        self.current_tags.clear();
+        self.current_attachments.clear();
+        self.tag_input.clear();
+        self.attach_input.clear();
+        self.opened_attachments.clear();
+        self.current_reminder = None;
+        self.reminder_input.clear();
+        self.overdue_reminders = 0;
+        self.reminders_checked_at = None;
+        self.editing_note_id = None;
+        self.search_input.clear();
+        self.search_active = false;
+        self.tag_filter.clear();
+        self.tag_panel_open = false;
+        self.multi_select = false;
+        self.marked_note_ids.clear();
+        self.bulk_tag_input = None;
+        self.sync_status = None;
+        self.sync_health = SyncStatusDisplay::default();
+        self.conflicts.clear();
+        self.history.clear();
+        self.last_sync = None;
+        self.show_sync_details = false;
+        self.mark_saved();
+
+        self.password_input.clear();
+        self.password_confirm.clear();
+        self.password_confirm_focused = false;
+        self.weak_password_confirmed = false;
+        self.password_change = Default::default();
+        self.password_change_field = 0;
+        self.pending_iterations = None;
+        self.is_new_database = !self.db_path.exists();
+        self.input_mode = InputMode::Normal;
+        self.state = AppState::Locked;
+    }
+}
+
+/// Message for a failed unlock, pointing at the likely cause when the notes can't be read
+fn unlock_error_message(e: &anyhow::Error) -> String {
+    match e.downcast_ref::<RepoError>() {
+        Some(RepoError::Decrypt { what, .. }) => {
+            format!("Failed to unlock: {} could not be decrypted. Check the password and keyfile.", what)
+        }
+        Some(RepoError::Corrupt { .. }) => {
+            format!("Failed to unlock: {}. The database may be damaged; run `jottery check`.", e)
+        }
+        _ => format!("Failed to unlock: {}", e),
+    }
+}
+
+/// Viewer to show a note with: `$JOTTERY_VIEWER`, then `$PAGER`, then `less`, split into words
+fn viewer_command(jottery_viewer: Option<String>, pager: Option<String>) -> Vec<String> {
+    [jottery_viewer, pager]
+        .into_iter()
+        .flatten()
+        .map(|command| command.split_whitespace().map(str::to_string).collect::<Vec<_>>())
+        .find(|words| !words.is_empty())
+        .unwrap_or_else(|| vec!["less".to_string()])
+}
+
+/// Settings fields edited by typing a value; the others toggle or cycle on Enter
+fn is_text_setting(index: usize) -> bool {
+    matches!(index, 0 | 3 | 6 | 9 | 10 | 13 | 14 | 15)
+}
+
+/// Colour a reminder is drawn in: red once overdue, yellow when due soon
+fn reminder_color(state: ReminderState) -> Color {
+    match state {
+        ReminderState::Overdue => Color::Red,
+        ReminderState::Upcoming => Color::Yellow,
+        ReminderState::Later => Color::DarkGray,
+    }
+}
+
+/// Split a styled line into the screen rows it takes up at `width` columns (see
+/// [`editor::wrap_rows`]), keeping each span's style
+fn wrap_line<'a>(line: Line<'a>, width: usize) -> Vec<Line<'a>> {
+    let text: String = line.spans.iter().map(|span| span.content.as_ref()).collect();
+    let rows = editor::wrap_rows(&text, width);
+    if rows.len() <= 1 {
+        return vec![line];
+    }
+    rows.into_iter()
+        .map(|row| {
+            let mut spans = Vec::new();
+            let mut start = 0;
+            for span in &line.spans {
+                let end = start + span.content.len();
+                let (from, to) = (row.start.max(start), row.end.min(end));
+                if from < to {
+                    spans.push(Span::styled(span.content[from - start..to - start].to_string(), span.style));
+                }
+                start = end;
+            }
+            Line::from(spans).style(line.style)
+        })
+        .collect()
+}
+
+/// Byte count for display, e.g. `4.5 KiB`
+fn format_size(bytes: i64) -> String {
+    match bytes {
+        b if b < 1024 => format!("{} B", b),
+        b if b < 1024 * 1024 => format!("{:.1} KiB", b as f64 / 1024.0),
+        b => format!("{:.1} MiB", b as f64 / (1024.0 * 1024.0)),
+    }
+}
+
+/// Cut `text` to at most `max_width` terminal columns, ending with `…` if shortened
+///
+/// Cuts between chars, so multi-byte and double-width characters (emoji, CJK) are
+/// never split.
+fn truncate_to_width(text: &str, max_width: usize) -> String {
+    if text.width() <= max_width {
+        return text.to_string();
+    }
+    let mut truncated = String::new();
+    let mut width = 0;
+    for c in text.chars() {
+        let char_width = c.width().unwrap_or(0);
+        // Keep a column for the ellipsis
+        if width + char_width >= max_width {
+            break;
+        }
+        truncated.push(c);
+        width += char_width;
+    }
+    truncated.push('…');
+    truncated
+}
+
+/// Sort notes for display: pinned first, then by the configured sort order
+fn sort_notes(notes: &mut [&NotePreview], sort_order: SortOrder) {
+    notes.sort_by(|a, b| {
+        b.meta.pinned.cmp(&a.meta.pinned).then_with(|| match sort_order {
+            SortOrder::Recent => b.meta.modified_at.cmp(&a.meta.modified_at),
+            SortOrder::Oldest => a.meta.modified_at.cmp(&b.meta.modified_at),
+            SortOrder::Created => b.meta.created_at.cmp(&a.meta.created_at),
+            SortOrder::Alpha => first_line_lower(a).cmp(&first_line_lower(b)),
+        })
+    });
+}
+
+/// Row of a sorted note list where the unpinned notes start, when both kinds are listed
+fn pinned_separator(notes: &[&NotePreview]) -> Option<usize> {
+    let split = notes.iter().position(|note| !note.meta.pinned)?;
+    (split > 0).then_some(split)
+}
+
+/// Lowercased first line of a note, used for alphabetical sorting
+fn first_line_lower(note: &NotePreview) -> String {
+    note.preview.lines().next().unwrap_or("").to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+    use ratatui::{backend::TestBackend, Terminal};
+
+    fn note(content: &str, created_mins_ago: i64, modified_mins_ago: i64) -> Note {
+        let now = Utc::now();
+        let mut note = Note::new(content.to_string());
+        note.created_at = now - Duration::minutes(created_mins_ago);
+        note.modified_at = now - Duration::minutes(modified_mins_ago);
+        note
+    }
+
+    fn sorted(notes: &[Note], sort_order: SortOrder) -> Vec<String> {
+        let titles = listed(notes);
+        let mut refs: Vec<&NotePreview> = titles.iter().collect();
+        sort_notes(&mut refs, sort_order);
+        refs.iter().map(|n| n.preview.clone()).collect()
+    }
+
+    /// Titles of `notes`, as the note list holds them
+    fn listed(notes: &[Note]) -> Vec<NotePreview> {
+        notes.iter().map(NotePreview::from).collect()
+    }
+
+    #[test]
+    fn test_sort_notes_by_order() {
+        let mut pinned = note("zebra pinned", 50, 40);
+        pinned.pinned = true;
+        let notes = vec![
+            note("banana", 30, 5),
+            note("Apple", 10, 20),
+            note("cherry", 20, 10),
+            pinned,
+        ];
+
+        assert_eq!(
+            sorted(&notes, SortOrder::Recent),
+            ["zebra pinned", "banana", "cherry", "Apple"]
+        );
+        assert_eq!(
+            sorted(&notes, SortOrder::Oldest),
+            ["zebra pinned", "Apple", "cherry", "banana"]
+        );
+        assert_eq!(
+            sorted(&notes, SortOrder::Created),
+            ["zebra pinned", "Apple", "cherry", "banana"]
+        );
+        assert_eq!(
+            sorted(&notes, SortOrder::Alpha),
+            ["zebra pinned", "Apple", "banana", "cherry"]
+        );
+    }
+
+    fn press(app: &mut App, code: KeyCode) {
+        app.handle_key(KeyEvent::new(code, KeyModifiers::NONE)).unwrap();
+    }
+
+    fn type_text(app: &mut App, text: &str) {
+        for c in text.chars() {
+            press(app, KeyCode::Char(c));
+        }
+    }
+
+    fn editing_app(content: &str) -> App {
+        let mut app = App::new(PathBuf::from("unused.db"), None).unwrap();
+        app.note_input = content.to_string();
+        app.note_cursor = content.len();
+        app.state = AppState::NoteView;
+        app.input_mode = InputMode::Insert;
+        app
+    }
+
+    #[test]
+    fn test_editor_inserts_and_deletes_mid_line() {
+        let mut app = editing_app("café 😀 ok");
+
+        press(&mut app, KeyCode::Home);
+        press(&mut app, KeyCode::Right);
+        press(&mut app, KeyCode::Right);
+        press(&mut app, KeyCode::Right);
+        type_text(&mut app, "f");
+        assert_eq!(app.note_input, "caffé 😀 ok");
+
+        press(&mut app, KeyCode::Delete);
+        assert_eq!(app.note_input, "caff 😀 ok");
+
+        press(&mut app, KeyCode::Right);
+        press(&mut app, KeyCode::Right);
+        press(&mut app, KeyCode::Backspace);
+        press(&mut app, KeyCode::Backspace);
+        assert_eq!(app.note_input, "caff ok");
+        assert!(app.note_input.is_char_boundary(app.note_cursor));
+    }
+
+    #[test]
+    fn test_editor_moves_between_lines() {
+        let mut app = editing_app("first\nsecond");
+
+        press(&mut app, KeyCode::Up);
+        press(&mut app, KeyCode::End);
+        press(&mut app, KeyCode::Enter);
+        type_text(&mut app, "middle");
+        assert_eq!(app.note_input, "first\nmiddle\nsecond");
+
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Home);
+        type_text(&mut app, "> ");
+        assert_eq!(app.note_input, "first\nmiddle\n> second");
+    }
+
+    fn ctrl(app: &mut App, c: char) {
+        app.handle_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL)).unwrap();
+    }
+
+    #[test]
+    fn test_undo_redo_insert_sessions() {
+        let mut app = editing_app("");
+        app.input_mode = InputMode::Normal;
+
+        press(&mut app, KeyCode::Char('i'));
+        type_text(&mut app, "abc");
+        press(&mut app, KeyCode::Esc);
+        press(&mut app, KeyCode::Char('i'));
+        type_text(&mut app, " def");
+        press(&mut app, KeyCode::Esc);
+        // A session without changes adds no undo step
+        press(&mut app, KeyCode::Char('i'));
+        press(&mut app, KeyCode::Esc);
+
+        press(&mut app, KeyCode::Char('u'));
+        assert_eq!(app.note_input, "abc");
+        press(&mut app, KeyCode::Char('u'));
+        assert_eq!(app.note_input, "");
+        press(&mut app, KeyCode::Char('u'));
+        assert_eq!(app.note_input, "");
+
+        ctrl(&mut app, 'r');
+        assert_eq!(app.note_input, "abc");
+        ctrl(&mut app, 'r');
+        assert_eq!(app.note_input, "abc def");
+        assert_eq!(app.note_cursor, app.note_input.len());
+    }
+
+    #[test]
+    fn test_undo_in_insert_mode_and_tags() {
+        let mut app = editing_app("note");
+        app.input_mode = InputMode::Normal;
+
+        press(&mut app, KeyCode::Char('t'));
+        type_text(&mut app, "work");
+        press(&mut app, KeyCode::Enter);
+        press(&mut app, KeyCode::Esc);
+        assert_eq!(app.current_tags, vec!["work".to_string()]);
+
+        press(&mut app, KeyCode::Char('i'));
+        type_text(&mut app, "!");
+        ctrl(&mut app, 'z');
+        assert_eq!(app.note_input, "note");
+        assert_eq!(app.current_tags, vec!["work".to_string()]);
+
+        // Typing after an undo starts a fresh entry and drops the redo
+        type_text(&mut app, "?");
+        ctrl(&mut app, 'y');
+        assert_eq!(app.note_input, "note?");
+
+        ctrl(&mut app, 'z');
+        ctrl(&mut app, 'z');
+        assert_eq!(app.note_input, "note");
+        assert!(app.current_tags.is_empty());
+    }
+
+    #[test]
+    fn test_language_picker_sets_language() {
+        let mut app = editing_app("SELECT 1");
+        app.input_mode = InputMode::Normal;
+        app.mark_saved();
+
+        press(&mut app, KeyCode::Char('L'));
+        assert!(matches!(app.input_mode, InputMode::Language));
+        press(&mut app, KeyCode::Up);
+        press(&mut app, KeyCode::Up);
+        press(&mut app, KeyCode::Esc);
+        assert_eq!(app.current_language, SyntaxLanguage::Plain);
+
+        press(&mut app, KeyCode::Char('L'));
+        press(&mut app, KeyCode::Up);
+        press(&mut app, KeyCode::Up);
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.current_language, SyntaxLanguage::Sql);
+        assert!(matches!(app.input_mode, InputMode::Normal));
+        assert!(app.is_dirty());
+    }
+
+
+    #[test]
+    fn test_duplicate_note_creates_separate_copy() {
+        let mut app = App::new(PathBuf::from("unused.db"), None).unwrap();
+        app.db = Some(Database::in_memory("test_password").unwrap());
+        app.key = Some(Zeroizing::new([7u8; 32]));
+        app.state = AppState::NoteList;
+
+        let mut original = Note::new("Template\n".to_string());
+        original.tags = vec!["work".to_string()];
+        original.pinned = true;
+        NoteRepository::new(app.db.as_ref().unwrap().connection())
+            .create(&original, app.key.as_ref().unwrap())
+            .unwrap();
+        app.load_notes().unwrap();
+
+        press(&mut app, KeyCode::Char('c'));
+        assert_eq!(app.notes.len(), 2);
+        let selected_id = app.filtered_notes()[app.selected_note].meta.id.clone();
+        assert_ne!(selected_id, original.id);
+        let selected = NoteRepository::new(app.db.as_ref().unwrap().connection())
+            .get(&selected_id, app.key.as_ref().unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(selected.content, original.content);
+        assert_eq!(selected.tags, original.tags);
+        assert!(!selected.pinned);
+        assert!(selected.synced_at.is_none());
+    }
+
+    #[test]
+    fn test_settings_text_fields_show_typed_value() {
+        let mut app = App::new(PathBuf::from("unused.db"), None).unwrap();
+        app.state = AppState::NoteList;
+        press(&mut app, KeyCode::Char('s'));
+        let mut terminal = Terminal::new(TestBackend::new(100, 50)).unwrap();
+
+        for index in (0..=15).filter(|&index| is_text_setting(index)) {
+            app.selected_setting = index;
+            press(&mut app, KeyCode::Enter);
+            assert!(matches!(app.input_mode, InputMode::SettingsEdit), "field {}", index);
+            app.setting_input = "987".to_string();
+            terminal.draw(|frame| app.render(frame)).unwrap();
+            let screen: String = terminal.backend().buffer().content().iter().map(|c| c.symbol()).collect();
+            assert!(screen.contains("987_"), "field {}", index);
+            press(&mut app, KeyCode::Esc);
+        }
+    }
+
+    #[test]
+    fn test_raise_key_iterations_from_settings() {
+        let crypto = CryptoService::new();
+        let salt = crypto.generate_salt();
+        let old_key = crypto.derive_key("test_password", &salt, 100_000).unwrap();
+
+        let mut app = App::new(PathBuf::from("unused.db"), None).unwrap();
+        app.db = Some(Database::in_memory("test_password").unwrap());
+        let conn = app.db.as_ref().unwrap().connection();
+        EncryptionRepository::new(conn)
+            .save(&EncryptionMetadata::with_iterations(salt, 100_000).unwrap())
+            .unwrap();
+        NoteRepository::new(conn).create(&Note::new("Kept".to_string()), &old_key).unwrap();
+        app.key = Some(old_key.clone());
+        app.key_iterations = 100_000;
+        app.state = AppState::NoteList;
+        app.load_notes().unwrap();
+
+        press(&mut app, KeyCode::Char('s'));
+        for _ in 0..14 {
+            press(&mut app, KeyCode::Down);
+        }
+        assert_eq!(app.selected_setting, 14);
+        press(&mut app, KeyCode::Enter);
+        app.setting_input.clear();
+        type_text(&mut app, "90000");
+        press(&mut app, KeyCode::Enter);
+        assert!(app.error.as_deref().is_some_and(|e| e.contains("only be increased")));
+
+        press(&mut app, KeyCode::Enter);
+        app.setting_input.clear();
+        type_text(&mut app, "150000");
+        press(&mut app, KeyCode::Enter);
+        assert!(matches!(app.state, AppState::ChangePassword { .. }));
+        assert_eq!(app.pending_iterations, Some(150_000));
+
+        type_text(&mut app, "wrong");
+        press(&mut app, KeyCode::Enter);
+        assert!(app.error.as_deref().is_some_and(|e| e.contains("incorrect")));
+        assert_eq!(app.key_iterations, 100_000);
+
+        type_text(&mut app, "test_password");
+        press(&mut app, KeyCode::Enter);
+        assert!(matches!(app.state, AppState::Settings { .. }));
+        assert_eq!(app.key_iterations, 150_000);
+        assert_eq!(app.pending_iterations, None);
+
+        let metadata = EncryptionRepository::new(app.db.as_ref().unwrap().connection()).get().unwrap().unwrap();
+        assert_eq!(metadata.iterations, 150_000);
+        let new_key = crypto.derive_key("test_password", &metadata.salt, 150_000).unwrap();
+        assert_eq!(app.key.as_ref(), Some(&new_key));
+        assert_eq!(app.notes[0].preview, "Kept");
+    }
+
+    #[test]
+    fn test_weak_new_password_needs_second_enter() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut app = App::new(temp_dir.path().join("new.db"), None).unwrap();
+        assert!(app.is_new_database);
+        app.min_password_score = 2;
+
+        type_text(&mut app, "secret");
+        press(&mut app, KeyCode::Tab);
+        type_text(&mut app, "secret");
+        press(&mut app, KeyCode::Enter);
+        assert!(matches!(app.state, AppState::Locked));
+        assert!(app.error.as_deref().is_some_and(|e| e.contains("Enter again")));
+
+        // Editing the password re-arms the check
+        press(&mut app, KeyCode::Tab);
+        press(&mut app, KeyCode::Backspace);
+        type_text(&mut app, "t");
+        press(&mut app, KeyCode::Enter);
+        assert!(matches!(app.state, AppState::Locked));
+
+        press(&mut app, KeyCode::Enter);
+        assert!(!matches!(app.state, AppState::Locked));
+    }
+
+    #[test]
+    fn test_tag_completion_extends_then_cycles() {
+        let mut app = editing_app("body");
+        let mut tagged = Note::new("other".to_string());
+        tagged.tags = vec!["work".to_string(), "workshop".to_string(), "home".to_string()];
+        let mut cased = Note::new("more".to_string());
+        cased.tags = vec!["Work".to_string(), "wiki".to_string()];
+        app.notes = listed(&[tagged, cased]);
+        app.input_mode = InputMode::Normal;
+
+        press(&mut app, KeyCode::Char('t'));
+        type_text(&mut app, "ho");
+        press(&mut app, KeyCode::Tab);
+        assert_eq!(app.tag_input, "home");
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.current_tags, vec!["home".to_string()]);
+
+        // "wo" matches Work/work/workshop with no longer common prefix, so Tab cycles
+        type_text(&mut app, "wo");
+        press(&mut app, KeyCode::Tab);
+        assert_eq!(app.tag_input, "Work");
+        assert!(app.tag_cycle.is_some());
+
+        // Case variants are both offered; cycling wraps both ways
+        let mut seen = vec![app.tag_input.clone()];
+        for _ in 0..3 {
+            press(&mut app, KeyCode::Tab);
+            seen.push(app.tag_input.clone());
+        }
+        assert_eq!(seen, vec!["Work", "work", "workshop", "Work"]);
+        press(&mut app, KeyCode::BackTab);
+        assert_eq!(app.tag_input, "workshop");
+
+        // Enter adds the completion and ends the cycle
+        press(&mut app, KeyCode::Enter);
+        assert!(app.tag_cycle.is_none());
+        assert_eq!(app.current_tags, vec!["home".to_string(), "workshop".to_string()]);
+
+        // Tags already on the note aren't suggested
+        type_text(&mut app, "wor");
+        assert_eq!(app.tag_suggestions("wor"), vec!["Work".to_string(), "work".to_string()]);
+
+        // Backspace on empty input still removes the last tag
+        app.tag_input.clear();
+        press(&mut app, KeyCode::Backspace);
+        assert_eq!(app.current_tags, vec!["home".to_string()]);
+    }
+
+    #[test]
+    fn test_tag_panel_filters_list() {
+        let mut app = App::new(PathBuf::from("unused.db"), None).unwrap();
+        app.state = AppState::NoteList;
+        let tagged = |content: &str, tags: &[&str]| {
+            let mut note = Note::new(content.to_string());
+            note.tags = tags.iter().map(|t| t.to_string()).collect();
+            note
+        };
+        app.notes = listed(&[
+            tagged("a", &["work", "urgent"]),
+            tagged("b", &["work"]),
+            tagged("c", &["home"]),
+        ]);
+        let listed = |app: &App| {
+            let mut contents: Vec<String> = app.filtered_notes().iter().map(|n| n.preview.clone()).collect();
+            contents.sort();
+            contents
+        };
+
+        press(&mut app, KeyCode::Char('f'));
+        assert!(app.tag_panel_open);
+        assert_eq!(
+            app.tag_counts(),
+            vec![("home".to_string(), 1), ("urgent".to_string(), 1), ("work".to_string(), 2)]
+        );
+
+        // Select work, then urgent: all must match by default
+        press(&mut app, KeyCode::Char('j'));
+        press(&mut app, KeyCode::Char('j'));
+        press(&mut app, KeyCode::Char(' '));
+        assert_eq!(listed(&app), vec!["a", "b"]);
+        press(&mut app, KeyCode::Char('k'));
+        press(&mut app, KeyCode::Char(' '));
+        assert_eq!(listed(&app), vec!["a"]);
+
+        // Any-mode widens; the filter survives closing the panel and combines with search
+        press(&mut app, KeyCode::Char('o'));
+        press(&mut app, KeyCode::Char('k'));
+        press(&mut app, KeyCode::Char(' '));
+        assert_eq!(listed(&app), vec!["a", "b", "c"]);
+        press(&mut app, KeyCode::Esc);
+        assert!(!app.tag_panel_open);
+        app.search_input = "b".to_string();
+        assert_eq!(listed(&app), vec!["b"]);
+        app.search_input.clear();
+
+        // Clearing returns to the full list
+        press(&mut app, KeyCode::Char('f'));
+        press(&mut app, KeyCode::Char('x'));
+        assert!(app.tag_filter.is_empty());
+        assert_eq!(listed(&app).len(), 3);
+    }
+
+    #[test]
+    fn test_search_case_toggle() {
+        let mut app = App::new(PathBuf::from("unused.db"), None).unwrap();
+        app.state = AppState::NoteList;
+        let mut upper = Note::new("TODO: call back".to_string());
+        upper.tags = vec!["Work".to_string()];
+        let mut lower = Note::new("todo later".to_string());
+        lower.tags = vec!["work".to_string()];
+        app.notes = listed(&[upper, lower]);
+        let listed = |app: &mut App, query: &str| {
+            app.search_input = query.to_string();
+            let mut contents: Vec<String> = app.filtered_notes().iter().map(|n| n.preview.clone()).collect();
+            contents.sort();
+            contents
+        };
+
+        press(&mut app, KeyCode::Char('/'));
+        assert_eq!(listed(&mut app, "TODO"), vec!["TODO: call back", "todo later"]);
+        assert_eq!(listed(&mut app, "#Work"), vec!["TODO: call back", "todo later"]);
+
+        // The toggle isn't typed into the query
+        app.handle_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL)).unwrap();
+        assert!(app.search_case_sensitive);
+        assert_eq!(app.search_input, "#Work");
+        assert_eq!(listed(&mut app, "TODO"), vec!["TODO: call back"]);
+        assert_eq!(listed(&mut app, "#Work"), vec!["TODO: call back"]);
+        assert_eq!(listed(&mut app, "-TODO"), vec!["todo later"]);
+
+        // A new search session starts case-insensitive again
+        press(&mut app, KeyCode::Esc);
+        press(&mut app, KeyCode::Char('/'));
+        assert!(!app.search_case_sensitive);
+    }
+
+    #[test]
+    fn test_regex_search() {
+        let mut app = App::new(PathBuf::from("unused.db"), None).unwrap();
+        app.notes = listed(&[
+            Note::new("Invoice #2024-001".to_string()),
+            Note::new("invoice draft".to_string()),
+            Note::new("Meeting notes".to_string()),
+        ]);
+        let listed = |app: &mut App, query: &str| {
+            app.search_input = query.to_string();
+            let mut contents: Vec<String> = app.filtered_notes().iter().map(|n| n.preview.clone()).collect();
+            contents.sort();
+            contents
+        };
+
+        assert_eq!(listed(&mut app, r"re:\d{4}-\d+"), vec!["Invoice #2024-001"]);
+        assert_eq!(listed(&mut app, "re:^invoice"), vec!["Invoice #2024-001", "invoice draft"]);
+        app.search_case_sensitive = true;
+        assert_eq!(listed(&mut app, "re:^invoice"), vec!["invoice draft"]);
+
+        // An invalid pattern reports its error and leaves the list unfiltered
+        assert_eq!(listed(&mut app, "re:(unclosed").len(), 3);
+        let error = app.search_regex().unwrap().unwrap_err();
+        assert!(!error.is_empty() && !error.contains('\n'), "{:?}", error);
+
+        // Without the prefix, regex syntax is searched literally
+        assert!(listed(&mut app, "^invoice").is_empty());
+    }
+
+    #[test]
+    fn test_preview_scroll_resets_on_selection_change() {
+        let mut app = App::new(PathBuf::from("unused.db"), None).unwrap();
+        app.state = AppState::NoteList;
+        let mut long = Note::new((1..=100).map(|n| n.to_string()).collect::<Vec<_>>().join("\n"));
+        long.pinned = true;
+        app.notes = listed(&[long, Note::new("short".to_string())]);
+        app.preview_extent.set((80, 20));
+
+        press(&mut app, KeyCode::PageDown);
+        assert_eq!(app.preview_offset(), 10);
+        app.handle_key(KeyEvent::new(KeyCode::Char('d'), KeyModifiers::CONTROL)).unwrap();
+        assert_eq!(app.preview_offset(), 20);
+        assert_eq!(app.notes.len(), 2, "Ctrl+d scrolls rather than deleting");
+        for _ in 0..10 {
+            press(&mut app, KeyCode::PageDown);
+        }
+        assert_eq!(app.preview_offset(), 80);
+        app.handle_key(KeyEvent::new(KeyCode::Char('u'), KeyModifiers::CONTROL)).unwrap();
+        assert_eq!(app.preview_offset(), 70);
+
+        // Moving to another note and back starts from the top
+        press(&mut app, KeyCode::Down);
+        assert_eq!(app.preview_offset(), 0);
+        press(&mut app, KeyCode::Up);
+        assert_eq!(app.preview_offset(), 0);
+
+        // The same keys scroll while searching, without typing into the search
+        press(&mut app, KeyCode::Char('/'));
+        assert!(app.search_active);
+        app.handle_key(KeyEvent::new(KeyCode::Char('d'), KeyModifiers::CONTROL)).unwrap();
+        press(&mut app, KeyCode::PageDown);
+        assert_eq!(app.preview_offset(), 20);
+        assert!(app.search_input.is_empty());
+    }
+
+    #[test]
+    fn test_wrap_line_keeps_span_styles() {
+        let bold = Style::default().add_modifier(Modifier::BOLD);
+        let line = Line::from(vec![Span::styled("bold words ", bold), Span::raw("and plain")]);
+        let rows: Vec<Vec<(String, Style)>> = wrap_line(line, 8)
+            .into_iter()
+            .map(|row| row.spans.into_iter().map(|span| (span.content.into_owned(), span.style)).collect())
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                vec![("bold ".to_string(), bold)],
+                vec![("words ".to_string(), bold)],
+                vec![("and ".to_string(), Style::default())],
+                vec![("plain".to_string(), Style::default())],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_preview_pane_decrypts_only_the_selected_note() {
+        let mut app = App::new(PathBuf::from("unused.db"), None).unwrap();
+        app.db = Some(Database::in_memory("test_password").unwrap());
+        app.key = Some(Zeroizing::new([7u8; 32]));
+        app.state = AppState::NoteList;
+        let repo = NoteRepository::new(app.db.as_ref().unwrap().connection());
+        let long = note(&format!("Long note\n{}\nthe very end", "filler ".repeat(30)), 2, 2);
+        let short = note("Short note", 1, 1);
+        for n in [&long, &short] {
+            repo.create(n, app.key.as_ref().unwrap()).unwrap();
+        }
+        app.load_notes().unwrap();
+        assert!(app.notes.iter().all(|n| !n.preview.contains("the very end")));
+
+        let mut terminal = Terminal::new(TestBackend::new(100, 20)).unwrap();
+        let screen = |terminal: &Terminal<TestBackend>| {
+            terminal.backend().buffer().content().iter().map(|c| c.symbol()).collect::<String>()
+        };
+        terminal.draw(|frame| app.render(frame)).unwrap();
+        assert_eq!(app.preview_note.as_ref().map(|n| n.id.as_str()), Some(short.id.as_str()));
+
+        press(&mut app, KeyCode::Down);
+        terminal.draw(|frame| app.render(frame)).unwrap();
+        assert_eq!(app.preview_note.as_ref().map(|n| n.id.as_str()), Some(long.id.as_str()));
+        assert!(screen(&terminal).contains("the very end"));
+    }
+
+    #[test]
+    fn test_note_list_follows_selection() {
+        let mut app = App::new(PathBuf::from("unused.db"), None).unwrap();
+        app.state = AppState::NoteList;
+        app.notes = (0..50).map(|n| NotePreview::from(&Note::new(format!("note number {}", n)))).collect();
+        let mut terminal = Terminal::new(TestBackend::new(80, 12)).unwrap();
+        let screen = |terminal: &Terminal<TestBackend>| {
+            let buffer = terminal.backend().buffer();
+            (0..buffer.area.height)
+                .map(|y| (0..buffer.area.width).map(|x| buffer[(x, y)].symbol()).collect::<String>())
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        let selected = |app: &App| app.filtered_notes()[app.selected_note].preview.clone();
+
+        for _ in 0..30 {
+            press(&mut app, KeyCode::Down);
+        }
+        terminal.draw(|frame| app.render(frame)).unwrap();
+        assert!(screen(&terminal).contains(&selected(&app)));
+        assert!(app.note_list_offset.get() > 0);
+
+        // Moving back up within the viewport doesn't scroll it
+        let offset = app.note_list_offset.get();
+        press(&mut app, KeyCode::Up);
+        terminal.draw(|frame| app.render(frame)).unwrap();
+        assert_eq!(app.note_list_offset.get(), offset);
+
+        // A smaller terminal still shows the selection
+        terminal.backend_mut().resize(80, 6);
+        terminal.draw(|frame| app.render(frame)).unwrap();
+        assert!(screen(&terminal).contains(&selected(&app)));
+    }
+
+    #[test]
+    fn test_note_list_width_keys_and_clamping() {
+        let mut app = App::new(PathBuf::from("unused.db"), None).unwrap();
+        app.state = AppState::NoteList;
+
+        press(&mut app, KeyCode::Char('>'));
+        assert_eq!(app.settings.note_list_width, 46);
+        for _ in 0..20 {
+            press(&mut app, KeyCode::Char('<'));
+        }
+        assert_eq!(app.settings.note_list_width, MIN_NOTE_LIST_WIDTH);
+        for _ in 0..50 {
+            press(&mut app, KeyCode::Char('>'));
+        }
+        assert_eq!(app.settings.note_list_width, MAX_NOTE_LIST_WIDTH);
+
+        // The preview keeps its minimum width, and narrow terminals give the list everything
+        assert_eq!(app.note_list_width(100), 100 - MIN_PREVIEW_WIDTH);
+        assert_eq!(app.note_list_width(30), 30);
+        app.settings.note_list_width = 42;
+        assert_eq!(app.note_list_width(100), 42);
+
+        press(&mut app, KeyCode::Char('v'));
+        assert!(!app.settings.show_preview);
+        assert_eq!(app.note_list_width(100), 100);
+        press(&mut app, KeyCode::Char('v'));
+        assert_eq!(app.note_list_width(100), 42);
+    }
+
+    #[test]
+    fn test_lock_countdown_shows_in_last_minute() {
+        let app = App::new(PathBuf::from("unused.db"), None).unwrap();
+
+        app.key_manager.set_auto_lock(0);
+        assert_eq!(app.lock_countdown(), None);
+        app.key_manager.set_auto_lock(5);
+        assert_eq!(app.lock_countdown(), None);
+
+        // A one-minute timeout is already inside the countdown window
+        app.key_manager.set_auto_lock(1);
+        app.key_manager.register_activity();
+        let countdown = app.lock_countdown().unwrap();
+        assert!(countdown == "locks in 1:00 " || countdown == "locks in 0:59 ", "{:?}", countdown);
+    }
+
+    #[test]
+    fn test_truncate_to_width_keeps_whole_chars() {
+        assert_eq!(truncate_to_width("short", 10), "short");
+        assert_eq!(truncate_to_width("exactly10!", 10), "exactly10!");
+        assert_eq!(truncate_to_width("a longer first line", 10), "a longer …");
+        // The emoji (4 bytes, 2 columns) straddles the cut, so it is left out whole
+        assert_eq!(truncate_to_width("12345678🎉 party", 10), "12345678…");
+        assert_eq!(truncate_to_width("1234567🎉 party", 10), "1234567🎉…");
+        assert_eq!(truncate_to_width("café crème brûlée", 8), "café cr…");
+
+        // A first line with emoji at the boundary renders without panicking
+        let mut app = App::new(PathBuf::from("unused.db"), None).unwrap();
+        app.state = AppState::NoteList;
+        app.notes = listed(&[Note::new(format!("{}\nbody", "🎉é".repeat(40)))]);
+        let mut terminal = Terminal::new(TestBackend::new(40, 8)).unwrap();
+        terminal.draw(|frame| app.render(frame)).unwrap();
+    }
+
+    #[test]
+    fn test_sync_details_render_in_tiny_terminal() {
+        let mut app = App::new(PathBuf::from("unused.db"), None).unwrap();
+        app.state = AppState::NoteList;
+        app.last_sync = Some(SyncResult::default());
+        app.show_sync_details = true;
+
+        // Shorter than the popup's minimum height
+        let mut terminal = Terminal::new(TestBackend::new(40, 2)).unwrap();
+        terminal.draw(|frame| app.render(frame)).unwrap();
+    }
+
+    #[test]
+    fn test_viewer_command_prefers_jottery_viewer() {
+        let words = |v: &[&str]| v.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            viewer_command(Some("glow -p".to_string()), Some("less -R".to_string())),
+            words(&["glow", "-p"])
+        );
+        assert_eq!(viewer_command(None, Some("less -R".to_string())), words(&["less", "-R"]));
+        assert_eq!(viewer_command(Some("  ".to_string()), Some("bat".to_string())), words(&["bat"]));
+        assert_eq!(viewer_command(None, None), words(&["less"]));
+    }
+
+    #[test]
+    fn test_pinned_marker_in_list() {
+        let mut app = App::new(PathBuf::from("unused.db"), None).unwrap();
+        app.state = AppState::NoteList;
+        let mut pinned = Note::new("pinned note".to_string());
+        pinned.pinned = true;
+        app.notes = listed(&[pinned, Note::new("plain note".to_string())]);
+        let mut terminal = Terminal::new(TestBackend::new(80, 8)).unwrap();
+        let row_with = |terminal: &Terminal<TestBackend>, text: &str| {
+            let buffer = terminal.backend().buffer();
+            (0..buffer.area.height)
+                .map(|y| (0..buffer.area.width).map(|x| buffer[(x, y)].symbol()).collect::<String>())
+                .find(|row| row.contains(text))
+                .unwrap()
+        };
+
+        assert_eq!(app.list_indicators(&app.notes[0]), "📌 ");
+        assert_eq!(app.list_indicators(&app.notes[1]), "");
+        terminal.draw(|frame| app.render(frame)).unwrap();
+        assert!(row_with(&terminal, "pinned note").contains("📌"));
+        assert!(!row_with(&terminal, "plain note").contains("📌"));
+
+        app.settings.emoji_icons = false;
+        assert_eq!(app.list_indicators(&app.notes[0]), "* ");
+        terminal.draw(|frame| app.render(frame)).unwrap();
+        assert!(row_with(&terminal, "pinned note").contains("* pinned note"));
+
+        // Image attachments with a thumbnail are counted separately
+        let photo = crate::models::Attachment::new("photo.png".into(), "image/png".into(), 10, String::new());
+        let doc = crate::models::Attachment::new("doc.pdf".into(), "application/pdf".into(), 10, String::new());
+        app.thumbnail_ids.insert(photo.id.clone());
+        app.notes[1].meta.attachment_ids = vec![photo.id, doc.id];
+        assert_eq!(app.list_indicators(&app.notes[1]), "@2 %1 ");
+        app.settings.emoji_icons = true;
+        assert_eq!(app.list_indicators(&app.notes[1]), "📎2 📷1 ");
+    }
+
+    #[test]
+    fn test_preview_date_lines_collapse_when_narrow() {
+        let note = note("dated", 3 * 24 * 60, 120);
+        let now = Utc::now();
+
+        let wide = App::note_date_lines(&NotePreview::from(&note).meta, now, 60);
+        assert_eq!(wide.len(), 2);
+        assert!(wide[0].starts_with("Created: ") && wide[0].ends_with("(3d ago)"), "{:?}", wide);
+        assert!(wide[1].starts_with("Modified: ") && wide[1].ends_with("(2h ago)"), "{:?}", wide);
+
+        assert_eq!(
+            App::note_date_lines(&NotePreview::from(&note).meta, now, 30),
+            vec!["Created 3d ago · Modified 2h ago"]
+        );
+    }
+
+    #[test]
+    fn test_pin_limit_and_pinned_separator() {
+        let mut app = App::new(PathBuf::from("unused.db"), None).unwrap();
+        app.db = Some(Database::in_memory("test_password").unwrap());
+        app.key = Some(Zeroizing::new([7u8; 32]));
+        app.state = AppState::NoteList;
+        let repo = NoteRepository::new(app.db.as_ref().unwrap().connection());
+        for (content, mins_ago) in [("alpha", 3), ("bravo", 2), ("charlie", 1)] {
+            repo.create(&note(content, mins_ago, mins_ago), app.key.as_ref().unwrap()).unwrap();
+        }
+        app.load_notes().unwrap();
+        app.settings.pin_limit = 1;
+        let pinned = |app: &App| app.notes.iter().filter(|n| n.meta.pinned).map(|n| n.preview.clone()).collect::<BTreeSet<_>>();
+
+        // Pinning charlie fills the limit, so bravo can't be pinned
+        press(&mut app, KeyCode::Char('p'));
+        assert!(app.error.is_none(), "{:?}", app.error);
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Char('p'));
+        assert!(app.error.as_ref().is_some_and(|e| e.starts_with("Pin limit reached: 1 of 1")));
+        assert_eq!(pinned(&app), BTreeSet::from(["charlie".to_string()]));
+
+        // A divider row separates charlie from the rest, and the selection stays on bravo
+        let mut terminal = Terminal::new(TestBackend::new(80, 10)).unwrap();
+        terminal.draw(|frame| app.render(frame)).unwrap();
+        let buffer = terminal.backend().buffer();
+        let rows: Vec<String> = (0..buffer.area.height)
+            .map(|y| (0..buffer.area.width).map(|x| buffer[(x, y)].symbol()).collect())
+            .collect();
+        let row_of = |text: &str| rows.iter().position(|row| row.contains(text)).unwrap();
+        assert_eq!(row_of("bravo"), row_of("charlie") + 2);
+        assert!(rows[row_of("charlie") + 1].contains("────"));
+        assert_eq!(buffer[(2, row_of("bravo") as u16)].fg, Color::Yellow);
+
+        // Bulk pinning is held to the limit too
+        app.settings.pin_limit = 2;
+        app.multi_select = true;
+        app.marked_note_ids = app.notes.iter().map(|n| n.meta.id.clone()).collect();
+        press(&mut app, KeyCode::Char('p'));
+        assert!(app.error.is_some());
+        assert_eq!(pinned(&app).len(), 1);
+        app.settings.pin_limit = 0;
+        press(&mut app, KeyCode::Char('p'));
+        assert!(app.error.is_none(), "{:?}", app.error);
+        assert_eq!(pinned(&app).len(), 3);
+
+        // With every note pinned there is nothing to separate
+        assert_eq!(pinned_separator(&app.filtered_notes()), None);
+    }
+
+    #[test]
+    fn test_multi_select_bulk_actions() {
+        let mut app = App::new(PathBuf::from("unused.db"), None).unwrap();
+        app.db = Some(Database::in_memory("test_password").unwrap());
+        app.key = Some(Zeroizing::new([7u8; 32]));
+        app.state = AppState::NoteList;
+        let repo = NoteRepository::new(app.db.as_ref().unwrap().connection());
+        for (content, mins_ago) in [("alpha", 3), ("bravo", 2), ("charlie", 1)] {
+            repo.create(&note(content, mins_ago, mins_ago), app.key.as_ref().unwrap()).unwrap();
+        }
+        app.load_notes().unwrap();
+        let stored = |app: &App| {
+            NoteRepository::new(app.db.as_ref().unwrap().connection())
+                .list(false, app.key.as_ref().unwrap())
+                .unwrap()
+        };
+
+        // Mark charlie and bravo (newest first)
+        press(&mut app, KeyCode::Char('V'));
+        press(&mut app, KeyCode::Char(' '));
+        press(&mut app, KeyCode::Char(' '));
+        assert_eq!(app.marked_note_ids.len(), 2);
+
+        // Pinning re-sorts the list, but the marks follow the notes
+        let before_pin = Utc::now();
+        press(&mut app, KeyCode::Char('p'));
+        let pinned: BTreeSet<String> =
+            stored(&app).into_iter().filter(|n| n.pinned).map(|n| n.content).collect();
+        assert_eq!(pinned, BTreeSet::from(["bravo".to_string(), "charlie".to_string()]));
+        // Touched, so the next sync pushes the pin
+        assert!(stored(&app).iter().filter(|n| n.pinned).all(|n| n.modified_at >= before_pin));
+        assert_eq!(app.marked_note_ids.len(), 2);
+
+        press(&mut app, KeyCode::Char('t'));
+        for c in "bulk".chars() {
+            press(&mut app, KeyCode::Char(c));
+        }
+        press(&mut app, KeyCode::Enter);
+        let tagged: BTreeSet<String> = stored(&app)
+            .into_iter()
+            .filter(|n| n.tags == vec!["bulk".to_string()])
+            .map(|n| n.content)
+            .collect();
+        assert_eq!(tagged, pinned);
+
+        // Pressing p again unpins them all
+        press(&mut app, KeyCode::Char('p'));
+        assert!(stored(&app).iter().all(|n| !n.pinned));
+
+        press(&mut app, KeyCode::Char('d'));
+        let remaining: Vec<String> = stored(&app).into_iter().map(|n| n.content).collect();
+        assert_eq!(remaining, vec!["alpha"]);
+        assert_eq!(app.notes.len(), 1);
+        assert!(app.marked_note_ids.is_empty());
+
+        // Leaving the mode clears the marks
+        press(&mut app, KeyCode::Char(' '));
+        press(&mut app, KeyCode::Esc);
+        assert!(!app.multi_select);
+        assert!(app.marked_note_ids.is_empty());
+    }
+
+    #[test]
+    fn test_empty_trash_needs_confirmation() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("trash.db");
+        let mut app = App::new(db_path.clone(), None).unwrap();
+        app.db = Some(Database::open(&db_path, "test_password").unwrap());
+        app.key = Some(Zeroizing::new([7u8; 32]));
+        app.state = AppState::NoteList;
+        app.show_deleted = true;
+
+        let repo = NoteRepository::new(app.db.as_ref().unwrap().connection());
+        let kept = Note::new("kept".to_string());
+        let trashed = Note::new("trashed".to_string());
+        repo.create(&kept, app.key.as_ref().unwrap()).unwrap();
+        repo.create(&trashed, app.key.as_ref().unwrap()).unwrap();
+        repo.delete(&trashed.id).unwrap();
+        app.load_notes().unwrap();
+        let total = |app: &App| {
+            NoteRepository::new(app.db.as_ref().unwrap().connection()).count(true).unwrap()
+        };
+
+        // Another key in between cancels the confirmation
+        press(&mut app, KeyCode::Char('X'));
+        assert!(app.sync_status.as_deref().unwrap().contains("Press X again"));
+        press(&mut app, KeyCode::Char('j'));
+        press(&mut app, KeyCode::Char('X'));
+        assert_eq!(total(&app), 2);
+
+        press(&mut app, KeyCode::Char('X'));
+        assert_eq!(total(&app), 1);
+        assert_eq!(app.notes.len(), 1);
+        assert!(app.sync_status.as_deref().unwrap().starts_with("Purged 1 note"));
+
+        press(&mut app, KeyCode::Char('X'));
+        assert_eq!(app.sync_status.as_deref(), Some("Trash is empty"));
+    }
+
+    /// Note list with two notes and sync enabled against `endpoint`
+    fn app_syncing_to(endpoint: String) -> App {
+        use crate::models::sync::SyncMetadata;
+
+        let mut app = App::new(PathBuf::from("unused.db"), None).unwrap();
+        app.db = Some(Database::in_memory("test_password").unwrap());
+        app.key = Some(Zeroizing::new([7u8; 32]));
+        app.state = AppState::NoteList;
+        let conn = app.db.as_ref().unwrap().connection();
+        let api_key = CryptoService::new().encrypt_text("secret", app.key.as_ref().unwrap()).unwrap();
+        SyncRepository::new(conn)
+            .update_metadata(&SyncMetadata {
+                api_key: Some(serde_json::to_string(&api_key).unwrap()),
+                sync_enabled: true,
+                sync_endpoint: endpoint.clone(),
+                ..SyncMetadata::default()
+            }, app.key.as_ref().unwrap())
+            .unwrap();
+        for content in ["first", "second"] {
+            NoteRepository::new(conn).create(&Note::new(content.to_string()), app.key.as_ref().unwrap()).unwrap();
+        }
+        app.load_notes().unwrap();
+        app.settings.sync_enabled = true;
+        app.settings.sync_endpoint = Some(endpoint);
+        app
+    }
+
+    #[test]
+    fn test_keys_stay_responsive_during_sync() {
+        use std::net::TcpListener;
+
+        // Accepts connections but never answers, so the sync stays in flight
+        let server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut app = app_syncing_to(format!("http://{}", server.local_addr().unwrap()));
+
+        press(&mut app, KeyCode::Char('y'));
+        assert!(app.sync_worker.is_some());
+        let (_connection, _) = server.accept().unwrap();
+
+        press(&mut app, KeyCode::Down);
+        app.tick();
+        assert_eq!(app.selected_note, 1);
+        assert!(app.sync_worker.is_some() && app.db.is_none());
+
+        // Keys that need the database are refused rather than waiting on the network
+        let started = std::time::Instant::now();
+        press(&mut app, KeyCode::Char('n'));
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+        assert!(matches!(app.state, AppState::NoteList));
+        assert!(app.error.take().is_some_and(|e| e.starts_with("Sync in progress")));
+        assert!(app.sync_worker.is_some());
+
+        // Hanging up fails the request; the result and the database come back on a tick
+        drop(_connection);
+        drop(server);
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while app.sync_worker.is_some() && std::time::Instant::now() < deadline {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            app.tick();
+        }
+        assert!(app.sync_worker.is_none() && app.db.is_some());
+        assert!(app.error.as_deref().is_some_and(|e| e.starts_with("Sync failed")));
+        assert_eq!(app.notes.len(), 2);
+    }
+
+    #[test]
+    fn test_lock_and_quit_cancel_sync() {
+        use std::net::TcpListener;
+        use std::time::{Duration, Instant};
+
+        // Accepts connections but never answers, so each sync stays in flight
+        let server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", server.local_addr().unwrap());
+
+        let mut app = app_syncing_to(endpoint.clone());
+        press(&mut app, KeyCode::Char('y'));
+        let (_locked_connection, _) = server.accept().unwrap();
+        let started = Instant::now();
+        app.lock();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(app.sync_worker.is_none() && app.db.is_none() && app.key.is_none());
+
+        let mut app = app_syncing_to(endpoint);
+        press(&mut app, KeyCode::Char('y'));
+        let (_quit_connection, _) = server.accept().unwrap();
+        let started = Instant::now();
+        app.handle_key(KeyEvent::new(KeyCode::Char('q'), KeyModifiers::CONTROL)).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(app.should_quit() && app.sync_worker.is_none() && app.db.is_some());
+    }
+
+    #[test]
+    fn test_attach_and_remove_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("report.txt");
+        std::fs::write(&path, b"quarterly numbers").unwrap();
+
+        let mut app = editing_app("note with a file");
+        app.db = Some(Database::in_memory("test_password").unwrap());
+        app.key = Some(Zeroizing::new([7u8; 32]));
+        app.input_mode = InputMode::Normal;
+        let key = [7u8; 32];
+
+        press(&mut app, KeyCode::Char('a'));
+        assert!(matches!(app.input_mode, InputMode::Attachments));
+        press(&mut app, KeyCode::Char('a'));
+        type_text(&mut app, "/no/such/file");
+        press(&mut app, KeyCode::Enter);
+        assert!(app.error.take().is_some_and(|e| e.starts_with("Failed to attach file")));
+        assert!(app.editing_note_id.is_none());
+
+        // Attaching to a new note saves it first
+        press(&mut app, KeyCode::Char('a'));
+        type_text(&mut app, path.to_str().unwrap());
+        press(&mut app, KeyCode::Enter);
+        assert!(app.error.is_none(), "{:?}", app.error);
+        assert!(matches!(app.input_mode, InputMode::Attachments));
+        let id = app.editing_note_id.clone().unwrap();
+
+        let conn = app.db.as_ref().unwrap().connection();
+        let stored = NoteRepository::new(conn).get(&id, &key).unwrap().unwrap();
+        assert_eq!(stored.content, "note with a file\n");
+        assert_eq!(stored.attachments.len(), 1);
+        let attachment = &stored.attachments[0];
+        assert_eq!((attachment.filename.as_str(), attachment.mime_type.as_str(), attachment.size), ("report.txt", "text/plain", 17));
+        let (_, _, _, bytes) = AttachmentRepository::new(conn).get(&attachment.id, &key).unwrap().unwrap();
+        assert_eq!(bytes, b"quarterly numbers");
+        let attachment_id = attachment.id.clone();
+
+        let mut terminal = Terminal::new(TestBackend::new(80, 20)).unwrap();
+        terminal.draw(|frame| app.render(frame)).unwrap();
+        let screen: String = terminal.backend().buffer().content().iter().map(|c| c.symbol()).collect();
+        assert!(screen.contains("report.txt (17 B)"));
+
+        // Removing asks first; any key but y keeps it
+        press(&mut app, KeyCode::Char('x'));
+        terminal.draw(|frame| app.render(frame)).unwrap();
+        let screen: String = terminal.backend().buffer().content().iter().map(|c| c.symbol()).collect();
+        assert!(screen.contains("Remove report.txt from the note? (y/N)"));
+        press(&mut app, KeyCode::Char('n'));
+        let conn = app.db.as_ref().unwrap().connection();
+        assert_eq!(NoteRepository::new(conn).get(&id, &key).unwrap().unwrap().attachments.len(), 1);
+
+        press(&mut app, KeyCode::Char('x'));
+        press(&mut app, KeyCode::Char('y'));
+        assert!(app.error.is_none(), "{:?}", app.error);
+        let conn = app.db.as_ref().unwrap().connection();
+        assert!(NoteRepository::new(conn).get(&id, &key).unwrap().unwrap().attachments.is_empty());
+        assert!(AttachmentRepository::new(conn).get(&attachment_id, &key).unwrap().is_none());
+
+        press(&mut app, KeyCode::Esc);
+        assert!(matches!(app.input_mode, InputMode::Normal));
+    }
+
+    #[test]
+    fn test_set_reminder_and_overdue_status() {
+        let mut app = editing_app("call the dentist");
+        app.db = Some(Database::in_memory("test_password").unwrap());
+        app.key = Some(Zeroizing::new([7u8; 32]));
+        app.input_mode = InputMode::Normal;
+        let key = [7u8; 32];
+
+        press(&mut app, KeyCode::Char('R'));
+        assert!(matches!(app.input_mode, InputMode::Reminder));
+        type_text(&mut app, "tomorrow");
+        press(&mut app, KeyCode::Enter);
+        assert!(app.error.take().is_some_and(|e| e.starts_with("Unrecognised reminder")));
+        assert!(matches!(app.input_mode, InputMode::Reminder));
+
+        app.reminder_input.clear();
+        type_text(&mut app, "+2h");
+        press(&mut app, KeyCode::Enter);
+        assert!(matches!(app.input_mode, InputMode::Normal));
+        let due = app.current_reminder.unwrap();
+        assert!(due > Utc::now() + Duration::minutes(119));
+        assert!(app.is_dirty());
+
+        press(&mut app, KeyCode::Char('q'));
+        let stored = &app.notes[0];
+        assert_eq!(stored.meta.reminder_at, Some(due));
+        assert_eq!(app.overdue_reminders, 0);
+
+        // An overdue reminder on another note shows in the status bar
+        let mut late = Note::new("renew passport".to_string());
+        late.reminder_at = Some(Utc::now() - Duration::hours(1));
+        let conn = app.db.as_ref().unwrap().connection();
+        NoteRepository::new(conn).create(&late, &key).unwrap();
+        app.load_notes().unwrap();
+        assert_eq!(app.overdue_reminders, 1);
+
+        let mut terminal = Terminal::new(TestBackend::new(100, 12)).unwrap();
+        terminal.draw(|frame| app.render(frame)).unwrap();
+        let screen: String = terminal.backend().buffer().content().iter().map(|c| c.symbol()).collect();
+        assert!(screen.contains("1 overdue reminder"));
+        assert!(screen.contains("⏰"));
+
+        // Clearing the reminder with empty input
+        press(&mut app, KeyCode::Enter);
+        press(&mut app, KeyCode::Char('R'));
+        assert!(!app.reminder_input.is_empty());
+        app.reminder_input.clear();
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.current_reminder, None);
+        press(&mut app, KeyCode::Char('q'));
+        assert_eq!(app.overdue_reminders, 0);
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(17), "17 B");
+        assert_eq!(format_size(4608), "4.5 KiB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0 MiB");
+    }
+
+    #[test]
+    fn test_word_wrap_toggle_marks_note_modified() {
+        let mut app = editing_app("a long line");
+        app.input_mode = InputMode::Normal;
+        app.mark_saved();
+
+        press(&mut app, KeyCode::Char('w'));
+        assert!(!app.current_word_wrap);
+        assert!(app.is_dirty());
+
+        press(&mut app, KeyCode::Char('w'));
+        assert!(app.current_word_wrap);
+        assert!(!app.is_dirty());
+    }
+
+    #[test]
+    fn test_editor_scrolls_to_keep_the_cursor_in_view() {
+        let cursor_cell = |app: &mut App| {
+            let mut terminal = Terminal::new(TestBackend::new(40, 12)).unwrap();
+            terminal.draw(|frame| app.render(frame)).unwrap();
+            let position = terminal.get_cursor_position().unwrap();
+            let buffer = terminal.backend().buffer().clone();
+            let before: String = (position.x - 3..position.x).map(|x| buffer[(x, position.y)].symbol()).collect();
+            let screen: String = buffer.content().iter().map(|c| c.symbol()).collect();
+            (before, screen)
+        };
+
+        let lines: Vec<String> = (0..10).map(|i| format!("line {}", i)).collect();
+        let mut app = editing_app(&lines.join("\n"));
+        let (before, screen) = cursor_cell(&mut app);
+        assert_eq!(before, "e 9");
+        assert!(!screen.contains("line 0"), "{}", screen);
+
+        // Moving back up scrolls back only once the cursor leaves the view
+        for _ in 0..9 {
+            press(&mut app, KeyCode::Up);
+        }
+        let (_, screen) = cursor_cell(&mut app);
+        assert!(screen.contains("line 0") && !screen.contains("line 9"), "{}", screen);
+
+        // Wrapped rows are counted the way they are drawn
+        let mut app = editing_app(&format!("{}the end", "some words ".repeat(20)));
+        app.current_word_wrap = true;
+        let (before, _) = cursor_cell(&mut app);
+        assert_eq!(before, "end");
+    }
+
+    #[test]
+    fn test_input_cursors_count_characters_not_bytes() {
+        let cursor_after = |app: &mut App| {
+            let mut terminal = Terminal::new(TestBackend::new(60, 16)).unwrap();
+            terminal.draw(|frame| app.render(frame)).unwrap();
+            let position = terminal.get_cursor_position().unwrap();
+            let buffer = terminal.backend().buffer().clone();
+            let row: String = (0..position.x).map(|x| buffer[(x, position.y)].symbol()).collect();
+            (row, buffer[(position.x, position.y)].symbol().to_string())
+        };
+
+        let mut app = editing_app("body");
+        app.input_mode = InputMode::Tag;
+        app.current_tags = vec!["café".to_string()];
+        type_text(&mut app, "naïve");
+        let (row, _) = cursor_after(&mut app);
+        assert!(row.ends_with("Tags: #café naïve"), "{:?}", row);
+
+        // One mask character per password character, with the cursor right after them
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut app = App::new(temp_dir.path().join("new.db"), None).unwrap();
+        type_text(&mut app, "pässwörd");
+        let (row, at) = cursor_after(&mut app);
+        assert!(row.ends_with("│********"), "{:?}", row);
+        assert_eq!(at, " ");
+    }
+
+    #[test]
+    fn test_autosave_creates_new_note_once_then_updates() {
+        let mut app = editing_app("");
+        app.db = Some(Database::in_memory("test_password").unwrap());
+        app.key = Some(Zeroizing::new([7u8; 32]));
+        app.mark_saved();
+
+        // Typing alone doesn't save until the editor has been idle
+        type_text(&mut app, "draft");
+        app.tick();
+        assert!(app.editing_note_id.is_none() && app.is_dirty());
+
+        app.last_edit_at = Some(Instant::now() - std::time::Duration::from_secs(60));
+        app.tick();
+        assert!(!app.is_dirty() && app.autosaved);
+        assert!(matches!(app.state, AppState::NoteView));
+        let id = app.editing_note_id.clone().unwrap();
+
+        type_text(&mut app, " two");
+        assert!(!app.autosaved);
+        app.last_edit_at = Some(Instant::now() - std::time::Duration::from_secs(60));
+        app.tick();
+
+        let repo = NoteRepository::new(app.db.as_ref().unwrap().connection());
+        let notes = repo.list(false, app.key.as_ref().unwrap()).unwrap();
+        assert_eq!(notes.len(), 1);
+        assert_eq!((notes[0].id.as_str(), notes[0].content.as_str()), (id.as_str(), "draft two\n"));
+        assert!(repo.history(&id, app.key.as_ref().unwrap()).unwrap().is_empty());
+
+        // The editor block sits below the tags line, inside the outer margin
+        let mut terminal = Terminal::new(TestBackend::new(60, 8)).unwrap();
+        terminal.draw(|frame| app.render(frame)).unwrap();
+        let buffer = terminal.backend().buffer();
+        let title: String = (0..buffer.area.width).map(|x| buffer[(x, 3)].symbol()).collect();
+        assert!(title.contains("Note [saved] - INSERT"), "{}", title);
+    }
+
+    #[test]
+    fn test_history_view_restores_version() {
+        let mut app = App::new(PathBuf::from("unused.db"), None).unwrap();
+        app.db = Some(Database::in_memory("test_password").unwrap());
+        app.key = Some(Zeroizing::new([7u8; 32]));
+        app.state = AppState::NoteList;
+        let key = [7u8; 32];
+        let mut n = note("Original text", 1, 1);
+        NoteRepository::new(app.db.as_ref().unwrap().connection()).create(&n, &key).unwrap();
+        app.load_notes().unwrap();
+
+        // Nothing to show yet
+        press(&mut app, KeyCode::Char('H'));
+        assert!(matches!(app.state, AppState::NoteList));
+
+        n.content = "Overwritten".to_string();
+        n.touch();
+        NoteRepository::new(app.db.as_ref().unwrap().connection()).update(&n, &key).unwrap();
+        app.load_notes().unwrap();
+
+        press(&mut app, KeyCode::Char('H'));
+        assert!(matches!(app.state, AppState::History));
+        let mut terminal = Terminal::new(TestBackend::new(100, 10)).unwrap();
+        terminal.draw(|frame| app.render(frame)).unwrap();
+        let screen: String = terminal.backend().buffer().content().iter().map(|c| c.symbol()).collect();
+        assert!(screen.contains("Original text"), "{}", screen);
+
+        press(&mut app, KeyCode::Char('r'));
+        assert!(matches!(app.state, AppState::NoteList));
+        assert_eq!(app.notes[0].preview, "Original text");
+        let repo = NoteRepository::new(app.db.as_ref().unwrap().connection());
+        assert_eq!(repo.history(&n.id, &key).unwrap()[0].content, "Overwritten");
+    }
+
+    #[test]
+    fn test_editing_session_records_one_history_version() {
+        let mut app = App::new(PathBuf::from("unused.db"), None).unwrap();
+        app.db = Some(Database::in_memory("test_password").unwrap());
+        app.key = Some(Zeroizing::new([7u8; 32]));
+        app.state = AppState::NoteList;
+        let key = [7u8; 32];
+        let n = note("Original text", 1, 1);
+        NoteRepository::new(app.db.as_ref().unwrap().connection()).create(&n, &key).unwrap();
+        app.load_notes().unwrap();
+
+        // Autosaves and the save on leaving all belong to one session
+        for session in [" one", " two"] {
+            press(&mut app, KeyCode::Enter);
+            press(&mut app, KeyCode::Char('i'));
+            for word in session.split_inclusive('o') {
+                type_text(&mut app, word);
+                app.last_edit_at = Some(Instant::now() - std::time::Duration::from_secs(60));
+                app.tick();
+            }
+            type_text(&mut app, "!");
+            press(&mut app, KeyCode::Esc);
+            press(&mut app, KeyCode::Esc);
+            assert!(matches!(app.state, AppState::NoteList));
+        }
+
+        let history = NoteRepository::new(app.db.as_ref().unwrap().connection()).history(&n.id, &key).unwrap();
+        let contents: Vec<_> = history.iter().map(|v| v.content.as_str()).collect();
+        assert_eq!(contents, ["Original text one!\n", "Original text"]);
+    }
+
+    #[test]
+    fn test_autosave_every_keystroke_batch_and_off_when_disabled() {
+        let mut app = editing_app("");
+        app.db = Some(Database::in_memory("test_password").unwrap());
+        app.key = Some(Zeroizing::new([7u8; 32]));
+        app.mark_saved();
+
+        // The burst save includes the keystroke that triggered it
+        type_text(&mut app, &"x".repeat(AUTOSAVE_KEYSTROKES));
+        assert!(app.editing_note_id.is_some() && !app.is_dirty());
+        let repo = NoteRepository::new(app.db.as_ref().unwrap().connection());
+        let notes = repo.list(false, app.key.as_ref().unwrap()).unwrap();
+        assert_eq!(notes[0].content, format!("{}\n", "x".repeat(AUTOSAVE_KEYSTROKES)));
+
+        app.settings.autosave_seconds = 0;
+        type_text(&mut app, &"y".repeat(AUTOSAVE_KEYSTROKES));
+        app.last_edit_at = Some(Instant::now() - std::time::Duration::from_secs(60));
+        app.tick();
+        assert!(app.is_dirty());
+    }
+
+    #[test]
+    fn test_unreadable_notes_are_hidden_and_counted() {
+        let mut app = App::new(PathBuf::from("unused.db"), None).unwrap();
+        app.db = Some(Database::in_memory("test_password").unwrap());
+        app.key = Some(Zeroizing::new([7u8; 32]));
+        app.state = AppState::NoteList;
+        let repo = NoteRepository::new(app.db.as_ref().unwrap().connection());
+        repo.create(&note("readable", 2, 2), app.key.as_ref().unwrap()).unwrap();
+        repo.create(&note("foreign", 1, 1), &[8u8; 32]).unwrap();
+
+        app.load_notes().unwrap();
+        assert_eq!(app.notes.iter().map(|n| n.preview.as_str()).collect::<Vec<_>>(), ["readable"]);
+        assert_eq!(app.unreadable_note_ids.len(), 1);
+
+        let mut terminal = Terminal::new(TestBackend::new(120, 8)).unwrap();
+        terminal.draw(|frame| app.render(frame)).unwrap();
+        let buffer = terminal.backend().buffer();
+        let status: String = (0..buffer.area.width).map(|x| buffer[(x, buffer.area.height - 1)].symbol()).collect();
+        assert!(status.contains("1 note could not be decrypted"), "{}", status);
+    }
+
+    #[test]
+    fn test_note_list_loads_pages_on_demand() {
+        let mut app = App::new(PathBuf::from("unused.db"), None).unwrap();
+        app.db = Some(Database::in_memory("test_password").unwrap());
+        app.key = Some(Zeroizing::new([7u8; 32]));
+        app.state = AppState::NoteList;
+        let repo = NoteRepository::new(app.db.as_ref().unwrap().connection());
+        let total = NOTE_PAGE_SIZE + 20;
+        for i in 0..total {
+            repo.create(&note(&format!("note {}", i), i as i64, i as i64), app.key.as_ref().unwrap()).unwrap();
+        }
+
+        app.load_notes().unwrap();
+        assert_eq!(app.notes.len(), NOTE_PAGE_SIZE);
+
+        // Scrolling close to the end of the page fetches the rest
+        app.selected_note = NOTE_PAGE_SIZE - NOTE_PAGE_MARGIN - 1;
+        press(&mut app, KeyCode::Down);
+        assert_eq!(app.notes.len(), total);
+        assert!(app.all_notes_loaded);
+
+        // Searching needs every note, wherever the selection is
+        app.load_notes().unwrap();
+        press(&mut app, KeyCode::Char('/'));
+        press(&mut app, KeyCode::Char('n'));
+        assert_eq!(app.notes.len(), total);
+        assert_eq!(app.filtered_notes().len(), total);
+    }
 }
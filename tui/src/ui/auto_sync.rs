@@ -0,0 +1,67 @@
+//! Background timer that signals the main loop when an auto-sync is due
+//!
+//! The timer thread only sleeps and sends a signal that is polled on `Event::Tick`;
+//! the sync itself runs on a `SyncWorker`.
+
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender};
+use std::thread;
+use std::time::Duration;
+
+/// Periodic auto-sync timer; dropping it stops the thread
+pub struct AutoSyncTimer {
+    /// Signals from the timer thread (bounded to one, so missed ticks never queue up)
+    due: Receiver<()>,
+    /// Dropping this wakes the timer thread and makes it exit
+    _stop: SyncSender<()>,
+}
+
+impl AutoSyncTimer {
+    /// Start a timer firing every `minutes`; returns None when the interval is 0 (disabled)
+    pub fn start(minutes: u64) -> Option<Self> {
+        if minutes == 0 {
+            return None;
+        }
+        Some(Self::with_interval(Duration::from_secs(minutes * 60)))
+    }
+
+    fn with_interval(interval: Duration) -> Self {
+        let (due_tx, due) = mpsc::sync_channel(1);
+        let (stop, stop_rx) = mpsc::sync_channel::<()>(0);
+
+        thread::spawn(move || {
+            // Anything but a timeout means the timer was dropped
+            while let Err(RecvTimeoutError::Timeout) = stop_rx.recv_timeout(interval) {
+                // A full channel means the last signal wasn't consumed yet
+                if let Err(mpsc::TrySendError::Disconnected(_)) = due_tx.try_send(()) {
+                    break;
+                }
+            }
+        });
+
+        Self { due, _stop: stop }
+    }
+
+    /// Whether a sync is due; consumes the pending signal
+    pub fn poll(&self) -> bool {
+        self.due.try_recv().is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zero_interval_is_disabled() {
+        assert!(AutoSyncTimer::start(0).is_none());
+    }
+
+    #[test]
+    fn test_signals_do_not_accumulate() {
+        let timer = AutoSyncTimer::with_interval(Duration::from_millis(50));
+        thread::sleep(Duration::from_millis(300));
+
+        assert!(timer.poll());
+        assert!(!timer.poll());
+    }
+}
@@ -0,0 +1,295 @@
+//! Cursor movement, row wrapping and undo history for the note buffer
+//!
+//! The cursor is a byte offset into the text that always sits on a char boundary,
+//! so inserting or removing at it never splits a multi-byte codepoint.
+
+use std::collections::VecDeque;
+use std::ops::Range;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Maximum number of undo entries kept per note
+const UNDO_LIMIT: usize = 100;
+
+/// Offset of the char before `pos` (or 0)
+pub fn prev_boundary(text: &str, pos: usize) -> usize {
+    text[..pos].char_indices().next_back().map_or(0, |(i, _)| i)
+}
+
+/// Offset just past the char at `pos` (or the end of the text)
+pub fn next_boundary(text: &str, pos: usize) -> usize {
+    text[pos..].chars().next().map_or(pos, |c| pos + c.len_utf8())
+}
+
+/// Start of the line containing `pos`
+pub fn line_start(text: &str, pos: usize) -> usize {
+    text[..pos].rfind('\n').map_or(0, |i| i + 1)
+}
+
+/// End of the line containing `pos` (before its newline)
+pub fn line_end(text: &str, pos: usize) -> usize {
+    text[pos..].find('\n').map_or(text.len(), |i| pos + i)
+}
+
+/// Move to the previous (`up`) or next line, keeping the column in chars where possible
+pub fn move_vertical(text: &str, pos: usize, up: bool) -> usize {
+    let start = line_start(text, pos);
+    let column = text[start..pos].chars().count();
+
+    let target_start = if up {
+        if start == 0 {
+            return 0;
+        }
+        line_start(text, start - 1)
+    } else {
+        let end = line_end(text, pos);
+        if end == text.len() {
+            return text.len();
+        }
+        end + 1
+    };
+
+    let target_end = line_end(text, target_start);
+    text[target_start..target_end]
+        .char_indices()
+        .nth(column)
+        .map_or(target_end, |(i, _)| target_start + i)
+}
+
+/// Byte ranges of the screen rows `text` takes up at `width` columns. Lines break after
+/// whitespace where they can and mid-word otherwise; no char is dropped, so every cursor
+/// offset lands in a row. `usize::MAX` gives one row per line (no wrapping).
+pub fn wrap_rows(text: &str, width: usize) -> Vec<Range<usize>> {
+    let width = width.max(1);
+    let mut rows = Vec::new();
+    let mut start = 0;
+    for line in text.split('\n') {
+        let end = start + line.len();
+        let mut row_start = start;
+        let mut row_width = 0;
+        // Offset just past the last whitespace in the current row
+        let mut last_break = None;
+        for (i, c) in line.char_indices() {
+            let i = start + i;
+            let char_width = c.width().unwrap_or(0);
+            if row_width + char_width > width && row_width > 0 {
+                let at = last_break.filter(|&at| at > row_start).unwrap_or(i);
+                rows.push(row_start..at);
+                row_start = at;
+                row_width = text[at..i].width();
+                last_break = None;
+            }
+            row_width += char_width;
+            if c.is_whitespace() {
+                last_break = Some(i + c.len_utf8());
+            }
+        }
+        rows.push(row_start..end);
+        start = end + 1;
+    }
+    rows
+}
+
+/// Screen position of the cursor within `rows` (from [`wrap_rows`] at `width`): (row,
+/// display column). A cursor just past a full row goes to the start of the next one.
+pub fn cursor_row_col(text: &str, rows: &[Range<usize>], pos: usize, width: usize) -> (usize, usize) {
+    let row = rows.partition_point(|r| r.start <= pos).saturating_sub(1);
+    let column = rows.get(row).map_or(0, |r| text[r.start..pos].width());
+    if column >= width.max(1) {
+        (row + 1, 0)
+    } else {
+        (row, column)
+    }
+}
+
+/// Word, character and line counts of `text`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextStats {
+    pub words: usize,
+    pub chars: usize,
+    pub lines: usize,
+}
+
+impl TextStats {
+    /// Words are whitespace-separated runs and characters are Unicode scalar values.
+    /// Empty text is one line; a single trailing newline ends the last line rather
+    /// than starting a new one.
+    pub fn of(text: &str) -> Self {
+        let newlines = text.matches('\n').count();
+        Self {
+            words: text.split_whitespace().count(),
+            chars: text.chars().count(),
+            lines: (newlines + 1 - usize::from(text.ends_with('\n'))).max(1),
+        }
+    }
+}
+
+/// Editor state captured for undo/redo
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EditSnapshot {
+    pub content: String,
+    pub cursor: usize,
+    pub tags: Vec<String>,
+}
+
+/// Bounded undo/redo stacks of editor snapshots
+#[derive(Debug, Default)]
+pub struct UndoHistory {
+    undo: VecDeque<EditSnapshot>,
+    redo: Vec<EditSnapshot>,
+}
+
+impl UndoHistory {
+    /// Record the state before an edit; drops the oldest entry past the limit and clears redo
+    pub fn record(&mut self, snapshot: EditSnapshot) {
+        if self.undo.back() != Some(&snapshot) {
+            self.undo.push_back(snapshot);
+            if self.undo.len() > UNDO_LIMIT {
+                self.undo.pop_front();
+            }
+        }
+        self.redo.clear();
+    }
+
+    /// Step back from `current`, skipping entries identical to it
+    pub fn undo(&mut self, current: EditSnapshot) -> Option<EditSnapshot> {
+        while let Some(snapshot) = self.undo.pop_back() {
+            if snapshot != current {
+                self.redo.push(current);
+                return Some(snapshot);
+            }
+        }
+        None
+    }
+
+    /// Re-apply the last undone state
+    pub fn redo(&mut self, current: EditSnapshot) -> Option<EditSnapshot> {
+        let snapshot = self.redo.pop()?;
+        self.undo.push_back(current);
+        Some(snapshot)
+    }
+
+    /// Forget all history (when switching notes)
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_stats() {
+        let stats = |text| {
+            let s = TextStats::of(text);
+            (s.words, s.chars, s.lines)
+        };
+        assert_eq!(stats(""), (0, 0, 1));
+        assert_eq!(stats("one"), (1, 3, 1));
+        assert_eq!(stats("one\n"), (1, 4, 1));
+        assert_eq!(stats("one two\n\nthree"), (3, 14, 3));
+        assert_eq!(stats("one\n\n"), (1, 5, 2));
+        assert_eq!(stats("  café 😀  "), (2, 10, 1));
+    }
+
+    #[test]
+    fn test_horizontal_movement_skips_whole_codepoints() {
+        let text = "aé😀b";
+        let mut pos = text.len();
+
+        let mut stops = vec![pos];
+        while pos > 0 {
+            pos = prev_boundary(text, pos);
+            stops.push(pos);
+        }
+        assert_eq!(stops, vec![8, 7, 3, 1, 0]);
+
+        assert_eq!(next_boundary(text, 1), 3);
+        assert_eq!(next_boundary(text, 3), 7);
+        assert_eq!(next_boundary(text, text.len()), text.len());
+    }
+
+    #[test]
+    fn test_vertical_movement_keeps_column() {
+        let text = "héllo\nab\nwörld";
+        // After "hél"
+        let pos = "hél".len();
+
+        let down = move_vertical(text, pos, false);
+        assert_eq!(down, "héllo\nab".len()); // clamped to the shorter line
+
+        let down_again = move_vertical(text, down, false);
+        assert_eq!(&text[line_start(text, down_again)..down_again], "wö");
+
+        assert_eq!(move_vertical(text, pos, true), 0);
+        assert_eq!(move_vertical(text, down_again, false), text.len());
+    }
+
+    #[test]
+    fn test_cursor_line_col_uses_display_width() {
+        let text = "one\n😀x";
+        let rows = wrap_rows(text, usize::MAX);
+        assert_eq!(rows, vec![0..3, 4..9]);
+        assert_eq!(cursor_row_col(text, &rows, text.len(), usize::MAX), (1, 3));
+        assert_eq!(cursor_row_col(text, &rows, 2, usize::MAX), (0, 2));
+    }
+
+    #[test]
+    fn test_wrap_rows_follow_the_cursor() {
+        let text = "the quick brown fox\n\nabcdefghij";
+        let rows = wrap_rows(text, 8);
+        let shown: Vec<&str> = rows.iter().map(|r| &text[r.clone()]).collect();
+        // Words move to the next row whole; one longer than a row is split
+        assert_eq!(shown, vec!["the ", "quick ", "brown ", "fox", "", "abcdefgh", "ij"]);
+
+        assert_eq!(cursor_row_col(text, &rows, "the qu".len(), 8), (1, 2));
+        assert_eq!(cursor_row_col(text, &rows, "the quick brown fox".len(), 8), (3, 3));
+        assert_eq!(cursor_row_col(text, &rows, "the quick brown fox\n".len(), 8), (4, 0));
+        assert_eq!(cursor_row_col(text, &rows, text.len(), 8), (6, 2));
+
+        // Wide chars count by display width
+        let text = "😀😀😀";
+        let rows = wrap_rows(text, 4);
+        assert_eq!(rows, vec![0..8, 8..12]);
+        // Just past a full row is shown at the start of the next
+        assert_eq!(cursor_row_col("abcd", &wrap_rows("abcd", 4), 4, 4), (1, 0));
+    }
+
+    fn snapshot(content: &str) -> EditSnapshot {
+        EditSnapshot {
+            content: content.to_string(),
+            cursor: content.len(),
+            tags: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_undo_history_is_bounded() {
+        let mut history = UndoHistory::default();
+        for i in 0..150 {
+            history.record(snapshot(&i.to_string()));
+        }
+
+        let mut current = snapshot("latest");
+        let mut steps = 0;
+        while let Some(previous) = history.undo(current.clone()) {
+            current = previous;
+            steps += 1;
+        }
+        assert_eq!(steps, UNDO_LIMIT);
+        assert_eq!(current.content, "50");
+    }
+
+    #[test]
+    fn test_record_clears_redo() {
+        let mut history = UndoHistory::default();
+        history.record(snapshot("a"));
+
+        let restored = history.undo(snapshot("ab")).unwrap();
+        assert_eq!(restored.content, "a");
+
+        history.record(snapshot("a"));
+        assert!(history.redo(snapshot("ac")).is_none());
+    }
+}
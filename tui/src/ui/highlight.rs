@@ -0,0 +1,366 @@
+//! Lightweight syntax highlighting for the read-only preview pane
+//!
+//! A small hand-written lexer per language that colours keywords, strings, comments
+//! and numbers. It works line by line, carrying only block-comment state across lines,
+//! so odd input degrades to plain text rather than failing.
+
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+};
+
+use crate::models::SyntaxLanguage;
+
+/// Lexical rules for one language
+struct LanguageSpec {
+    keywords: &'static [&'static str],
+    /// Match keywords ignoring case (SQL)
+    ignore_case: bool,
+    line_comment: Option<&'static str>,
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+    /// Characters allowed inside identifiers besides alphanumerics and `_`
+    ident_extra: &'static [char],
+    /// Colour the name after `<` / `</` as a tag (HTML)
+    tags: bool,
+}
+
+const JAVASCRIPT: LanguageSpec = LanguageSpec {
+    keywords: &[
+        "async", "await", "break", "case", "catch", "class", "const", "continue", "default", "delete",
+        "do", "else", "export", "extends", "false", "finally", "for", "from", "function", "if", "import",
+        "in", "instanceof", "let", "new", "null", "of", "return", "static", "super", "switch", "this",
+        "throw", "true", "try", "typeof", "undefined", "var", "void", "while", "yield",
+    ],
+    ignore_case: false,
+    line_comment: Some("//"),
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"', '\'', '`'],
+    ident_extra: &['$'],
+    tags: false,
+};
+
+const PYTHON: LanguageSpec = LanguageSpec {
+    keywords: &[
+        "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue",
+        "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import", "in",
+        "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "self", "try", "while",
+        "with", "yield",
+    ],
+    ignore_case: false,
+    line_comment: Some("#"),
+    block_comment: None,
+    quotes: &['"', '\''],
+    ident_extra: &[],
+    tags: false,
+};
+
+const JSON: LanguageSpec = LanguageSpec {
+    keywords: &["true", "false", "null"],
+    ignore_case: false,
+    line_comment: None,
+    block_comment: None,
+    quotes: &['"'],
+    ident_extra: &[],
+    tags: false,
+};
+
+const HTML: LanguageSpec = LanguageSpec {
+    keywords: &[],
+    ignore_case: false,
+    line_comment: None,
+    block_comment: Some(("<!--", "-->")),
+    quotes: &['"', '\''],
+    ident_extra: &['-'],
+    tags: true,
+};
+
+const CSS: LanguageSpec = LanguageSpec {
+    keywords: &["!important", "@import", "@media", "@keyframes", "@font-face", "inherit", "initial", "none", "auto"],
+    ignore_case: false,
+    line_comment: None,
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"', '\''],
+    ident_extra: &['-', '@', '!'],
+    tags: false,
+};
+
+const SQL: LanguageSpec = LanguageSpec {
+    keywords: &[
+        "add", "all", "alter", "and", "as", "asc", "begin", "between", "by", "case", "commit", "create",
+        "delete", "desc", "distinct", "drop", "else", "end", "exists", "from", "group", "having", "if",
+        "in", "index", "inner", "insert", "into", "is", "join", "key", "left", "like", "limit", "not",
+        "null", "offset", "on", "or", "order", "outer", "primary", "references", "right", "rollback",
+        "select", "set", "table", "then", "union", "update", "values", "view", "when", "where", "with",
+    ],
+    ignore_case: true,
+    line_comment: Some("--"),
+    block_comment: Some(("/*", "*/")),
+    quotes: &['\'', '"'],
+    ident_extra: &[],
+    tags: false,
+};
+
+const BASH: LanguageSpec = LanguageSpec {
+    keywords: &[
+        "case", "do", "done", "echo", "elif", "else", "esac", "exit", "export", "fi", "for", "function",
+        "if", "in", "local", "read", "return", "set", "source", "then", "until", "while",
+    ],
+    ignore_case: false,
+    line_comment: Some("#"),
+    block_comment: None,
+    quotes: &['"', '\''],
+    ident_extra: &[],
+    tags: false,
+};
+
+fn spec_for(language: SyntaxLanguage) -> Option<&'static LanguageSpec> {
+    match language {
+        SyntaxLanguage::Javascript => Some(&JAVASCRIPT),
+        SyntaxLanguage::Python => Some(&PYTHON),
+        SyntaxLanguage::Json => Some(&JSON),
+        SyntaxLanguage::Html => Some(&HTML),
+        SyntaxLanguage::Css => Some(&CSS),
+        SyntaxLanguage::Sql => Some(&SQL),
+        SyntaxLanguage::Bash => Some(&BASH),
+        SyntaxLanguage::Plain | SyntaxLanguage::Markdown => None,
+    }
+}
+
+fn keyword_style() -> Style {
+    Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD)
+}
+
+fn string_style() -> Style {
+    Style::default().fg(Color::Green)
+}
+
+fn comment_style() -> Style {
+    Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC)
+}
+
+fn number_style() -> Style {
+    Style::default().fg(Color::Yellow)
+}
+
+fn tag_style() -> Style {
+    Style::default().fg(Color::Blue)
+}
+
+/// Highlight `text` as `language`; languages without rules come back unstyled
+pub fn highlight(text: &str, language: SyntaxLanguage) -> Vec<Line<'static>> {
+    let Some(spec) = spec_for(language) else {
+        return text.lines().map(|line| Line::from(line.to_string())).collect();
+    };
+
+    let mut in_block = false;
+    text.lines()
+        .map(|line| Line::from(highlight_line(line, spec, &mut in_block)))
+        .collect()
+}
+
+/// Collects spans, merging consecutive unstyled text into one span
+struct SpanBuilder {
+    spans: Vec<Span<'static>>,
+    plain: String,
+}
+
+impl SpanBuilder {
+    fn plain(&mut self, text: &str) {
+        self.plain.push_str(text);
+    }
+
+    fn styled(&mut self, text: &str, style: Style) {
+        if !self.plain.is_empty() {
+            self.spans.push(Span::raw(std::mem::take(&mut self.plain)));
+        }
+        self.spans.push(Span::styled(text.to_string(), style));
+    }
+
+    fn finish(mut self) -> Vec<Span<'static>> {
+        if !self.plain.is_empty() || self.spans.is_empty() {
+            self.spans.push(Span::raw(self.plain));
+        }
+        self.spans
+    }
+}
+
+fn highlight_line(line: &str, spec: &LanguageSpec, in_block: &mut bool) -> Vec<Span<'static>> {
+    let mut out = SpanBuilder { spans: Vec::new(), plain: String::new() };
+    let mut rest = line;
+    let mut prev: Option<char> = None;
+
+    while let Some(c) = rest.chars().next() {
+        // Inside a block comment carried over from an earlier line
+        if *in_block {
+            let (_, close) = spec.block_comment.expect("block state without block comment rules");
+            match rest.find(close) {
+                Some(i) => {
+                    let len = i + close.len();
+                    out.styled(&rest[..len], comment_style());
+                    rest = &rest[len..];
+                    *in_block = false;
+                    prev = None;
+                    continue;
+                }
+                None => {
+                    out.styled(rest, comment_style());
+                    break;
+                }
+            }
+        }
+
+        if let Some(marker) = spec.line_comment {
+            // `#` inside a word (e.g. `a#b` in bash) is not a comment
+            if rest.starts_with(marker) && !prev.is_some_and(|p| is_ident_char(p, spec)) {
+                out.styled(rest, comment_style());
+                break;
+            }
+        }
+
+        if let Some((open, _)) = spec.block_comment {
+            if rest.starts_with(open) {
+                out.styled(open, comment_style());
+                rest = &rest[open.len()..];
+                *in_block = true;
+                continue;
+            }
+        }
+
+        if spec.quotes.contains(&c) {
+            let len = string_len(rest, c);
+            out.styled(&rest[..len], string_style());
+            prev = rest[..len].chars().next_back();
+            rest = &rest[len..];
+            continue;
+        }
+
+        let after_ident = prev.is_some_and(|p| is_ident_char(p, spec));
+        if c.is_ascii_digit() && !after_ident {
+            let len = rest
+                .find(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '.' || ch == '_'))
+                .unwrap_or(rest.len());
+            out.styled(&rest[..len], number_style());
+            prev = rest[..len].chars().next_back();
+            rest = &rest[len..];
+            continue;
+        }
+
+        if is_ident_char(c, spec) && !c.is_ascii_digit() {
+            let len = rest.find(|ch: char| !is_ident_char(ch, spec)).unwrap_or(rest.len());
+            let word = &rest[..len];
+            let before = &line[..line.len() - rest.len()];
+            let is_tag = spec.tags && (before.ends_with('<') || before.ends_with("</"));
+            if is_tag {
+                out.styled(word, tag_style());
+            } else if is_keyword(word, spec) {
+                out.styled(word, keyword_style());
+            } else {
+                out.plain(word);
+            }
+            prev = word.chars().next_back();
+            rest = &rest[len..];
+            continue;
+        }
+
+        out.plain(&rest[..c.len_utf8()]);
+        prev = Some(c);
+        rest = &rest[c.len_utf8()..];
+    }
+
+    out.finish()
+}
+
+fn is_ident_char(c: char, spec: &LanguageSpec) -> bool {
+    c.is_alphanumeric() || c == '_' || spec.ident_extra.contains(&c)
+}
+
+fn is_keyword(word: &str, spec: &LanguageSpec) -> bool {
+    if spec.ignore_case {
+        spec.keywords.iter().any(|k| k.eq_ignore_ascii_case(word))
+    } else {
+        spec.keywords.contains(&word)
+    }
+}
+
+/// Length of the string literal at the start of `text`, through the closing quote
+/// (or to the end of the line if it is unterminated)
+fn string_len(text: &str, quote: char) -> usize {
+    let mut escaped = false;
+    for (i, c) in text.char_indices().skip(1) {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == quote {
+            return i + c.len_utf8();
+        }
+    }
+    text.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn styled_words(line: &Line) -> Vec<(String, Style)> {
+        line.spans.iter().map(|s| (s.content.to_string(), s.style)).collect()
+    }
+
+    #[test]
+    fn test_plain_and_unknown_pass_through_unstyled() {
+        let text = "def foo(): # \"not code\"\nSELECT 1";
+        for language in [SyntaxLanguage::Plain, SyntaxLanguage::Markdown] {
+            let lines = highlight(text, language);
+            assert_eq!(lines.len(), 2);
+            for line in &lines {
+                assert_eq!(line.spans.len(), 1);
+                assert_eq!(line.spans[0].style, Style::default());
+            }
+            assert_eq!(lines[0].spans[0].content, "def foo(): # \"not code\"");
+        }
+    }
+
+    #[test]
+    fn test_python_tokens() {
+        let lines = highlight("def f(x): return \"a # b\" + 42 # done", SyntaxLanguage::Python);
+        let words = styled_words(&lines[0]);
+
+        assert_eq!(words[0], ("def".to_string(), keyword_style()));
+        assert!(words.contains(&("return".to_string(), keyword_style())));
+        assert!(words.contains(&("\"a # b\"".to_string(), string_style())));
+        assert!(words.contains(&("42".to_string(), number_style())));
+        assert_eq!(words.last().unwrap(), &("# done".to_string(), comment_style()));
+    }
+
+    #[test]
+    fn test_sql_keywords_ignore_case_and_identifiers_keep_digits() {
+        let lines = highlight("select col1 FROM t2 -- note", SyntaxLanguage::Sql);
+        let words = styled_words(&lines[0]);
+
+        assert_eq!(words[0], ("select".to_string(), keyword_style()));
+        assert!(words.contains(&("FROM".to_string(), keyword_style())));
+        assert!(!words.iter().any(|(_, style)| *style == number_style()));
+        assert_eq!(words.last().unwrap(), &("-- note".to_string(), comment_style()));
+    }
+
+    #[test]
+    fn test_block_comments_span_lines() {
+        let lines = highlight("let a = 1; /* start\nstill comment\nend */ const b", SyntaxLanguage::Javascript);
+
+        assert_eq!(styled_words(&lines[1]), vec![("still comment".to_string(), comment_style())]);
+        let last = styled_words(&lines[2]);
+        assert_eq!(last[0], ("end */".to_string(), comment_style()));
+        assert!(last.contains(&("const".to_string(), keyword_style())));
+    }
+
+    #[test]
+    fn test_html_tags() {
+        let lines = highlight("<div class=\"x\">hi</div>", SyntaxLanguage::Html);
+        let words = styled_words(&lines[0]);
+
+        assert_eq!(words.iter().filter(|(w, s)| w == "div" && *s == tag_style()).count(), 2);
+        assert!(words.contains(&("\"x\"".to_string(), string_style())));
+        assert!(words.iter().any(|(w, s)| w.contains("class") && *s == Style::default()));
+    }
+}
@@ -0,0 +1,172 @@
+//! Minimal markdown rendering for the read-only preview pane
+//!
+//! Handles headings, emphasis, inline code, list bullets, block quotes and code fences.
+//! Anything it does not recognise (including unmatched markers) is shown as plain text.
+
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+};
+
+/// Render markdown into styled lines
+pub fn render(text: &str) -> Vec<Line<'static>> {
+    let mut lines = Vec::new();
+    let mut in_fence = false;
+
+    for raw in text.lines() {
+        let trimmed = raw.trim_start();
+
+        if trimmed.starts_with("```") {
+            in_fence = !in_fence;
+            lines.push(Line::styled(raw.to_string(), code_style()));
+            continue;
+        }
+        if in_fence {
+            lines.push(Line::styled(raw.to_string(), code_style()));
+            continue;
+        }
+
+        if let Some((level, heading)) = parse_heading(trimmed) {
+            let color = if level == 1 { Color::Cyan } else { Color::Blue };
+            lines.push(Line::styled(
+                heading.to_string(),
+                Style::default().fg(color).add_modifier(Modifier::BOLD),
+            ));
+        } else if let Some(item) = ["- ", "* ", "+ "].iter().find_map(|m| trimmed.strip_prefix(m)) {
+            let indent = &raw[..raw.len() - trimmed.len()];
+            let mut spans = vec![Span::raw(format!("{}• ", indent))];
+            spans.extend(inline(item));
+            lines.push(Line::from(spans));
+        } else if let Some(quote) = trimmed.strip_prefix('>') {
+            let style = Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC);
+            let mut spans = vec![Span::styled("│ ", style)];
+            spans.extend(inline(quote.trim_start()).into_iter().map(|s| s.patch_style(style)));
+            lines.push(Line::from(spans));
+        } else {
+            lines.push(Line::from(inline(raw)));
+        }
+    }
+
+    lines
+}
+
+fn code_style() -> Style {
+    Style::default().fg(Color::DarkGray)
+}
+
+/// `# Title` -> (1, "Title"); requires a space after the hashes, at most six of them
+fn parse_heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    if (1..=6).contains(&level) {
+        line[level..].strip_prefix(' ').map(|rest| (level, rest.trim()))
+    } else {
+        None
+    }
+}
+
+/// Split a line into spans for `**strong**`, `*emphasis*`/`_emphasis_` and `` `code` ``
+fn inline(text: &str) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    let mut plain = String::new();
+    let mut rest = text;
+    let mut prev: Option<char> = None;
+
+    while let Some(c) = rest.chars().next() {
+        // snake_case words are not emphasis
+        let intraword = prev.is_some_and(char::is_alphanumeric);
+        let styled = match c {
+            '`' => delimited(rest, "`").map(|(inner, len)| (inner, code_style().fg(Color::Yellow), len)),
+            '*' if rest.starts_with("**") => delimited(rest, "**")
+                .map(|(inner, len)| (inner, Style::default().add_modifier(Modifier::BOLD), len)),
+            '*' | '_' if !(c == '_' && intraword) => delimited(rest, &rest[..1])
+                .map(|(inner, len)| (inner, Style::default().add_modifier(Modifier::ITALIC), len)),
+            _ => None,
+        };
+
+        match styled {
+            Some((inner, style, len)) => {
+                if !plain.is_empty() {
+                    spans.push(Span::raw(std::mem::take(&mut plain)));
+                }
+                spans.push(Span::styled(inner.to_string(), style));
+                rest = &rest[len..];
+                prev = inner.chars().next_back();
+            }
+            None => {
+                plain.push(c);
+                rest = &rest[c.len_utf8()..];
+                prev = Some(c);
+            }
+        }
+    }
+
+    if !plain.is_empty() || spans.is_empty() {
+        spans.push(Span::raw(plain));
+    }
+    spans
+}
+
+/// If `text` starts with `marker`, find the closing marker: (inner text, total length)
+fn delimited<'t>(text: &'t str, marker: &str) -> Option<(&'t str, usize)> {
+    let body = text.strip_prefix(marker)?;
+    let end = body.find(marker)?;
+    let inner = &body[..end];
+    // `* not emphasis *` and empty pairs stay literal
+    if inner.is_empty() || inner.starts_with(' ') || inner.ends_with(' ') {
+        return None;
+    }
+    Some((inner, marker.len() * 2 + end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_of(line: &Line) -> String {
+        line.spans.iter().map(|s| s.content.as_ref()).collect()
+    }
+
+    #[test]
+    fn test_headings_and_bullets() {
+        let lines = render("# Title\n## Sub\n- one\n  * two\n#nospace");
+
+        assert_eq!(text_of(&lines[0]), "Title");
+        assert!(lines[0].style.add_modifier.contains(Modifier::BOLD));
+        assert_eq!(lines[0].style.fg, Some(Color::Cyan));
+        assert_eq!(text_of(&lines[1]), "Sub");
+        assert_eq!(text_of(&lines[2]), "• one");
+        assert_eq!(text_of(&lines[3]), "  • two");
+        assert_eq!(text_of(&lines[4]), "#nospace");
+    }
+
+    #[test]
+    fn test_inline_styles() {
+        let lines = render("a **bold** and *it* or _it_ with `code`");
+        let spans = &lines[0].spans;
+
+        let styled: Vec<(&str, Style)> = spans.iter().map(|s| (s.content.as_ref(), s.style)).collect();
+        assert_eq!(styled[1].0, "bold");
+        assert!(styled[1].1.add_modifier.contains(Modifier::BOLD));
+        assert_eq!(styled[3].0, "it");
+        assert!(styled[3].1.add_modifier.contains(Modifier::ITALIC));
+        assert_eq!(styled[5].0, "it");
+        assert_eq!(styled[7].0, "code");
+        assert_eq!(styled[7].1.fg, Some(Color::Yellow));
+    }
+
+    #[test]
+    fn test_unmatched_markers_stay_literal() {
+        let lines = render("2 * 3 = 6, a*b, `open and **half, my_snake_case");
+        assert_eq!(lines[0].spans.len(), 1);
+        assert_eq!(text_of(&lines[0]), "2 * 3 = 6, a*b, `open and **half, my_snake_case");
+    }
+
+    #[test]
+    fn test_code_fences_are_dimmed_verbatim() {
+        let lines = render("```rust\nlet x = *y*;\n```\nafter *it*");
+
+        assert_eq!(text_of(&lines[1]), "let x = *y*;");
+        assert_eq!(lines[1].style.fg, Some(Color::DarkGray));
+        assert_eq!(lines[3].spans.len(), 2);
+    }
+}
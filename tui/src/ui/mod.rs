@@ -2,6 +2,11 @@
 /// Built with ratatui
 
 pub mod app;
+pub mod auto_sync;
+pub mod editor;
+pub mod highlight;
+pub mod markdown;
+pub mod sync_worker;
 pub mod event;
 pub mod terminal;
 
@@ -0,0 +1,124 @@
+//! Runs a sync on a worker thread so the UI keeps drawing and taking keys
+//!
+//! The sync needs the database connection, which can't be shared between threads, so
+//! the connection is lent to the worker and handed back with the result; the main loop
+//! polls for it on `Event::Tick`.
+
+use anyhow::Result;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use zeroize::Zeroizing;
+
+use crate::db::Database;
+use crate::sync::{SharedSyncProgress, SyncEngine, SyncResult};
+
+/// What a finished sync hands back: the lent connection and the sync result
+pub struct SyncOutcome {
+    pub db: Database,
+    pub result: Result<SyncResult>,
+}
+
+/// A sync in flight; holds the database until it finishes
+pub struct SyncWorker {
+    done: Receiver<SyncOutcome>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl SyncWorker {
+    /// Start syncing `db` on a new thread
+    pub fn start(db: Database, key: Zeroizing<[u8; 32]>, progress: SharedSyncProgress) -> Self {
+        let (done_tx, done) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let worker_cancel = Arc::clone(&cancel);
+
+        thread::spawn(move || {
+            // A panic must not take the connection down with it
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                SyncEngine::new(&db, &key)
+                    .with_progress(progress)
+                    .with_cancel(worker_cancel)
+                    .run()
+            }))
+            .unwrap_or_else(|_| Err(anyhow::anyhow!("Sync worker panicked")));
+            let _ = done_tx.send(SyncOutcome { db, result });
+        });
+
+        Self { done, cancel }
+    }
+
+    /// The outcome if the sync has finished; None while it is still running
+    pub fn poll(&self) -> Option<Result<SyncOutcome>> {
+        match self.done.try_recv() {
+            Ok(outcome) => Some(Ok(outcome)),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(Self::lost())),
+        }
+    }
+
+    /// Block until the sync finishes
+    pub fn wait(self) -> Result<SyncOutcome> {
+        self.done.recv().map_err(|_| Self::lost())
+    }
+
+    /// Cancel the sync and wait for the database
+    ///
+    /// The sync stops at its next request (an in-flight one is abandoned), so this only
+    /// waits for the database work it is in the middle of.
+    pub fn cancel(self) -> Result<SyncOutcome> {
+        self.cancel.store(true, Ordering::Relaxed);
+        self.wait()
+    }
+
+    fn lost() -> anyhow::Error {
+        anyhow::anyhow!("Sync worker exited without returning the database")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hands_back_database_with_result() {
+        // No endpoint configured, so the sync fails without touching the network
+        let db = Database::in_memory("test_password").unwrap();
+        let worker = SyncWorker::start(db, Zeroizing::new([7u8; 32]), SharedSyncProgress::default());
+
+        let outcome = worker.wait().unwrap();
+        assert!(outcome.result.is_err());
+        outcome.db.connection().execute_batch("SELECT 1").unwrap();
+    }
+
+    #[test]
+    fn test_cancel_stops_a_hanging_sync() {
+        use crate::crypto::CryptoService;
+        use crate::models::sync::SyncMetadata;
+        use crate::repository::sync::SyncRepository;
+        use std::net::TcpListener;
+        use std::time::{Duration, Instant};
+
+        // Accepts connections but never answers
+        let server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let key = Zeroizing::new([7u8; 32]);
+        let db = Database::in_memory("test_password").unwrap();
+        let api_key = CryptoService::new().encrypt_text("secret", &key).unwrap();
+        SyncRepository::new(db.connection())
+            .update_metadata(&SyncMetadata {
+                api_key: Some(serde_json::to_string(&api_key).unwrap()),
+                sync_endpoint: format!("http://{}", server.local_addr().unwrap()),
+                ..SyncMetadata::default()
+            }, &key)
+            .unwrap();
+
+        let worker = SyncWorker::start(db, key, SharedSyncProgress::default());
+        let (_connection, _) = server.accept().unwrap();
+        let started = Instant::now();
+        let outcome = worker.cancel().unwrap();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(outcome.result.unwrap_err().to_string(), "Sync cancelled");
+        outcome.db.connection().execute_batch("SELECT 1").unwrap();
+    }
+}
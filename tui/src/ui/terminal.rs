@@ -1,5 +1,6 @@
 use anyhow::Result;
 use crossterm::{
+    cursor::Show,
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
@@ -30,8 +31,7 @@ impl Tui {
 
     /// Exit the terminal UI
     pub fn exit(&mut self) -> Result<()> {
-        disable_raw_mode()?;
-        execute!(io::stdout(), LeaveAlternateScreen)?;
+        restore()?;
         self.terminal.show_cursor()?;
         Ok(())
     }
@@ -46,6 +46,24 @@ impl Tui {
     }
 }
 
+/// Restore the terminal to its normal (cooked, main screen) state
+/// Safe to call more than once
+pub fn restore() -> Result<()> {
+    disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen, Show)?;
+    Ok(())
+}
+
+/// Install a panic hook that restores the terminal before the panic is printed
+/// Without this the message can be swallowed by the alternate screen
+pub fn install_panic_hook() {
+    let original_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |panic_info| {
+        let _ = restore();
+        original_hook(panic_info);
+    }));
+}
+
 impl Drop for Tui {
     fn drop(&mut self) {
         let _ = self.exit();
@@ -0,0 +1,211 @@
+//! Opening the database file and deriving the content key on unlock
+//! The file key includes the keyfile when there is one; the content key is checked against
+//! the stored notes, recovering from a recorded PBKDF2 iteration count they weren't encrypted with
+
+use anyhow::{Context, Result};
+use rusqlite::Connection;
+use std::path::Path;
+use tracing::{info, warn};
+use zeroize::Zeroizing;
+
+use crate::{
+    crypto::{combine_with_keyfile, read_keyfile, CryptoService},
+    db::Database,
+    models::encryption::EncryptionMetadata,
+    repository::{error::RepoError, EncryptionRepository, NoteRepository},
+};
+
+/// SQLCipher key for `password`: combined with the keyfile when one is given, the same
+/// way as the content key's PBKDF2 input
+pub fn file_key(password: &str, keyfile: Option<&Path>) -> Result<Zeroizing<String>> {
+    match keyfile {
+        Some(path) => Ok(combine_with_keyfile(password, &read_keyfile(path)?)),
+        None => Ok(Zeroizing::new(password.to_string())),
+    }
+}
+
+/// Open (or create) the database at `path` with the file key for `password` and `keyfile`
+///
+/// Databases set up with a keyfile while the file key was the password alone still open,
+/// once the keyfile is confirmed to derive the content key, and are rekeyed to include it.
+pub fn open_database(path: &Path, password: &str, keyfile: Option<&Path>) -> Result<Database> {
+    let key = file_key(password, keyfile)?;
+    let Some(keyfile) = keyfile else {
+        return Database::open(path, &key)
+            .context("Failed to open database. Check your password, and pass --keyfile if it was set up with one.");
+    };
+    if let Ok(db) = Database::open(path, &key) {
+        return Ok(db);
+    }
+
+    let db = Database::open(path, password)
+        .context("Failed to open database. Check your password and that the keyfile is the one it was set up with.")?;
+    let metadata = EncryptionRepository::new(db.connection()).get()?;
+    let Some(metadata) = metadata.filter(|m| m.keyfile_required) else {
+        anyhow::bail!("This database was not set up with a keyfile; drop --keyfile");
+    };
+    match derive_checked_key(db.connection(), &key, &metadata) {
+        Ok(_) => {}
+        Err(e) if matches!(e.downcast_ref::<RepoError>(), Some(RepoError::Decrypt { .. })) => {
+            anyhow::bail!("Wrong keyfile: {} is not the one this database was set up with", keyfile.display());
+        }
+        Err(e) => return Err(e),
+    }
+    info!("Rekeying the database file to include its keyfile");
+    db.rekey(&key)?;
+    Ok(db)
+}
+
+/// Content key that decrypts the stored notes, and the parameters it was derived with
+pub struct CheckedKey {
+    pub key: Zeroizing<[u8; 32]>,
+    pub iterations: u32,
+    /// Iterations the database recorded, when the notes needed a different count
+    pub recorded_iterations: Option<u32>,
+}
+
+/// Derive the content key from `metadata`, falling back to the other known iteration
+/// counts if the recorded one is doubtful and doesn't decrypt the notes
+///
+/// Only a missing or non-default count is doubted: under a default count, notes that
+/// don't decrypt mean a wrong password or keyfile, reported as `RepoError::Decrypt`.
+/// A fallback that works is recorded in the database so later unlocks use it directly.
+pub fn derive_checked_key(conn: &Connection, password: &str, metadata: &EncryptionMetadata) -> Result<CheckedKey> {
+    let crypto = CryptoService::new();
+    let notes = NoteRepository::new(conn);
+
+    let key = crypto.derive_key(password, &metadata.salt, metadata.iterations)?;
+    let error = match notes.check_key(&key) {
+        Ok(()) => {
+            info!("Derived content key with {} PBKDF2 iterations", metadata.iterations);
+            return Ok(CheckedKey { key, iterations: metadata.iterations, recorded_iterations: None });
+        }
+        Err(e @ RepoError::Decrypt { .. }) => e,
+        Err(e) => return Err(e.into()),
+    };
+    if EncryptionMetadata::KNOWN_ITERATIONS.contains(&metadata.iterations) {
+        return Err(error.into());
+    }
+
+    for iterations in EncryptionMetadata::KNOWN_ITERATIONS {
+        let key = crypto.derive_key(password, &metadata.salt, iterations)?;
+        if notes.check_key(&key).is_ok() {
+            warn!(
+                "Notes decrypt with {} PBKDF2 iterations, not the recorded {}; updating the record",
+                iterations, metadata.iterations
+            );
+            EncryptionRepository::new(conn).save(&EncryptionMetadata { iterations, ..metadata.clone() })?;
+            return Ok(CheckedKey { key, iterations, recorded_iterations: Some(metadata.iterations) });
+        }
+    }
+
+    warn!(
+        "Notes decrypt with neither the recorded {} PBKDF2 iterations nor the known counts {:?}",
+        metadata.iterations,
+        EncryptionMetadata::KNOWN_ITERATIONS
+    );
+    Err(error.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::models::Note;
+
+    #[test]
+    fn test_falls_back_to_the_iterations_notes_use() {
+        let db = Database::in_memory("test_password").unwrap();
+        let crypto = CryptoService::new();
+        let salt = crypto.generate_salt();
+        let web_key = crypto.derive_key("pw", &salt, EncryptionMetadata::WEB_APP_ITERATIONS).unwrap();
+        NoteRepository::new(db.connection())
+            .create(&Note::new("From the web app".to_string()), &web_key)
+            .unwrap();
+
+        // A custom count the notes weren't encrypted with
+        let metadata = EncryptionMetadata::with_iterations(salt.clone(), 300_000).unwrap();
+        EncryptionRepository::new(db.connection()).save(&metadata).unwrap();
+
+        let checked = derive_checked_key(db.connection(), "pw", &metadata).unwrap();
+        assert_eq!(checked.iterations, EncryptionMetadata::WEB_APP_ITERATIONS);
+        assert_eq!(checked.recorded_iterations, Some(300_000));
+        assert_eq!(*checked.key, *web_key);
+
+        let recorded = EncryptionRepository::new(db.connection()).get().unwrap().unwrap();
+        assert_eq!(recorded.iterations, EncryptionMetadata::WEB_APP_ITERATIONS);
+        let checked = derive_checked_key(db.connection(), "pw", &recorded).unwrap();
+        assert_eq!(checked.recorded_iterations, None);
+
+        // A different salt matches no known count
+        let other = EncryptionMetadata::with_iterations(crypto.generate_salt(), 300_000).unwrap();
+        let error = derive_checked_key(db.connection(), "pw", &other).err().unwrap();
+        assert!(matches!(error.downcast_ref::<RepoError>(), Some(RepoError::Decrypt { .. })), "{}", error);
+    }
+
+    #[test]
+    fn test_wrong_password_under_default_count_is_a_decrypt_error() {
+        let db = Database::in_memory("test_password").unwrap();
+        let metadata = EncryptionMetadata::new(CryptoService::new().generate_salt());
+        let key = CryptoService::new().derive_key("pw", &metadata.salt, metadata.iterations).unwrap();
+        NoteRepository::new(db.connection()).create(&Note::new("Secret".to_string()), &key).unwrap();
+
+        let error = derive_checked_key(db.connection(), "wrong", &metadata).err().unwrap();
+        assert!(matches!(error.downcast_ref::<RepoError>(), Some(RepoError::Decrypt { .. })), "{}", error);
+        assert!(derive_checked_key(db.connection(), "pw", &metadata).is_ok());
+    }
+
+    #[test]
+    fn test_check_key_looks_past_a_foreign_note() {
+        let db = Database::in_memory("test_password").unwrap();
+        let repo = NoteRepository::new(db.connection());
+        let mut foreign = Note::new("Written elsewhere".to_string());
+        foreign.id = "0".to_string();
+        repo.create(&foreign, &[8u8; 32]).unwrap();
+        assert!(matches!(repo.check_key(&[7u8; 32]), Err(RepoError::Decrypt { .. })));
+
+        repo.create(&Note::new("Ours".to_string()), &[7u8; 32]).unwrap();
+        assert!(repo.check_key(&[7u8; 32]).is_ok());
+    }
+
+    #[test]
+    fn test_keyfile_is_part_of_the_file_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.db");
+        let keyfile = dir.path().join("jottery.key");
+        let other = dir.path().join("other.key");
+        std::fs::write(&keyfile, b"keyfile bytes").unwrap();
+        std::fs::write(&other, b"other bytes").unwrap();
+
+        // Set up the way keyfile databases used to be, with the file keyed by the password alone
+        {
+            let db = Database::open(&path, "pw").unwrap();
+            let mut metadata = EncryptionMetadata::new(CryptoService::new().generate_salt());
+            metadata.keyfile_required = true;
+            EncryptionRepository::new(db.connection()).save(&metadata).unwrap();
+            let input = combine_with_keyfile("pw", b"keyfile bytes");
+            let key = CryptoService::new().derive_key(&input, &metadata.salt, metadata.iterations).unwrap();
+            NoteRepository::new(db.connection()).create(&Note::new("Secret".to_string()), &key).unwrap();
+        }
+
+        let error = open_database(&path, "pw", Some(&other)).err().unwrap();
+        assert!(error.to_string().starts_with("Wrong keyfile"), "{}", error);
+        assert!(open_database(&path, "pw", Some(&keyfile)).is_ok());
+
+        // Rekeyed on that open, so the password alone no longer opens the file
+        assert!(Database::open(&path, "pw").is_err());
+        assert!(open_database(&path, "pw", None).is_err());
+        assert!(open_database(&path, "pw", Some(&keyfile)).is_ok());
+        let error = open_database(&path, "pw", Some(&other)).err().unwrap();
+        assert!(error.to_string().contains("keyfile"), "{}", error);
+    }
+
+    #[test]
+    fn test_empty_database_uses_recorded_iterations() {
+        let db = Database::in_memory("test_password").unwrap();
+        let metadata = EncryptionMetadata::new(CryptoService::new().generate_salt());
+        let checked = derive_checked_key(db.connection(), "pw", &metadata).unwrap();
+        assert_eq!(checked.iterations, EncryptionMetadata::DEFAULT_ITERATIONS);
+        assert_eq!(checked.recorded_iterations, None);
+    }
+}
@@ -0,0 +1,153 @@
+//! Unlocked handle on a Jottery database for tools embedding its storage
+//! Keeps the database and content key together so callers never handle the key
+
+use anyhow::Result;
+use std::collections::HashSet;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+use crate::{
+    crypto::kdf_input,
+    db::Database,
+    integrity,
+    models::{Note, NotePreview},
+    repository::{EncryptionRepository, NoteRepository, RepoError, SettingsRepository},
+    unlock,
+};
+
+/// An unlocked database; the content key is zeroized when it is dropped
+pub struct Vault {
+    db: Database,
+    key: Zeroizing<[u8; 32]>,
+}
+
+impl Vault {
+    /// Unlock a database that has been set up by the TUI
+    pub fn open<P: AsRef<Path>>(path: P, password: &str) -> Result<Self> {
+        Self::unlock(path.as_ref(), password, None)
+    }
+
+    /// Unlock a database that was set up with a keyfile (`jottery --keyfile`)
+    pub fn open_with_keyfile<P: AsRef<Path>>(path: P, password: &str, keyfile: &Path) -> Result<Self> {
+        Self::unlock(path.as_ref(), password, Some(keyfile))
+    }
+
+    fn unlock(path: &Path, password: &str, keyfile: Option<&Path>) -> Result<Self> {
+        if !path.exists() {
+            anyhow::bail!("Database does not exist: {}", path.display());
+        }
+        let db = unlock::open_database(path, password, keyfile)?;
+
+        let metadata = EncryptionRepository::new(db.connection())
+            .get()?
+            .ok_or_else(|| anyhow::anyhow!("Database has not been set up yet. Unlock it in the TUI first."))?;
+        let input = kdf_input(password, metadata.keyfile_required, keyfile)?;
+        let checked = unlock::derive_checked_key(db.connection(), &input, &metadata)?;
+        NoteRepository::new(db.connection()).fill_previews(&checked.key)?;
+
+        Ok(Self { db, key: checked.key })
+    }
+
+    /// Notes outside the trash, most recently modified first
+    pub fn notes(&self) -> Result<Vec<Note>, RepoError> {
+        NoteRepository::new(self.db.connection()).list(false, &self.key)
+    }
+
+    /// A note by id, including one in the trash
+    pub fn note(&self, id: &str) -> Result<Option<Note>, RepoError> {
+        NoteRepository::new(self.db.connection()).get(id, &self.key)
+    }
+
+    /// Save a new note, applying the trailing-newline setting like the TUI does
+    ///
+    /// Tags are trimmed, a leading `#` is dropped and duplicates are ignored. Fails if the
+    /// content is blank.
+    pub fn create_note(&self, content: &str, tags: &[&str]) -> Result<Note> {
+        let settings = SettingsRepository::new(self.db.connection()).get()?;
+        let content = settings.trailing_newline.apply(content);
+        if content.trim().is_empty() {
+            anyhow::bail!("Note content is empty; nothing saved");
+        }
+
+        let mut note = Note::new(content);
+        for tag in tags {
+            let tag = tag.trim().trim_start_matches('#').to_string();
+            if !tag.is_empty() && !note.tags.contains(&tag) {
+                note.tags.push(tag);
+            }
+        }
+        NoteRepository::new(self.db.connection()).create(&note, &self.key)?;
+        integrity::seal_notes(self.db.connection(), &self.key, &[&note.id])?;
+        Ok(note)
+    }
+
+    /// Notes outside the trash matching a TUI search query (words, `#tag`, `-word`),
+    /// most recently modified first
+    pub fn search(&self, query: &str) -> Result<Vec<Note>, RepoError> {
+        let mut notes = self.notes()?;
+        if query.trim().is_empty() {
+            return Ok(notes);
+        }
+        let matching: HashSet<String> = NoteRepository::new(self.db.connection())
+            .search(query, &self.key)?
+            .into_iter()
+            .collect();
+        notes.retain(|note| matching.contains(&note.id));
+        Ok(notes)
+    }
+
+    /// Like `search`, but with each note's tags and the start of its content instead of
+    /// the whole note; without a query, no note content is decrypted
+    pub fn previews(&self, query: &str) -> Result<Vec<NotePreview>, RepoError> {
+        let repo = NoteRepository::new(self.db.connection());
+        let mut previews = repo.list_titles(false, &self.key)?;
+        if query.trim().is_empty() {
+            return Ok(previews);
+        }
+        let matching: HashSet<String> = repo.search(query, &self.key)?.into_iter().collect();
+        previews.retain(|preview| matching.contains(&preview.meta.id));
+        Ok(previews)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{crypto::CryptoService, models::encryption::EncryptionMetadata};
+
+    /// A database set up the way the TUI does on first unlock
+    fn set_up(path: &Path, password: &str) {
+        let db = Database::open(path, password).unwrap();
+        let metadata = EncryptionMetadata::new(CryptoService::new().generate_salt());
+        EncryptionRepository::new(db.connection()).save(&metadata).unwrap();
+    }
+
+    #[test]
+    fn test_vault_creates_lists_and_searches_notes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.db");
+        set_up(&path, "pw");
+
+        let vault = Vault::open(&path, "pw").unwrap();
+        let groceries = vault.create_note("Buy oat milk", &["#errands", "errands", " home "]).unwrap();
+        assert_eq!(groceries.tags, ["errands", "home"]);
+        vault.create_note("Draft the quarterly report", &[]).unwrap();
+        assert!(vault.create_note("  \n", &[]).is_err());
+        drop(vault);
+
+        // Reopened from disk, with the content key derived again
+        let vault = Vault::open(&path, "pw").unwrap();
+        assert_eq!(vault.notes().unwrap().len(), 2);
+        assert_eq!(vault.note(&groceries.id).unwrap().unwrap().content, "Buy oat milk\n");
+        let found = vault.search("milk #errands").unwrap();
+        assert_eq!(found.iter().map(|n| n.id.as_str()).collect::<Vec<_>>(), [groceries.id.as_str()]);
+        assert_eq!(vault.search("").unwrap().len(), 2);
+        let previews = vault.previews("#errands").unwrap();
+        assert_eq!((previews[0].meta.id.as_str(), previews[0].title()), (groceries.id.as_str(), "Buy oat milk".to_string()));
+        assert_eq!(vault.previews("").unwrap().len(), 2);
+
+        assert!(Vault::open(&path, "wrong").is_err());
+        assert!(Vault::open(dir.path().join("missing.db"), "pw").is_err());
+        assert!(!dir.path().join("missing.db").exists());
+    }
+}